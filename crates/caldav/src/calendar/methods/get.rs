@@ -0,0 +1,85 @@
+use crate::calendar::resource::CalendarResource;
+use crate::calendar::share::resolve_calendar;
+use crate::Error;
+use actix_web::http::header;
+use actix_web::web::{Data, Path, Query};
+use actix_web::HttpResponse;
+use rustical_dav::privileges::UserPrivilege;
+use rustical_dav::resource::Resource;
+use rustical_store::auth::User;
+use rustical_store::calendar::{export_calendar, UtcDateTime};
+use rustical_store::calendar_store::CalendarQuery;
+use rustical_store::CalendarStore;
+use rustical_xml::ValueDeserialize;
+use serde::Deserialize;
+use std::ops::Deref;
+use tracing::instrument;
+use tracing_actix_web::RootSpan;
+
+#[derive(Deserialize, Debug)]
+pub struct GetCalendarQuery {
+    // UTC timestamps like in CalDAV time-ranges, e.g. 20240101T000000Z
+    start: Option<String>,
+    end: Option<String>,
+}
+
+// Returns the whole calendar as a single iCalendar file for read-only clients
+#[instrument(parent = root_span.id(), skip(store, root_span))]
+pub async fn route_get<C: CalendarStore>(
+    path: Path<(String, String)>,
+    query: Query<GetCalendarQuery>,
+    user: User,
+    store: Data<C>,
+    root_span: RootSpan,
+) -> Result<HttpResponse, Error> {
+    let (principal, cal_id) = path.into_inner();
+    if !user.can_read_calendars(&principal) {
+        return Err(Error::Unauthorized);
+    }
+    let (principal, cal_id, share) = resolve_calendar(store.as_ref(), &principal, &cal_id).await?;
+    let calendar = store.get_calendar(&principal, &cal_id).await?;
+
+    let calendar_resource = CalendarResource {
+        cal: calendar,
+        read_only: true,
+        share,
+        shares: vec![],
+    };
+    if !calendar_resource
+        .get_user_privileges(&user)?
+        .has(&UserPrivilege::Read)
+    {
+        return Err(Error::Unauthorized);
+    }
+
+    let parse = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|value| UtcDateTime::deserialize(value).map(|value| *value.deref()))
+            .transpose()
+    };
+    let (start, end) = (parse(&query.start)?, parse(&query.end)?);
+
+    let objects = store
+        .calendar_query(
+            &principal,
+            &cal_id,
+            CalendarQuery {
+                time_start: start.map(|start| start.date_naive()),
+                time_end: end.map(|end| end.date_naive()),
+            },
+        )
+        .await?
+        .into_iter()
+        .filter(|object| object.occurs_between(start, end))
+        .collect::<Vec<_>>();
+
+    let calendar = calendar_resource.cal;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/calendar; charset=utf-8"))
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("inline; filename=\"{}.ics\"", calendar.id),
+        ))
+        .body(export_calendar(&calendar, &objects)?))
+}
@@ -4,7 +4,7 @@ use actix_web::web::{Data, Path};
 use actix_web::HttpResponse;
 use rustical_store::auth::User;
 use rustical_store::calendar::CalendarObjectType;
-use rustical_store::{Calendar, CalendarStore};
+use rustical_store::{Calendar, CalendarStore, Share};
 use rustical_xml::{Unparsed, XmlDeserialize, XmlDocument, XmlRootTag};
 use tracing::instrument;
 use tracing_actix_web::RootSpan;
@@ -57,9 +57,14 @@ pub async fn route_mkcalendar<C: CalendarStore>(
     root_span: RootSpan,
 ) -> Result<HttpResponse, Error> {
     let (principal, cal_id) = path.into_inner();
-    if principal != user.id {
+    if !user.can_write_calendars(&principal) {
         return Err(Error::Unauthorized);
     }
+    if !Share::is_valid_collection_id(&cal_id) {
+        return Err(
+            rustical_dav::Error::BadRequest(format!("Invalid calendar id: {cal_id}")).into(),
+        );
+    }
 
     let request = MkcalendarRequest::parse_str(&body)?;
     let request = request.set.prop;
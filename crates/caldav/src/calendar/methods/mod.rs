@@ -1,3 +1,4 @@
+pub mod get;
 pub mod mkcalendar;
 pub mod post;
 pub mod report;
@@ -1,4 +1,7 @@
 use crate::calendar::resource::CalendarResource;
+use crate::calendar::share::resolve_calendar;
+use crate::principal::PrincipalResource;
+use crate::scheduling::SchedulingConfig;
 use crate::Error;
 use actix_web::http::header;
 use actix_web::web::{Data, Path};
@@ -6,31 +9,56 @@ use actix_web::{HttpRequest, HttpResponse};
 use rustical_dav::privileges::UserPrivilege;
 use rustical_dav::push::PushRegister;
 use rustical_dav::resource::Resource;
-use rustical_store::auth::User;
-use rustical_store::{CalendarStore, Subscription, SubscriptionStore};
+use rustical_dav::xml::sharing::ShareRequest;
+use rustical_store::auth::{AuthenticationProvider, User};
+use rustical_store::{CalendarStore, Share, Subscription, SubscriptionStore};
 use rustical_xml::XmlDocument;
 use tracing::instrument;
 use tracing_actix_web::RootSpan;
 
-#[instrument(parent = root_span.id(), skip(store, subscription_store, root_span, req))]
-pub async fn route_post<C: CalendarStore, S: SubscriptionStore>(
+#[instrument(parent = root_span.id(), skip(auth_provider, store, subscription_store, scheduling, root_span, req))]
+#[allow(clippy::too_many_arguments)]
+pub async fn route_post<AP: AuthenticationProvider, C: CalendarStore, S: SubscriptionStore>(
     path: Path<(String, String)>,
     body: String,
     user: User,
+    auth_provider: Data<AP>,
     store: Data<C>,
     subscription_store: Data<S>,
+    scheduling: Data<SchedulingConfig>,
     root_span: RootSpan,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (principal, cal_id) = path.into_inner();
-    if principal != user.id {
+    if !user.can_write_calendars(&principal) {
         return Err(Error::Unauthorized);
     }
+    let (principal, cal_id, share) = resolve_calendar(store.as_ref(), &principal, &cal_id).await?;
 
     let calendar = store.get_calendar(&principal, &cal_id).await?;
+
+    if let Ok(share_request) = ShareRequest::parse_str(&body) {
+        // Only the owner may share a calendar
+        if share.is_some() {
+            return Err(Error::Unauthorized);
+        }
+        return handle_share_request(
+            share_request,
+            &principal,
+            &cal_id,
+            auth_provider.as_ref(),
+            store.as_ref(),
+            &scheduling,
+            &req,
+        )
+        .await;
+    }
+
     let calendar_resource = CalendarResource {
         cal: calendar,
         read_only: true,
+        share,
+        shares: vec![],
     };
 
     if !calendar_resource
@@ -72,3 +100,62 @@ pub async fn route_post<C: CalendarStore, S: SubscriptionStore>(
         .append_header((header::EXPIRES, expires.to_rfc2822()))
         .finish())
 }
+
+// Changes the sharees of a calendar
+// https://github.com/apple/ccs-calendarserver/blob/master/doc/Extensions/caldav-sharing.txt
+async fn handle_share_request<AP: AuthenticationProvider, C: CalendarStore>(
+    share_request: ShareRequest,
+    principal: &str,
+    cal_id: &str,
+    auth_provider: &AP,
+    store: &C,
+    scheduling: &SchedulingConfig,
+    req: &HttpRequest,
+) -> Result<HttpResponse, Error> {
+    // Calendars created before ids were validated cannot be mounted unambiguously
+    if !Share::is_valid_collection_id(cal_id) {
+        return Err(
+            rustical_dav::Error::BadRequest(format!("Calendar {cal_id} cannot be shared")).into(),
+        );
+    }
+
+    let mut changes = vec![];
+    for (href, access) in share_request.into_changes() {
+        let sharee =
+            PrincipalResource::get_principal_from_address(req.resource_map(), scheduling, &href)
+                .ok_or_else(|| {
+                    rustical_dav::Error::BadRequest(format!("Invalid sharee: {href}"))
+                })?;
+        if sharee == principal {
+            return Err(rustical_dav::Error::BadRequest(
+                "Calendars cannot be shared with their owner".to_owned(),
+            )
+            .into());
+        }
+        if access.is_some() && auth_provider.get_principal(&sharee).await?.is_none() {
+            return Err(rustical_dav::Error::BadRequest(format!("Unknown sharee: {href}")).into());
+        }
+        changes.push((sharee, access));
+    }
+
+    for (sharee, access) in changes {
+        match access {
+            Some(access) => {
+                store
+                    .put_calendar_share(Share {
+                        principal: principal.to_owned(),
+                        collection_id: cal_id.to_owned(),
+                        sharee,
+                        access,
+                    })
+                    .await?
+            }
+            None => {
+                store
+                    .delete_calendar_share(principal, cal_id, &sharee)
+                    .await?
+            }
+        }
+    }
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,102 @@
+use crate::Error;
+use rustical_dav::xml::{PropElement, PropfindType};
+use rustical_store::{calendar::UtcDateTime, CalendarObject};
+use rustical_xml::XmlDeserialize;
+use std::ops::Deref;
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+// <!ELEMENT expand EMPTY>, <!ELEMENT limit-recurrence-set EMPTY>, <!ELEMENT limit-freebusy-set EMPTY>
+// with start and end attributes
+pub(crate) struct RecurrenceRangeElement {
+    #[xml(ty = "attr")]
+    pub(crate) start: UtcDateTime,
+    #[xml(ty = "attr")]
+    pub(crate) end: UtcDateTime,
+}
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+#[xml(allow_invalid)]
+// A requested property that in the case of calendar-data can contain further instructions
+// https://datatracker.ietf.org/doc/html/rfc4791#section-9.6
+// <!ELEMENT calendar-data (comp?, (expand | limit-recurrence-set)?, limit-freebusy-set?)>
+// comp is currently ignored and the whole object is returned
+pub(crate) struct CalendarPropname {
+    #[xml(ty = "tag_name")]
+    pub(crate) name: String,
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
+    pub(crate) expand: Option<RecurrenceRangeElement>,
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
+    pub(crate) limit_recurrence_set: Option<RecurrenceRangeElement>,
+    // Only applies to VFREEBUSY components which we don't store
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
+    pub(crate) limit_freebusy_set: Option<RecurrenceRangeElement>,
+}
+
+// The calendar-data instructions of a REPORT request
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CalendarDataRequest {
+    pub(crate) expand: Option<RecurrenceRangeElement>,
+    pub(crate) limit_recurrence_set: Option<RecurrenceRangeElement>,
+}
+
+impl CalendarDataRequest {
+    pub(crate) fn from_propfind(prop: &PropfindType<CalendarPropname>) -> Result<Self, Error> {
+        let PropfindType::Prop(PropElement(props)) = prop else {
+            return Ok(Self::default());
+        };
+        let Some(calendar_data) = props.iter().find(|prop| prop.name == "calendar-data") else {
+            return Ok(Self::default());
+        };
+        if calendar_data.expand.is_some() && calendar_data.limit_recurrence_set.is_some() {
+            return Err(rustical_dav::Error::BadRequest(
+                "expand and limit-recurrence-set are mutually exclusive".to_owned(),
+            )
+            .into());
+        }
+        for range in [&calendar_data.expand, &calendar_data.limit_recurrence_set]
+            .into_iter()
+            .flatten()
+        {
+            if range.end.deref() <= range.start.deref() {
+                return Err(rustical_dav::Error::BadRequest(
+                    "end must be later than start".to_owned(),
+                )
+                .into());
+            }
+        }
+        Ok(Self {
+            expand: calendar_data.expand.to_owned(),
+            limit_recurrence_set: calendar_data.limit_recurrence_set.to_owned(),
+        })
+    }
+
+    // Returns the calendar-data to serve instead of the stored iCalendar data
+    pub(crate) fn get_calendar_data(
+        &self,
+        object: &CalendarObject,
+    ) -> Result<Option<String>, Error> {
+        if let Some(expand) = &self.expand {
+            return Ok(Some(object.expand_recurrence(
+                Some(*expand.start.deref()),
+                Some(*expand.end.deref()),
+            )?));
+        }
+        if let Some(limit) = &self.limit_recurrence_set {
+            return Ok(Some(object.limit_recurrence_set(
+                Some(*limit.start.deref()),
+                Some(*limit.end.deref()),
+            )?));
+        }
+        Ok(None)
+    }
+}
+
+pub(crate) fn get_propnames(prop: &PropfindType<CalendarPropname>) -> Vec<&str> {
+    match prop {
+        PropfindType::Allprop => vec!["allprop"],
+        PropfindType::Propname => vec!["propname"],
+        PropfindType::Prop(PropElement(props)) => {
+            props.iter().map(|prop| prop.name.as_str()).collect()
+        }
+    }
+}
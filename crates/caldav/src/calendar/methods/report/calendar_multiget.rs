@@ -1,3 +1,4 @@
+use super::calendar_data::{get_propnames, CalendarDataRequest, CalendarPropname};
 use crate::{
     calendar_object::resource::{CalendarObjectPropWrapper, CalendarObjectResource},
     Error,
@@ -9,9 +10,9 @@ use actix_web::{
 };
 use rustical_dav::{
     resource::Resource,
-    xml::{multistatus::ResponseElement, MultistatusElement, PropElement, PropfindType},
+    xml::{multistatus::ResponseElement, MultistatusElement, PropfindType},
 };
-use rustical_store::{auth::User, CalendarObject, CalendarStore};
+use rustical_store::{auth::User, CalendarObject, CalendarStore, Share};
 use rustical_xml::XmlDeserialize;
 
 #[derive(XmlDeserialize, Clone, Debug, PartialEq)]
@@ -19,7 +20,7 @@ use rustical_xml::XmlDeserialize;
 // <!ELEMENT calendar-query ((DAV:allprop | DAV:propname | DAV:prop)?, href+)>
 pub(crate) struct CalendarMultigetRequest {
     #[xml(ty = "untagged")]
-    pub(crate) prop: PropfindType,
+    pub(crate) prop: PropfindType<CalendarPropname>,
     #[xml(flatten)]
     #[xml(ns = "rustical_dav::namespace::NS_DAV")]
     pub(crate) href: Vec<String>,
@@ -59,32 +60,25 @@ pub async fn handle_calendar_multiget<C: CalendarStore>(
     user: &User,
     principal: &str,
     cal_id: &str,
+    share: Option<&Share>,
     cal_store: &C,
 ) -> Result<MultistatusElement<CalendarObjectPropWrapper, String>, Error> {
     let (objects, not_found) =
         get_objects_calendar_multiget(&cal_multiget, req.path(), principal, cal_id, cal_store)
             .await?;
 
-    let props = match cal_multiget.prop {
-        PropfindType::Allprop => {
-            vec!["allprop".to_owned()]
-        }
-        PropfindType::Propname => {
-            vec!["propname".to_owned()]
-        }
-        PropfindType::Prop(PropElement(prop_tags)) => {
-            prop_tags.into_iter().map(|propname| propname.0).collect()
-        }
-    };
-    let props: Vec<&str> = props.iter().map(String::as_str).collect();
+    let calendar_data = CalendarDataRequest::from_propfind(&cal_multiget.prop)?;
+    let props = get_propnames(&cal_multiget.prop);
 
     let mut responses = Vec::new();
     for object in objects {
         let path = format!("{}/{}", req.path(), object.get_id());
         responses.push(
             CalendarObjectResource {
+                calendar_data: calendar_data.get_calendar_data(&object)?,
                 object,
                 principal: principal.to_owned(),
+                share: share.cloned(),
             }
             .propfind(&path, &props, user, req.resource_map())?,
         );
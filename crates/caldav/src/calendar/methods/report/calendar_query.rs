@@ -1,14 +1,23 @@
 use actix_web::HttpRequest;
+use ical::{
+    generator::IcalCalendar,
+    parser::ical::component::{IcalAlarm, IcalTimeZone},
+    property::Property,
+};
 use rustical_dav::{
     resource::Resource,
-    xml::{MultistatusElement, PropElement, PropfindType},
+    xml::{MultistatusElement, PropfindType, TextCollation, TextMatchElement},
 };
 use rustical_store::{
-    auth::User, calendar::UtcDateTime, calendar_store::CalendarQuery, CalendarObject, CalendarStore,
+    auth::User,
+    calendar::{get_timezones, CalDateTime, UtcDateTime},
+    calendar_store::CalendarQuery,
+    CalendarObject, CalendarStore, Share,
 };
 use rustical_xml::XmlDeserialize;
-use std::ops::Deref;
+use std::{collections::HashMap, ops::Deref};
 
+use super::calendar_data::{get_propnames, CalendarDataRequest, CalendarPropname};
 use crate::{
     calendar_object::resource::{CalendarObjectPropWrapper, CalendarObjectResource},
     Error,
@@ -23,8 +32,20 @@ pub(crate) struct TimeRangeElement {
     pub(crate) end: Option<UtcDateTime>,
 }
 
+impl TimeRangeElement {
+    // Whether a DATE-TIME value lies within [start, end)
+    fn contains(&self, value: &CalDateTime) -> bool {
+        let value = value.utc();
+        self.start
+            .as_ref()
+            .is_none_or(|start| &value >= start.deref())
+            && self.end.as_ref().is_none_or(|end| &value < end.deref())
+    }
+}
+
 #[derive(XmlDeserialize, Clone, Debug, PartialEq)]
 #[allow(dead_code)]
+// https://datatracker.ietf.org/doc/html/rfc4791#section-9.7.3
 struct ParamFilterElement {
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
     is_not_defined: Option<()>,
@@ -35,17 +56,29 @@ struct ParamFilterElement {
     name: String,
 }
 
-#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
-#[allow(dead_code)]
-struct TextMatchElement {
-    #[xml(ty = "attr")]
-    collation: String,
-    #[xml(ty = "attr")]
-    negate_collation: String,
+impl ParamFilterElement {
+    fn matches(&self, prop: &Property) -> bool {
+        let mut values = prop
+            .params
+            .iter()
+            .flatten()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(&self.name))
+            .flat_map(|(_, values)| values)
+            .peekable();
+
+        match (self.is_not_defined, &self.text_match) {
+            (Some(()), _) => values.peek().is_none(),
+            (None, None) => values.peek().is_some(),
+            (None, Some(text_match)) => {
+                values.any(|value| text_match.matches(value, TextCollation::AsciiCasemap))
+            }
+        }
+    }
 }
 
 #[derive(XmlDeserialize, Clone, Debug, PartialEq)]
 #[allow(dead_code)]
+// https://datatracker.ietf.org/doc/html/rfc4791#section-9.7.2
 pub(crate) struct PropFilterElement {
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
     is_not_defined: Option<()>,
@@ -55,6 +88,102 @@ pub(crate) struct PropFilterElement {
     text_match: Option<TextMatchElement>,
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV", flatten)]
     param_filter: Vec<ParamFilterElement>,
+
+    #[xml(ty = "attr")]
+    name: String,
+}
+
+impl PropFilterElement {
+    fn matches(&self, properties: &[Property], timezones: &HashMap<String, IcalTimeZone>) -> bool {
+        let mut props = properties
+            .iter()
+            .filter(|prop| prop.name.eq_ignore_ascii_case(&self.name))
+            .peekable();
+
+        if self.is_not_defined.is_some() {
+            return props.peek().is_none();
+        }
+        // The filter matches if any instance of the property matches all conditions
+        props.any(|prop| self.matches_property(prop, timezones))
+    }
+
+    fn matches_property(&self, prop: &Property, timezones: &HashMap<String, IcalTimeZone>) -> bool {
+        if let Some(time_range) = &self.time_range {
+            match CalDateTime::parse_prop(prop, timezones) {
+                Ok(Some(value)) if time_range.contains(&value) => {}
+                _ => return false,
+            }
+        }
+        if let Some(text_match) = &self.text_match {
+            if !text_match.matches(
+                prop.value.as_deref().unwrap_or_default(),
+                TextCollation::AsciiCasemap,
+            ) {
+                return false;
+            }
+        }
+        self.param_filter.iter().all(|filter| filter.matches(prop))
+    }
+}
+
+// The parts of an iCalendar component that filters are applied to
+struct FilterComponent<'a> {
+    name: &'static str,
+    properties: &'a [Property],
+    children: Vec<FilterComponent<'a>>,
+}
+
+impl<'a> FilterComponent<'a> {
+    fn new(name: &'static str, properties: &'a [Property]) -> Self {
+        Self {
+            name,
+            properties,
+            children: vec![],
+        }
+    }
+
+    fn with_alarms(mut self, alarms: &'a [IcalAlarm]) -> Self {
+        self.children.extend(
+            alarms
+                .iter()
+                .map(|alarm| FilterComponent::new("VALARM", &alarm.properties)),
+        );
+        self
+    }
+}
+
+impl<'a> From<&'a IcalCalendar> for FilterComponent<'a> {
+    fn from(cal: &'a IcalCalendar) -> Self {
+        let mut children = vec![];
+        children.extend(cal.events.iter().map(|event| {
+            FilterComponent::new("VEVENT", &event.properties).with_alarms(&event.alarms)
+        }));
+        children.extend(
+            cal.todos.iter().map(|todo| {
+                FilterComponent::new("VTODO", &todo.properties).with_alarms(&todo.alarms)
+            }),
+        );
+        children.extend(
+            cal.journals
+                .iter()
+                .map(|journal| FilterComponent::new("VJOURNAL", &journal.properties)),
+        );
+        children.extend(
+            cal.free_busys
+                .iter()
+                .map(|free_busy| FilterComponent::new("VFREEBUSY", &free_busy.properties)),
+        );
+        children.extend(
+            cal.timezones
+                .iter()
+                .map(|timezone| FilterComponent::new("VTIMEZONE", &timezone.properties)),
+        );
+        Self {
+            name: "VCALENDAR",
+            properties: &cal.properties,
+            children,
+        }
+    }
 }
 
 #[derive(XmlDeserialize, Clone, Debug, PartialEq)]
@@ -91,50 +220,70 @@ impl CompFilterElement {
             return false;
         }
 
-        // TODO: Implement prop-filter at some point
+        let Ok(cal) = cal_object.get_ical() else {
+            return false;
+        };
+        let timezones = get_timezones(&cal);
+        let root = FilterComponent::from(&cal);
 
-        // Apply sub-comp-filters on VEVENT/VTODO/VJOURNAL component
-        if self
-            .comp_filter
-            .iter()
-            .all(|filter| filter.matches(cal_object))
-        {
-            return true;
-        }
+        self.matches_content(cal_object, &root, &timezones)
+    }
 
-        false
+    // Apply the prop-filters and sub-comp-filters to a matching component
+    fn matches_content(
+        &self,
+        cal_object: &CalendarObject,
+        component: &FilterComponent,
+        timezones: &HashMap<String, IcalTimeZone>,
+    ) -> bool {
+        self.prop_filter
+            .iter()
+            .all(|filter| filter.matches(component.properties, timezones))
+            && self
+                .comp_filter
+                .iter()
+                .all(|filter| filter.matches(cal_object, component, timezones))
     }
 
-    // match the VEVENT/VTODO/VJOURNAL part
-    pub fn matches(&self, cal_object: &CalendarObject) -> bool {
-        let comp_name_matches = self.name == cal_object.get_component_name();
-        match (self.is_not_defined, comp_name_matches) {
-            // Client wants VCALENDAR to not exist but we are a VCALENDAR
-            (Some(()), true) => return false,
-            // Client is asking for something different than a vcalendar
-            (None, false) => return false,
-            _ => {}
-        };
+    // match the VEVENT/VTODO/VJOURNAL part and nested components like VALARM
+    fn matches(
+        &self,
+        cal_object: &CalendarObject,
+        parent: &FilterComponent,
+        timezones: &HashMap<String, IcalTimeZone>,
+    ) -> bool {
+        let components: Vec<_> = parent
+            .children
+            .iter()
+            .filter(|component| component.name == self.name)
+            .collect();
 
-        // TODO: Implement prop-filter (and comp-filter?) at some point
+        if self.is_not_defined.is_some() {
+            return components.is_empty();
+        }
+        if components.is_empty() {
+            return false;
+        }
 
         if let Some(time_range) = &self.time_range {
-            if let Some(start) = &time_range.start {
-                if let Some(last_occurence) = cal_object.get_last_occurence().unwrap_or(None) {
-                    if start.deref() > &last_occurence.utc() {
-                        return false;
-                    }
-                };
-            }
-            if let Some(end) = &time_range.end {
-                if let Some(first_occurence) = cal_object.get_first_occurence().unwrap_or(None) {
-                    if end.deref() < &first_occurence.utc() {
-                        return false;
-                    }
-                };
+            // TODO: time-range on nested components (VALARM) is not supported
+            if parent.name == "VCALENDAR" && !Self::matches_time_range(cal_object, time_range) {
+                return false;
             }
         }
-        true
+
+        // Overridden instances of recurring events are separate components
+        components
+            .iter()
+            .any(|component| self.matches_content(cal_object, component, timezones))
+    }
+
+    fn matches_time_range(cal_object: &CalendarObject, time_range: &TimeRangeElement) -> bool {
+        // Recurring events match if any of their instances overlaps with the range
+        cal_object.occurs_between(
+            time_range.start.as_ref().map(|start| *start.deref()),
+            time_range.end.as_ref().map(|end| *end.deref()),
+        )
     }
 }
 
@@ -179,7 +328,7 @@ impl From<&FilterElement> for CalendarQuery {
 // <!ELEMENT calendar-query ((DAV:allprop | DAV:propname | DAV:prop)?, filter, timezone?)>
 pub struct CalendarQueryRequest {
     #[xml(ty = "untagged")]
-    pub prop: PropfindType,
+    pub(crate) prop: PropfindType<CalendarPropname>,
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
     pub(crate) filter: Option<FilterElement>,
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
@@ -219,30 +368,23 @@ pub async fn handle_calendar_query<C: CalendarStore>(
     user: &User,
     principal: &str,
     cal_id: &str,
+    share: Option<&Share>,
     cal_store: &C,
 ) -> Result<MultistatusElement<CalendarObjectPropWrapper, String>, Error> {
     let objects = get_objects_calendar_query(&cal_query, principal, cal_id, cal_store).await?;
 
-    let props = match cal_query.prop {
-        PropfindType::Allprop => {
-            vec!["allprop".to_owned()]
-        }
-        PropfindType::Propname => {
-            vec!["propname".to_owned()]
-        }
-        PropfindType::Prop(PropElement(prop_tags)) => {
-            prop_tags.into_iter().map(|propname| propname.0).collect()
-        }
-    };
-    let props: Vec<&str> = props.iter().map(String::as_str).collect();
+    let calendar_data = CalendarDataRequest::from_propfind(&cal_query.prop)?;
+    let props = get_propnames(&cal_query.prop);
 
     let mut responses = Vec::new();
     for object in objects {
         let path = format!("{}/{}", req.path().trim_end_matches('/'), object.get_id());
         responses.push(
             CalendarObjectResource {
+                calendar_data: calendar_data.get_calendar_data(&object)?,
                 object,
                 principal: principal.to_owned(),
+                share: share.cloned(),
             }
             .propfind(&path, &props, user, req.resource_map())?,
         );
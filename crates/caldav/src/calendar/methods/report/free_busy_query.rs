@@ -0,0 +1,58 @@
+use super::calendar_query::TimeRangeElement;
+use crate::Error;
+use actix_web::HttpResponse;
+use rustical_store::{
+    calendar::{generate_freebusy, get_busy_periods},
+    calendar_store::CalendarQuery,
+    CalendarStore,
+};
+use rustical_xml::XmlDeserialize;
+use std::ops::Deref;
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+// <!ELEMENT free-busy-query (time-range)>
+// https://datatracker.ietf.org/doc/html/rfc4791#section-7.10
+pub struct FreeBusyQueryRequest {
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
+    pub(crate) time_range: TimeRangeElement,
+}
+
+pub async fn handle_free_busy_query<C: CalendarStore>(
+    free_busy_query: FreeBusyQueryRequest,
+    principal: &str,
+    cal_id: &str,
+    cal_store: &C,
+) -> Result<HttpResponse, Error> {
+    let TimeRangeElement {
+        start: Some(start),
+        end: Some(end),
+    } = free_busy_query.time_range
+    else {
+        return Err(rustical_dav::Error::BadRequest(
+            "free-busy-query requires a time-range with start and end".to_owned(),
+        )
+        .into());
+    };
+    let (start, end) = (*start.deref(), *end.deref());
+    if end <= start {
+        return Err(
+            rustical_dav::Error::BadRequest("end must be later than start".to_owned()).into(),
+        );
+    }
+
+    let objects = cal_store
+        .calendar_query(
+            principal,
+            cal_id,
+            CalendarQuery {
+                time_start: Some(start.date_naive()),
+                time_end: Some(end.date_naive()),
+            },
+        )
+        .await?;
+    let periods = get_busy_periods(&objects, start, end)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar;charset=utf-8")
+        .body(generate_freebusy(&periods, start, end)))
+}
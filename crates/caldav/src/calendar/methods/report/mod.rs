@@ -1,18 +1,22 @@
+use crate::calendar::share::resolve_calendar;
 use crate::Error;
 use actix_web::{
     web::{Data, Path},
-    HttpRequest, Responder,
+    Either, HttpRequest, Responder,
 };
 use calendar_multiget::{handle_calendar_multiget, CalendarMultigetRequest};
 use calendar_query::{handle_calendar_query, CalendarQueryRequest};
+use free_busy_query::{handle_free_busy_query, FreeBusyQueryRequest};
 use rustical_dav::xml::sync_collection::SyncCollectionRequest;
 use rustical_store::{auth::User, CalendarStore};
 use rustical_xml::{XmlDeserialize, XmlDocument};
 use sync_collection::handle_sync_collection;
 use tracing::instrument;
 
+mod calendar_data;
 mod calendar_multiget;
 mod calendar_query;
+mod free_busy_query;
 mod sync_collection;
 
 #[derive(XmlDeserialize, XmlDocument, Clone, Debug, PartialEq)]
@@ -21,6 +25,8 @@ pub(crate) enum ReportRequest {
     CalendarMultiget(CalendarMultigetRequest),
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
     CalendarQuery(CalendarQueryRequest),
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
+    FreeBusyQuery(FreeBusyQueryRequest),
     #[xml(ns = "rustical_dav::namespace::NS_DAV")]
     SyncCollection(SyncCollectionRequest),
 }
@@ -34,13 +40,22 @@ pub async fn route_report_calendar<C: CalendarStore>(
     cal_store: Data<C>,
 ) -> Result<impl Responder, Error> {
     let (principal, cal_id) = path.into_inner();
-    if principal != user.id {
+    if !user.can_read_calendars(&principal) {
         return Err(Error::Unauthorized);
     }
+    let (principal, cal_id, share) =
+        resolve_calendar(cal_store.as_ref(), &principal, &cal_id).await?;
 
     let request = ReportRequest::parse_str(&body)?;
 
-    Ok(match request.clone() {
+    Ok(Either::Left(match request.clone() {
+        // free-busy-query responds with a VFREEBUSY instead of a multistatus
+        ReportRequest::FreeBusyQuery(free_busy_query) => {
+            return Ok(Either::Right(
+                handle_free_busy_query(free_busy_query, &principal, &cal_id, cal_store.as_ref())
+                    .await?,
+            ));
+        }
         ReportRequest::CalendarQuery(cal_query) => {
             handle_calendar_query(
                 cal_query,
@@ -48,6 +63,7 @@ pub async fn route_report_calendar<C: CalendarStore>(
                 &user,
                 &principal,
                 &cal_id,
+                share.as_ref(),
                 cal_store.as_ref(),
             )
             .await?
@@ -59,6 +75,7 @@ pub async fn route_report_calendar<C: CalendarStore>(
                 &user,
                 &principal,
                 &cal_id,
+                share.as_ref(),
                 cal_store.as_ref(),
             )
             .await?
@@ -70,22 +87,33 @@ pub async fn route_report_calendar<C: CalendarStore>(
                 &user,
                 &principal,
                 &cal_id,
+                share.as_ref(),
                 cal_store.as_ref(),
             )
             .await?
         }
-    })
+    }))
 }
 
 #[cfg(test)]
 mod tests {
+    use calendar_data::{CalendarPropname, RecurrenceRangeElement};
     use calendar_query::{CompFilterElement, FilterElement, TimeRangeElement};
-    use rustical_dav::xml::{PropElement, PropfindType, Propname};
+    use rustical_dav::xml::{PropElement, PropfindType};
     use rustical_store::calendar::UtcDateTime;
     use rustical_xml::ValueDeserialize;
 
     use super::*;
 
+    fn propname(name: &str) -> CalendarPropname {
+        CalendarPropname {
+            name: name.to_owned(),
+            expand: None,
+            limit_recurrence_set: None,
+            limit_freebusy_set: None,
+        }
+    }
+
     #[test]
     fn test_xml_calendar_query() {
         let report_request = ReportRequest::parse_str(
@@ -108,7 +136,7 @@ mod tests {
         assert_eq!(
             report_request,
             ReportRequest::CalendarQuery(CalendarQueryRequest {
-                prop: PropfindType::Prop(PropElement(vec![Propname("getetag".to_owned())])),
+                prop: PropfindType::Prop(PropElement(vec![propname("getetag")])),
                 filter: Some(FilterElement {
                     comp_filter: CompFilterElement {
                         is_not_defined: None,
@@ -155,8 +183,8 @@ mod tests {
             report_request,
             ReportRequest::CalendarMultiget(CalendarMultigetRequest {
                 prop: rustical_dav::xml::PropfindType::Prop(PropElement(vec![
-                    Propname("getetag".to_owned()),
-                    Propname("displayname".to_owned())
+                    propname("getetag"),
+                    propname("displayname")
                 ])),
                 href: vec![
                     "/caldav/user/user/6f787542-5256-401a-8db97003260da/ae7a998fdfd1d84a20391168962c62b".to_owned()
@@ -164,4 +192,136 @@ mod tests {
             })
         )
     }
+
+    #[test]
+    fn test_xml_calendar_data_expand() {
+        let report_request = ReportRequest::parse_str(
+            r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+                <D:prop>
+                    <C:calendar-data>
+                        <C:comp name="VCALENDAR">
+                            <C:prop name="VERSION"/>
+                            <C:comp name="VEVENT">
+                                <C:prop name="SUMMARY"/>
+                            </C:comp>
+                        </C:comp>
+                        <C:expand start="20060103T000000Z" end="20060105T000000Z"/>
+                    </C:calendar-data>
+                </D:prop>
+                <C:filter>
+                    <C:comp-filter name="VCALENDAR" />
+                </C:filter>
+            </C:calendar-query>"#,
+        )
+        .unwrap();
+        let ReportRequest::CalendarQuery(calendar_query) = report_request else {
+            panic!("Expected a calendar-query");
+        };
+        assert_eq!(
+            calendar_query.prop,
+            PropfindType::Prop(PropElement(vec![CalendarPropname {
+                expand: Some(RecurrenceRangeElement {
+                    start: <UtcDateTime as ValueDeserialize>::deserialize("20060103T000000Z")
+                        .unwrap(),
+                    end: <UtcDateTime as ValueDeserialize>::deserialize("20060105T000000Z")
+                        .unwrap(),
+                }),
+                ..propname("calendar-data")
+            }]))
+        );
+    }
+
+    #[test]
+    fn test_calendar_query_prop_filter() {
+        let todo = |status: &str| {
+            rustical_store::CalendarObject::from_ics(
+                "todo".to_owned(),
+                format!(
+                    "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:test\r\nBEGIN:VTODO\r\nUID:todo\r\nDTSTAMP:20240101T000000Z\r\nSUMMARY:Buy groceries\r\n{status}END:VTODO\r\nEND:VCALENDAR\r\n"
+                ),
+            )
+            .unwrap()
+        };
+        let parse_filter = |filter: &str| {
+            let ReportRequest::CalendarQuery(query) = ReportRequest::parse_str(&format!(
+                r#"<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+                    <D:prop><D:getetag/></D:prop>
+                    <C:filter><C:comp-filter name="VCALENDAR">{filter}</C:comp-filter></C:filter>
+                </C:calendar-query>"#
+            ))
+            .unwrap() else {
+                panic!("Expected a calendar-query");
+            };
+            query.filter.unwrap()
+        };
+
+        let not_completed = parse_filter(
+            r#"<C:comp-filter name="VTODO">
+                <C:prop-filter name="STATUS">
+                    <C:text-match negate-condition="yes">COMPLETED</C:text-match>
+                </C:prop-filter>
+            </C:comp-filter>"#,
+        );
+        assert!(not_completed.matches(&todo("STATUS:NEEDS-ACTION\r\n")));
+        assert!(!not_completed.matches(&todo("STATUS:COMPLETED\r\n")));
+        // The property has to be defined
+        assert!(!not_completed.matches(&todo("")));
+
+        let no_status = parse_filter(
+            r#"<C:comp-filter name="VTODO">
+                <C:prop-filter name="STATUS"><C:is-not-defined/></C:prop-filter>
+            </C:comp-filter>"#,
+        );
+        assert!(no_status.matches(&todo("")));
+        assert!(!no_status.matches(&todo("STATUS:COMPLETED\r\n")));
+
+        let summary = parse_filter(
+            r#"<C:comp-filter name="VTODO">
+                <C:prop-filter name="SUMMARY">
+                    <C:text-match collation="i;octet">groceries</C:text-match>
+                </C:prop-filter>
+            </C:comp-filter>"#,
+        );
+        assert!(summary.matches(&todo("")));
+        let summary = parse_filter(
+            r#"<C:comp-filter name="VTODO">
+                <C:prop-filter name="SUMMARY">
+                    <C:text-match collation="i;octet">GROCERIES</C:text-match>
+                </C:prop-filter>
+            </C:comp-filter>"#,
+        );
+        assert!(!summary.matches(&todo("")));
+
+        let no_event =
+            parse_filter(r#"<C:comp-filter name="VEVENT"><C:is-not-defined/></C:comp-filter>"#);
+        assert!(no_event.matches(&todo("")));
+    }
+
+    #[test]
+    fn test_xml_free_busy_query() {
+        let report_request = ReportRequest::parse_str(
+            r#"
+            <?xml version="1.0" encoding="utf-8" ?>
+            <C:free-busy-query xmlns:C="urn:ietf:params:xml:ns:caldav">
+                <C:time-range start="20060104T140000Z" end="20060105T220000Z"/>
+            </C:free-busy-query>
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            report_request,
+            ReportRequest::FreeBusyQuery(FreeBusyQueryRequest {
+                time_range: TimeRangeElement {
+                    start: Some(
+                        <UtcDateTime as ValueDeserialize>::deserialize("20060104T140000Z").unwrap()
+                    ),
+                    end: Some(
+                        <UtcDateTime as ValueDeserialize>::deserialize("20060105T220000Z").unwrap()
+                    ),
+                }
+            })
+        );
+    }
 }
@@ -9,7 +9,7 @@ use rustical_dav::{
 use rustical_store::{
     auth::User,
     synctoken::{format_synctoken, parse_synctoken},
-    CalendarStore,
+    CalendarStore, Share,
 };
 
 use crate::{
@@ -23,6 +23,7 @@ pub async fn handle_sync_collection<C: CalendarStore>(
     user: &User,
     principal: &str,
     cal_id: &str,
+    share: Option<&Share>,
     cal_store: &C,
 ) -> Result<MultistatusElement<CalendarObjectPropWrapper, String>, Error> {
     let props = match sync_collection.prop {
@@ -50,6 +51,8 @@ pub async fn handle_sync_collection<C: CalendarStore>(
             CalendarObjectResource {
                 object,
                 principal: principal.to_owned(),
+                calendar_data: None,
+                share: share.cloned(),
             }
             .propfind(&path, &props, user, req.resource_map())?,
         );
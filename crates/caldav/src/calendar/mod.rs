@@ -1,3 +1,4 @@
 pub mod methods;
 pub mod prop;
 pub mod resource;
+pub(crate) mod share;
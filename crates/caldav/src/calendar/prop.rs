@@ -62,6 +62,7 @@ pub struct SupportedCalendarData {
 pub enum ReportMethod {
     CalendarQuery,
     CalendarMultiget,
+    FreeBusyQuery,
     SyncCollection,
 }
 
@@ -87,6 +88,9 @@ impl Default for SupportedReportSet {
                 ReportWrapper {
                     report: ReportMethod::CalendarMultiget,
                 },
+                ReportWrapper {
+                    report: ReportMethod::FreeBusyQuery,
+                },
                 ReportWrapper {
                     report: ReportMethod::SyncCollection,
                 },
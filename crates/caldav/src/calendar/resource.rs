@@ -1,7 +1,9 @@
+use super::methods::get::route_get;
 use super::methods::mkcalendar::route_mkcalendar;
 use super::methods::post::route_post;
 use super::methods::report::route_report_calendar;
 use super::prop::{SupportedCalendarComponentSet, SupportedCalendarData, SupportedReportSet};
+use super::share::{resolve_calendar, share_privileges};
 use crate::calendar_object::resource::CalendarObjectResource;
 use crate::principal::PrincipalResource;
 use crate::Error;
@@ -15,12 +17,13 @@ use rustical_dav::extensions::{
     CommonPropertiesExtension, CommonPropertiesProp, DavPushExtension, DavPushExtensionProp,
     SyncTokenExtension, SyncTokenExtensionProp,
 };
-use rustical_dav::privileges::UserPrivilegeSet;
+use rustical_dav::privileges::{UserPrivilege, UserPrivilegeSet};
 use rustical_dav::resource::{Resource, ResourceService};
+use rustical_dav::xml::sharing::{AllowedSharingModes, InviteElement, InviteUserElement};
 use rustical_dav::xml::{HrefElement, Resourcetype, ResourcetypeInner};
-use rustical_store::auth::User;
+use rustical_store::auth::{AuthenticationProvider, User};
 use rustical_store::calendar::CalDateTime;
-use rustical_store::{Calendar, CalendarStore, SubscriptionStore};
+use rustical_store::{Calendar, CalendarStore, Share, SubscriptionStore};
 use rustical_xml::{EnumUnitVariants, EnumVariants};
 use rustical_xml::{XmlDeserialize, XmlSerialize};
 use std::marker::PhantomData;
@@ -65,6 +68,12 @@ pub enum CalendarProp {
     #[xml(skip_deserializing)]
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
     MaxDateTime(String),
+
+    // Sharing (calendarserver)
+    #[xml(ns = "rustical_dav::namespace::NS_CALENDARSERVER", skip_deserializing)]
+    Invite(InviteElement),
+    #[xml(ns = "rustical_dav::namespace::NS_CALENDARSERVER", skip_deserializing)]
+    AllowedSharingModes(AllowedSharingModes),
 }
 
 #[derive(XmlDeserialize, XmlSerialize, PartialEq, Clone, EnumVariants, EnumUnitVariants)]
@@ -80,6 +89,10 @@ pub enum CalendarPropWrapper {
 pub struct CalendarResource {
     pub cal: Calendar,
     pub read_only: bool,
+    // Set if the calendar is mounted into the home of a sharee
+    pub share: Option<Share>,
+    // Principals the calendar is shared with
+    pub shares: Vec<Share>,
 }
 
 impl From<CalendarResource> for Calendar {
@@ -106,7 +119,13 @@ impl Resource for CalendarResource {
     type PrincipalResource = PrincipalResource;
 
     fn get_resourcetype(&self) -> Resourcetype {
-        if self.cal.subscription_url.is_none() {
+        if self.cal.subscription_url.is_none() && self.share.is_some() {
+            Resourcetype(&[
+                ResourcetypeInner(Some(rustical_dav::namespace::NS_DAV), "collection"),
+                ResourcetypeInner(Some(rustical_dav::namespace::NS_CALDAV), "calendar"),
+                ResourcetypeInner(Some(rustical_dav::namespace::NS_CALENDARSERVER), "shared"),
+            ])
+        } else if self.cal.subscription_url.is_none() {
             Resourcetype(&[
                 ResourcetypeInner(Some(rustical_dav::namespace::NS_DAV), "collection"),
                 ResourcetypeInner(Some(rustical_dav::namespace::NS_CALDAV), "calendar"),
@@ -171,6 +190,25 @@ impl Resource for CalendarResource {
                 CalendarPropName::MaxDateTime => {
                     CalendarProp::MaxDateTime(CalDateTime::Utc(DateTime::<Utc>::MAX_UTC).format())
                 }
+                CalendarPropName::Invite => CalendarProp::Invite(InviteElement {
+                    organizer: PrincipalResource::get_principal_url(rmap, &self.cal.principal)
+                        .into(),
+                    user: self
+                        .shares
+                        .iter()
+                        .map(|share| InviteUserElement {
+                            href: PrincipalResource::get_principal_url(rmap, &share.sharee),
+                            invite_accepted: (),
+                            access: share.access.into(),
+                        })
+                        .collect(),
+                }),
+                // Only the owner can share a calendar
+                CalendarPropName::AllowedSharingModes => {
+                    CalendarProp::AllowedSharingModes(AllowedSharingModes {
+                        can_be_shared: self.share.is_none().then_some(()),
+                    })
+                }
             }),
             CalendarPropWrapperName::SyncToken(prop) => {
                 CalendarPropWrapper::SyncToken(SyncTokenExtension::get_prop(self, prop)?)
@@ -237,6 +275,8 @@ impl Resource for CalendarResource {
                 CalendarProp::Source(_) => Err(rustical_dav::Error::PropReadOnly),
                 CalendarProp::MinDateTime(_) => Err(rustical_dav::Error::PropReadOnly),
                 CalendarProp::MaxDateTime(_) => Err(rustical_dav::Error::PropReadOnly),
+                CalendarProp::Invite(_) => Err(rustical_dav::Error::PropReadOnly),
+                CalendarProp::AllowedSharingModes(_) => Err(rustical_dav::Error::PropReadOnly),
             },
             CalendarPropWrapper::SyncToken(prop) => SyncTokenExtension::set_prop(self, prop),
             CalendarPropWrapper::DavPush(prop) => DavPushExtension::set_prop(self, prop),
@@ -285,6 +325,8 @@ impl Resource for CalendarResource {
                 CalendarPropName::Source => Err(rustical_dav::Error::PropReadOnly),
                 CalendarPropName::MinDateTime => Err(rustical_dav::Error::PropReadOnly),
                 CalendarPropName::MaxDateTime => Err(rustical_dav::Error::PropReadOnly),
+                CalendarPropName::Invite => Err(rustical_dav::Error::PropReadOnly),
+                CalendarPropName::AllowedSharingModes => Err(rustical_dav::Error::PropReadOnly),
             },
             CalendarPropWrapperName::SyncToken(prop) => SyncTokenExtension::remove_prop(self, prop),
             CalendarPropWrapperName::DavPush(prop) => DavPushExtension::remove_prop(self, prop),
@@ -298,31 +340,69 @@ impl Resource for CalendarResource {
         Some(&self.cal.principal)
     }
 
+    // Collections change their sync token with every modification
+    fn get_etag(&self) -> Option<String> {
+        Some(format!("\"{}\"", self.cal.format_synctoken()))
+    }
+
+    fn get_share(&self) -> Option<&Share> {
+        self.share.as_ref()
+    }
+
     fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
-        if self.cal.subscription_url.is_some() || self.read_only {
-            return Ok(UserPrivilegeSet::owner_read(self.cal.principal == user.id));
+        let read_only = self.cal.subscription_url.is_some() || self.read_only;
+        if let Some(share) = &self.share {
+            return Ok(share_privileges(share, user, read_only));
         }
+        if read_only {
+            return Ok(UserPrivilegeSet::owner_read(
+                user.can_read_calendars(&self.cal.principal),
+            ));
+        }
+
+        Ok(UserPrivilegeSet::owner_or_reader(
+            user.can_write_calendars(&self.cal.principal),
+            user.can_read_calendars(&self.cal.principal),
+        ))
+    }
 
-        Ok(UserPrivilegeSet::owner_only(self.cal.principal == user.id))
+    // Deleting a shared calendar only removes it from the sharee's home,
+    // which the sharee may do regardless of the access the share grants
+    fn can_delete(&self, user: &User) -> Result<bool, Self::Error> {
+        if let Some(share) = &self.share {
+            return Ok(user.can_write_calendars(&share.sharee));
+        }
+        let privileges = self.get_user_privileges(user)?;
+        Ok(privileges.has(&UserPrivilege::Write))
     }
 }
 
-pub struct CalendarResourceService<C: CalendarStore, S: SubscriptionStore> {
+pub struct CalendarResourceService<
+    AP: AuthenticationProvider,
+    C: CalendarStore,
+    S: SubscriptionStore,
+> {
     cal_store: Arc<C>,
+    __phantom_auth: PhantomData<AP>,
     __phantom_sub: PhantomData<S>,
 }
 
-impl<C: CalendarStore, S: SubscriptionStore> CalendarResourceService<C, S> {
+impl<AP: AuthenticationProvider, C: CalendarStore, S: SubscriptionStore>
+    CalendarResourceService<AP, C, S>
+{
     pub fn new(cal_store: Arc<C>) -> Self {
         Self {
             cal_store,
+            __phantom_auth: PhantomData,
             __phantom_sub: PhantomData,
         }
     }
 }
 
 #[async_trait(?Send)]
-impl<C: CalendarStore, S: SubscriptionStore> ResourceService for CalendarResourceService<C, S> {
+impl<AP: AuthenticationProvider, C: CalendarStore, S: SubscriptionStore> ResourceService
+    for CalendarResourceService<AP, C, S>
+{
     type MemberType = CalendarObjectResource;
     type PathComponents = (String, String); // principal, calendar_id
     type Resource = CalendarResource;
@@ -332,10 +412,17 @@ impl<C: CalendarStore, S: SubscriptionStore> ResourceService for CalendarResourc
         &self,
         (principal, cal_id): &Self::PathComponents,
     ) -> Result<Self::Resource, Error> {
-        let calendar = self.cal_store.get_calendar(principal, cal_id).await?;
+        let (principal, cal_id, share) =
+            resolve_calendar(self.cal_store.as_ref(), principal, cal_id).await?;
+        let calendar = self.cal_store.get_calendar(&principal, &cal_id).await?;
         Ok(CalendarResource {
             cal: calendar,
             read_only: self.cal_store.is_read_only(),
+            share,
+            shares: self
+                .cal_store
+                .get_calendar_shares(&principal, &cal_id)
+                .await?,
         })
     }
 
@@ -343,9 +430,11 @@ impl<C: CalendarStore, S: SubscriptionStore> ResourceService for CalendarResourc
         &self,
         (principal, cal_id): &Self::PathComponents,
     ) -> Result<Vec<(String, Self::MemberType)>, Self::Error> {
+        let (principal, cal_id, share) =
+            resolve_calendar(self.cal_store.as_ref(), principal, cal_id).await?;
         Ok(self
             .cal_store
-            .get_objects(principal, cal_id)
+            .get_objects(&principal, &cal_id)
             .await?
             .into_iter()
             .map(|object| {
@@ -354,6 +443,8 @@ impl<C: CalendarStore, S: SubscriptionStore> ResourceService for CalendarResourc
                     CalendarObjectResource {
                         object,
                         principal: principal.to_owned(),
+                        calendar_data: None,
+                        share: share.clone(),
                     },
                 )
             })
@@ -365,6 +456,10 @@ impl<C: CalendarStore, S: SubscriptionStore> ResourceService for CalendarResourc
         (principal, cal_id): &Self::PathComponents,
         file: Self::Resource,
     ) -> Result<(), Self::Error> {
+        // Only the owner may change the properties of a calendar
+        if file.share.is_some() {
+            return Err(Error::Unauthorized);
+        }
         self.cal_store
             .update_calendar(principal.to_owned(), cal_id.to_owned(), file.into())
             .await?;
@@ -375,7 +470,17 @@ impl<C: CalendarStore, S: SubscriptionStore> ResourceService for CalendarResourc
         &self,
         (principal, cal_id): &Self::PathComponents,
         use_trashbin: bool,
+        _rmap: &ResourceMap,
     ) -> Result<(), Self::Error> {
+        // Sharees deleting a shared calendar only remove it from their home
+        if let (owner, owner_cal_id, Some(share)) =
+            resolve_calendar(self.cal_store.as_ref(), principal, cal_id).await?
+        {
+            self.cal_store
+                .delete_calendar_share(&owner, &owner_cal_id, &share.sharee)
+                .await?;
+            return Ok(());
+        }
         self.cal_store
             .delete_calendar(principal, cal_id, use_trashbin)
             .await?;
@@ -389,6 +494,7 @@ impl<C: CalendarStore, S: SubscriptionStore> ResourceService for CalendarResourc
 
         res.route(report_method.to(route_report_calendar::<C>))
             .route(mkcalendar_method.to(route_mkcalendar::<C>))
-            .post(route_post::<C, S>)
+            .post(route_post::<AP, C, S>)
+            .get(route_get::<C>)
     }
 }
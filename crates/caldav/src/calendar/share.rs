@@ -0,0 +1,98 @@
+use crate::Error;
+use rustical_dav::privileges::UserPrivilegeSet;
+use rustical_store::{auth::User, CalendarStore, Share};
+
+// Calendars shared with a principal are mounted into their calendar home.
+// Resolves a calendar in the home of a principal to the owner's calendar
+// and the share granting access to it.
+pub(crate) async fn resolve_calendar<C: CalendarStore>(
+    store: &C,
+    principal: &str,
+    cal_id: &str,
+) -> Result<(String, String, Option<Share>), Error> {
+    let share = store
+        .get_received_calendar_shares(principal)
+        .await?
+        .into_iter()
+        .find(|share| share.mount_id() == cal_id);
+    Ok(match share {
+        Some(share) => (
+            share.principal.to_owned(),
+            share.collection_id.to_owned(),
+            Some(share),
+        ),
+        None => (principal.to_owned(), cal_id.to_owned(), None),
+    })
+}
+
+// Shared calendars are accessed on behalf of the sharee,
+// so the sharee's delegates get the share's privileges as well
+pub(crate) fn share_privileges(share: &Share, user: &User, read_only: bool) -> UserPrivilegeSet {
+    if user.can_write_calendars(&share.sharee) {
+        if read_only {
+            UserPrivilegeSet::read_only()
+        } else {
+            share.access.into()
+        }
+    } else {
+        UserPrivilegeSet::owner_read(user.can_read_calendars(&share.sharee))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::resource::CalendarResource;
+    use rustical_dav::{privileges::UserPrivilege, resource::Resource};
+    use rustical_store::{
+        auth::{PrincipalType, User},
+        Calendar, Share, ShareAccess,
+    };
+
+    fn user(id: &str) -> User {
+        User {
+            id: id.to_owned(),
+            displayname: None,
+            principal_type: PrincipalType::Individual,
+            password: None,
+            memberships: vec![],
+            calendar_proxy_read_for: vec![],
+            calendar_proxy_write_for: vec![],
+        }
+    }
+
+    fn mount(access: ShareAccess) -> CalendarResource {
+        CalendarResource {
+            cal: Calendar {
+                principal: "alice".to_owned(),
+                id: "work".to_owned(),
+                ..Default::default()
+            },
+            read_only: false,
+            share: Some(Share {
+                principal: "alice".to_owned(),
+                collection_id: "work".to_owned(),
+                sharee: "bob".to_owned(),
+                access,
+            }),
+            shares: vec![],
+        }
+    }
+
+    #[test]
+    fn test_share_privileges() {
+        let bob = user("bob");
+        let privileges = mount(ShareAccess::ReadWrite)
+            .get_user_privileges(&bob)
+            .unwrap();
+        assert!(privileges.has(&UserPrivilege::WriteContent));
+        assert!(privileges.has(&UserPrivilege::Unbind));
+        // Sharees may not change the calendar's properties
+        assert!(!privileges.has(&UserPrivilege::Write));
+        assert!(!privileges.has(&UserPrivilege::WriteProperties));
+
+        // Sharees can always remove a share from their home, but nobody else can
+        assert!(mount(ShareAccess::Read).can_delete(&bob).unwrap());
+        assert!(mount(ShareAccess::ReadWrite).can_delete(&bob).unwrap());
+        assert!(!mount(ShareAccess::Read).can_delete(&user("carol")).unwrap());
+    }
+}
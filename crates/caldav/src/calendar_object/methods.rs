@@ -1,11 +1,22 @@
+use crate::calendar::share::resolve_calendar;
+use crate::principal::PrincipalResource;
+use crate::schedule_inbox::deliver_itip_messages;
+use crate::scheduling::SchedulingConfig;
 use crate::Error;
 use actix_web::http::header;
 use actix_web::http::header::HeaderValue;
 use actix_web::web::{Data, Path};
 use actix_web::HttpRequest;
 use actix_web::HttpResponse;
+use rustical_dav::destination_header::Destination;
+use rustical_dav::lock::{lock_key, LockManager};
+use rustical_dav::overwrite_header::Overwrite;
+use rustical_dav::precondition::check_preconditions;
+use rustical_dav::privileges::{UserPrivilege, UserPrivilegeSet};
+use rustical_dav::xml::error::Precondition;
 use rustical_store::auth::User;
-use rustical_store::{CalendarObject, CalendarStore};
+use rustical_store::calendar::{get_itip_messages, CalendarObjectComponent};
+use rustical_store::{CalendarObject, CalendarStore, Share};
 use tracing::instrument;
 use tracing_actix_web::RootSpan;
 
@@ -24,14 +35,11 @@ pub async fn get_event<C: CalendarStore>(
         object_id,
     } = path.into_inner();
 
-    if user.id != principal {
-        return Ok(HttpResponse::Unauthorized().body(""));
-    }
-
-    let calendar = store.get_calendar(&principal, &cal_id).await?;
-    if user.id != calendar.principal {
+    if !user.can_read_calendars(&principal) {
         return Ok(HttpResponse::Unauthorized().body(""));
     }
+    let (principal, cal_id, _share) = resolve_calendar(store.as_ref(), &principal, &cal_id).await?;
+    store.get_calendar(&principal, &cal_id).await?;
 
     let event = store.get_object(&principal, &cal_id, &object_id).await?;
 
@@ -41,13 +49,16 @@ pub async fn get_event<C: CalendarStore>(
         .body(event.get_ics().to_owned()))
 }
 
-#[instrument(parent = root_span.id(), skip(store, req, root_span))]
+#[instrument(parent = root_span.id(), skip(store, req, root_span, lock_manager, scheduling))]
+#[allow(clippy::too_many_arguments)]
 pub async fn put_event<C: CalendarStore>(
     path: Path<CalendarObjectPathComponents>,
     store: Data<C>,
     body: String,
     user: User,
     req: HttpRequest,
+    lock_manager: Data<LockManager>,
+    scheduling: Data<SchedulingConfig>,
     root_span: RootSpan,
 ) -> Result<HttpResponse, Error> {
     let CalendarObjectPathComponents {
@@ -56,20 +67,180 @@ pub async fn put_event<C: CalendarStore>(
         object_id,
     } = path.into_inner();
 
-    if user.id != principal {
+    if !user.can_write_calendars(&principal) {
         return Ok(HttpResponse::Unauthorized().body(""));
     }
-
-    // TODO: implement If-Match
-    //
+    let (principal, cal_id, share) = resolve_calendar(store.as_ref(), &principal, &cal_id).await?;
+    if let Some(share) = &share {
+        if !UserPrivilegeSet::from(share.access).has(&UserPrivilege::WriteContent) {
+            return Ok(HttpResponse::Unauthorized().body(""));
+        }
+    }
 
     let overwrite =
         Some(&HeaderValue::from_static("*")) != req.headers().get(header::IF_NONE_MATCH);
 
     let object = CalendarObject::from_ics(object_id, body)?;
+    // An invalid RRULE could not be expanded for time-range queries later on
+    if let CalendarObjectComponent::Event(event) = object.get_data() {
+        event.get_rrule()?;
+    }
+
+    // Implicit scheduling depends on what changed compared to the stored object
+    // https://datatracker.ietf.org/doc/html/rfc6638#section-3.2
+    let old_object = match store.get_object(&principal, &cal_id, object.get_id()).await {
+        Ok(old_object) => Some(old_object),
+        Err(rustical_store::Error::NotFound) => None,
+        Err(err) => return Err(err.into()),
+    };
+    let etag = old_object.as_ref().map(CalendarObject::get_etag);
+    check_preconditions(&req, etag.as_deref())?;
+    let key = lock_key(req.path(), share.as_ref());
+    lock_manager.check_write(&req, &user, req.path(), &key, etag.as_deref())?;
+    let messages = get_itip_messages(old_object.as_ref(), Some(&object), |address| {
+        PrincipalResource::get_principal_from_address(req.resource_map(), &scheduling, address)
+            .as_deref()
+            == Some(principal.as_str())
+    })?;
+
+    let new_etag = object.get_etag();
     store
-        .put_object(principal, cal_id, object, overwrite)
+        .put_object(principal.to_owned(), cal_id, object, overwrite)
         .await?;
+    deliver_itip_messages(store.as_ref(), req.resource_map(), &scheduling, &messages).await;
+
+    Ok(match old_object {
+        Some(_) => HttpResponse::NoContent(),
+        None => HttpResponse::Created(),
+    }
+    .insert_header((header::ETAG, new_etag))
+    .body(""))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4918#section-9.8
+#[instrument(parent = root_span.id(), skip(store, req, root_span, lock_manager))]
+#[allow(clippy::too_many_arguments)]
+pub async fn copy_move_event<C: CalendarStore>(
+    path: Path<CalendarObjectPathComponents>,
+    store: Data<C>,
+    user: User,
+    destination: Destination,
+    Overwrite(overwrite): Overwrite,
+    req: HttpRequest,
+    lock_manager: Data<LockManager>,
+    root_span: RootSpan,
+) -> Result<HttpResponse, Error> {
+    let CalendarObjectPathComponents {
+        principal,
+        cal_id,
+        object_id,
+    } = path.into_inner();
+    let is_move = req.method().as_str() == "MOVE";
+
+    if !user.can_write_calendars(&principal) {
+        return Ok(HttpResponse::Unauthorized().body(""));
+    }
+    // Objects can only be copied between calendars of the same principal
+    let Some((dest_cal_id, dest_object_id)) = destination.sibling_object(req.path()) else {
+        return Ok(HttpResponse::Forbidden().body("Invalid destination"));
+    };
+    let dest_object_id = dest_object_id.trim_end_matches(".ics");
+    if (dest_cal_id, dest_object_id) == (cal_id.as_str(), object_id.as_str()) {
+        return Ok(HttpResponse::Forbidden().body("Source and destination are the same"));
+    }
+
+    let (owner, cal_id, share) = resolve_calendar(store.as_ref(), &principal, &cal_id).await?;
+    let (dest_owner, dest_cal_id, dest_share) =
+        resolve_calendar(store.as_ref(), &principal, dest_cal_id).await?;
+    if owner != dest_owner {
+        return Ok(HttpResponse::Forbidden().body("Destination belongs to another principal"));
+    }
+    let can_write = |share: &Option<Share>| {
+        share.as_ref().is_none_or(|share| {
+            UserPrivilegeSet::from(share.access).has(&UserPrivilege::WriteContent)
+        })
+    };
+    if (is_move && !can_write(&share)) || !can_write(&dest_share) {
+        return Ok(HttpResponse::Unauthorized().body(""));
+    }
+    // Make sure that both calendars exist
+    store.get_calendar(&owner, &dest_cal_id).await?;
+    let object = store.get_object(&owner, &cal_id, &object_id).await?;
+    let etag = object.get_etag();
+    check_preconditions(&req, Some(&etag))?;
+
+    let dest_etag = match store.get_object(&owner, &dest_cal_id, dest_object_id).await {
+        Ok(dest_object) => Some(dest_object.get_etag()),
+        Err(rustical_store::Error::NotFound) => None,
+        Err(err) => return Err(err.into()),
+    };
+    let key = lock_key(req.path(), share.as_ref());
+    if is_move {
+        lock_manager.check_write(&req, &user, req.path(), &key, Some(&etag))?;
+    }
+    let dest_path = destination.path();
+    lock_manager.check_write(
+        &req,
+        &user,
+        &dest_path,
+        &lock_key(&dest_path, dest_share.as_ref()),
+        dest_etag.as_deref(),
+    )?;
+    let dest_exists = dest_etag.is_some();
+    if dest_exists && !overwrite {
+        return Ok(HttpResponse::PreconditionFailed().body(""));
+    }
+    // A calendar must not contain two objects with the same UID, a moved object leaves its own
+    // https://datatracker.ietf.org/doc/html/rfc4791#section-5.3.2.1
+    if let Some(uid) = object.get_uid() {
+        let is_source = |id: &str| is_move && dest_cal_id == cal_id && id == object_id;
+        if let Some(conflict) = store
+            .get_objects(&owner, &dest_cal_id)
+            .await?
+            .into_iter()
+            .find(|other| {
+                other.get_uid() == Some(uid)
+                    && other.get_id() != dest_object_id
+                    && !is_source(other.get_id())
+            })
+        {
+            let (parent, _) = dest_path.rsplit_once('/').unwrap_or_default();
+            return Err(
+                rustical_dav::Error::Conflict(Precondition::CalendarNoUidConflict(
+                    format!("{parent}/{}", conflict.get_id()).into(),
+                ))
+                .into(),
+            );
+        }
+    }
+
+    if is_move {
+        store
+            .move_object(
+                &owner,
+                &cal_id,
+                &object_id,
+                &dest_cal_id,
+                dest_object_id,
+                overwrite,
+            )
+            .await?;
+        lock_manager.remove_locks(&key);
+    } else {
+        store
+            .copy_object(
+                &owner,
+                &cal_id,
+                &object_id,
+                &dest_cal_id,
+                dest_object_id,
+                overwrite,
+            )
+            .await?;
+    }
 
-    Ok(HttpResponse::Created().body(""))
+    Ok(match dest_exists {
+        true => HttpResponse::NoContent().finish(),
+        false => HttpResponse::Created().finish(),
+    })
 }
@@ -1,6 +1,14 @@
-use super::methods::{get_event, put_event};
-use crate::{principal::PrincipalResource, Error};
+use super::methods::{copy_move_event, get_event, put_event};
+use crate::{
+    calendar::share::{resolve_calendar, share_privileges},
+    principal::PrincipalResource,
+    schedule_inbox::deliver_itip_messages,
+    scheduling::SchedulingConfig,
+    Error,
+};
 use actix_web::dev::ResourceMap;
+use actix_web::http::Method;
+use actix_web::web;
 use async_trait::async_trait;
 use derive_more::derive::{From, Into};
 use rustical_dav::{
@@ -9,18 +17,25 @@ use rustical_dav::{
     resource::{Resource, ResourceService},
     xml::Resourcetype,
 };
-use rustical_store::{auth::User, CalendarObject, CalendarStore};
+use rustical_store::{
+    auth::User, calendar::get_itip_messages, CalendarObject, CalendarStore, Share,
+};
 use rustical_xml::{EnumUnitVariants, EnumVariants, XmlDeserialize, XmlSerialize};
 use serde::Deserialize;
+use std::str::FromStr;
 use std::sync::Arc;
 
 pub struct CalendarObjectResourceService<C: CalendarStore> {
     cal_store: Arc<C>,
+    scheduling: SchedulingConfig,
 }
 
 impl<C: CalendarStore> CalendarObjectResourceService<C> {
-    pub fn new(cal_store: Arc<C>) -> Self {
-        Self { cal_store }
+    pub fn new(cal_store: Arc<C>, scheduling: SchedulingConfig) -> Self {
+        Self {
+            cal_store,
+            scheduling,
+        }
     }
 }
 
@@ -49,6 +64,10 @@ pub enum CalendarObjectPropWrapper {
 pub struct CalendarObjectResource {
     pub object: CalendarObject,
     pub principal: String,
+    // Served instead of the stored iCalendar data, e.g. for expanded recurrences
+    pub calendar_data: Option<String>,
+    // Set if the object is accessed through a shared calendar
+    pub share: Option<Share>,
 }
 
 impl Resource for CalendarObjectResource {
@@ -72,9 +91,12 @@ impl Resource for CalendarObjectResource {
                     CalendarObjectPropName::Getetag => {
                         CalendarObjectProp::Getetag(self.object.get_etag())
                     }
-                    CalendarObjectPropName::CalendarData => {
-                        CalendarObjectProp::CalendarData(self.object.get_ics().to_owned())
-                    }
+                    CalendarObjectPropName::CalendarData => CalendarObjectProp::CalendarData(
+                        self.calendar_data
+                            .as_deref()
+                            .unwrap_or(self.object.get_ics())
+                            .to_owned(),
+                    ),
                     CalendarObjectPropName::Getcontenttype => {
                         CalendarObjectProp::Getcontenttype("text/calendar;charset=utf-8")
                     }
@@ -90,8 +112,22 @@ impl Resource for CalendarObjectResource {
         Some(&self.principal)
     }
 
+    fn get_etag(&self) -> Option<String> {
+        Some(self.object.get_etag())
+    }
+
+    fn get_share(&self) -> Option<&Share> {
+        self.share.as_ref()
+    }
+
     fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
-        Ok(UserPrivilegeSet::owner_only(self.principal == user.id))
+        if let Some(share) = &self.share {
+            return Ok(share_privileges(share, user, false));
+        }
+        Ok(UserPrivilegeSet::owner_or_reader(
+            user.can_write_calendars(&self.principal),
+            user.can_read_calendars(&self.principal),
+        ))
     }
 }
 
@@ -135,13 +171,17 @@ impl<C: CalendarStore> ResourceService for CalendarObjectResourceService<C> {
             object_id,
         }: &Self::PathComponents,
     ) -> Result<Self::Resource, Self::Error> {
+        let (principal, cal_id, share) =
+            resolve_calendar(self.cal_store.as_ref(), principal, cal_id).await?;
         let object = self
             .cal_store
-            .get_object(principal, cal_id, object_id)
+            .get_object(&principal, &cal_id, object_id)
             .await?;
         Ok(CalendarObjectResource {
             object,
-            principal: principal.to_owned(),
+            principal,
+            calendar_data: None,
+            share,
         })
     }
 
@@ -153,15 +193,32 @@ impl<C: CalendarStore> ResourceService for CalendarObjectResourceService<C> {
             object_id,
         }: &Self::PathComponents,
         use_trashbin: bool,
+        rmap: &ResourceMap,
     ) -> Result<(), Self::Error> {
+        let (principal, cal_id, _share) =
+            resolve_calendar(self.cal_store.as_ref(), principal, cal_id).await?;
+        // Deleting a scheduled event cancels it for the attendees or declines it for the organizer
+        let object = self
+            .cal_store
+            .get_object(&principal, &cal_id, object_id)
+            .await?;
+        let messages = get_itip_messages(Some(&object), None, |address| {
+            PrincipalResource::get_principal_from_address(rmap, &self.scheduling, address)
+                .as_deref()
+                == Some(principal.as_str())
+        })?;
         self.cal_store
-            .delete_object(principal, cal_id, object_id, use_trashbin)
+            .delete_object(&principal, &cal_id, object_id, use_trashbin)
             .await?;
+        deliver_itip_messages(self.cal_store.as_ref(), rmap, &self.scheduling, &messages).await;
         Ok(())
     }
 
     #[inline]
     fn actix_additional_routes(res: actix_web::Resource) -> actix_web::Resource {
-        res.get(get_event::<C>).put(put_event::<C>)
+        res.get(get_event::<C>)
+            .put(put_event::<C>)
+            .route(web::method(Method::from_str("COPY").unwrap()).to(copy_move_event::<C>))
+            .route(web::method(Method::from_str("MOVE").unwrap()).to(copy_move_event::<C>))
     }
 }
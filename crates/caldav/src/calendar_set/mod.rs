@@ -55,9 +55,12 @@ impl Resource for CalendarSetResource {
 
     fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
         Ok(if self.read_only {
-            UserPrivilegeSet::owner_read(self.principal == user.id)
+            UserPrivilegeSet::owner_read(user.can_read_calendars(&self.principal))
         } else {
-            UserPrivilegeSet::owner_only(self.principal == user.id)
+            UserPrivilegeSet::owner_or_reader(
+                user.can_write_calendars(&self.principal),
+                user.can_read_calendars(&self.principal),
+            )
         })
     }
 }
@@ -93,18 +96,47 @@ impl<C: CalendarStore> ResourceService for CalendarSetResourceService<C> {
         &self,
         (principal,): &Self::PathComponents,
     ) -> Result<Vec<(String, Self::MemberType)>, Self::Error> {
-        let calendars = self.cal_store.get_calendars(principal).await?;
-        Ok(calendars
-            .into_iter()
-            .map(|cal| {
-                (
-                    cal.id.to_owned(),
-                    CalendarResource {
-                        cal,
-                        read_only: self.cal_store.is_read_only(),
-                    },
-                )
-            })
-            .collect())
+        let read_only = self.cal_store.is_read_only();
+        let mut members = vec![];
+        for cal in self.cal_store.get_calendars(principal).await? {
+            let shares = self
+                .cal_store
+                .get_calendar_shares(principal, &cal.id)
+                .await?;
+            members.push((
+                cal.id.to_owned(),
+                CalendarResource {
+                    cal,
+                    read_only,
+                    share: None,
+                    shares,
+                },
+            ));
+        }
+        // Calendars other principals shared with us
+        for share in self
+            .cal_store
+            .get_received_calendar_shares(principal)
+            .await?
+        {
+            let cal = self
+                .cal_store
+                .get_calendar(&share.principal, &share.collection_id)
+                .await?;
+            let shares = self
+                .cal_store
+                .get_calendar_shares(&share.principal, &share.collection_id)
+                .await?;
+            members.push((
+                share.mount_id(),
+                CalendarResource {
+                    cal,
+                    read_only,
+                    share: Some(share),
+                    shares,
+                },
+            ));
+        }
+        Ok(members)
     }
 }
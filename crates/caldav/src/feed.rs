@@ -0,0 +1,32 @@
+use actix_web::{
+    http::header,
+    web::{self, Data, Path},
+    HttpResponse,
+};
+use rustical_store::{calendar::export_feed, CalendarStore};
+
+// Public calendar feeds don't require authentication, the token is the credential
+async fn handle_get<C: CalendarStore>(
+    store: Data<C>,
+    path: Path<String>,
+) -> Result<HttpResponse, rustical_store::Error> {
+    let token = path.into_inner();
+    // Some clients only accept subscription URLs ending with .ics
+    let token = token.strip_suffix(".ics").unwrap_or(&token);
+    let feed = store.get_calendar_feed(token).await?;
+    let calendar = store.get_calendar(&feed.principal, &feed.cal_id).await?;
+    if calendar.deleted_at.is_some() {
+        return Err(rustical_store::Error::NotFound);
+    }
+    let objects = store.get_objects(&feed.principal, &feed.cal_id).await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/calendar; charset=utf-8"))
+        .body(export_feed(&calendar, &objects, feed.privacy)?))
+}
+
+pub fn feed_resource<C: CalendarStore>() -> actix_web::Resource {
+    web::resource("/feed/{token}")
+        .name("calendar_feed")
+        .get(handle_get::<C>)
+}
@@ -7,11 +7,15 @@ use actix_web::HttpResponse;
 use calendar::resource::CalendarResourceService;
 use calendar_object::resource::CalendarObjectResourceService;
 use calendar_set::CalendarSetResourceService;
+use feed::feed_resource;
 use principal::{PrincipalResource, PrincipalResourceService};
 use rustical_dav::resource::{NamedRoute, ResourceService, ResourceServiceRoute};
 use rustical_dav::resources::RootResourceService;
 use rustical_store::auth::{AuthenticationMiddleware, AuthenticationProvider};
 use rustical_store::{AddressbookStore, CalendarStore, ContactBirthdayStore, SubscriptionStore};
+use schedule_inbox::{ScheduleInboxObjectResourceService, ScheduleInboxResourceService};
+use schedule_outbox::ScheduleOutboxResourceService;
+use scheduling::SchedulingConfig;
 use std::sync::Arc;
 use subscription::subscription_resource;
 
@@ -19,8 +23,13 @@ pub mod calendar;
 pub mod calendar_object;
 pub mod calendar_set;
 pub mod error;
+mod feed;
 pub mod principal;
+pub mod schedule_inbox;
+pub mod schedule_outbox;
+pub mod scheduling;
 mod subscription;
+pub mod webcal;
 
 pub use error::Error;
 
@@ -39,11 +48,12 @@ pub fn configure_dav<
     store: Arc<C>,
     addr_store: Arc<AS>,
     subscription_store: Arc<S>,
+    scheduling: SchedulingConfig,
 ) {
     let birthday_store = Arc::new(ContactBirthdayStore::new(addr_store));
     cfg.service(
         web::scope("")
-            .wrap(AuthenticationMiddleware::new(auth_provider))
+            .wrap(AuthenticationMiddleware::new(auth_provider.clone()))
             .wrap(
                 ErrorHandlers::new().handler(StatusCode::METHOD_NOT_ALLOWED, |res| {
                     Ok(ErrorHandlerResponse::Response(
@@ -53,7 +63,7 @@ pub fn configure_dav<
                                     HeaderName::from_static("dav"),
                                     // https://datatracker.ietf.org/doc/html/rfc4918#section-18
                                     HeaderValue::from_static(
-                                        "1, 3, access-control, calendar-access, extended-mkcol, calendar-no-timezone",
+                                        "1, 2, 3, access-control, calendar-access, extended-mkcol, calendar-no-timezone, calendar-auto-schedule",
                                     ),
                                 ))
                                 .finish();
@@ -64,39 +74,49 @@ pub fn configure_dav<
                     ))
                 }),
             )
+            .app_data(Data::from(auth_provider.clone()))
             .app_data(Data::from(store.clone()))
             .app_data(Data::from(birthday_store.clone()))
             .app_data(Data::from(subscription_store))
+            .app_data(Data::new(scheduling.clone()))
             .service(RootResourceService::<PrincipalResource>::default().actix_resource())
             .service(
                 web::scope("/user").service(
                     web::scope("/{principal}")
-                        .service(PrincipalResourceService(&[
+                        .service(PrincipalResourceService::new(auth_provider, &[
                             ("calendar", false), ("birthdays", true)
-                        ]).actix_resource().name(PrincipalResource::route_name()))
+                        ], scheduling.clone()).actix_resource().name(PrincipalResource::route_name()))
                         .service(web::scope("/calendar")
                             .service(CalendarSetResourceService::new(store.clone()).actix_resource())
                             .service(
                                 web::scope("/{calendar}")
                                     .service(
-                                        ResourceServiceRoute(CalendarResourceService::<_, S>::new(store.clone()))
+                                        ResourceServiceRoute(CalendarResourceService::<AP, _, S>::new(store.clone()))
                                     )
-                                        .service(web::scope("/{object}").service(CalendarObjectResourceService::new(store.clone()).actix_resource()
+                                        .service(web::scope("/{object}").service(CalendarObjectResourceService::new(store.clone(), scheduling.clone()).actix_resource()
                                     ))
                             )
                         )
+                        .service(web::scope("/inbox")
+                            .service(ScheduleInboxResourceService::new(store.clone()).actix_resource())
+                            .service(web::scope("/{object}").service(ScheduleInboxObjectResourceService::new(store.clone()).actix_resource()))
+                        )
+                        .service(web::scope("/outbox")
+                            .service(ScheduleOutboxResourceService::<C>::default().actix_resource())
+                        )
                         .service(web::scope("/birthdays")
                             .service(CalendarSetResourceService::new(birthday_store.clone()).actix_resource())
                             .service(
                                 web::scope("/{calendar}")
                                     .service(
-                                        ResourceServiceRoute(CalendarResourceService::<_, S>::new(birthday_store.clone()))
+                                        ResourceServiceRoute(CalendarResourceService::<AP, _, S>::new(birthday_store.clone()))
                                     )
-                                        .service(web::scope("/{object}").service(CalendarObjectResourceService::new(birthday_store.clone()).actix_resource()
+                                        .service(web::scope("/{object}").service(CalendarObjectResourceService::new(birthday_store.clone(), scheduling).actix_resource()
                                     ))
                             )
                         )
                 ),
-            ).service(subscription_resource::<S>()),
+            ).service(subscription_resource::<S>())
+            .service(feed_resource::<C>()),
     );
 }
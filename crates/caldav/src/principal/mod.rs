@@ -1,18 +1,23 @@
 use crate::calendar_set::CalendarSetResource;
+use crate::scheduling::SchedulingConfig;
 use crate::Error;
 use actix_web::dev::ResourceMap;
 use async_trait::async_trait;
 use rustical_dav::extensions::{CommonPropertiesExtension, CommonPropertiesProp};
 use rustical_dav::privileges::UserPrivilegeSet;
 use rustical_dav::resource::{NamedRoute, Resource, ResourceService};
-use rustical_dav::xml::{HrefElement, Resourcetype, ResourcetypeInner};
-use rustical_store::auth::User;
+use rustical_dav::xml::{HrefElement, HrefListElement, Resourcetype, ResourcetypeInner};
+use rustical_store::auth::{AuthenticationProvider, PrincipalType, User};
 use rustical_xml::{EnumUnitVariants, EnumVariants, XmlDeserialize, XmlSerialize};
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct PrincipalResource {
-    principal: String,
+    principal: User,
+    // Members of a group principal
+    members: Vec<String>,
     home_set: &'static [(&'static str, bool)],
+    scheduling: SchedulingConfig,
 }
 
 #[derive(XmlDeserialize, XmlSerialize, PartialEq, Clone)]
@@ -27,12 +32,33 @@ pub enum PrincipalProp {
     // Scheduling Extensions to CalDAV (RFC 6638)
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV", skip_deserializing)]
     CalendarUserType(&'static str),
-    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
-    CalendarUserAddressSet(HrefElement),
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV", skip_deserializing)]
+    CalendarUserAddressSet(HrefListElement),
+    #[xml(
+        ns = "rustical_dav::namespace::NS_CALDAV",
+        rename = b"schedule-inbox-URL"
+    )]
+    ScheduleInboxUrl(HrefElement),
+    #[xml(
+        ns = "rustical_dav::namespace::NS_CALDAV",
+        rename = b"schedule-outbox-URL"
+    )]
+    ScheduleOutboxUrl(HrefElement),
 
     // WebDAV Access Control (RFC 3744)
     #[xml(ns = "rustical_dav::namespace::NS_DAV", rename = b"principal-URL")]
     PrincipalUrl(HrefElement),
+    #[xml(ns = "rustical_dav::namespace::NS_DAV", skip_deserializing)]
+    GroupMemberSet(HrefListElement),
+    #[xml(ns = "rustical_dav::namespace::NS_DAV", skip_deserializing)]
+    GroupMembership(HrefListElement),
+
+    // Calendar delegation
+    // https://github.com/apple/ccs-calendarserver/blob/master/doc/Extensions/caldav-proxy.txt
+    #[xml(ns = "rustical_dav::namespace::NS_CALENDARSERVER", skip_deserializing)]
+    CalendarProxyReadFor(HrefListElement),
+    #[xml(ns = "rustical_dav::namespace::NS_CALENDARSERVER", skip_deserializing)]
+    CalendarProxyWriteFor(HrefListElement),
 
     // CalDAV (RFC 4791)
     #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
@@ -50,6 +76,26 @@ impl PrincipalResource {
     pub fn get_principal_url(rmap: &ResourceMap, principal: &str) -> String {
         Self::get_url(rmap, vec![principal]).unwrap()
     }
+
+    // Maps a calendar user address to a local principal
+    // Principals are addressed by their URL and, if configured, a mailto: address
+    pub fn get_principal_from_address(
+        rmap: &ResourceMap,
+        scheduling: &SchedulingConfig,
+        address: &str,
+    ) -> Option<String> {
+        scheduling
+            .get_principal_from_email(address)
+            .or_else(|| Self::get_url_element(rmap, address))
+    }
+
+    fn get_principal_urls(rmap: &ResourceMap, principals: &[String]) -> HrefListElement {
+        principals
+            .iter()
+            .map(|principal| Self::get_principal_url(rmap, principal))
+            .collect::<Vec<_>>()
+            .into()
+    }
 }
 
 impl NamedRoute for PrincipalResource {
@@ -76,7 +122,7 @@ impl Resource for PrincipalResource {
         user: &User,
         prop: &PrincipalPropWrapperName,
     ) -> Result<Self::Prop, Self::Error> {
-        let principal_url = Self::get_url(rmap, vec![&self.principal]).unwrap();
+        let principal_url = Self::get_url(rmap, vec![&self.principal.id]).unwrap();
         let home_set = CalendarHomeSet(
             self.home_set
                 .iter()
@@ -87,18 +133,50 @@ impl Resource for PrincipalResource {
         Ok(match prop {
             PrincipalPropWrapperName::Principal(prop) => {
                 PrincipalPropWrapper::Principal(match prop {
-                    PrincipalPropName::CalendarUserType => {
-                        PrincipalProp::CalendarUserType("INDIVIDUAL")
-                    }
-                    PrincipalPropName::Displayname => {
-                        PrincipalProp::Displayname(self.principal.to_owned())
-                    }
+                    PrincipalPropName::CalendarUserType => PrincipalProp::CalendarUserType(
+                        self.principal.principal_type.as_calendar_user_type(),
+                    ),
+                    PrincipalPropName::Displayname => PrincipalProp::Displayname(
+                        self.principal
+                            .displayname
+                            .to_owned()
+                            .unwrap_or_else(|| self.principal.id.to_owned()),
+                    ),
                     PrincipalPropName::PrincipalUrl => {
                         PrincipalProp::PrincipalUrl(principal_url.into())
                     }
+                    PrincipalPropName::GroupMemberSet => {
+                        PrincipalProp::GroupMemberSet(Self::get_principal_urls(rmap, &self.members))
+                    }
+                    PrincipalPropName::GroupMembership => PrincipalProp::GroupMembership(
+                        Self::get_principal_urls(rmap, &self.principal.memberships),
+                    ),
+                    PrincipalPropName::CalendarProxyReadFor => PrincipalProp::CalendarProxyReadFor(
+                        Self::get_principal_urls(rmap, &self.principal.calendar_proxy_read_for),
+                    ),
+                    PrincipalPropName::CalendarProxyWriteFor => {
+                        PrincipalProp::CalendarProxyWriteFor(Self::get_principal_urls(
+                            rmap,
+                            &self.principal.calendar_proxy_write_for,
+                        ))
+                    }
                     PrincipalPropName::CalendarHomeSet => PrincipalProp::CalendarHomeSet(home_set),
                     PrincipalPropName::CalendarUserAddressSet => {
-                        PrincipalProp::CalendarUserAddressSet(principal_url.into())
+                        // Clients use the first address as ORGANIZER
+                        PrincipalProp::CalendarUserAddressSet(
+                            self.scheduling
+                                .get_email_address(&self.principal.id)
+                                .into_iter()
+                                .chain([principal_url])
+                                .collect::<Vec<_>>()
+                                .into(),
+                        )
+                    }
+                    PrincipalPropName::ScheduleInboxUrl => {
+                        PrincipalProp::ScheduleInboxUrl(format!("{principal_url}/inbox").into())
+                    }
+                    PrincipalPropName::ScheduleOutboxUrl => {
+                        PrincipalProp::ScheduleOutboxUrl(format!("{principal_url}/outbox").into())
                     }
                 })
             }
@@ -109,18 +187,38 @@ impl Resource for PrincipalResource {
     }
 
     fn get_owner(&self) -> Option<&str> {
-        Some(&self.principal)
+        Some(&self.principal.id)
     }
 
     fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
-        Ok(UserPrivilegeSet::owner_read(self.principal == user.id))
+        Ok(UserPrivilegeSet::owner_read(
+            user.can_read_calendars(&self.principal.id),
+        ))
     }
 }
 
-pub struct PrincipalResourceService(pub &'static [(&'static str, bool)]);
+pub struct PrincipalResourceService<AP: AuthenticationProvider> {
+    auth_provider: Arc<AP>,
+    home_set: &'static [(&'static str, bool)],
+    scheduling: SchedulingConfig,
+}
+
+impl<AP: AuthenticationProvider> PrincipalResourceService<AP> {
+    pub fn new(
+        auth_provider: Arc<AP>,
+        home_set: &'static [(&'static str, bool)],
+        scheduling: SchedulingConfig,
+    ) -> Self {
+        Self {
+            auth_provider,
+            home_set,
+            scheduling,
+        }
+    }
+}
 
 #[async_trait(?Send)]
-impl ResourceService for PrincipalResourceService {
+impl<AP: AuthenticationProvider> ResourceService for PrincipalResourceService<AP> {
     type PathComponents = (String,);
     type MemberType = CalendarSetResource;
     type Resource = PrincipalResource;
@@ -130,9 +228,21 @@ impl ResourceService for PrincipalResourceService {
         &self,
         (principal,): &Self::PathComponents,
     ) -> Result<Self::Resource, Self::Error> {
+        let principal = self
+            .auth_provider
+            .get_principal(principal)
+            .await?
+            .ok_or(Error::NotFound)?;
+        // Only groups have members
+        let members = match principal.principal_type {
+            PrincipalType::Group => self.auth_provider.get_group_members(&principal.id).await?,
+            PrincipalType::Individual => vec![],
+        };
         Ok(PrincipalResource {
-            principal: principal.to_owned(),
-            home_set: self.0,
+            principal,
+            members,
+            home_set: self.home_set,
+            scheduling: self.scheduling.clone(),
         })
     }
 
@@ -141,7 +251,7 @@ impl ResourceService for PrincipalResourceService {
         (principal,): &Self::PathComponents,
     ) -> Result<Vec<(String, Self::MemberType)>, Self::Error> {
         Ok(self
-            .0
+            .home_set
             .iter()
             .map(|&(set_name, read_only)| {
                 (
@@ -0,0 +1,227 @@
+use crate::calendar_object::resource::CalendarObjectResource;
+use crate::principal::PrincipalResource;
+use crate::scheduling::SchedulingConfig;
+use crate::Error;
+use actix_web::dev::ResourceMap;
+use actix_web::web::{Data, Path};
+use actix_web::HttpResponse;
+use async_trait::async_trait;
+use rustical_dav::extensions::{CommonPropertiesExtension, CommonPropertiesProp};
+use rustical_dav::privileges::UserPrivilegeSet;
+use rustical_dav::resource::{Resource, ResourceService};
+use rustical_dav::xml::{Resourcetype, ResourcetypeInner};
+use rustical_store::auth::User;
+use rustical_store::calendar::ItipMessage;
+use rustical_store::{CalendarObject, CalendarStore};
+use rustical_xml::{EnumUnitVariants, EnumVariants, XmlDeserialize, XmlSerialize};
+use std::sync::Arc;
+use tracing::{error, instrument};
+use tracing_actix_web::RootSpan;
+
+// Collection receiving the scheduling messages of a principal
+// https://datatracker.ietf.org/doc/html/rfc6638#section-2.2
+#[derive(Clone)]
+pub struct ScheduleInboxResource {
+    pub(crate) principal: String,
+}
+
+#[derive(XmlDeserialize, XmlSerialize, PartialEq, Clone, EnumVariants, EnumUnitVariants)]
+#[xml(unit_variants_ident = "ScheduleInboxPropWrapperName", untagged)]
+pub enum ScheduleInboxPropWrapper {
+    Common(CommonPropertiesProp),
+}
+
+impl Resource for ScheduleInboxResource {
+    type Prop = ScheduleInboxPropWrapper;
+    type Error = Error;
+    type PrincipalResource = PrincipalResource;
+
+    fn get_resourcetype(&self) -> Resourcetype {
+        Resourcetype(&[
+            ResourcetypeInner(Some(rustical_dav::namespace::NS_DAV), "collection"),
+            ResourcetypeInner(Some(rustical_dav::namespace::NS_CALDAV), "schedule-inbox"),
+        ])
+    }
+
+    fn get_prop(
+        &self,
+        rmap: &ResourceMap,
+        user: &User,
+        prop: &ScheduleInboxPropWrapperName,
+    ) -> Result<Self::Prop, Self::Error> {
+        Ok(match prop {
+            ScheduleInboxPropWrapperName::Common(prop) => ScheduleInboxPropWrapper::Common(
+                <Self as CommonPropertiesExtension>::get_prop(self, rmap, user, prop)?,
+            ),
+        })
+    }
+
+    fn get_owner(&self) -> Option<&str> {
+        Some(&self.principal)
+    }
+
+    fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
+        Ok(UserPrivilegeSet::owner_or_reader(
+            user.can_write_calendars(&self.principal),
+            user.can_read_calendars(&self.principal),
+        ))
+    }
+}
+
+pub struct ScheduleInboxResourceService<C: CalendarStore> {
+    cal_store: Arc<C>,
+}
+
+impl<C: CalendarStore> ScheduleInboxResourceService<C> {
+    pub fn new(cal_store: Arc<C>) -> Self {
+        Self { cal_store }
+    }
+}
+
+#[async_trait(?Send)]
+impl<C: CalendarStore> ResourceService for ScheduleInboxResourceService<C> {
+    type PathComponents = (String,);
+    type MemberType = CalendarObjectResource;
+    type Resource = ScheduleInboxResource;
+    type Error = Error;
+
+    async fn get_resource(
+        &self,
+        (principal,): &Self::PathComponents,
+    ) -> Result<Self::Resource, Self::Error> {
+        Ok(ScheduleInboxResource {
+            principal: principal.to_owned(),
+        })
+    }
+
+    async fn get_members(
+        &self,
+        (principal,): &Self::PathComponents,
+    ) -> Result<Vec<(String, Self::MemberType)>, Self::Error> {
+        Ok(self
+            .cal_store
+            .get_inbox_objects(principal)
+            .await?
+            .into_iter()
+            .map(|object| {
+                (
+                    object.get_id().to_owned(),
+                    CalendarObjectResource {
+                        object,
+                        principal: principal.to_owned(),
+                        calendar_data: None,
+                        share: None,
+                    },
+                )
+            })
+            .collect())
+    }
+}
+
+pub struct ScheduleInboxObjectResourceService<C: CalendarStore> {
+    cal_store: Arc<C>,
+}
+
+impl<C: CalendarStore> ScheduleInboxObjectResourceService<C> {
+    pub fn new(cal_store: Arc<C>) -> Self {
+        Self { cal_store }
+    }
+}
+
+#[async_trait(?Send)]
+impl<C: CalendarStore> ResourceService for ScheduleInboxObjectResourceService<C> {
+    type PathComponents = (String, String); // principal, object_id
+    type MemberType = CalendarObjectResource;
+    type Resource = CalendarObjectResource;
+    type Error = Error;
+
+    async fn get_resource(
+        &self,
+        (principal, object_id): &Self::PathComponents,
+    ) -> Result<Self::Resource, Self::Error> {
+        let object = self
+            .cal_store
+            .get_inbox_object(principal, object_id)
+            .await?;
+        Ok(CalendarObjectResource {
+            object,
+            principal: principal.to_owned(),
+            calendar_data: None,
+            share: None,
+        })
+    }
+
+    // Clients delete messages from the inbox once they processed them
+    async fn delete_resource(
+        &self,
+        (principal, object_id): &Self::PathComponents,
+        _use_trashbin: bool,
+        _rmap: &ResourceMap,
+    ) -> Result<(), Self::Error> {
+        self.cal_store
+            .delete_inbox_object(principal, object_id)
+            .await?;
+        Ok(())
+    }
+
+    #[inline]
+    fn actix_additional_routes(res: actix_web::Resource) -> actix_web::Resource {
+        res.get(get_inbox_object::<C>)
+    }
+}
+
+#[instrument(parent = root_span.id(), skip(store, root_span))]
+pub async fn get_inbox_object<C: CalendarStore>(
+    path: Path<(String, String)>,
+    store: Data<C>,
+    user: User,
+    root_span: RootSpan,
+) -> Result<HttpResponse, Error> {
+    let (principal, object_id) = path.into_inner();
+    if !user.can_read_calendars(&principal) {
+        return Ok(HttpResponse::Unauthorized().body(""));
+    }
+
+    let object = store.get_inbox_object(&principal, &object_id).await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("ETag", object.get_etag()))
+        .insert_header(("Content-Type", "text/calendar"))
+        .body(object.get_ics().to_owned()))
+}
+
+// Delivers scheduling messages into the inboxes of local recipients
+// https://datatracker.ietf.org/doc/html/rfc6638#section-4
+pub(crate) async fn deliver_itip_messages<C: CalendarStore>(
+    store: &C,
+    rmap: &ResourceMap,
+    scheduling: &SchedulingConfig,
+    messages: &[ItipMessage],
+) {
+    for message in messages {
+        for recipient in &message.recipients {
+            // Messages to external calendar users are left to the client
+            let Some(principal) =
+                PrincipalResource::get_principal_from_address(rmap, scheduling, recipient)
+            else {
+                continue;
+            };
+            let object = match CalendarObject::from_ics(
+                uuid::Uuid::new_v4().to_string(),
+                message.ics.clone(),
+            ) {
+                Ok(object) => object,
+                Err(err) => {
+                    error!("Invalid {} message: {err}", message.method.as_str());
+                    continue;
+                }
+            };
+            if let Err(err) = store.put_inbox_object(principal, object).await {
+                error!(
+                    "Delivering {} message failed: {err}",
+                    message.method.as_str()
+                );
+            }
+        }
+    }
+}
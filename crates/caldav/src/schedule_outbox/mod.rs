@@ -0,0 +1,262 @@
+use crate::principal::PrincipalResource;
+use crate::scheduling::SchedulingConfig;
+use crate::Error;
+use actix_web::body::BoxBody;
+use actix_web::dev::ResourceMap;
+use actix_web::http::header::ContentType;
+use actix_web::web::{Data, Path};
+use actix_web::{HttpRequest, HttpResponse, Responder, ResponseError};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rustical_dav::extensions::{CommonPropertiesExtension, CommonPropertiesProp};
+use rustical_dav::privileges::UserPrivilegeSet;
+use rustical_dav::resource::{Resource, ResourceService};
+use rustical_dav::xml::{HrefElement, Resourcetype, ResourcetypeInner};
+use rustical_store::auth::User;
+use rustical_store::calendar::{
+    generate_freebusy_reply, get_busy_periods, get_timezones, CalDateTime, FreeBusyPeriod,
+};
+use rustical_store::calendar_store::CalendarQuery;
+use rustical_store::CalendarStore;
+use rustical_xml::{
+    EnumUnitVariants, EnumVariants, XmlDeserialize, XmlRootTag, XmlSerialize, XmlSerializeRoot,
+};
+use std::io::BufReader;
+use tracing::instrument;
+use tracing_actix_web::RootSpan;
+
+// Collection to POST scheduling requests to
+// https://datatracker.ietf.org/doc/html/rfc6638#section-2.1
+#[derive(Clone)]
+pub struct ScheduleOutboxResource {
+    pub(crate) principal: String,
+}
+
+#[derive(XmlDeserialize, XmlSerialize, PartialEq, Clone, EnumVariants, EnumUnitVariants)]
+#[xml(unit_variants_ident = "ScheduleOutboxPropWrapperName", untagged)]
+pub enum ScheduleOutboxPropWrapper {
+    Common(CommonPropertiesProp),
+}
+
+impl Resource for ScheduleOutboxResource {
+    type Prop = ScheduleOutboxPropWrapper;
+    type Error = Error;
+    type PrincipalResource = PrincipalResource;
+
+    fn get_resourcetype(&self) -> Resourcetype {
+        Resourcetype(&[
+            ResourcetypeInner(Some(rustical_dav::namespace::NS_DAV), "collection"),
+            ResourcetypeInner(Some(rustical_dav::namespace::NS_CALDAV), "schedule-outbox"),
+        ])
+    }
+
+    fn get_prop(
+        &self,
+        rmap: &ResourceMap,
+        user: &User,
+        prop: &ScheduleOutboxPropWrapperName,
+    ) -> Result<Self::Prop, Self::Error> {
+        Ok(match prop {
+            ScheduleOutboxPropWrapperName::Common(prop) => ScheduleOutboxPropWrapper::Common(
+                <Self as CommonPropertiesExtension>::get_prop(self, rmap, user, prop)?,
+            ),
+        })
+    }
+
+    fn get_owner(&self) -> Option<&str> {
+        Some(&self.principal)
+    }
+
+    fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
+        Ok(UserPrivilegeSet::owner_only(
+            user.can_write_calendars(&self.principal),
+        ))
+    }
+}
+
+pub struct ScheduleOutboxResourceService<C: CalendarStore>(std::marker::PhantomData<C>);
+
+impl<C: CalendarStore> Default for ScheduleOutboxResourceService<C> {
+    fn default() -> Self {
+        Self(std::marker::PhantomData)
+    }
+}
+
+#[async_trait(?Send)]
+impl<C: CalendarStore> ResourceService for ScheduleOutboxResourceService<C> {
+    type PathComponents = (String,);
+    type MemberType = ScheduleOutboxResource;
+    type Resource = ScheduleOutboxResource;
+    type Error = Error;
+
+    async fn get_resource(
+        &self,
+        (principal,): &Self::PathComponents,
+    ) -> Result<Self::Resource, Self::Error> {
+        Ok(ScheduleOutboxResource {
+            principal: principal.to_owned(),
+        })
+    }
+
+    #[inline]
+    fn actix_additional_routes(res: actix_web::Resource) -> actix_web::Resource {
+        res.post(route_post_outbox::<C>)
+    }
+}
+
+// <!ELEMENT response (recipient, request-status, calendar-data?, DAV:error?, DAV:responsedescription?)>
+#[derive(XmlSerialize)]
+struct ScheduleResponseElement {
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
+    recipient: HrefElement,
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
+    request_status: String,
+    #[xml(ns = "rustical_dav::namespace::NS_CALDAV")]
+    calendar_data: Option<String>,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6638#section-10.2
+#[derive(XmlSerialize, XmlRootTag)]
+#[xml(root = b"schedule-response", ns = "rustical_dav::namespace::NS_CALDAV")]
+#[xml(ns_prefix(
+    rustical_dav::namespace::NS_DAV = b"",
+    rustical_dav::namespace::NS_CALDAV = b"CAL"
+))]
+struct ScheduleResponse {
+    #[xml(
+        ns = "rustical_dav::namespace::NS_CALDAV",
+        rename = b"response",
+        flatten
+    )]
+    responses: Vec<ScheduleResponseElement>,
+}
+
+impl Responder for ScheduleResponse {
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let mut output: Vec<_> = b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".into();
+        let mut writer = quick_xml::Writer::new_with_indent(&mut output, b' ', 4);
+        if let Err(err) = self.serialize_root(&mut writer) {
+            return rustical_dav::Error::from(err).error_response();
+        }
+
+        HttpResponse::Ok()
+            .content_type(ContentType::xml())
+            .body(String::from_utf8(output).unwrap())
+    }
+}
+
+// Busy time across all calendars of a principal
+async fn get_principal_busy_periods<C: CalendarStore>(
+    store: &C,
+    principal: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<FreeBusyPeriod>, Error> {
+    let mut objects = vec![];
+    for calendar in store.get_calendars(principal).await? {
+        // Subscribed calendars don't affect availability
+        if calendar.subscription_url.is_some() {
+            continue;
+        }
+        let query = CalendarQuery {
+            time_start: Some(start.date_naive()),
+            time_end: Some(end.date_naive()),
+        };
+        objects.extend(store.calendar_query(principal, &calendar.id, query).await?);
+    }
+    Ok(get_busy_periods(&objects, start, end)?)
+}
+
+// Free-busy lookups between local calendar users
+// https://datatracker.ietf.org/doc/html/rfc6638#section-5
+#[instrument(parent = root_span.id(), skip(store, root_span, req, scheduling))]
+pub async fn route_post_outbox<C: CalendarStore>(
+    path: Path<String>,
+    body: String,
+    user: User,
+    store: Data<C>,
+    scheduling: Data<SchedulingConfig>,
+    root_span: RootSpan,
+    req: HttpRequest,
+) -> Result<impl Responder, Error> {
+    let principal = path.into_inner();
+    if !user.can_write_calendars(&principal) {
+        return Err(Error::Unauthorized);
+    }
+    let bad_request =
+        |msg: &str| -> Error { rustical_dav::Error::BadRequest(msg.to_owned()).into() };
+
+    let cal = ical::IcalParser::new(BufReader::new(body.as_bytes()))
+        .next()
+        .ok_or_else(|| bad_request("Missing iCalendar object"))?
+        .map_err(|err| bad_request(&err.to_string()))?;
+    let is_request = cal
+        .properties
+        .iter()
+        .any(|prop| prop.name == "METHOD" && prop.value.as_deref() == Some("REQUEST"));
+    let [freebusy] = cal.free_busys.as_slice() else {
+        return Err(bad_request("Only VFREEBUSY requests are supported"));
+    };
+    if !is_request {
+        return Err(bad_request("Only VFREEBUSY requests are supported"));
+    }
+
+    let timezones = get_timezones(&cal);
+    let get_datetime = |name: &str| -> Result<DateTime<Utc>, Error> {
+        let prop = freebusy
+            .properties
+            .iter()
+            .find(|prop| prop.name == name)
+            .ok_or_else(|| bad_request(&format!("Missing {name}")))?;
+        Ok(CalDateTime::parse_prop(prop, &timezones)?
+            .ok_or_else(|| bad_request(&format!("Missing {name}")))?
+            .utc())
+    };
+    let (start, end) = (get_datetime("DTSTART")?, get_datetime("DTEND")?);
+
+    // Only the owner of the outbox may act as the organizer
+    let organizer = freebusy
+        .properties
+        .iter()
+        .find(|prop| prop.name == "ORGANIZER")
+        .and_then(|prop| prop.value.as_deref())
+        .and_then(|address| {
+            PrincipalResource::get_principal_from_address(req.resource_map(), &scheduling, address)
+        });
+    if organizer.as_deref() != Some(principal.as_str()) {
+        return Err(Error::Unauthorized);
+    }
+
+    let mut responses = vec![];
+    for attendee in freebusy
+        .properties
+        .iter()
+        .filter(|prop| prop.name == "ATTENDEE")
+    {
+        let address = attendee.value.to_owned().unwrap_or_default();
+        let Some(recipient) = PrincipalResource::get_principal_from_address(
+            req.resource_map(),
+            &scheduling,
+            &address,
+        ) else {
+            responses.push(ScheduleResponseElement {
+                recipient: address.into(),
+                request_status: "3.7;Invalid calendar user".to_owned(),
+                calendar_data: None,
+            });
+            continue;
+        };
+        let periods = get_principal_busy_periods(store.as_ref(), &recipient, start, end).await?;
+        responses.push(ScheduleResponseElement {
+            recipient: address.into(),
+            request_status: "2.0;Success".to_owned(),
+            calendar_data: Some(generate_freebusy_reply(
+                &periods, start, end, freebusy, attendee,
+            )),
+        });
+    }
+
+    Ok(ScheduleResponse { responses })
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct SchedulingConfig {
+    // Gives every principal the calendar user address mailto:<principal>@<email_domain>
+    // Clients put mailto: addresses into ORGANIZER and ATTENDEE, so without it implicit
+    // scheduling only works with principal URLs
+    pub email_domain: Option<String>,
+}
+
+impl SchedulingConfig {
+    pub fn get_email_address(&self, principal: &str) -> Option<String> {
+        self.email_domain
+            .as_ref()
+            .map(|domain| format!("mailto:{principal}@{domain}"))
+    }
+
+    // Maps mailto:<principal>@<email_domain> back to the principal
+    pub fn get_principal_from_email(&self, address: &str) -> Option<String> {
+        let domain = self.email_domain.as_deref()?;
+        let (scheme, email) = address.split_once(':')?;
+        if !scheme.eq_ignore_ascii_case("mailto") {
+            return None;
+        }
+        let (principal, email_domain) = email.rsplit_once('@')?;
+        (!principal.is_empty() && email_domain.eq_ignore_ascii_case(domain))
+            .then(|| principal.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SchedulingConfig;
+    use rustical_store::{
+        calendar::{get_itip_messages, ItipMethod},
+        CalendarObject,
+    };
+
+    fn config() -> SchedulingConfig {
+        SchedulingConfig {
+            email_domain: Some("example.com".to_owned()),
+        }
+    }
+
+    fn meeting(bob_partstat: &str) -> CalendarObject {
+        CalendarObject::from_ics(
+            "meeting".to_owned(),
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VEVENT\r\nUID:meeting\r\nDTSTAMP:20240101T000000Z\r\nDTSTART:20240101T100000Z\r\nDTEND:20240101T110000Z\r\nORGANIZER;CN=Alice:mailto:alice@example.com\r\nATTENDEE;PARTSTAT=ACCEPTED:mailto:alice@example.com\r\nATTENDEE;PARTSTAT={bob_partstat}:MAILTO:bob@Example.com\r\nATTENDEE:mailto:carol@example.org\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+            ),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_itip_with_email_addresses() {
+        let config = config();
+        let is_principal = |principal: &'static str| {
+            let config = config.clone();
+            move |address: &str| {
+                config.get_principal_from_email(address).as_deref() == Some(principal)
+            }
+        };
+
+        // The organizer invites both attendees, only bob is delivered locally
+        let invite = meeting("NEEDS-ACTION");
+        let messages = get_itip_messages(None, Some(&invite), is_principal("alice")).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method, ItipMethod::Request);
+        let local: Vec<_> = messages[0]
+            .recipients
+            .iter()
+            .filter_map(|recipient| config.get_principal_from_email(recipient))
+            .collect();
+        assert_eq!(local, vec!["bob".to_owned()]);
+
+        // Bob's reply goes back to the organizer
+        let accepted = meeting("ACCEPTED");
+        let messages =
+            get_itip_messages(Some(&invite), Some(&accepted), is_principal("bob")).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method, ItipMethod::Reply);
+        assert_eq!(
+            config
+                .get_principal_from_email(&messages[0].recipients[0])
+                .as_deref(),
+            Some("alice")
+        );
+    }
+
+    #[test]
+    fn test_email_addresses() {
+        let config = config();
+        assert_eq!(
+            config.get_email_address("alice").as_deref(),
+            Some("mailto:alice@example.com")
+        );
+        for address in ["mailto:alice@example.com", "MAILTO:alice@Example.COM"] {
+            assert_eq!(
+                config.get_principal_from_email(address).as_deref(),
+                Some("alice")
+            );
+        }
+        for address in [
+            "mailto:alice@example.org",
+            "mailto:@example.com",
+            "https://example.com/alice",
+            "alice@example.com",
+        ] {
+            assert_eq!(config.get_principal_from_email(address), None);
+        }
+
+        let config = SchedulingConfig::default();
+        assert_eq!(config.get_email_address("alice"), None);
+        assert_eq!(
+            config.get_principal_from_email("mailto:alice@example.com"),
+            None
+        );
+    }
+}
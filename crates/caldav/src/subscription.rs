@@ -25,6 +25,7 @@ pub fn subscription_resource<S: SubscriptionStore>() -> actix_web::Resource {
 
 #[derive(XmlSerialize, XmlRootTag)]
 #[xml(root = b"push-message", ns = "rustical_dav::namespace::NS_DAVPUSH")]
+#[allow(dead_code)]
 pub struct PushMessage {
     propstat: PropstatElement<CalendarProp>,
 }
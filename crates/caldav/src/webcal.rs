@@ -0,0 +1,309 @@
+use anyhow::{anyhow, bail, Result};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header, redirect, StatusCode, Url,
+};
+use rustical_store::{
+    calendar::{split_webcal_feed, WebcalStatus},
+    Calendar, CalendarStore,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct WebcalConfig {
+    // Periodically download the feeds of subscribed calendars
+    pub enabled: bool,
+    // Seconds between two refreshes
+    pub refresh_interval: u64,
+    // Feeds larger than this many bytes are rejected
+    pub max_size: usize,
+    // Subscription URLs are chosen by users, so by default they must not point into the local network
+    pub allow_private_addresses: bool,
+}
+
+impl Default for WebcalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            refresh_interval: 3600,
+            max_size: 10 * 1024 * 1024,
+            allow_private_addresses: false,
+        }
+    }
+}
+
+// Loopback, link-local and private networks
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_ipv4(ip),
+            None => is_private_ipv6(ip),
+        },
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // Carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()
+}
+
+// Host names are checked after resolving them, IP addresses in the URL right away
+fn check_url(url: &Url, allow_private_addresses: bool) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("Unsupported URL scheme {}", url.scheme());
+    }
+    let ip = match url.host() {
+        Some(url::Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(url::Host::Ipv6(ip)) => IpAddr::V6(ip),
+        Some(url::Host::Domain(_)) => return Ok(()),
+        None => bail!("URL has no host"),
+    };
+    if !allow_private_addresses && is_private_ip(ip) {
+        bail!("{ip} is a private address");
+    }
+    Ok(())
+}
+
+// Drops private addresses from DNS responses, so that neither the feed's host nor a redirect
+// target can resolve into the local network
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| !is_private_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(anyhow!("{host} only resolves to private addresses").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+pub fn webcal_client(config: &WebcalConfig) -> reqwest::Client {
+    let allow_private_addresses = config.allow_private_addresses;
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .redirect(redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(anyhow!("Too many redirects"));
+            }
+            match check_url(attempt.url(), allow_private_addresses) {
+                Ok(()) => attempt.follow(),
+                Err(err) => attempt.error(err),
+            }
+        }));
+    if !allow_private_addresses {
+        builder = builder.dns_resolver(Arc::new(PublicResolver));
+    }
+    builder.build().expect("Could not build HTTP client")
+}
+
+pub async fn webcal_refresher<C: CalendarStore>(config: WebcalConfig, store: Arc<C>) {
+    let client = webcal_client(&config);
+    let mut interval = tokio::time::interval(Duration::from_secs(config.refresh_interval.max(60)));
+    // Slow feeds shouldn't cause a burst of refreshes afterwards
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let calendars = match store.get_subscribed_calendars().await {
+            Ok(calendars) => calendars,
+            Err(err) => {
+                error!("{err}");
+                continue;
+            }
+        };
+        for calendar in calendars {
+            if let Err(err) = refresh_calendar(&client, &config, store.as_ref(), &calendar).await {
+                error!(
+                    "Could not refresh {}/{}: {err}",
+                    calendar.principal, calendar.id
+                );
+            }
+        }
+    }
+}
+
+// Downloads the feed of a subscribed calendar and records the outcome
+pub async fn refresh_calendar<C: CalendarStore>(
+    client: &reqwest::Client,
+    config: &WebcalConfig,
+    store: &C,
+    calendar: &Calendar,
+) -> Result<(), rustical_store::Error> {
+    let mut status = store
+        .get_webcal_status(&calendar.principal, &calendar.id)
+        .await?;
+    status.last_error = sync_feed(client, config, store, calendar, &mut status)
+        .await
+        .err()
+        .map(|err| err.to_string());
+    status.last_refresh = Some(chrono::Utc::now().naive_utc());
+    store
+        .put_webcal_status(&calendar.principal, &calendar.id, status)
+        .await
+}
+
+// Reads the body while making sure that it doesn't exceed max_size
+async fn read_body(mut response: reqwest::Response, max_size: usize) -> Result<String> {
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_size as u64)
+    {
+        bail!("Feed is larger than {max_size} bytes");
+    }
+    let mut body = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_size {
+            bail!("Feed is larger than {max_size} bytes");
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8(body)?)
+}
+
+async fn sync_feed<C: CalendarStore>(
+    client: &reqwest::Client,
+    config: &WebcalConfig,
+    store: &C,
+    calendar: &Calendar,
+    status: &mut WebcalStatus,
+) -> Result<()> {
+    let url = calendar
+        .subscription_url
+        .as_deref()
+        .ok_or(anyhow!("Calendar has no subscription URL"))?;
+    // webcal:// is just a hint for clients to subscribe to the URL
+    let url = match url.split_once("://") {
+        Some(("webcal" | "webcals", rest)) => format!("https://{rest}"),
+        _ => url.to_owned(),
+    };
+
+    check_url(&Url::parse(&url)?, config.allow_private_addresses)?;
+
+    let mut request = client.get(&url);
+    if let Some(etag) = &status.etag {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &status.last_modified {
+        request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = request.send().await?.error_for_status()?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(());
+    }
+    let header_value = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    let (etag, last_modified) = (
+        header_value(header::ETAG),
+        header_value(header::LAST_MODIFIED),
+    );
+    let objects = split_webcal_feed(&read_body(response, config.max_size).await?)?;
+
+    // Only apply the difference so that sync tokens and push notifications reflect actual changes
+    let mut existing: HashMap<_, _> = store
+        .get_objects(&calendar.principal, &calendar.id)
+        .await?
+        .into_iter()
+        .map(|object| (object.get_id().to_owned(), object))
+        .collect();
+    let (mut updated, mut deleted) = (0, 0);
+    for object in objects {
+        let unchanged = existing
+            .remove(object.get_id())
+            .is_some_and(|old| old.get_ics() == object.get_ics());
+        if !unchanged {
+            store
+                .put_object(
+                    calendar.principal.to_owned(),
+                    calendar.id.to_owned(),
+                    object,
+                    true,
+                )
+                .await?;
+            updated += 1;
+        }
+    }
+    for object_id in existing.keys() {
+        store
+            .delete_object(&calendar.principal, &calendar.id, object_id, false)
+            .await?;
+        deleted += 1;
+    }
+    info!(
+        "Refreshed {}/{} from {url}: {updated} updated, {deleted} deleted",
+        calendar.principal, calendar.id
+    );
+
+    // Only remember the validators once the feed has been applied
+    status.etag = etag;
+    status.last_modified = last_modified;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_url, is_private_ip};
+    use reqwest::Url;
+
+    #[test]
+    fn test_private_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{ip} should be private");
+        }
+        for ip in ["93.184.215.14", "2606:2800:21f:cb07:6820:80da:af6b:8b2c"] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{ip} should be public");
+        }
+
+        let url = |url: &str| Url::parse(url).unwrap();
+        assert!(check_url(&url("https://example.com/feed.ics"), false).is_ok());
+        assert!(check_url(&url("http://127.0.0.1:8080/feed.ics"), false).is_err());
+        assert!(check_url(&url("http://[::1]/feed.ics"), false).is_err());
+        assert!(check_url(&url("http://127.0.0.1:8080/feed.ics"), true).is_ok());
+        assert!(check_url(&url("file:///etc/passwd"), true).is_err());
+    }
+}
@@ -1,15 +1,21 @@
 use super::resource::AddressObjectPathComponents;
 use crate::addressbook::resource::AddressbookResource;
+use crate::addressbook::share::resolve_addressbook;
 use crate::Error;
 use actix_web::http::header;
 use actix_web::http::header::HeaderValue;
 use actix_web::web::{Data, Path};
 use actix_web::HttpRequest;
 use actix_web::HttpResponse;
-use rustical_dav::privileges::UserPrivilege;
+use rustical_dav::destination_header::Destination;
+use rustical_dav::lock::{lock_key, LockManager};
+use rustical_dav::overwrite_header::Overwrite;
+use rustical_dav::precondition::check_preconditions;
+use rustical_dav::privileges::{UserPrivilege, UserPrivilegeSet};
 use rustical_dav::resource::Resource;
+use rustical_dav::xml::error::Precondition;
 use rustical_store::auth::User;
-use rustical_store::{AddressObject, AddressbookStore};
+use rustical_store::{AddressObject, AddressbookStore, Share};
 use tracing::instrument;
 use tracing_actix_web::RootSpan;
 
@@ -26,12 +32,18 @@ pub async fn get_object<AS: AddressbookStore>(
         object_id,
     } = path.into_inner();
 
-    if user.id != principal {
+    if !user.is_principal(&principal) {
         return Ok(HttpResponse::Unauthorized().body(""));
     }
 
+    let (principal, addressbook_id, share) =
+        resolve_addressbook(store.as_ref(), &principal, &addressbook_id).await?;
     let addressbook = store.get_addressbook(&principal, &addressbook_id).await?;
-    let addressbook_resource = AddressbookResource(addressbook);
+    let addressbook_resource = AddressbookResource {
+        addressbook,
+        share,
+        shares: vec![],
+    };
     if !addressbook_resource
         .get_user_privileges(&user)?
         .has(&UserPrivilege::Read)
@@ -49,13 +61,14 @@ pub async fn get_object<AS: AddressbookStore>(
         .body(object.get_vcf().to_owned()))
 }
 
-#[instrument(parent = root_span.id(), skip(store, req, root_span))]
+#[instrument(parent = root_span.id(), skip(store, req, root_span, lock_manager))]
 pub async fn put_object<AS: AddressbookStore>(
     path: Path<AddressObjectPathComponents>,
     store: Data<AS>,
     body: String,
     user: User,
     req: HttpRequest,
+    lock_manager: Data<LockManager>,
     root_span: RootSpan,
 ) -> Result<HttpResponse, Error> {
     let AddressObjectPathComponents {
@@ -64,20 +77,177 @@ pub async fn put_object<AS: AddressbookStore>(
         object_id,
     } = path.into_inner();
 
-    if user.id != principal {
+    if !user.is_principal(&principal) {
         return Ok(HttpResponse::Unauthorized().body(""));
     }
-
-    // TODO: implement If-Match
-    //
+    let (principal, addressbook_id, share) =
+        resolve_addressbook(store.as_ref(), &principal, &addressbook_id).await?;
+    if let Some(share) = &share {
+        if !UserPrivilegeSet::from(share.access).has(&UserPrivilege::WriteContent) {
+            return Ok(HttpResponse::Unauthorized().body(""));
+        }
+    }
 
     let overwrite =
         Some(&HeaderValue::from_static("*")) != req.headers().get(header::IF_NONE_MATCH);
 
     let object = AddressObject::from_vcf(object_id, body)?;
+    let old_object = match store
+        .get_object(&principal, &addressbook_id, object.get_id())
+        .await
+    {
+        Ok(old_object) => Some(old_object),
+        Err(rustical_store::Error::NotFound) => None,
+        Err(err) => return Err(err.into()),
+    };
+    let etag = old_object.as_ref().map(AddressObject::get_etag);
+    check_preconditions(&req, etag.as_deref())?;
+    let key = lock_key(req.path(), share.as_ref());
+    lock_manager.check_write(&req, &user, req.path(), &key, etag.as_deref())?;
+    let new_etag = object.get_etag();
     store
         .put_object(principal, addressbook_id, object, overwrite)
         .await?;
 
-    Ok(HttpResponse::Created().body(""))
+    Ok(match old_object {
+        Some(_) => HttpResponse::NoContent(),
+        None => HttpResponse::Created(),
+    }
+    .insert_header((header::ETAG, new_etag))
+    .body(""))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4918#section-9.8
+#[instrument(parent = root_span.id(), skip(store, req, root_span, lock_manager))]
+#[allow(clippy::too_many_arguments)]
+pub async fn copy_move_object<AS: AddressbookStore>(
+    path: Path<AddressObjectPathComponents>,
+    store: Data<AS>,
+    user: User,
+    destination: Destination,
+    Overwrite(overwrite): Overwrite,
+    req: HttpRequest,
+    lock_manager: Data<LockManager>,
+    root_span: RootSpan,
+) -> Result<HttpResponse, Error> {
+    let AddressObjectPathComponents {
+        principal,
+        addressbook_id,
+        object_id,
+    } = path.into_inner();
+    let is_move = req.method().as_str() == "MOVE";
+
+    if !user.is_principal(&principal) {
+        return Ok(HttpResponse::Unauthorized().body(""));
+    }
+    // Objects can only be copied between addressbooks of the same principal
+    let Some((dest_addressbook_id, dest_object_id)) = destination.sibling_object(req.path()) else {
+        return Ok(HttpResponse::Forbidden().body("Invalid destination"));
+    };
+    let dest_object_id = dest_object_id.trim_end_matches(".vcf");
+    if (dest_addressbook_id, dest_object_id) == (addressbook_id.as_str(), object_id.as_str()) {
+        return Ok(HttpResponse::Forbidden().body("Source and destination are the same"));
+    }
+
+    let (owner, addressbook_id, share) =
+        resolve_addressbook(store.as_ref(), &principal, &addressbook_id).await?;
+    let (dest_owner, dest_addressbook_id, dest_share) =
+        resolve_addressbook(store.as_ref(), &principal, dest_addressbook_id).await?;
+    if owner != dest_owner {
+        return Ok(HttpResponse::Forbidden().body("Destination belongs to another principal"));
+    }
+    let can_write = |share: &Option<Share>| {
+        share.as_ref().is_none_or(|share| {
+            UserPrivilegeSet::from(share.access).has(&UserPrivilege::WriteContent)
+        })
+    };
+    if (is_move && !can_write(&share)) || !can_write(&dest_share) {
+        return Ok(HttpResponse::Unauthorized().body(""));
+    }
+    // Make sure that both addressbooks exist
+    store.get_addressbook(&owner, &dest_addressbook_id).await?;
+    let object = store
+        .get_object(&owner, &addressbook_id, &object_id)
+        .await?;
+    let etag = object.get_etag();
+    check_preconditions(&req, Some(&etag))?;
+
+    let dest_etag = match store
+        .get_object(&owner, &dest_addressbook_id, dest_object_id)
+        .await
+    {
+        Ok(dest_object) => Some(dest_object.get_etag()),
+        Err(rustical_store::Error::NotFound) => None,
+        Err(err) => return Err(err.into()),
+    };
+    let key = lock_key(req.path(), share.as_ref());
+    if is_move {
+        lock_manager.check_write(&req, &user, req.path(), &key, Some(&etag))?;
+    }
+    let dest_path = destination.path();
+    lock_manager.check_write(
+        &req,
+        &user,
+        &dest_path,
+        &lock_key(&dest_path, dest_share.as_ref()),
+        dest_etag.as_deref(),
+    )?;
+    let dest_exists = dest_etag.is_some();
+    if dest_exists && !overwrite {
+        return Ok(HttpResponse::PreconditionFailed().body(""));
+    }
+    // An addressbook must not contain two objects with the same UID, a moved object leaves its own
+    // https://datatracker.ietf.org/doc/html/rfc6352#section-6.3.2.1
+    if let Some(uid) = object.get_uid() {
+        let is_source =
+            |id: &str| is_move && dest_addressbook_id == addressbook_id && id == object_id;
+        if let Some(conflict) = store
+            .get_objects(&owner, &dest_addressbook_id)
+            .await?
+            .into_iter()
+            .find(|other| {
+                other.get_uid() == Some(uid)
+                    && other.get_id() != dest_object_id
+                    && !is_source(other.get_id())
+            })
+        {
+            let (parent, _) = dest_path.rsplit_once('/').unwrap_or_default();
+            return Err(
+                rustical_dav::Error::Conflict(Precondition::AddressbookNoUidConflict(
+                    format!("{parent}/{}", conflict.get_id()).into(),
+                ))
+                .into(),
+            );
+        }
+    }
+
+    if is_move {
+        store
+            .move_object(
+                &owner,
+                &addressbook_id,
+                &object_id,
+                &dest_addressbook_id,
+                dest_object_id,
+                overwrite,
+            )
+            .await?;
+        lock_manager.remove_locks(&key);
+    } else {
+        store
+            .copy_object(
+                &owner,
+                &addressbook_id,
+                &object_id,
+                &dest_addressbook_id,
+                dest_object_id,
+                overwrite,
+            )
+            .await?;
+    }
+
+    Ok(match dest_exists {
+        true => HttpResponse::NoContent().finish(),
+        false => HttpResponse::Created().finish(),
+    })
 }
@@ -1,5 +1,7 @@
-use crate::{principal::PrincipalResource, Error};
+use crate::{addressbook::share::resolve_addressbook, principal::PrincipalResource, Error};
 use actix_web::dev::ResourceMap;
+use actix_web::http::Method;
+use actix_web::web;
 use async_trait::async_trait;
 use derive_more::derive::{Constructor, From, Into};
 use rustical_dav::{
@@ -8,12 +10,13 @@ use rustical_dav::{
     resource::{Resource, ResourceService},
     xml::Resourcetype,
 };
-use rustical_store::{auth::User, AddressObject, AddressbookStore};
+use rustical_store::{auth::User, AddressObject, AddressbookStore, Share};
 use rustical_xml::{EnumUnitVariants, EnumVariants, XmlDeserialize, XmlSerialize};
 use serde::Deserialize;
+use std::str::FromStr;
 use std::sync::Arc;
 
-use super::methods::{get_object, put_object};
+use super::methods::{copy_move_object, get_object, put_object};
 
 #[derive(Constructor)]
 pub struct AddressObjectResourceService<AS: AddressbookStore> {
@@ -45,6 +48,8 @@ pub enum AddressObjectPropWrapper {
 pub struct AddressObjectResource {
     pub object: AddressObject,
     pub principal: String,
+    // Set if the object is accessed through a shared addressbook
+    pub share: Option<Share>,
 }
 
 impl Resource for AddressObjectResource {
@@ -86,8 +91,25 @@ impl Resource for AddressObjectResource {
         Some(&self.principal)
     }
 
+    fn get_etag(&self) -> Option<String> {
+        Some(self.object.get_etag())
+    }
+
+    fn get_share(&self) -> Option<&Share> {
+        self.share.as_ref()
+    }
+
     fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
-        Ok(UserPrivilegeSet::owner_only(self.principal == user.id))
+        if let Some(share) = self
+            .share
+            .as_ref()
+            .filter(|share| user.is_principal(&share.sharee))
+        {
+            return Ok(share.access.into());
+        }
+        Ok(UserPrivilegeSet::owner_only(
+            user.is_principal(&self.principal),
+        ))
     }
 }
 
@@ -131,13 +153,16 @@ impl<AS: AddressbookStore> ResourceService for AddressObjectResourceService<AS>
             object_id,
         }: &Self::PathComponents,
     ) -> Result<Self::Resource, Self::Error> {
+        let (principal, addressbook_id, share) =
+            resolve_addressbook(self.addr_store.as_ref(), principal, addressbook_id).await?;
         let object = self
             .addr_store
-            .get_object(principal, addressbook_id, object_id)
+            .get_object(&principal, &addressbook_id, object_id)
             .await?;
         Ok(AddressObjectResource {
             object,
-            principal: principal.to_owned(),
+            principal,
+            share,
         })
     }
 
@@ -149,15 +174,21 @@ impl<AS: AddressbookStore> ResourceService for AddressObjectResourceService<AS>
             object_id,
         }: &Self::PathComponents,
         use_trashbin: bool,
+        _rmap: &ResourceMap,
     ) -> Result<(), Self::Error> {
+        let (principal, addressbook_id, _share) =
+            resolve_addressbook(self.addr_store.as_ref(), principal, addressbook_id).await?;
         self.addr_store
-            .delete_object(principal, addressbook_id, object_id, use_trashbin)
+            .delete_object(&principal, &addressbook_id, object_id, use_trashbin)
             .await?;
         Ok(())
     }
 
     #[inline]
     fn actix_additional_routes(res: actix_web::Resource) -> actix_web::Resource {
-        res.get(get_object::<AS>).put(put_object::<AS>)
+        res.get(get_object::<AS>)
+            .put(put_object::<AS>)
+            .route(web::method(Method::from_str("COPY").unwrap()).to(copy_move_object::<AS>))
+            .route(web::method(Method::from_str("MOVE").unwrap()).to(copy_move_object::<AS>))
     }
 }
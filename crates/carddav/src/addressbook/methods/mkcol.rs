@@ -1,7 +1,7 @@
 use crate::Error;
 use actix_web::web::Path;
 use actix_web::{web::Data, HttpResponse};
-use rustical_store::{auth::User, Addressbook, AddressbookStore};
+use rustical_store::{auth::User, Addressbook, AddressbookStore, Share};
 use rustical_xml::{XmlDeserialize, XmlDocument, XmlRootTag};
 use tracing::instrument;
 use tracing_actix_web::RootSpan;
@@ -48,9 +48,15 @@ pub async fn route_mkcol<AS: AddressbookStore>(
     root_span: RootSpan,
 ) -> Result<HttpResponse, Error> {
     let (principal, addressbook_id) = path.into_inner();
-    if principal != user.id {
+    if !user.is_principal(&principal) {
         return Err(Error::Unauthorized);
     }
+    if !Share::is_valid_collection_id(&addressbook_id) {
+        return Err(rustical_dav::Error::BadRequest(format!(
+            "Invalid addressbook id: {addressbook_id}"
+        ))
+        .into());
+    }
 
     let request = MkcolRequest::parse_str(&body)?;
     let request = request.set.prop;
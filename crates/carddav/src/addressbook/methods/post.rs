@@ -1,30 +1,55 @@
+use crate::addressbook::share::resolve_addressbook;
+use crate::principal::PrincipalResource;
 use crate::Error;
 use actix_web::http::header;
 use actix_web::web::{Data, Path};
 use actix_web::{HttpRequest, HttpResponse};
 use rustical_dav::push::PushRegister;
-use rustical_store::auth::User;
-use rustical_store::{AddressbookStore, Subscription, SubscriptionStore};
+use rustical_dav::xml::sharing::ShareRequest;
+use rustical_store::auth::{AuthenticationProvider, User};
+use rustical_store::{AddressbookStore, Share, Subscription, SubscriptionStore};
 use rustical_xml::XmlDocument;
 use tracing::instrument;
 use tracing_actix_web::RootSpan;
 
-#[instrument(parent = root_span.id(), skip(store, subscription_store, root_span, req))]
-pub async fn route_post<A: AddressbookStore, S: SubscriptionStore>(
+#[instrument(parent = root_span.id(), skip(auth_provider, store, subscription_store, root_span, req))]
+#[allow(clippy::too_many_arguments)]
+pub async fn route_post<AP: AuthenticationProvider, A: AddressbookStore, S: SubscriptionStore>(
     path: Path<(String, String)>,
     body: String,
     user: User,
+    auth_provider: Data<AP>,
     store: Data<A>,
     subscription_store: Data<S>,
     root_span: RootSpan,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (principal, addressbook_id) = path.into_inner();
-    if principal != user.id {
+    if !user.is_principal(&principal) {
         return Err(Error::Unauthorized);
     }
 
+    let (principal, addressbook_id, share) =
+        resolve_addressbook(store.as_ref(), &principal, &addressbook_id).await?;
+
     let addressbook = store.get_addressbook(&principal, &addressbook_id).await?;
+
+    if let Ok(share_request) = ShareRequest::parse_str(&body) {
+        // Only the owner may share an addressbook
+        if share.is_some() {
+            return Err(Error::Unauthorized);
+        }
+        return handle_share_request(
+            share_request,
+            &principal,
+            &addressbook_id,
+            auth_provider.as_ref(),
+            store.as_ref(),
+            &req,
+        )
+        .await;
+    }
+
     let request = PushRegister::parse_str(&body)?;
     let sub_id = uuid::Uuid::new_v4().to_string();
 
@@ -57,3 +82,59 @@ pub async fn route_post<A: AddressbookStore, S: SubscriptionStore>(
         .append_header((header::EXPIRES, expires.to_rfc2822()))
         .finish())
 }
+
+// Changes the sharees of an addressbook
+// https://github.com/apple/ccs-calendarserver/blob/master/doc/Extensions/caldav-sharing.txt
+async fn handle_share_request<AP: AuthenticationProvider, A: AddressbookStore>(
+    share_request: ShareRequest,
+    principal: &str,
+    addressbook_id: &str,
+    auth_provider: &AP,
+    store: &A,
+    req: &HttpRequest,
+) -> Result<HttpResponse, Error> {
+    // Addressbooks created before ids were validated cannot be mounted unambiguously
+    if !Share::is_valid_collection_id(addressbook_id) {
+        return Err(rustical_dav::Error::BadRequest(format!(
+            "Addressbook {addressbook_id} cannot be shared"
+        ))
+        .into());
+    }
+
+    let mut changes = vec![];
+    for (href, access) in share_request.into_changes() {
+        let sharee = PrincipalResource::get_principal_from_href(req.resource_map(), &href)
+            .ok_or_else(|| rustical_dav::Error::BadRequest(format!("Invalid sharee: {href}")))?;
+        if sharee == principal {
+            return Err(rustical_dav::Error::BadRequest(
+                "Addressbooks cannot be shared with their owner".to_owned(),
+            )
+            .into());
+        }
+        if access.is_some() && auth_provider.get_principal(&sharee).await?.is_none() {
+            return Err(rustical_dav::Error::BadRequest(format!("Unknown sharee: {href}")).into());
+        }
+        changes.push((sharee, access));
+    }
+
+    for (sharee, access) in changes {
+        match access {
+            Some(access) => {
+                store
+                    .put_addressbook_share(Share {
+                        principal: principal.to_owned(),
+                        collection_id: addressbook_id.to_owned(),
+                        sharee,
+                        access,
+                    })
+                    .await?
+            }
+            None => {
+                store
+                    .delete_addressbook_share(principal, addressbook_id, &sharee)
+                    .await?
+            }
+        }
+    }
+    Ok(HttpResponse::Ok().finish())
+}
@@ -11,7 +11,7 @@ use rustical_dav::{
     resource::Resource,
     xml::{multistatus::ResponseElement, MultistatusElement, PropElement, PropfindType},
 };
-use rustical_store::{auth::User, AddressObject, AddressbookStore};
+use rustical_store::{auth::User, AddressObject, AddressbookStore, Share};
 use rustical_xml::XmlDeserialize;
 
 #[derive(XmlDeserialize, Clone, Debug, PartialEq)]
@@ -59,6 +59,7 @@ pub async fn handle_addressbook_multiget<AS: AddressbookStore>(
     user: &User,
     principal: &str,
     cal_id: &str,
+    share: Option<&Share>,
     addr_store: &AS,
 ) -> Result<MultistatusElement<AddressObjectPropWrapper, String>, Error> {
     let (objects, not_found) =
@@ -85,6 +86,7 @@ pub async fn handle_addressbook_multiget<AS: AddressbookStore>(
             AddressObjectResource {
                 object,
                 principal: principal.to_owned(),
+                share: share.cloned(),
             }
             .propfind(&path, &props, user, req.resource_map())?,
         );
@@ -0,0 +1,242 @@
+use crate::{
+    address_object::resource::{AddressObjectPropWrapper, AddressObjectResource},
+    Error,
+};
+use actix_web::{http::StatusCode, HttpRequest};
+use ical::property::Property;
+use rustical_dav::{
+    resource::Resource,
+    xml::{
+        multistatus::ResponseElement, MultistatusElement, PropElement, PropfindType, TextCollation,
+        TextMatchElement,
+    },
+};
+use rustical_store::{auth::User, AddressObject, AddressbookStore, Share};
+use rustical_xml::{ValueDeserialize, XmlDeserialize, XmlError};
+
+// https://datatracker.ietf.org/doc/html/rfc6352#section-10.5
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) enum FilterTest {
+    #[default]
+    AnyOf,
+    AllOf,
+}
+
+impl FilterTest {
+    fn apply(&self, mut results: impl Iterator<Item = bool>) -> bool {
+        match self {
+            Self::AnyOf => results.any(|result| result),
+            Self::AllOf => results.all(|result| result),
+        }
+    }
+}
+
+impl ValueDeserialize for FilterTest {
+    fn deserialize(val: &str) -> Result<Self, XmlError> {
+        match <String as ValueDeserialize>::deserialize(val)?.as_str() {
+            "anyof" => Ok(Self::AnyOf),
+            "allof" => Ok(Self::AllOf),
+            _ => Err(XmlError::Other(format!(
+                "Invalid test: {val}, must be anyof or allof"
+            ))),
+        }
+    }
+}
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+// <!ELEMENT param-filter (is-not-defined | text-match)?>
+// https://datatracker.ietf.org/doc/html/rfc6352#section-10.5.2
+pub(crate) struct ParamFilterElement {
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
+    pub(crate) is_not_defined: Option<()>,
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
+    pub(crate) text_match: Option<TextMatchElement>,
+
+    #[xml(ty = "attr")]
+    pub(crate) name: String,
+}
+
+impl ParamFilterElement {
+    fn matches(&self, prop: &Property) -> bool {
+        let mut values = prop
+            .params
+            .iter()
+            .flatten()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(&self.name))
+            .flat_map(|(_, values)| values)
+            .peekable();
+
+        match (self.is_not_defined, &self.text_match) {
+            (Some(()), _) => values.peek().is_none(),
+            (None, None) => values.peek().is_some(),
+            (None, Some(text_match)) => {
+                values.any(|value| text_match.matches(value, TextCollation::UnicodeCasemap))
+            }
+        }
+    }
+}
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+// <!ELEMENT prop-filter (is-not-defined | (text-match*, param-filter*))>
+// https://datatracker.ietf.org/doc/html/rfc6352#section-10.5.1
+pub(crate) struct PropFilterElement {
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
+    pub(crate) is_not_defined: Option<()>,
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV", flatten)]
+    pub(crate) text_match: Vec<TextMatchElement>,
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV", flatten)]
+    pub(crate) param_filter: Vec<ParamFilterElement>,
+
+    #[xml(ty = "attr")]
+    pub(crate) name: String,
+    #[xml(ty = "attr", default = "Default::default")]
+    pub(crate) test: FilterTest,
+}
+
+impl PropFilterElement {
+    fn matches(&self, properties: &[Property]) -> bool {
+        // Property names may be prefixed with a group, e.g. item1.EMAIL
+        let mut props = properties
+            .iter()
+            .filter(|prop| {
+                let name = prop.name.rsplit('.').next().unwrap_or_default();
+                name.eq_ignore_ascii_case(&self.name)
+            })
+            .peekable();
+
+        if self.is_not_defined.is_some() {
+            return props.peek().is_none();
+        }
+        // The filter matches if any instance of the property matches
+        props.any(|prop| self.matches_property(prop))
+    }
+
+    fn matches_property(&self, prop: &Property) -> bool {
+        if self.text_match.is_empty() && self.param_filter.is_empty() {
+            return true;
+        }
+        let value = prop.value.as_deref().unwrap_or_default();
+        let text_matches = self
+            .text_match
+            .iter()
+            .map(|text_match| text_match.matches(value, TextCollation::UnicodeCasemap));
+        let param_matches = self.param_filter.iter().map(|filter| filter.matches(prop));
+        self.test.apply(text_matches.chain(param_matches))
+    }
+}
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+// <!ELEMENT filter (prop-filter*)>
+// https://datatracker.ietf.org/doc/html/rfc6352#section-10.5
+pub(crate) struct FilterElement {
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV", flatten)]
+    pub(crate) prop_filter: Vec<PropFilterElement>,
+
+    #[xml(ty = "attr", default = "Default::default")]
+    pub(crate) test: FilterTest,
+}
+
+impl FilterElement {
+    pub fn matches(&self, object: &AddressObject) -> bool {
+        // An empty filter matches all address objects
+        if self.prop_filter.is_empty() {
+            return true;
+        }
+        let properties = &object.get_vcard().properties;
+        self.test.apply(
+            self.prop_filter
+                .iter()
+                .map(|filter| filter.matches(properties)),
+        )
+    }
+}
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+// <!ELEMENT limit (nresults)>
+// https://datatracker.ietf.org/doc/html/rfc6352#section-8.6.1
+pub(crate) struct LimitElement {
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
+    pub(crate) nresults: u64,
+}
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+// <!ELEMENT addressbook-query ((DAV:allprop | DAV:propname | DAV:prop)?, filter, limit?)>
+// https://datatracker.ietf.org/doc/html/rfc6352#section-8.6
+pub struct AddressbookQueryRequest {
+    #[xml(ns = "rustical_dav::namespace::NS_DAV", ty = "untagged")]
+    pub(crate) prop: PropfindType,
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
+    pub(crate) filter: FilterElement,
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
+    pub(crate) limit: Option<LimitElement>,
+}
+
+pub async fn get_objects_addressbook_query<AS: AddressbookStore>(
+    addr_query: &AddressbookQueryRequest,
+    principal: &str,
+    addressbook_id: &str,
+    store: &AS,
+) -> Result<Vec<AddressObject>, Error> {
+    let mut objects = store.get_objects(principal, addressbook_id).await?;
+    objects.retain(|object| addr_query.filter.matches(object));
+    Ok(objects)
+}
+
+pub async fn handle_addressbook_query<AS: AddressbookStore>(
+    addr_query: AddressbookQueryRequest,
+    req: HttpRequest,
+    user: &User,
+    principal: &str,
+    addressbook_id: &str,
+    share: Option<&Share>,
+    addr_store: &AS,
+) -> Result<MultistatusElement<AddressObjectPropWrapper, String>, Error> {
+    let mut objects =
+        get_objects_addressbook_query(&addr_query, principal, addressbook_id, addr_store).await?;
+
+    // If the result set gets truncated the request-URI gets a 507 response
+    let mut member_responses = vec![];
+    if let Some(LimitElement { nresults }) = addr_query.limit {
+        let nresults = nresults as usize;
+        if objects.len() > nresults {
+            objects.truncate(nresults);
+            member_responses.push(ResponseElement {
+                href: req.path().to_owned(),
+                status: Some(StatusCode::INSUFFICIENT_STORAGE),
+                ..Default::default()
+            });
+        }
+    }
+
+    let props = match addr_query.prop {
+        PropfindType::Allprop => {
+            vec!["allprop".to_owned()]
+        }
+        PropfindType::Propname => {
+            vec!["propname".to_owned()]
+        }
+        PropfindType::Prop(PropElement(prop_tags)) => {
+            prop_tags.into_iter().map(|propname| propname.0).collect()
+        }
+    };
+    let props: Vec<&str> = props.iter().map(String::as_str).collect();
+
+    let mut responses = Vec::new();
+    for object in objects {
+        let path = format!("{}/{}", req.path().trim_end_matches('/'), object.get_id());
+        responses.push(
+            AddressObjectResource {
+                object,
+                principal: principal.to_owned(),
+                share: share.cloned(),
+            }
+            .propfind(&path, &props, user, req.resource_map())?,
+        );
+    }
+
+    Ok(MultistatusElement {
+        responses,
+        member_responses,
+        ..Default::default()
+    })
+}
@@ -1,9 +1,11 @@
+use crate::addressbook::share::resolve_addressbook;
 use crate::Error;
 use actix_web::{
     web::{Data, Path},
     HttpRequest, Responder,
 };
 use addressbook_multiget::{handle_addressbook_multiget, AddressbookMultigetRequest};
+use addressbook_query::{handle_addressbook_query, AddressbookQueryRequest};
 use rustical_dav::xml::sync_collection::SyncCollectionRequest;
 use rustical_store::{auth::User, AddressbookStore};
 use rustical_xml::{XmlDeserialize, XmlDocument};
@@ -11,12 +13,15 @@ use sync_collection::handle_sync_collection;
 use tracing::instrument;
 
 mod addressbook_multiget;
+mod addressbook_query;
 mod sync_collection;
 
 #[derive(XmlDeserialize, XmlDocument, Clone, Debug, PartialEq)]
 pub(crate) enum ReportRequest {
     #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
     AddressbookMultiget(AddressbookMultigetRequest),
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
+    AddressbookQuery(AddressbookQueryRequest),
     #[xml(ns = "rustical_dav::namespace::NS_DAV")]
     SyncCollection(SyncCollectionRequest),
 }
@@ -30,9 +35,11 @@ pub async fn route_report_addressbook<AS: AddressbookStore>(
     addr_store: Data<AS>,
 ) -> Result<impl Responder, Error> {
     let (principal, addressbook_id) = path.into_inner();
-    if principal != user.id {
+    if !user.is_principal(&principal) {
         return Err(Error::Unauthorized);
     }
+    let (principal, addressbook_id, share) =
+        resolve_addressbook(addr_store.as_ref(), &principal, &addressbook_id).await?;
 
     let request = ReportRequest::parse_str(&body)?;
 
@@ -44,6 +51,19 @@ pub async fn route_report_addressbook<AS: AddressbookStore>(
                 &user,
                 &principal,
                 &addressbook_id,
+                share.as_ref(),
+                addr_store.as_ref(),
+            )
+            .await?
+        }
+        ReportRequest::AddressbookQuery(addr_query) => {
+            handle_addressbook_query(
+                addr_query,
+                req,
+                &user,
+                &principal,
+                &addressbook_id,
+                share.as_ref(),
                 addr_store.as_ref(),
             )
             .await?
@@ -55,6 +75,7 @@ pub async fn route_report_addressbook<AS: AddressbookStore>(
                 &user,
                 &principal,
                 &addressbook_id,
+                share.as_ref(),
                 addr_store.as_ref(),
             )
             .await?
@@ -67,6 +88,8 @@ mod tests {
     use rustical_dav::xml::{sync_collection::SyncLevel, PropElement, Propname};
 
     use super::*;
+    use addressbook_query::{FilterElement, FilterTest};
+    use rustical_store::AddressObject;
 
     #[test]
     fn test_xml_sync_collection() {
@@ -121,4 +144,57 @@ mod tests {
             })
         )
     }
+
+    #[test]
+    fn test_addressbook_query_filter() {
+        let report_request = ReportRequest::parse_str(
+            r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <C:addressbook-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:carddav">
+                <D:prop>
+                    <D:getetag/>
+                </D:prop>
+                <C:filter test="allof">
+                    <C:prop-filter name="FN">
+                        <C:text-match collation="i;unicode-casemap" match-type="starts-with">jane</C:text-match>
+                    </C:prop-filter>
+                    <C:prop-filter name="EMAIL" test="anyof">
+                        <C:text-match match-type="ends-with">@example.org</C:text-match>
+                        <C:param-filter name="TYPE">
+                            <C:text-match match-type="equals">work</C:text-match>
+                        </C:param-filter>
+                    </C:prop-filter>
+                </C:filter>
+                <C:limit>
+                    <C:nresults>10</C:nresults>
+                </C:limit>
+            </C:addressbook-query>
+        "#,
+        )
+        .unwrap();
+        let ReportRequest::AddressbookQuery(addr_query) = report_request else {
+            panic!("expected addressbook-query");
+        };
+        assert_eq!(addr_query.limit.as_ref().unwrap().nresults, 10);
+
+        let object = |fn_name: &str, email: &str| {
+            AddressObject::from_vcf(
+                "contact".to_owned(),
+                format!("BEGIN:VCARD\r\nVERSION:4.0\r\nFN:{fn_name}\r\n{email}\r\nEND:VCARD\r\n"),
+            )
+            .unwrap()
+        };
+        let filter = &addr_query.filter;
+        assert!(filter.matches(&object("Jane Doe", "EMAIL:jane@example.org")));
+        assert!(filter.matches(&object("Jane Doe", "item1.EMAIL;TYPE=WORK:jane@acme.com")));
+        assert!(!filter.matches(&object("Jane Doe", "EMAIL;TYPE=home:jane@acme.com")));
+        assert!(!filter.matches(&object("John Doe", "EMAIL:john@example.org")));
+
+        let filter = FilterElement {
+            test: FilterTest::AnyOf,
+            ..filter.clone()
+        };
+        assert!(filter.matches(&object("John Doe", "EMAIL:john@example.org")));
+        assert!(!filter.matches(&object("John Doe", "EMAIL:john@acme.com")));
+    }
 }
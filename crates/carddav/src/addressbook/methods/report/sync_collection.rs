@@ -13,7 +13,7 @@ use rustical_dav::{
 use rustical_store::{
     auth::User,
     synctoken::{format_synctoken, parse_synctoken},
-    AddressbookStore,
+    AddressbookStore, Share,
 };
 
 pub async fn handle_sync_collection<AS: AddressbookStore>(
@@ -22,6 +22,7 @@ pub async fn handle_sync_collection<AS: AddressbookStore>(
     user: &User,
     principal: &str,
     addressbook_id: &str,
+    share: Option<&Share>,
     addr_store: &AS,
 ) -> Result<MultistatusElement<AddressObjectPropWrapper, String>, Error> {
     let props = match sync_collection.prop {
@@ -49,6 +50,7 @@ pub async fn handle_sync_collection<AS: AddressbookStore>(
             AddressObjectResource {
                 object,
                 principal: principal.to_owned(),
+                share: share.cloned(),
             }
             .propfind(&path, &props, user, req.resource_map())?,
         );
@@ -35,6 +35,8 @@ impl Default for SupportedAddressData {
 pub enum ReportMethod {
     #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
     AddressbookMultiget,
+    #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
+    AddressbookQuery,
     SyncCollection,
 }
 
@@ -58,6 +60,9 @@ impl Default for SupportedReportSet {
                 SupportedReportWrapper {
                     report: ReportMethod::AddressbookMultiget,
                 },
+                SupportedReportWrapper {
+                    report: ReportMethod::AddressbookQuery,
+                },
                 SupportedReportWrapper {
                     report: ReportMethod::SyncCollection,
                 },
@@ -2,6 +2,7 @@ use super::methods::mkcol::route_mkcol;
 use super::methods::post::route_post;
 use super::methods::report::route_report_addressbook;
 use super::prop::{SupportedAddressData, SupportedReportSet};
+use super::share::resolve_addressbook;
 use crate::address_object::resource::AddressObjectResource;
 use crate::principal::PrincipalResource;
 use crate::Error;
@@ -9,30 +10,38 @@ use actix_web::dev::ResourceMap;
 use actix_web::http::Method;
 use actix_web::web;
 use async_trait::async_trait;
-use derive_more::derive::{From, Into};
 use rustical_dav::extensions::{
     CommonPropertiesExtension, CommonPropertiesProp, DavPushExtension, DavPushExtensionProp,
     SyncTokenExtension, SyncTokenExtensionProp,
 };
-use rustical_dav::privileges::UserPrivilegeSet;
+use rustical_dav::privileges::{UserPrivilege, UserPrivilegeSet};
 use rustical_dav::resource::{Resource, ResourceService};
+use rustical_dav::xml::sharing::{AllowedSharingModes, InviteElement, InviteUserElement};
 use rustical_dav::xml::{Resourcetype, ResourcetypeInner};
-use rustical_store::auth::User;
-use rustical_store::{Addressbook, AddressbookStore, SubscriptionStore};
+use rustical_store::auth::{AuthenticationProvider, User};
+use rustical_store::{Addressbook, AddressbookStore, Share, SubscriptionStore};
 use rustical_xml::{EnumUnitVariants, EnumVariants, XmlDeserialize, XmlSerialize};
 use std::marker::PhantomData;
 use std::str::FromStr;
 use std::sync::Arc;
 
-pub struct AddressbookResourceService<AS: AddressbookStore, S: SubscriptionStore> {
+pub struct AddressbookResourceService<
+    AP: AuthenticationProvider,
+    AS: AddressbookStore,
+    S: SubscriptionStore,
+> {
     addr_store: Arc<AS>,
+    __phantom_auth: PhantomData<AP>,
     __phantom_sub: PhantomData<S>,
 }
 
-impl<A: AddressbookStore, S: SubscriptionStore> AddressbookResourceService<A, S> {
+impl<AP: AuthenticationProvider, A: AddressbookStore, S: SubscriptionStore>
+    AddressbookResourceService<AP, A, S>
+{
     pub fn new(addr_store: Arc<A>) -> Self {
         Self {
             addr_store,
+            __phantom_auth: PhantomData,
             __phantom_sub: PhantomData,
         }
     }
@@ -54,6 +63,12 @@ pub enum AddressbookProp {
     SupportedReportSet(SupportedReportSet),
     #[xml(ns = "rustical_dav::namespace::NS_DAV")]
     MaxResourceSize(i64),
+
+    // Sharing (calendarserver)
+    #[xml(ns = "rustical_dav::namespace::NS_CALENDARSERVER", skip_deserializing)]
+    Invite(InviteElement),
+    #[xml(ns = "rustical_dav::namespace::NS_CALENDARSERVER", skip_deserializing)]
+    AllowedSharingModes(AllowedSharingModes),
 }
 
 #[derive(XmlDeserialize, XmlSerialize, PartialEq, Clone, EnumVariants, EnumUnitVariants)]
@@ -65,18 +80,40 @@ pub enum AddressbookPropWrapper {
     Common(CommonPropertiesProp),
 }
 
-#[derive(Clone, Debug, From, Into)]
-pub struct AddressbookResource(pub(crate) Addressbook);
+#[derive(Clone, Debug)]
+pub struct AddressbookResource {
+    pub(crate) addressbook: Addressbook,
+    // Set if the addressbook is mounted into the home of a sharee
+    pub(crate) share: Option<Share>,
+    // Principals the addressbook is shared with
+    pub(crate) shares: Vec<Share>,
+}
+
+impl From<Addressbook> for AddressbookResource {
+    fn from(addressbook: Addressbook) -> Self {
+        Self {
+            addressbook,
+            share: None,
+            shares: vec![],
+        }
+    }
+}
+
+impl From<AddressbookResource> for Addressbook {
+    fn from(value: AddressbookResource) -> Self {
+        value.addressbook
+    }
+}
 
 impl SyncTokenExtension for AddressbookResource {
     fn get_synctoken(&self) -> String {
-        self.0.format_synctoken()
+        self.addressbook.format_synctoken()
     }
 }
 
 impl DavPushExtension for AddressbookResource {
     fn get_topic(&self) -> String {
-        self.0.push_topic.to_owned()
+        self.addressbook.push_topic.to_owned()
     }
 }
 
@@ -86,10 +123,18 @@ impl Resource for AddressbookResource {
     type PrincipalResource = PrincipalResource;
 
     fn get_resourcetype(&self) -> Resourcetype {
-        Resourcetype(&[
-            ResourcetypeInner(Some(rustical_dav::namespace::NS_DAV), "collection"),
-            ResourcetypeInner(Some(rustical_dav::namespace::NS_CARDDAV), "addressbook"),
-        ])
+        if self.share.is_some() {
+            Resourcetype(&[
+                ResourcetypeInner(Some(rustical_dav::namespace::NS_DAV), "collection"),
+                ResourcetypeInner(Some(rustical_dav::namespace::NS_CARDDAV), "addressbook"),
+                ResourcetypeInner(Some(rustical_dav::namespace::NS_CALENDARSERVER), "shared"),
+            ])
+        } else {
+            Resourcetype(&[
+                ResourcetypeInner(Some(rustical_dav::namespace::NS_DAV), "collection"),
+                ResourcetypeInner(Some(rustical_dav::namespace::NS_CARDDAV), "addressbook"),
+            ])
+        }
     }
 
     fn get_prop(
@@ -102,7 +147,7 @@ impl Resource for AddressbookResource {
             AddressbookPropWrapperName::Addressbook(prop) => {
                 AddressbookPropWrapper::Addressbook(match prop {
                     AddressbookPropName::Displayname => {
-                        AddressbookProp::Displayname(self.0.displayname.clone())
+                        AddressbookProp::Displayname(self.addressbook.displayname.clone())
                     }
                     AddressbookPropName::MaxResourceSize => {
                         AddressbookProp::MaxResourceSize(10000000)
@@ -111,11 +156,35 @@ impl Resource for AddressbookResource {
                         AddressbookProp::SupportedReportSet(SupportedReportSet::default())
                     }
                     AddressbookPropName::AddressbookDescription => {
-                        AddressbookProp::AddressbookDescription(self.0.description.to_owned())
+                        AddressbookProp::AddressbookDescription(
+                            self.addressbook.description.to_owned(),
+                        )
                     }
                     AddressbookPropName::SupportedAddressData => {
                         AddressbookProp::SupportedAddressData(SupportedAddressData::default())
                     }
+                    AddressbookPropName::Invite => AddressbookProp::Invite(InviteElement {
+                        organizer: PrincipalResource::get_principal_url(
+                            rmap,
+                            &self.addressbook.principal,
+                        )
+                        .into(),
+                        user: self
+                            .shares
+                            .iter()
+                            .map(|share| InviteUserElement {
+                                href: PrincipalResource::get_principal_url(rmap, &share.sharee),
+                                invite_accepted: (),
+                                access: share.access.into(),
+                            })
+                            .collect(),
+                    }),
+                    // Only the owner can share an addressbook
+                    AddressbookPropName::AllowedSharingModes => {
+                        AddressbookProp::AllowedSharingModes(AllowedSharingModes {
+                            can_be_shared: self.share.is_none().then_some(()),
+                        })
+                    }
                 })
             }
 
@@ -135,16 +204,18 @@ impl Resource for AddressbookResource {
         match prop {
             AddressbookPropWrapper::Addressbook(prop) => match prop {
                 AddressbookProp::Displayname(displayname) => {
-                    self.0.displayname = displayname;
+                    self.addressbook.displayname = displayname;
                     Ok(())
                 }
                 AddressbookProp::AddressbookDescription(description) => {
-                    self.0.description = description;
+                    self.addressbook.description = description;
                     Ok(())
                 }
                 AddressbookProp::MaxResourceSize(_) => Err(rustical_dav::Error::PropReadOnly),
                 AddressbookProp::SupportedReportSet(_) => Err(rustical_dav::Error::PropReadOnly),
                 AddressbookProp::SupportedAddressData(_) => Err(rustical_dav::Error::PropReadOnly),
+                AddressbookProp::Invite(_) => Err(rustical_dav::Error::PropReadOnly),
+                AddressbookProp::AllowedSharingModes(_) => Err(rustical_dav::Error::PropReadOnly),
             },
             AddressbookPropWrapper::SyncToken(prop) => SyncTokenExtension::set_prop(self, prop),
             AddressbookPropWrapper::DavPush(prop) => DavPushExtension::set_prop(self, prop),
@@ -159,16 +230,18 @@ impl Resource for AddressbookResource {
         match prop {
             AddressbookPropWrapperName::Addressbook(prop) => match prop {
                 AddressbookPropName::Displayname => {
-                    self.0.displayname = None;
+                    self.addressbook.displayname = None;
                     Ok(())
                 }
                 AddressbookPropName::AddressbookDescription => {
-                    self.0.description = None;
+                    self.addressbook.description = None;
                     Ok(())
                 }
                 AddressbookPropName::MaxResourceSize => Err(rustical_dav::Error::PropReadOnly),
                 AddressbookPropName::SupportedReportSet => Err(rustical_dav::Error::PropReadOnly),
                 AddressbookPropName::SupportedAddressData => Err(rustical_dav::Error::PropReadOnly),
+                AddressbookPropName::Invite => Err(rustical_dav::Error::PropReadOnly),
+                AddressbookPropName::AllowedSharingModes => Err(rustical_dav::Error::PropReadOnly),
             },
             AddressbookPropWrapperName::SyncToken(prop) => {
                 SyncTokenExtension::remove_prop(self, prop)
@@ -181,17 +254,45 @@ impl Resource for AddressbookResource {
     }
 
     fn get_owner(&self) -> Option<&str> {
-        Some(&self.0.principal)
+        Some(&self.addressbook.principal)
+    }
+
+    // Collections change their sync token with every modification
+    fn get_etag(&self) -> Option<String> {
+        Some(format!("\"{}\"", self.addressbook.format_synctoken()))
+    }
+
+    fn get_share(&self) -> Option<&Share> {
+        self.share.as_ref()
     }
 
     fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
-        Ok(UserPrivilegeSet::owner_only(self.0.principal == user.id))
+        if let Some(share) = self
+            .share
+            .as_ref()
+            .filter(|share| user.is_principal(&share.sharee))
+        {
+            return Ok(share.access.into());
+        }
+        Ok(UserPrivilegeSet::owner_only(
+            user.is_principal(&self.addressbook.principal),
+        ))
+    }
+
+    // Deleting a shared addressbook only removes it from the sharee's home,
+    // which the sharee may do regardless of the access the share grants
+    fn can_delete(&self, user: &User) -> Result<bool, Self::Error> {
+        if let Some(share) = &self.share {
+            return Ok(user.is_principal(&share.sharee));
+        }
+        let privileges = self.get_user_privileges(user)?;
+        Ok(privileges.has(&UserPrivilege::Write))
     }
 }
 
 #[async_trait(?Send)]
-impl<AS: AddressbookStore, S: SubscriptionStore> ResourceService
-    for AddressbookResourceService<AS, S>
+impl<AP: AuthenticationProvider, AS: AddressbookStore, S: SubscriptionStore> ResourceService
+    for AddressbookResourceService<AP, AS, S>
 {
     type MemberType = AddressObjectResource;
     type PathComponents = (String, String); // principal, addressbook_id
@@ -202,21 +303,32 @@ impl<AS: AddressbookStore, S: SubscriptionStore> ResourceService
         &self,
         (principal, addressbook_id): &Self::PathComponents,
     ) -> Result<Self::Resource, Error> {
+        let (principal, addressbook_id, share) =
+            resolve_addressbook(self.addr_store.as_ref(), principal, addressbook_id).await?;
         let addressbook = self
             .addr_store
-            .get_addressbook(principal, addressbook_id)
+            .get_addressbook(&principal, &addressbook_id)
             .await
             .map_err(|_e| Error::NotFound)?;
-        Ok(addressbook.into())
+        Ok(AddressbookResource {
+            addressbook,
+            share,
+            shares: self
+                .addr_store
+                .get_addressbook_shares(&principal, &addressbook_id)
+                .await?,
+        })
     }
 
     async fn get_members(
         &self,
         (principal, addressbook_id): &Self::PathComponents,
     ) -> Result<Vec<(String, Self::MemberType)>, Self::Error> {
+        let (principal, addressbook_id, share) =
+            resolve_addressbook(self.addr_store.as_ref(), principal, addressbook_id).await?;
         Ok(self
             .addr_store
-            .get_objects(principal, addressbook_id)
+            .get_objects(&principal, &addressbook_id)
             .await?
             .into_iter()
             .map(|object| {
@@ -225,6 +337,7 @@ impl<AS: AddressbookStore, S: SubscriptionStore> ResourceService
                     AddressObjectResource {
                         object,
                         principal: principal.to_owned(),
+                        share: share.clone(),
                     },
                 )
             })
@@ -236,6 +349,10 @@ impl<AS: AddressbookStore, S: SubscriptionStore> ResourceService
         (principal, addressbook_id): &Self::PathComponents,
         file: Self::Resource,
     ) -> Result<(), Self::Error> {
+        // Only the owner may change the properties of an addressbook
+        if file.share.is_some() {
+            return Err(Error::Unauthorized);
+        }
         self.addr_store
             .update_addressbook(principal.to_owned(), addressbook_id.to_owned(), file.into())
             .await?;
@@ -246,7 +363,17 @@ impl<AS: AddressbookStore, S: SubscriptionStore> ResourceService
         &self,
         (principal, addressbook_id): &Self::PathComponents,
         use_trashbin: bool,
+        _rmap: &ResourceMap,
     ) -> Result<(), Self::Error> {
+        // Sharees deleting a shared addressbook only remove it from their home
+        if let (owner, owner_addressbook_id, Some(share)) =
+            resolve_addressbook(self.addr_store.as_ref(), principal, addressbook_id).await?
+        {
+            self.addr_store
+                .delete_addressbook_share(&owner, &owner_addressbook_id, &share.sharee)
+                .await?;
+            return Ok(());
+        }
         self.addr_store
             .delete_addressbook(principal, addressbook_id, use_trashbin)
             .await?;
@@ -259,6 +386,6 @@ impl<AS: AddressbookStore, S: SubscriptionStore> ResourceService
         let report_method = web::method(Method::from_str("REPORT").unwrap());
         res.route(mkcol_method.to(route_mkcol::<AS>))
             .route(report_method.to(route_report_addressbook::<AS>))
-            .post(route_post::<AS, S>)
+            .post(route_post::<AP, AS, S>)
     }
 }
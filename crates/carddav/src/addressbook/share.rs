@@ -0,0 +1,25 @@
+use crate::Error;
+use rustical_store::{AddressbookStore, Share};
+
+// Addressbooks shared with a principal are mounted into their addressbook home.
+// Resolves an addressbook in the home of a principal to the owner's addressbook
+// and the share granting access to it.
+pub(crate) async fn resolve_addressbook<AS: AddressbookStore>(
+    store: &AS,
+    principal: &str,
+    addressbook_id: &str,
+) -> Result<(String, String, Option<Share>), Error> {
+    let share = store
+        .get_received_addressbook_shares(principal)
+        .await?
+        .into_iter()
+        .find(|share| share.mount_id() == addressbook_id);
+    Ok(match share {
+        Some(share) => (
+            share.principal.to_owned(),
+            share.collection_id.to_owned(),
+            Some(share),
+        ),
+        None => (principal.to_owned(), addressbook_id.to_owned(), None),
+    })
+}
@@ -37,7 +37,7 @@ pub fn configure_dav<AP: AuthenticationProvider, A: AddressbookStore, S: Subscri
 ) {
     cfg.service(
         web::scope("")
-            .wrap(AuthenticationMiddleware::new(auth_provider))
+            .wrap(AuthenticationMiddleware::new(auth_provider.clone()))
             .wrap(
                 ErrorHandlers::new().handler(StatusCode::METHOD_NOT_ALLOWED, |res| {
                     Ok(ErrorHandlerResponse::Response(
@@ -47,7 +47,7 @@ pub fn configure_dav<AP: AuthenticationProvider, A: AddressbookStore, S: Subscri
                                     HeaderName::from_static("dav"),
                                     // https://datatracker.ietf.org/doc/html/rfc4918#section-18
                                     HeaderValue::from_static(
-                                        "1, 3, access-control, addressbook, extended-mkcol",
+                                        "1, 2, 3, access-control, addressbook, extended-mkcol",
                                     ),
                                 ))
                                 .finish();
@@ -58,6 +58,7 @@ pub fn configure_dav<AP: AuthenticationProvider, A: AddressbookStore, S: Subscri
                     ))
                 }),
             )
+            .app_data(Data::from(auth_provider.clone()))
             .app_data(Data::from(store.clone()))
             .app_data(Data::from(subscription_store))
             .service(RootResourceService::<PrincipalResource>::default().actix_resource())
@@ -65,14 +66,14 @@ pub fn configure_dav<AP: AuthenticationProvider, A: AddressbookStore, S: Subscri
                 web::scope("/user").service(
                     web::scope("/{principal}")
                         .service(
-                            PrincipalResourceService::new(store.clone())
+                            PrincipalResourceService::new(auth_provider, store.clone())
                                 .actix_resource()
                                 .name(PrincipalResource::route_name()),
                         )
                         .service(
                             web::scope("/{addressbook}")
                                 .service(
-                                    AddressbookResourceService::<A, S>::new(store.clone())
+                                    AddressbookResourceService::<AP, A, S>::new(store.clone())
                                         .actix_resource(),
                                 )
                                 .service(
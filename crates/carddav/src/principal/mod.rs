@@ -5,25 +5,31 @@ use async_trait::async_trait;
 use rustical_dav::extensions::{CommonPropertiesExtension, CommonPropertiesProp};
 use rustical_dav::privileges::UserPrivilegeSet;
 use rustical_dav::resource::{NamedRoute, Resource, ResourceService};
-use rustical_dav::xml::{HrefElement, Resourcetype, ResourcetypeInner};
-use rustical_store::auth::User;
+use rustical_dav::xml::{HrefElement, HrefListElement, Resourcetype, ResourcetypeInner};
+use rustical_store::auth::{AuthenticationProvider, PrincipalType, User};
 use rustical_store::AddressbookStore;
 use rustical_xml::{EnumUnitVariants, EnumVariants, XmlDeserialize, XmlSerialize};
 use std::sync::Arc;
 
-pub struct PrincipalResourceService<A: AddressbookStore> {
+pub struct PrincipalResourceService<AP: AuthenticationProvider, A: AddressbookStore> {
+    auth_provider: Arc<AP>,
     addr_store: Arc<A>,
 }
 
-impl<A: AddressbookStore> PrincipalResourceService<A> {
-    pub fn new(addr_store: Arc<A>) -> Self {
-        Self { addr_store }
+impl<AP: AuthenticationProvider, A: AddressbookStore> PrincipalResourceService<AP, A> {
+    pub fn new(auth_provider: Arc<AP>, addr_store: Arc<A>) -> Self {
+        Self {
+            auth_provider,
+            addr_store,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct PrincipalResource {
-    principal: String,
+    principal: User,
+    // Members of a group principal
+    members: Vec<String>,
 }
 
 #[derive(XmlDeserialize, XmlSerialize, PartialEq, Clone, EnumVariants, EnumUnitVariants)]
@@ -36,6 +42,10 @@ pub enum PrincipalProp {
     #[xml(rename = b"principal-URL")]
     #[xml(ns = "rustical_dav::namespace::NS_DAV")]
     PrincipalUrl(HrefElement),
+    #[xml(ns = "rustical_dav::namespace::NS_DAV", skip_deserializing)]
+    GroupMemberSet(HrefListElement),
+    #[xml(ns = "rustical_dav::namespace::NS_DAV", skip_deserializing)]
+    GroupMembership(HrefListElement),
 
     // CardDAV (RFC 6352)
     #[xml(ns = "rustical_dav::namespace::NS_CARDDAV")]
@@ -55,6 +65,18 @@ impl PrincipalResource {
     pub fn get_principal_url(rmap: &ResourceMap, principal: &str) -> String {
         Self::get_url(rmap, vec![principal]).unwrap()
     }
+
+    pub fn get_principal_from_href(rmap: &ResourceMap, href: &str) -> Option<String> {
+        Self::get_url_element(rmap, href)
+    }
+
+    fn get_principal_urls(rmap: &ResourceMap, principals: &[String]) -> HrefListElement {
+        principals
+            .iter()
+            .map(|principal| Self::get_principal_url(rmap, principal))
+            .collect::<Vec<_>>()
+            .into()
+    }
 }
 
 impl NamedRoute for PrincipalResource {
@@ -81,15 +103,24 @@ impl Resource for PrincipalResource {
         user: &User,
         prop: &PrincipalPropWrapperName,
     ) -> Result<Self::Prop, Self::Error> {
-        let principal_href = HrefElement::new(Self::get_principal_url(rmap, &self.principal));
+        let principal_href = HrefElement::new(Self::get_principal_url(rmap, &self.principal.id));
 
         Ok(match prop {
             PrincipalPropWrapperName::Principal(prop) => {
                 PrincipalPropWrapper::Principal(match prop {
-                    PrincipalPropName::Displayname => {
-                        PrincipalProp::Displayname(self.principal.to_owned())
-                    }
+                    PrincipalPropName::Displayname => PrincipalProp::Displayname(
+                        self.principal
+                            .displayname
+                            .to_owned()
+                            .unwrap_or_else(|| self.principal.id.to_owned()),
+                    ),
                     PrincipalPropName::PrincipalUrl => PrincipalProp::PrincipalUrl(principal_href),
+                    PrincipalPropName::GroupMemberSet => {
+                        PrincipalProp::GroupMemberSet(Self::get_principal_urls(rmap, &self.members))
+                    }
+                    PrincipalPropName::GroupMembership => PrincipalProp::GroupMembership(
+                        Self::get_principal_urls(rmap, &self.principal.memberships),
+                    ),
                     PrincipalPropName::AddressbookHomeSet => {
                         PrincipalProp::AddressbookHomeSet(principal_href)
                     }
@@ -104,16 +135,20 @@ impl Resource for PrincipalResource {
     }
 
     fn get_owner(&self) -> Option<&str> {
-        Some(&self.principal)
+        Some(&self.principal.id)
     }
 
     fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error> {
-        Ok(UserPrivilegeSet::owner_only(self.principal == user.id))
+        Ok(UserPrivilegeSet::owner_only(
+            user.is_principal(&self.principal.id),
+        ))
     }
 }
 
 #[async_trait(?Send)]
-impl<A: AddressbookStore> ResourceService for PrincipalResourceService<A> {
+impl<AP: AuthenticationProvider, A: AddressbookStore> ResourceService
+    for PrincipalResourceService<AP, A>
+{
     type PathComponents = (String,);
     type MemberType = AddressbookResource;
     type Resource = PrincipalResource;
@@ -123,19 +158,61 @@ impl<A: AddressbookStore> ResourceService for PrincipalResourceService<A> {
         &self,
         (principal,): &Self::PathComponents,
     ) -> Result<Self::Resource, Self::Error> {
-        Ok(PrincipalResource {
-            principal: principal.to_owned(),
-        })
+        let principal = self
+            .auth_provider
+            .get_principal(principal)
+            .await?
+            .ok_or(Error::NotFound)?;
+        // Only groups have members
+        let members = match principal.principal_type {
+            PrincipalType::Group => self.auth_provider.get_group_members(&principal.id).await?,
+            PrincipalType::Individual => vec![],
+        };
+        Ok(PrincipalResource { principal, members })
     }
 
     async fn get_members(
         &self,
         (principal,): &Self::PathComponents,
     ) -> Result<Vec<(String, Self::MemberType)>, Self::Error> {
-        let addressbooks = self.addr_store.get_addressbooks(principal).await?;
-        Ok(addressbooks
-            .into_iter()
-            .map(|addressbook| (addressbook.id.to_owned(), addressbook.into()))
-            .collect())
+        let mut members = vec![];
+        for addressbook in self.addr_store.get_addressbooks(principal).await? {
+            let shares = self
+                .addr_store
+                .get_addressbook_shares(principal, &addressbook.id)
+                .await?;
+            members.push((
+                addressbook.id.to_owned(),
+                AddressbookResource {
+                    addressbook,
+                    share: None,
+                    shares,
+                },
+            ));
+        }
+        // Addressbooks other principals shared with us
+        for share in self
+            .addr_store
+            .get_received_addressbook_shares(principal)
+            .await?
+        {
+            let addressbook = self
+                .addr_store
+                .get_addressbook(&share.principal, &share.collection_id)
+                .await?;
+            let shares = self
+                .addr_store
+                .get_addressbook_shares(&share.principal, &share.collection_id)
+                .await?;
+            members.push((
+                share.mount_id(),
+                AddressbookResource {
+                    addressbook,
+                    share: Some(share),
+                    shares,
+                },
+            ));
+        }
+        Ok(members)
     }
 }
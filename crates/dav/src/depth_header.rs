@@ -12,7 +12,7 @@ impl ResponseError for InvalidDepthHeader {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Depth {
     Zero,
     One,
@@ -0,0 +1,120 @@
+use actix_web::{http::StatusCode, FromRequest, HttpRequest, ResponseError};
+use futures_util::future::{ready, Ready};
+use percent_encoding::percent_decode_str;
+use thiserror::Error;
+use url::Url;
+
+#[derive(Error, Debug)]
+#[error("Invalid Destination header")]
+pub struct InvalidDestinationHeader;
+
+impl ResponseError for InvalidDestinationHeader {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// Decoded path segments of the Destination header of COPY and MOVE
+/// https://datatracker.ietf.org/doc/html/rfc4918#section-10.3
+#[derive(Debug, PartialEq)]
+pub struct Destination(pub Vec<String>);
+
+impl TryFrom<&str> for Destination {
+    type Error = InvalidDestinationHeader;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        // The header is either an absolute URI or an absolute path
+        let url = Url::parse("http://localhost")
+            .and_then(|base| base.join(value))
+            .map_err(|_| InvalidDestinationHeader)?;
+        let segments = url
+            .path_segments()
+            .ok_or(InvalidDestinationHeader)?
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                percent_decode_str(segment)
+                    .decode_utf8()
+                    .map(|segment| segment.into_owned())
+                    .map_err(|_| InvalidDestinationHeader)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self(segments))
+    }
+}
+
+impl Destination {
+    pub fn path(&self) -> String {
+        format!("/{}", self.0.join("/"))
+    }
+
+    /// Collection and object name of the destination if it's in the same collection set
+    /// as the source object, e.g. /caldav/user/alice/calendar/{collection}/{object}
+    pub fn sibling_object(&self, source_path: &str) -> Option<(&str, &str)> {
+        let Self(source) = Self::try_from(source_path).ok()?;
+        match self.0.as_slice() {
+            [parent @ .., collection, object]
+                if source.len() == self.0.len() && source.starts_with(parent) =>
+            {
+                Some((collection, object))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromRequest for Destination {
+    type Error = InvalidDestinationHeader;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn extract(req: &HttpRequest) -> Self::Future {
+        ready(
+            req.headers()
+                .get("Destination")
+                .ok_or(InvalidDestinationHeader)
+                .and_then(|value| value.to_str().map_err(|_| InvalidDestinationHeader))
+                .and_then(Destination::try_from),
+        )
+    }
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        Self::extract(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Destination;
+
+    #[test]
+    fn test_destination_header() {
+        let segments = |value: &str| Destination::try_from(value).unwrap().0;
+        assert_eq!(
+            segments("https://example.com/caldav/user/alice/calendar/work/event%201.ics"),
+            vec!["caldav", "user", "alice", "calendar", "work", "event 1.ics"]
+        );
+        assert_eq!(
+            segments("/caldav/user/alice/calendar/work/"),
+            vec!["caldav", "user", "alice", "calendar", "work"]
+        );
+
+        let source = "/caldav/user/alice/calendar/home/event.ics";
+        assert_eq!(
+            Destination::try_from("/caldav/user/alice/calendar/work/event.ics")
+                .unwrap()
+                .sibling_object(source),
+            Some(("work", "event.ics"))
+        );
+        assert_eq!(
+            Destination::try_from("/caldav/user/bob/calendar/work/event.ics")
+                .unwrap()
+                .sibling_object(source),
+            None
+        );
+        assert_eq!(
+            Destination::try_from("/caldav/user/alice/calendar/work")
+                .unwrap()
+                .sibling_object(source),
+            None
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use actix_web::{http::StatusCode, HttpResponse};
+use crate::xml::error::{ErrorElement, Precondition};
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    HttpResponse,
+};
+use rustical_xml::XmlSerializeRoot;
 use thiserror::Error;
 use tracing::error;
 
@@ -19,6 +24,18 @@ pub enum Error {
     #[error("prop is read-only")]
     PropReadOnly,
 
+    #[error("Precondition failed")]
+    PreconditionFailed,
+
+    #[error("Resource is locked")]
+    Locked,
+
+    #[error("Lock token does not match the request URI")]
+    LockTokenMismatch,
+
+    #[error("Conflict: {0:?}")]
+    Conflict(Precondition),
+
     #[error(transparent)]
     XmlDeserializationError(#[from] rustical_xml::XmlError),
 
@@ -35,6 +52,10 @@ impl actix_web::error::ResponseError for Error {
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::XmlDeserializationError(_) => StatusCode::BAD_REQUEST,
             Error::PropReadOnly => StatusCode::CONFLICT,
+            Error::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Error::Locked => StatusCode::LOCKED,
+            Error::LockTokenMismatch => StatusCode::CONFLICT,
+            Error::Conflict(_) => StatusCode::CONFLICT,
             Self::IOError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -45,6 +66,19 @@ impl actix_web::error::ResponseError for Error {
             Error::Unauthorized => HttpResponse::build(self.status_code())
                 .append_header(("WWW-Authenticate", "Basic"))
                 .body(self.to_string()),
+            Error::Conflict(precondition) => {
+                let mut output: Vec<_> = b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".into();
+                let mut writer = quick_xml::Writer::new_with_indent(&mut output, b' ', 4);
+                let body = ErrorElement {
+                    precondition: precondition.to_owned(),
+                };
+                if body.serialize_root(&mut writer).is_err() {
+                    return HttpResponse::InternalServerError().finish();
+                }
+                HttpResponse::build(self.status_code())
+                    .content_type(ContentType::xml())
+                    .body(String::from_utf8(output).unwrap())
+            }
             _ => HttpResponse::build(self.status_code()).body(self.to_string()),
         }
     }
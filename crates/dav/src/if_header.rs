@@ -0,0 +1,217 @@
+use actix_web::{http::StatusCode, HttpRequest, ResponseError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("Invalid If header")]
+pub struct InvalidIfHeader;
+
+impl ResponseError for InvalidIfHeader {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    // Lock token
+    StateToken(String),
+    // Entity tag without quotes
+    ETag(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfCondition {
+    pub not: bool,
+    pub condition: Condition,
+}
+
+// List of conditions which all have to match, optionally tagged with a resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfList {
+    pub resource: Option<String>,
+    pub conditions: Vec<IfCondition>,
+}
+
+/// The If header as used by WebDAV locking
+/// https://datatracker.ietf.org/doc/html/rfc4918#section-10.4
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfHeader(pub Vec<IfList>);
+
+impl IfHeader {
+    pub fn from_request(req: &HttpRequest) -> Result<Option<Self>, InvalidIfHeader> {
+        match req.headers().get("If") {
+            Some(value) => Ok(Some(Self::try_from(
+                value.to_str().map_err(|_| InvalidIfHeader)?,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// All lock tokens submitted with the header
+    pub fn state_tokens(&self) -> impl Iterator<Item = &str> {
+        self.0
+            .iter()
+            .flat_map(|list| list.conditions.iter())
+            .filter_map(|condition| match &condition.condition {
+                Condition::StateToken(token) => Some(token.as_str()),
+                Condition::ETag(_) => None,
+            })
+    }
+}
+
+// Reads up to the closing delimiter and returns the content in between
+fn take_until<'a>(input: &mut &'a str, end: char) -> Result<&'a str, InvalidIfHeader> {
+    let (content, rest) = input.split_once(end).ok_or(InvalidIfHeader)?;
+    *input = rest;
+    Ok(content)
+}
+
+impl TryFrom<&str> for IfHeader {
+    type Error = InvalidIfHeader;
+
+    // If = "If" ":" ( 1*No-tag-list | 1*Tagged-list )
+    // No-tag-list = List
+    // Tagged-list = Resource-Tag 1*List
+    // List = "(" 1*Condition ")"
+    // Condition = ["Not"] (State-token | "[" entity-tag "]")
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut input = value.trim_start();
+        let mut lists = vec![];
+        let mut resource = None;
+        let mut tagged = None;
+
+        while let Some(c) = input.chars().next() {
+            input = &input[c.len_utf8()..];
+            match c {
+                '<' => {
+                    // Tagged and untagged lists must not be mixed
+                    if tagged == Some(false) {
+                        return Err(InvalidIfHeader);
+                    }
+                    tagged = Some(true);
+                    resource = Some(take_until(&mut input, '>')?.to_owned());
+                }
+                '(' => {
+                    if tagged == Some(true) && resource.is_none() {
+                        return Err(InvalidIfHeader);
+                    }
+                    tagged.get_or_insert(false);
+                    let mut conditions = vec![];
+                    let mut list = take_until(&mut input, ')')?.trim_start();
+                    while !list.is_empty() {
+                        let not = match list.get(..3) {
+                            Some(keyword) if keyword.eq_ignore_ascii_case("not") => {
+                                list = list[3..].trim_start();
+                                true
+                            }
+                            _ => false,
+                        };
+                        let condition = match list.chars().next() {
+                            Some('<') => {
+                                list = &list[1..];
+                                Condition::StateToken(take_until(&mut list, '>')?.to_owned())
+                            }
+                            Some('[') => {
+                                list = &list[1..];
+                                let etag = take_until(&mut list, ']')?.trim();
+                                let etag = etag.strip_prefix("W/").unwrap_or(etag);
+                                let etag = etag
+                                    .strip_prefix('"')
+                                    .and_then(|etag| etag.strip_suffix('"'))
+                                    .ok_or(InvalidIfHeader)?;
+                                Condition::ETag(etag.to_owned())
+                            }
+                            _ => return Err(InvalidIfHeader),
+                        };
+                        conditions.push(IfCondition { not, condition });
+                        list = list.trim_start();
+                    }
+                    if conditions.is_empty() {
+                        return Err(InvalidIfHeader);
+                    }
+                    lists.push(IfList {
+                        resource: resource.clone(),
+                        conditions,
+                    });
+                }
+                c if c.is_whitespace() => {}
+                _ => return Err(InvalidIfHeader),
+            }
+        }
+
+        if lists.is_empty() {
+            return Err(InvalidIfHeader);
+        }
+        Ok(Self(lists))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Condition, IfCondition, IfHeader, IfList};
+
+    #[test]
+    fn test_if_header() {
+        assert_eq!(
+            IfHeader::try_from("(<urn:uuid:181d4fae-7d8c-11d0-a765-00a0c91e6bf2>)").unwrap(),
+            IfHeader(vec![IfList {
+                resource: None,
+                conditions: vec![IfCondition {
+                    not: false,
+                    condition: Condition::StateToken(
+                        "urn:uuid:181d4fae-7d8c-11d0-a765-00a0c91e6bf2".to_owned()
+                    )
+                }]
+            }])
+        );
+
+        let header =
+            IfHeader::try_from(r#"</specs/> (<urn:uuid:181d4fae> Not ["I am an ETag"]) (W/"xyz")"#);
+        assert!(header.is_err());
+
+        let header = IfHeader::try_from(
+            r#"</specs/> (<urn:uuid:181d4fae> Not ["I am an ETag"]) </other> (NOT <DAV:no-lock> [W/"xyz"])"#,
+        )
+        .unwrap();
+        assert_eq!(
+            header,
+            IfHeader(vec![
+                IfList {
+                    resource: Some("/specs/".to_owned()),
+                    conditions: vec![
+                        IfCondition {
+                            not: false,
+                            condition: Condition::StateToken("urn:uuid:181d4fae".to_owned())
+                        },
+                        IfCondition {
+                            not: true,
+                            condition: Condition::ETag("I am an ETag".to_owned())
+                        }
+                    ]
+                },
+                IfList {
+                    resource: Some("/other".to_owned()),
+                    conditions: vec![
+                        IfCondition {
+                            not: true,
+                            condition: Condition::StateToken("DAV:no-lock".to_owned())
+                        },
+                        IfCondition {
+                            not: false,
+                            condition: Condition::ETag("xyz".to_owned())
+                        }
+                    ]
+                }
+            ])
+        );
+        assert_eq!(
+            header.state_tokens().collect::<Vec<_>>(),
+            vec!["urn:uuid:181d4fae", "DAV:no-lock"]
+        );
+
+        // Tagged and untagged lists can't be mixed
+        assert!(IfHeader::try_from("(<a:b>) </specs/> (<c:d>)").is_err());
+        assert!(IfHeader::try_from("").is_err());
+        assert!(IfHeader::try_from("()").is_err());
+    }
+}
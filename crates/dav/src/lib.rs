@@ -1,7 +1,12 @@
 pub mod depth_header;
+pub mod destination_header;
 pub mod error;
 pub mod extensions;
+pub mod if_header;
+pub mod lock;
 pub mod namespace;
+pub mod overwrite_header;
+pub mod precondition;
 pub mod privileges;
 pub mod push;
 pub mod resource;
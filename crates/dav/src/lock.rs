@@ -0,0 +1,547 @@
+use crate::{
+    depth_header::Depth,
+    if_header::{Condition, IfHeader},
+    precondition::opaque_tag,
+    xml::{
+        lock::{
+            ActiveLockElement, LockOwner, LockScope, LockScopeElement, LockType, LockTypeElement,
+        },
+        HrefElement,
+    },
+    Error,
+};
+use actix_web::HttpRequest;
+use percent_encoding::percent_decode_str;
+use rustical_store::{auth::User, Share};
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+const MAX_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Locks are identified by the decoded path without trailing slash.
+/// Objects are reachable with and without their file extension.
+/// share is set if the path is in a shared collection, whose mount in the home of the sharee
+/// refers to the same resources as the owner's path.
+pub fn lock_key(path: &str, share: Option<&Share>) -> String {
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    let path = path.trim_end_matches('/');
+    let path = [".ics", ".vcf"]
+        .into_iter()
+        .find_map(|extension| path.strip_suffix(extension))
+        .unwrap_or(path);
+    let Some(share) = share else {
+        return path.to_owned();
+    };
+    let mount = format!("/{}/{}", share.sharee, share.mount_id());
+    let collection = format!("/{}/{}", share.principal, share.collection_id);
+    match path.match_indices(&mount).find(|(index, _)| {
+        path[index + mount.len()..].is_empty() || path[index + mount.len()..].starts_with('/')
+    }) {
+        Some((index, _)) => format!(
+            "{}{collection}{}",
+            &path[..index],
+            &path[index + mount.len()..]
+        ),
+        None => path.to_owned(),
+    }
+}
+
+// Whether key is a member of the collection at parent, at any depth
+fn is_member(key: &str, parent: &str) -> bool {
+    key.strip_prefix(parent)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+// Resource tags of the If header are absolute URIs or absolute paths
+fn resource_tag_key(tag: &str) -> Option<String> {
+    let url = Url::parse("http://localhost").ok()?.join(tag).ok()?;
+    Some(lock_key(url.path(), None))
+}
+
+/// Parses the Timeout header of LOCK
+/// https://datatracker.ietf.org/doc/html/rfc4918#section-10.7
+pub fn parse_timeout(req: &HttpRequest) -> Duration {
+    let Some(value) = req
+        .headers()
+        .get("Timeout")
+        .and_then(|value| value.to_str().ok())
+    else {
+        return DEFAULT_TIMEOUT;
+    };
+    // The client can list multiple timeouts in order of preference
+    value
+        .split(',')
+        .map(str::trim)
+        .find_map(|timeout| match timeout {
+            "Infinite" => Some(MAX_TIMEOUT),
+            _ => timeout
+                .strip_prefix("Second-")?
+                .parse()
+                .ok()
+                .map(Duration::from_secs),
+        })
+        .unwrap_or(DEFAULT_TIMEOUT)
+        .min(MAX_TIMEOUT)
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveLock {
+    pub token: String,
+    // The request path the lock was created on
+    pub root: String,
+    pub key: String,
+    pub depth: Depth,
+    pub owner: Option<LockOwner>,
+    // Only the principal that created the lock may use its token
+    pub principal: String,
+    pub expires_at: Instant,
+}
+
+impl ActiveLock {
+    fn covers(&self, key: &str) -> bool {
+        self.key == key || (self.depth == Depth::Infinity && is_member(key, &self.key))
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at <= Instant::now()
+    }
+}
+
+impl From<&ActiveLock> for ActiveLockElement {
+    fn from(lock: &ActiveLock) -> Self {
+        let remaining = lock.expires_at.saturating_duration_since(Instant::now());
+        Self {
+            lockscope: LockScopeElement {
+                scope: LockScope::Exclusive,
+            },
+            locktype: LockTypeElement {
+                locktype: LockType::Write,
+            },
+            depth: match lock.depth {
+                Depth::Infinity => "infinity",
+                _ => "0",
+            },
+            owner: lock.owner.clone(),
+            timeout: format!("Second-{}", remaining.as_secs()),
+            locktoken: HrefElement::new(lock.token.clone()),
+            lockroot: HrefElement::new(lock.root.clone()),
+        }
+    }
+}
+
+/// In-memory manager of exclusive write locks
+/// https://datatracker.ietf.org/doc/html/rfc4918#section-6
+#[derive(Debug, Default)]
+pub struct LockManager {
+    // Active locks by their token
+    locks: RwLock<HashMap<String, ActiveLock>>,
+}
+
+impl LockManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Active locks on the resource with the given lock_key, including depth-infinity locks on
+    /// its parents
+    pub fn get_locks(&self, key: &str) -> Vec<ActiveLock> {
+        self.locks
+            .read()
+            .unwrap()
+            .values()
+            .filter(|lock| !lock.is_expired() && lock.covers(key))
+            .cloned()
+            .collect()
+    }
+
+    /// Locks the resource with the given lock_key that was requested at path
+    pub fn lock(
+        &self,
+        path: &str,
+        key: String,
+        depth: Depth,
+        owner: Option<LockOwner>,
+        user: &User,
+        timeout: Duration,
+    ) -> Result<ActiveLock, Error> {
+        let mut locks = self.locks.write().unwrap();
+        locks.retain(|_, lock| !lock.is_expired());
+
+        // Exclusive locks conflict with all locks on the resource, its parents and
+        // for depth-infinity also its members
+        let conflict = locks.values().any(|lock| {
+            lock.covers(&key) || (depth == Depth::Infinity && is_member(&lock.key, &key))
+        });
+        if conflict {
+            return Err(Error::Locked);
+        }
+
+        let lock = ActiveLock {
+            token: format!("urn:uuid:{}", uuid::Uuid::new_v4()),
+            root: path.to_owned(),
+            key,
+            depth,
+            owner,
+            principal: user.id.to_owned(),
+            expires_at: Instant::now() + timeout,
+        };
+        locks.insert(lock.token.to_owned(), lock.clone());
+        Ok(lock)
+    }
+
+    /// Refreshes the lock on a resource whose token was submitted in the If header
+    pub fn refresh(
+        &self,
+        req: &HttpRequest,
+        key: &str,
+        user: &User,
+        timeout: Duration,
+    ) -> Result<ActiveLock, Error> {
+        let if_header = IfHeader::from_request(req)
+            .map_err(|err| Error::BadRequest(err.to_string()))?
+            .ok_or(Error::BadRequest("Missing lock token".to_owned()))?;
+        let mut locks = self.locks.write().unwrap();
+        for token in if_header.state_tokens() {
+            if let Some(lock) = locks.get_mut(token) {
+                if !lock.is_expired() && lock.covers(key) && lock.principal == user.id {
+                    lock.expires_at = Instant::now() + timeout;
+                    return Ok(lock.clone());
+                }
+            }
+        }
+        Err(Error::PreconditionFailed)
+    }
+
+    pub fn unlock(&self, key: &str, token: &str, user: &User) -> Result<(), Error> {
+        let mut locks = self.locks.write().unwrap();
+        let lock = locks
+            .get(token)
+            .filter(|lock| !lock.is_expired() && lock.covers(key))
+            .ok_or(Error::LockTokenMismatch)?;
+        if lock.principal != user.id {
+            return Err(Error::Unauthorized);
+        }
+        locks.remove(token);
+        Ok(())
+    }
+
+    /// Removes the locks on a deleted or moved resource and its members
+    pub fn remove_locks(&self, key: &str) {
+        self.locks
+            .write()
+            .unwrap()
+            .retain(|_, lock| lock.key != key && !is_member(&lock.key, key));
+    }
+
+    /// Evaluates the If header for the resource at path with the given lock_key and makes sure
+    /// that the tokens of all locks on it were submitted by the user holding them.
+    /// etag is the current ETag of the resource, None meaning that it does not exist
+    pub fn check_write(
+        &self,
+        req: &HttpRequest,
+        user: &User,
+        path: &str,
+        key: &str,
+        etag: Option<&str>,
+    ) -> Result<(), Error> {
+        let locks = self.get_locks(key);
+        let if_header =
+            IfHeader::from_request(req).map_err(|err| Error::BadRequest(err.to_string()))?;
+
+        if let Some(if_header) = &if_header {
+            // The If header refers to resources by the URIs the client used
+            let path_key = lock_key(path, None);
+            // Untagged lists refer to the request URI
+            let is_request_uri = path_key == lock_key(req.path(), None);
+            let mut lists = if_header
+                .0
+                .iter()
+                .filter(|list| match &list.resource {
+                    Some(tag) => resource_tag_key(tag).as_ref() == Some(&path_key),
+                    None => is_request_uri,
+                })
+                .peekable();
+            if lists.peek().is_some()
+                && !lists.any(|list| {
+                    list.conditions.iter().all(|condition| {
+                        let matches = match &condition.condition {
+                            Condition::StateToken(token) => {
+                                locks.iter().any(|lock| &lock.token == token)
+                            }
+                            Condition::ETag(tag) => etag.map(opaque_tag) == Some(tag.as_str()),
+                        };
+                        matches != condition.not
+                    })
+                })
+            {
+                return Err(Error::PreconditionFailed);
+            }
+        }
+
+        let submitted_tokens: Vec<_> = if_header
+            .iter()
+            .flat_map(|if_header| if_header.state_tokens())
+            .collect();
+        let is_unlocked = locks.iter().all(|lock| {
+            lock.principal == user.id && submitted_tokens.contains(&lock.token.as_str())
+        });
+        if !is_unlocked {
+            return Err(Error::Locked);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lock_key, parse_timeout, LockManager, DEFAULT_TIMEOUT, MAX_TIMEOUT};
+    use crate::{depth_header::Depth, Error};
+    use actix_web::test::TestRequest;
+    use rustical_store::auth::{PrincipalType, User};
+    use rustical_store::{Share, ShareAccess};
+    use std::time::Duration;
+
+    fn user(id: &str) -> User {
+        User {
+            id: id.to_owned(),
+            displayname: None,
+            principal_type: PrincipalType::Individual,
+            password: None,
+            memberships: vec![],
+            calendar_proxy_read_for: vec![],
+            calendar_proxy_write_for: vec![],
+        }
+    }
+
+    fn key(path: &str) -> String {
+        lock_key(path, None)
+    }
+
+    #[test]
+    fn test_lock_timeout() {
+        let timeout = |value: &str| {
+            parse_timeout(
+                &TestRequest::default()
+                    .insert_header(("Timeout", value))
+                    .to_http_request(),
+            )
+        };
+        assert_eq!(
+            parse_timeout(&TestRequest::default().to_http_request()),
+            DEFAULT_TIMEOUT
+        );
+        assert_eq!(timeout("Second-60"), Duration::from_secs(60));
+        assert_eq!(timeout("Infinite, Second-60"), MAX_TIMEOUT);
+        assert_eq!(timeout("Second-99999999"), MAX_TIMEOUT);
+        assert_eq!(timeout("Whenever, Second-60"), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_lock_conflicts() {
+        let locks = LockManager::new();
+        let alice = user("alice");
+        let timeout = Duration::from_secs(60);
+
+        let lock = locks
+            .lock(
+                "/calendar/work",
+                key("/calendar/work"),
+                Depth::Infinity,
+                None,
+                &alice,
+                timeout,
+            )
+            .unwrap();
+        assert!(matches!(
+            locks.lock(
+                "/calendar/work/event.ics",
+                key("/calendar/work/event.ics"),
+                Depth::Zero,
+                None,
+                &alice,
+                timeout
+            ),
+            Err(Error::Locked)
+        ));
+        assert!(matches!(
+            locks.lock(
+                "/calendar",
+                key("/calendar"),
+                Depth::Infinity,
+                None,
+                &alice,
+                timeout
+            ),
+            Err(Error::Locked)
+        ));
+        locks
+            .lock(
+                "/calendar/workshop",
+                key("/calendar/workshop"),
+                Depth::Zero,
+                None,
+                &alice,
+                timeout,
+            )
+            .unwrap();
+        assert!(locks
+            .lock(
+                "/calendar",
+                key("/calendar"),
+                Depth::Zero,
+                None,
+                &alice,
+                timeout
+            )
+            .is_ok());
+
+        assert_eq!(locks.get_locks(&key("/calendar/work/event.ics")).len(), 1);
+        assert!(matches!(
+            locks.unlock(&key("/calendar/work/event.ics"), "urn:uuid:wrong", &alice),
+            Err(Error::LockTokenMismatch)
+        ));
+        assert!(matches!(
+            locks.unlock(&key("/calendar/work"), &lock.token, &user("bob")),
+            Err(Error::Unauthorized)
+        ));
+        locks
+            .unlock(&key("/calendar/work"), &lock.token, &alice)
+            .unwrap();
+        assert!(locks.get_locks(&key("/calendar/work/event.ics")).is_empty());
+    }
+
+    #[test]
+    fn test_lock_check_write() {
+        let locks = LockManager::new();
+        let alice = user("alice");
+        let path = "/calendar/work/event.ics";
+        let lock = locks
+            .lock(
+                path,
+                key(path),
+                Depth::Zero,
+                None,
+                &alice,
+                Duration::from_secs(60),
+            )
+            .unwrap();
+
+        let check = |if_header: Option<&str>, user: &str, etag: Option<&str>| {
+            let mut req = TestRequest::default().uri(path);
+            if let Some(if_header) = if_header {
+                req = req.insert_header(("If", if_header));
+            }
+            locks.check_write(
+                &req.to_http_request(),
+                &self::user(user),
+                path,
+                &key(path),
+                etag,
+            )
+        };
+
+        assert!(matches!(check(None, "alice", None), Err(Error::Locked)));
+        let token_list = format!("(<{}>)", lock.token);
+        assert!(check(Some(&token_list), "alice", None).is_ok());
+        // The token is only valid for the principal holding the lock
+        assert!(matches!(
+            check(Some(&token_list), "bob", None),
+            Err(Error::Locked)
+        ));
+        let token_etag_list = format!("(<{}> [\"abc\"])", lock.token);
+        assert!(check(Some(&token_etag_list), "alice", Some("abc")).is_ok());
+        assert!(matches!(
+            check(Some(&token_etag_list), "alice", Some("xyz")),
+            Err(Error::PreconditionFailed)
+        ));
+        // Lists tagged with other resources are not evaluated but the token is still submitted
+        let tagged_list = format!(
+            "</calendar/other.ics> ([\"xyz\"]) <{path}> (<{}>)",
+            lock.token
+        );
+        assert!(check(Some(&tagged_list), "alice", Some("abc")).is_ok());
+
+        // Unlocked resources only evaluate the condition
+        let other = "/calendar/work/other.ics";
+        let req = TestRequest::default()
+            .uri(other)
+            .insert_header(("If", "(<DAV:no-lock>)"))
+            .to_http_request();
+        assert!(matches!(
+            locks.check_write(&req, &alice, other, &key(other), None),
+            Err(Error::PreconditionFailed)
+        ));
+
+        // The same object is reachable without file extension
+        assert_eq!(locks.get_locks(&key("/calendar/work/event")).len(), 1);
+
+        locks.remove_locks(&key("/calendar/work"));
+        assert!(check(None, "alice", None).is_ok());
+    }
+
+    #[test]
+    fn test_lock_shared_collection() {
+        let locks = LockManager::new();
+        let share = Share {
+            principal: "alice".to_owned(),
+            collection_id: "work".to_owned(),
+            sharee: "bob".to_owned(),
+            access: ShareAccess::ReadWrite,
+        };
+        let mount = "/caldav/principal/bob/work_shared_by_alice";
+        assert_eq!(
+            lock_key(&format!("{mount}/event.ics"), Some(&share)),
+            "/caldav/principal/alice/work/event"
+        );
+        assert_eq!(
+            lock_key(mount, Some(&share)),
+            "/caldav/principal/alice/work"
+        );
+        // Only whole path segments are replaced
+        assert_eq!(
+            lock_key(&format!("{mount}2/event.ics"), Some(&share)),
+            format!("{mount}2/event")
+        );
+
+        // A lock taken by the owner also applies to the sharee's mount
+        let owner_path = "/caldav/principal/alice/work";
+        locks
+            .lock(
+                owner_path,
+                key(owner_path),
+                Depth::Infinity,
+                None,
+                &user("alice"),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        let path = format!("{mount}/event.ics");
+        let req = TestRequest::default().uri(&path).to_http_request();
+        assert!(matches!(
+            locks.check_write(
+                &req,
+                &user("bob"),
+                &path,
+                &lock_key(&path, Some(&share)),
+                None
+            ),
+            Err(Error::Locked)
+        ));
+        assert!(matches!(
+            locks.lock(
+                mount,
+                lock_key(mount, Some(&share)),
+                Depth::Zero,
+                None,
+                &user("bob"),
+                Duration::from_secs(60)
+            ),
+            Err(Error::Locked)
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+use actix_web::{http::StatusCode, FromRequest, HttpRequest, ResponseError};
+use futures_util::future::{err, ok, Ready};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("Invalid Overwrite header")]
+pub struct InvalidOverwriteHeader;
+
+impl ResponseError for InvalidOverwriteHeader {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4918#section-10.6
+#[derive(Debug, PartialEq)]
+pub struct Overwrite(pub bool);
+
+impl TryFrom<&[u8]> for Overwrite {
+    type Error = InvalidOverwriteHeader;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        match value {
+            b"T" => Ok(Self(true)),
+            b"F" => Ok(Self(false)),
+            _ => Err(InvalidOverwriteHeader),
+        }
+    }
+}
+
+impl FromRequest for Overwrite {
+    type Error = InvalidOverwriteHeader;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn extract(req: &HttpRequest) -> Self::Future {
+        if let Some(overwrite_header) = req.headers().get("Overwrite") {
+            match overwrite_header.as_bytes().try_into() {
+                Ok(overwrite) => ok(overwrite),
+                Err(e) => err(e),
+            }
+        } else {
+            // default is to overwrite
+            ok(Self(true))
+        }
+    }
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        Self::extract(req)
+    }
+}
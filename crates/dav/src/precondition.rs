@@ -0,0 +1,96 @@
+use crate::Error;
+use actix_web::{
+    http::header::{self, HeaderName},
+    HttpRequest,
+};
+
+// The opaque part of an entity tag, some clients send them without quotes
+pub(crate) fn opaque_tag(tag: &str) -> &str {
+    tag.strip_prefix('"')
+        .and_then(|tag| tag.strip_suffix('"'))
+        .unwrap_or(tag)
+}
+
+// Entity tags of a header along with whether they are weak
+fn entity_tags<'a>(
+    req: &'a HttpRequest,
+    name: &HeaderName,
+) -> Option<impl Iterator<Item = (bool, &'a str)>> {
+    let mut values = req.headers().get_all(name).peekable();
+    values.peek()?;
+    Some(
+        values
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| match tag.strip_prefix("W/") {
+                Some(tag) => (true, tag),
+                None => (false, tag),
+            })
+            .map(|(weak, tag)| (weak, opaque_tag(tag))),
+    )
+}
+
+/// Evaluates If-Match and If-None-Match against the current ETag of a resource,
+/// None meaning that the resource does not exist
+/// https://datatracker.ietf.org/doc/html/rfc7232#section-6
+pub fn check_preconditions(req: &HttpRequest, etag: Option<&str>) -> Result<(), Error> {
+    let etag = etag.map(opaque_tag);
+    if let Some(mut tags) = entity_tags(req, &header::IF_MATCH) {
+        // If-Match uses the strong comparison
+        let matches =
+            etag.is_some_and(|etag| tags.any(|(weak, tag)| tag == "*" || (!weak && tag == etag)));
+        if !matches {
+            return Err(Error::PreconditionFailed);
+        }
+    }
+
+    if let Some(mut tags) = entity_tags(req, &header::IF_NONE_MATCH) {
+        // If-None-Match uses the weak comparison
+        let matches = etag.is_some_and(|etag| tags.any(|(_weak, tag)| tag == "*" || tag == etag));
+        if matches {
+            return Err(Error::PreconditionFailed);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_preconditions;
+    use actix_web::test::TestRequest;
+
+    fn check(headers: &[(&str, &str)], etag: Option<&str>) -> bool {
+        let mut req = TestRequest::default();
+        for header in headers {
+            req = req.append_header(*header);
+        }
+        check_preconditions(&req.to_http_request(), etag).is_ok()
+    }
+
+    #[test]
+    fn test_if_match() {
+        assert!(check(&[], Some("\"abc\"")));
+        assert!(check(&[], None));
+        assert!(check(&[("If-Match", "\"abc\"")], Some("\"abc\"")));
+        assert!(check(&[("If-Match", "abc")], Some("\"abc\"")));
+        assert!(check(&[("If-Match", "\"xyz\", \"abc\"")], Some("\"abc\"")));
+        assert!(check(&[("If-Match", "*")], Some("\"abc\"")));
+        assert!(!check(&[("If-Match", "*")], None));
+        assert!(!check(&[("If-Match", "\"xyz\"")], Some("\"abc\"")));
+        assert!(!check(&[("If-Match", "\"abc\"")], None));
+        // Weak tags never match strongly
+        assert!(!check(&[("If-Match", "W/\"abc\"")], Some("\"abc\"")));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        assert!(check(&[("If-None-Match", "*")], None));
+        assert!(!check(&[("If-None-Match", "*")], Some("\"abc\"")));
+        assert!(check(&[("If-None-Match", "\"xyz\"")], Some("\"abc\"")));
+        assert!(!check(&[("If-None-Match", "\"abc\"")], Some("\"abc\"")));
+        assert!(!check(&[("If-None-Match", "W/\"abc\"")], Some("\"abc\"")));
+        assert!(check(&[("If-None-Match", "\"abc\"")], None));
+    }
+}
@@ -1,4 +1,5 @@
 use quick_xml::name::Namespace;
+use rustical_store::ShareAccess;
 use rustical_xml::{XmlDeserialize, XmlSerialize};
 use std::collections::{HashMap, HashSet};
 
@@ -8,6 +9,8 @@ pub enum UserPrivilege {
     Write,
     WriteProperties,
     WriteContent,
+    Bind,
+    Unbind,
     ReadAcl,
     ReadCurrentUserPrivilegeSet,
     WriteAcl,
@@ -72,6 +75,15 @@ impl UserPrivilegeSet {
         }
     }
 
+    // Full access for the owner, read access for e.g. delegates
+    pub fn owner_or_reader(is_owner: bool, is_reader: bool) -> Self {
+        if is_owner {
+            Self::all()
+        } else {
+            Self::owner_read(is_reader)
+        }
+    }
+
     pub fn read_only() -> Self {
         Self {
             privileges: HashSet::from([
@@ -81,6 +93,29 @@ impl UserPrivilegeSet {
             ]),
         }
     }
+
+    // Content may be changed but not the properties of the resource
+    pub fn read_write() -> Self {
+        Self {
+            privileges: HashSet::from([
+                UserPrivilege::Read,
+                UserPrivilege::WriteContent,
+                UserPrivilege::Bind,
+                UserPrivilege::Unbind,
+                UserPrivilege::ReadAcl,
+                UserPrivilege::ReadCurrentUserPrivilegeSet,
+            ]),
+        }
+    }
+}
+
+impl From<ShareAccess> for UserPrivilegeSet {
+    fn from(access: ShareAccess) -> Self {
+        match access {
+            ShareAccess::Read => Self::read_only(),
+            ShareAccess::ReadWrite => Self::read_write(),
+        }
+    }
 }
 
 impl<const N: usize> From<[UserPrivilege; N]> for UserPrivilegeSet {
@@ -1,4 +1,5 @@
-use crate::privileges::UserPrivilege;
+use crate::lock::{lock_key, LockManager};
+use crate::precondition::check_preconditions;
 use crate::resource::Resource;
 use crate::resource::ResourceService;
 use crate::Error;
@@ -11,12 +12,13 @@ use rustical_store::auth::User;
 use tracing::instrument;
 use tracing_actix_web::RootSpan;
 
-#[instrument(parent = root_span.id(), skip(path, req, root_span, resource_service))]
+#[instrument(parent = root_span.id(), skip(path, req, root_span, resource_service, lock_manager))]
 pub async fn route_delete<R: ResourceService>(
     path: Path<R::PathComponents>,
     req: HttpRequest,
     user: User,
     resource_service: Data<R>,
+    lock_manager: Data<LockManager>,
     root_span: RootSpan,
 ) -> Result<impl Responder, R::Error> {
     let no_trash = req
@@ -26,13 +28,17 @@ pub async fn route_delete<R: ResourceService>(
         .unwrap_or(false);
 
     let resource = resource_service.get_resource(&path).await?;
-    let privileges = resource.get_user_privileges(&user)?;
-    if !privileges.has(&UserPrivilege::Write) {
-        // TODO: Actually the spec wants us to look whether we have unbind access in the parent
-        // collection
+    if !resource.can_delete(&user)? {
         return Err(Error::Unauthorized.into());
     }
-    resource_service.delete_resource(&path, !no_trash).await?;
+    let etag = resource.get_etag();
+    check_preconditions(&req, etag.as_deref())?;
+    let key = lock_key(req.path(), resource.get_share());
+    lock_manager.check_write(&req, &user, req.path(), &key, etag.as_deref())?;
+    resource_service
+        .delete_resource(&path, !no_trash, req.resource_map())
+        .await?;
+    lock_manager.remove_locks(&key);
 
     Ok(HttpResponse::Ok().body(""))
 }
@@ -0,0 +1,106 @@
+use crate::depth_header::Depth;
+use crate::lock::{lock_key, parse_timeout, LockManager};
+use crate::precondition::check_preconditions;
+use crate::privileges::UserPrivilege;
+use crate::resource::Resource;
+use crate::resource::ResourceService;
+use crate::xml::lock::{
+    ActiveLockElement, LockDiscovery, LockInfoElement, LockResponse, LockScope,
+};
+use crate::Error;
+use actix_web::http::header::ContentType;
+use actix_web::web::Data;
+use actix_web::web::Path;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::HttpResponseBuilder;
+use rustical_store::auth::User;
+use rustical_xml::XmlDocument;
+use rustical_xml::XmlSerializeRoot;
+use tracing::instrument;
+use tracing_actix_web::RootSpan;
+
+// https://datatracker.ietf.org/doc/html/rfc4918#section-9.10
+#[instrument(parent = root_span.id(), skip(path, req, root_span, resource_service, lock_manager))]
+pub(crate) async fn route_lock<R: ResourceService>(
+    path: Path<R::PathComponents>,
+    body: String,
+    req: HttpRequest,
+    user: User,
+    root_span: RootSpan,
+    resource_service: Data<R>,
+    lock_manager: Data<LockManager>,
+) -> Result<HttpResponse, R::Error> {
+    let resource = resource_service.get_resource(&path).await?;
+    let privileges = resource.get_user_privileges(&user)?;
+    // https://datatracker.ietf.org/doc/html/rfc3744#appendix-B
+    if !privileges.has(&UserPrivilege::WriteContent) {
+        return Err(Error::Unauthorized.into());
+    }
+    check_preconditions(&req, resource.get_etag().as_deref())?;
+    let timeout = parse_timeout(&req);
+    let key = lock_key(req.path(), resource.get_share());
+
+    // An empty body refreshes an existing lock
+    if body.is_empty() {
+        let lock = lock_manager.refresh(&req, &key, &user, timeout)?;
+        return lock_response(HttpResponse::Ok(), vec![(&lock).into()]);
+    }
+
+    let lockinfo = LockInfoElement::parse_str(&body).map_err(Error::XmlDeserializationError)?;
+    if lockinfo.lockscope.scope != LockScope::Exclusive {
+        return Err(Error::BadRequest("Only exclusive locks are supported".to_owned()).into());
+    }
+    // Without a Depth header LOCK acts like Depth: infinity
+    let depth = match req.headers().get("Depth") {
+        Some(value) => {
+            Depth::try_from(value.as_bytes()).map_err(|err| Error::BadRequest(err.to_string()))?
+        }
+        None => Depth::Infinity,
+    };
+    if depth == Depth::One {
+        return Err(Error::BadRequest("Depth: 1 is not allowed for LOCK".to_owned()).into());
+    }
+
+    let lock = lock_manager.lock(req.path(), key, depth, lockinfo.owner, &user, timeout)?;
+    let mut response = HttpResponse::Ok();
+    response.insert_header(("Lock-Token", format!("<{}>", lock.token)));
+    lock_response(response, vec![(&lock).into()])
+}
+
+fn lock_response<E: From<Error>>(
+    mut response: HttpResponseBuilder,
+    activelock: Vec<ActiveLockElement>,
+) -> Result<HttpResponse, E> {
+    let mut output: Vec<_> = b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".into();
+    let mut writer = quick_xml::Writer::new_with_indent(&mut output, b' ', 4);
+    LockResponse {
+        lockdiscovery: LockDiscovery { activelock },
+    }
+    .serialize_root(&mut writer)
+    .map_err(Error::from)?;
+    Ok(response
+        .content_type(ContentType::xml())
+        .body(String::from_utf8(output).unwrap()))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4918#section-9.11
+#[instrument(parent = root_span.id(), skip(path, req, root_span, resource_service, lock_manager))]
+pub(crate) async fn route_unlock<R: ResourceService>(
+    path: Path<R::PathComponents>,
+    req: HttpRequest,
+    user: User,
+    root_span: RootSpan,
+    resource_service: Data<R>,
+    lock_manager: Data<LockManager>,
+) -> Result<HttpResponse, R::Error> {
+    let resource = resource_service.get_resource(&path).await?;
+    let token = req
+        .headers()
+        .get("Lock-Token")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().strip_prefix('<')?.strip_suffix('>'))
+        .ok_or(Error::BadRequest("Missing Lock-Token header".to_owned()))?;
+    lock_manager.unlock(&lock_key(req.path(), resource.get_share()), token, &user)?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,7 +1,9 @@
 mod delete;
+mod lock;
 mod propfind;
 mod proppatch;
 
 pub(crate) use delete::route_delete;
+pub(crate) use lock::{route_lock, route_unlock};
 pub(crate) use propfind::route_propfind;
 pub(crate) use proppatch::route_proppatch;
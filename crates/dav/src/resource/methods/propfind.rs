@@ -1,4 +1,5 @@
 use crate::depth_header::Depth;
+use crate::lock::{lock_key, LockManager};
 use crate::privileges::UserPrivilege;
 use crate::resource::Resource;
 use crate::resource::ResourceService;
@@ -15,8 +16,8 @@ use rustical_xml::XmlDocument;
 use tracing::instrument;
 use tracing_actix_web::RootSpan;
 
-#[instrument(parent = root_span.id(), skip(path, req, root_span, resource_service))]
-#[allow(clippy::type_complexity)]
+#[instrument(parent = root_span.id(), skip(path, req, root_span, resource_service, lock_manager))]
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub(crate) async fn route_propfind<R: ResourceService>(
     path: Path<R::PathComponents>,
     body: String,
@@ -25,6 +26,7 @@ pub(crate) async fn route_propfind<R: ResourceService>(
     depth: Depth,
     root_span: RootSpan,
     resource_service: Data<R>,
+    lock_manager: Data<LockManager>,
 ) -> Result<
     MultistatusElement<<R::Resource as Resource>::Prop, <R::MemberType as Resource>::Prop>,
     R::Error,
@@ -57,16 +59,24 @@ pub(crate) async fn route_propfind<R: ResourceService>(
     let mut member_responses = Vec::new();
     if depth != Depth::Zero {
         for (subpath, member) in resource_service.get_members(&path).await? {
-            member_responses.push(member.propfind(
-                &format!("{}/{}", req.path().trim_end_matches('/'), subpath),
+            let member_path = format!("{}/{}", req.path().trim_end_matches('/'), subpath);
+            member_responses.push(member.propfind_with_locks(
+                &member_path,
                 &props,
                 &user,
                 req.resource_map(),
+                &lock_manager.get_locks(&lock_key(&member_path, member.get_share())),
             )?);
         }
     }
 
-    let response = resource.propfind(req.path(), &props, &user, req.resource_map())?;
+    let response = resource.propfind_with_locks(
+        req.path(),
+        &props,
+        &user,
+        req.resource_map(),
+        &lock_manager.get_locks(&lock_key(req.path(), resource.get_share())),
+    )?;
 
     Ok(MultistatusElement {
         responses: vec![response],
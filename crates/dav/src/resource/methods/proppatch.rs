@@ -1,3 +1,5 @@
+use crate::lock::{lock_key, LockManager};
+use crate::precondition::check_preconditions;
 use crate::privileges::UserPrivilege;
 use crate::resource::Resource;
 use crate::resource::ResourceService;
@@ -64,7 +66,7 @@ enum Operation<T: XmlDeserialize> {
 #[xml(ns = "crate::namespace::NS_DAV")]
 struct PropertyupdateElement<T: XmlDeserialize>(#[xml(ty = "untagged", flatten)] Vec<Operation<T>>);
 
-#[instrument(parent = root_span.id(), skip(path, req, root_span, resource_service))]
+#[instrument(parent = root_span.id(), skip(path, req, root_span, resource_service, lock_manager))]
 pub(crate) async fn route_proppatch<R: ResourceService>(
     path: Path<R::PathComponents>,
     body: String,
@@ -72,6 +74,7 @@ pub(crate) async fn route_proppatch<R: ResourceService>(
     user: User,
     root_span: RootSpan,
     resource_service: Data<R>,
+    lock_manager: Data<LockManager>,
 ) -> Result<MultistatusElement<String, String>, R::Error> {
     let href = req.path().to_owned();
 
@@ -85,6 +88,10 @@ pub(crate) async fn route_proppatch<R: ResourceService>(
     if !privileges.has(&UserPrivilege::Write) {
         return Err(Error::Unauthorized.into());
     }
+    let etag = resource.get_etag();
+    check_preconditions(&req, etag.as_deref())?;
+    let key = lock_key(req.path(), resource.get_share());
+    lock_manager.check_write(&req, &user, req.path(), &key, etag.as_deref())?;
 
     let mut props_ok = Vec::new();
     let mut props_conflict = Vec::new();
@@ -1,4 +1,6 @@
-use crate::privileges::UserPrivilegeSet;
+use crate::lock::ActiveLock;
+use crate::privileges::{UserPrivilege, UserPrivilegeSet};
+use crate::xml::lock::{LockDiscovery, LockProp, LockPropName, SupportedLock};
 use crate::xml::multistatus::{PropTagWrapper, PropstatElement, PropstatWrapper};
 use crate::xml::Resourcetype;
 use crate::xml::{multistatus::ResponseElement, TagList};
@@ -9,6 +11,7 @@ use itertools::Itertools;
 use quick_xml::name::Namespace;
 pub use resource_service::ResourceService;
 use rustical_store::auth::User;
+use rustical_store::Share;
 use rustical_xml::{EnumUnitVariants, EnumVariants, XmlDeserialize, XmlSerialize};
 use std::str::FromStr;
 
@@ -56,14 +59,43 @@ pub trait Resource: Clone + 'static {
         None
     }
 
+    // Used to evaluate If-Match and If-None-Match
+    fn get_etag(&self) -> Option<String> {
+        None
+    }
+
     fn get_user_privileges(&self, user: &User) -> Result<UserPrivilegeSet, Self::Error>;
 
+    // Strictly DELETE requires DAV:unbind on the parent collection,
+    // we approximate that with the privileges on the resource itself
+    fn can_delete(&self, user: &User) -> Result<bool, Self::Error> {
+        let privileges = self.get_user_privileges(user)?;
+        Ok(privileges.has(&UserPrivilege::Write) || privileges.has(&UserPrivilege::Unbind))
+    }
+
+    /// The share through which the resource is reached if it is mounted in a sharee's home
+    fn get_share(&self) -> Option<&Share> {
+        None
+    }
+
     fn propfind(
         &self,
         path: &str,
         props: &[&str],
         user: &User,
         rmap: &ResourceMap,
+    ) -> Result<ResponseElement<Self::Prop>, Self::Error> {
+        self.propfind_with_locks(path, props, user, rmap, &[])
+    }
+
+    // locks are the active locks on the resource, used for DAV:lockdiscovery
+    fn propfind_with_locks(
+        &self,
+        path: &str,
+        props: &[&str],
+        user: &User,
+        rmap: &ResourceMap,
+        locks: &[ActiveLock],
     ) -> Result<ResponseElement<Self::Prop>, Self::Error> {
         let mut props = props.to_vec();
 
@@ -77,6 +109,7 @@ pub trait Resource: Clone + 'static {
 
             let props = Self::list_props()
                 .into_iter()
+                .chain(LockProp::variant_names())
                 .map(|(ns, tag)| (ns.to_owned(), tag.to_string()))
                 .collect_vec();
 
@@ -99,15 +132,26 @@ pub trait Resource: Clone + 'static {
             }
             props = Self::list_props()
                 .into_iter()
+                .chain(LockProp::variant_names())
                 .map(|(_ns, tag)| tag)
                 .collect();
         }
 
         let mut valid_props = vec![];
+        let mut lock_props = vec![];
         let mut invalid_props = vec![];
         for prop in props {
             if let Ok(valid_prop) = <Self::Prop as EnumUnitVariants>::UnitVariants::from_str(prop) {
                 valid_props.push(valid_prop);
+            } else if let Ok(lock_prop) = LockPropName::from_str(prop) {
+                lock_props.push(match lock_prop {
+                    LockPropName::Supportedlock => {
+                        LockProp::Supportedlock(SupportedLock::default())
+                    }
+                    LockPropName::Lockdiscovery => LockProp::Lockdiscovery(LockDiscovery {
+                        activelock: locks.iter().map(Into::into).collect(),
+                    }),
+                });
             } else {
                 invalid_props.push(prop.to_string())
             }
@@ -120,7 +164,7 @@ pub trait Resource: Clone + 'static {
 
         let mut propstats = vec![PropstatWrapper::Normal(PropstatElement {
             status: StatusCode::OK,
-            prop: PropTagWrapper(prop_responses),
+            prop: PropTagWrapper(prop_responses, lock_props),
         })];
         if !invalid_props.is_empty() {
             propstats.push(PropstatWrapper::TagList(PropstatElement {
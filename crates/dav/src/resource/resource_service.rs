@@ -6,8 +6,9 @@ use actix_web::{dev::ResourceMap, http::Method, web, ResponseError};
 use async_trait::async_trait;
 use serde::Deserialize;
 use std::str::FromStr;
+use url::Url;
 
-use super::methods::{route_delete, route_propfind, route_proppatch};
+use super::methods::{route_delete, route_lock, route_propfind, route_proppatch, route_unlock};
 use super::Resource;
 
 #[async_trait(?Send)]
@@ -35,10 +36,12 @@ pub trait ResourceService: Sized + 'static {
     ) -> Result<(), Self::Error> {
         Err(crate::Error::Unauthorized.into())
     }
+    // rmap is needed by resources whose deletion affects other resources, i.e. scheduling messages
     async fn delete_resource(
         &self,
         _path: &Self::PathComponents,
         _use_trashbin: bool,
+        _rmap: &ResourceMap,
     ) -> Result<(), Self::Error> {
         Err(crate::Error::Unauthorized.into())
     }
@@ -54,6 +57,8 @@ pub trait ResourceService: Sized + 'static {
                 .route(
                     web::method(Method::from_str("PROPPATCH").unwrap()).to(route_proppatch::<Self>),
                 )
+                .route(web::method(Method::from_str("LOCK").unwrap()).to(route_lock::<Self>))
+                .route(web::method(Method::from_str("UNLOCK").unwrap()).to(route_unlock::<Self>))
                 .delete(route_delete::<Self>),
         )
     }
@@ -82,6 +87,21 @@ pub trait NamedRoute {
             .path()
             .to_owned())
     }
+
+    // Inverse of get_url for routes with a single path element
+    // Accepts absolute http(s) URLs and paths
+    fn get_url_element(rmap: &ResourceMap, href: &str) -> Option<String> {
+        let path = match Url::parse(href) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => url.path().to_owned(),
+            // e.g. mailto: addresses
+            Ok(_) => return None,
+            Err(_) => href.to_owned(),
+        };
+        let path = path.trim_end_matches('/');
+        let element = path.rsplit('/').next()?;
+        (!element.is_empty() && Self::get_url(rmap, [element]).ok()? == path)
+            .then(|| element.to_owned())
+    }
 }
 
 pub struct ResourceServiceRoute<RS: ResourceService>(pub RS);
@@ -0,0 +1,51 @@
+use super::HrefElement;
+use rustical_xml::{XmlRootTag, XmlSerialize};
+
+// Preconditions reported in the body of an error response
+#[derive(XmlSerialize, Debug, Clone, PartialEq)]
+pub enum Precondition {
+    // https://datatracker.ietf.org/doc/html/rfc4791#section-5.3.2.1
+    #[xml(ns = "crate::namespace::NS_CALDAV", rename = b"no-uid-conflict")]
+    CalendarNoUidConflict(HrefElement),
+    // https://datatracker.ietf.org/doc/html/rfc6352#section-6.3.2.1
+    #[xml(ns = "crate::namespace::NS_CARDDAV", rename = b"no-uid-conflict")]
+    AddressbookNoUidConflict(HrefElement),
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4918#section-16
+#[derive(XmlSerialize, XmlRootTag)]
+#[xml(root = b"error", ns = "crate::namespace::NS_DAV")]
+#[xml(ns_prefix(
+    crate::namespace::NS_DAV = b"",
+    crate::namespace::NS_CARDDAV = b"CARD",
+    crate::namespace::NS_CALDAV = b"CAL"
+))]
+pub struct ErrorElement {
+    #[xml(ty = "untagged")]
+    pub precondition: Precondition,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorElement, Precondition};
+    use rustical_xml::XmlSerializeRoot;
+
+    #[test]
+    fn test_serialize_no_uid_conflict() {
+        let mut buf = Vec::new();
+        let mut writer = quick_xml::Writer::new(&mut buf);
+        ErrorElement {
+            precondition: Precondition::CalendarNoUidConflict(
+                "/caldav/user/alice/calendar/work/event".to_owned().into(),
+            ),
+        }
+        .serialize_root(&mut writer)
+        .unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        // The order of the namespace declarations is not stable
+        assert!(out.starts_with("<error "));
+        assert!(out.contains(" xmlns=\"DAV:\""));
+        assert!(out.contains("xmlns:CAL=\"urn:ietf:params:xml:ns:caldav\""));
+        assert!(out.ends_with("><CAL:no-uid-conflict><href>/caldav/user/alice/calendar/work/event</href></CAL:no-uid-conflict></error>"));
+    }
+}
@@ -0,0 +1,164 @@
+use super::HrefElement;
+use rustical_xml::{EnumUnitVariants, EnumVariants, XmlDeserialize, XmlRootTag, XmlSerialize};
+
+// WebDAV locking as specified in RFC 4918
+// https://datatracker.ietf.org/doc/html/rfc4918#section-14
+
+#[derive(XmlDeserialize, XmlSerialize, Clone, Debug, PartialEq)]
+pub enum LockScope {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    Exclusive,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    Shared,
+}
+
+// <!ELEMENT lockscope (exclusive | shared) >
+#[derive(XmlDeserialize, XmlSerialize, Clone, Debug, PartialEq)]
+pub struct LockScopeElement {
+    #[xml(ty = "untagged")]
+    pub scope: LockScope,
+}
+
+#[derive(XmlDeserialize, XmlSerialize, Clone, Debug, PartialEq)]
+pub enum LockType {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    Write,
+}
+
+// <!ELEMENT locktype (write) >
+#[derive(XmlDeserialize, XmlSerialize, Clone, Debug, PartialEq)]
+pub struct LockTypeElement {
+    #[xml(ty = "untagged")]
+    pub locktype: LockType,
+}
+
+// <!ELEMENT owner ANY >
+// We only keep an href or the text content
+#[derive(XmlDeserialize, XmlSerialize, Clone, Debug, Default, PartialEq)]
+#[xml(allow_invalid)]
+pub struct LockOwner {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub href: Option<String>,
+    #[xml(ty = "text", default = "Default::default")]
+    pub text: String,
+}
+
+// <!ELEMENT lockinfo (lockscope, locktype, owner?)  >
+#[derive(XmlDeserialize, XmlRootTag, Clone, Debug, PartialEq)]
+#[xml(root = b"lockinfo", ns = "crate::namespace::NS_DAV")]
+pub struct LockInfoElement {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub lockscope: LockScopeElement,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub locktype: LockTypeElement,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub owner: Option<LockOwner>,
+}
+
+// <!ELEMENT activelock (lockscope, locktype, depth, owner?, timeout?,
+//           locktoken?, lockroot)>
+#[derive(XmlSerialize, Clone, Debug, PartialEq)]
+pub struct ActiveLockElement {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub lockscope: LockScopeElement,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub locktype: LockTypeElement,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub depth: &'static str,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub owner: Option<LockOwner>,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub timeout: String,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub locktoken: HrefElement,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub lockroot: HrefElement,
+}
+
+// <!ELEMENT lockdiscovery (activelock)* >
+#[derive(XmlSerialize, Clone, Debug, Default, PartialEq)]
+pub struct LockDiscovery {
+    #[xml(ns = "crate::namespace::NS_DAV", flatten)]
+    pub activelock: Vec<ActiveLockElement>,
+}
+
+// <!ELEMENT lockentry (lockscope, locktype) >
+#[derive(XmlSerialize, Clone, Debug, PartialEq)]
+pub struct LockEntry {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub lockscope: LockScopeElement,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub locktype: LockTypeElement,
+}
+
+// <!ELEMENT supportedlock (lockentry)* >
+#[derive(XmlSerialize, Clone, Debug, PartialEq)]
+pub struct SupportedLock {
+    #[xml(ns = "crate::namespace::NS_DAV", flatten)]
+    pub lockentry: Vec<LockEntry>,
+}
+
+impl Default for SupportedLock {
+    fn default() -> Self {
+        // We only support exclusive write locks
+        Self {
+            lockentry: vec![LockEntry {
+                lockscope: LockScopeElement {
+                    scope: LockScope::Exclusive,
+                },
+                locktype: LockTypeElement {
+                    locktype: LockType::Write,
+                },
+            }],
+        }
+    }
+}
+
+// Locking properties are available on every resource
+#[derive(XmlSerialize, Clone, PartialEq, EnumVariants, EnumUnitVariants)]
+#[xml(unit_variants_ident = "LockPropName")]
+pub enum LockProp {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    Supportedlock(SupportedLock),
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    Lockdiscovery(LockDiscovery),
+}
+
+// Response body of LOCK
+#[derive(XmlSerialize, XmlRootTag)]
+#[xml(root = b"prop", ns = "crate::namespace::NS_DAV")]
+#[xml(ns_prefix(crate::namespace::NS_DAV = b""))]
+pub struct LockResponse {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub lockdiscovery: LockDiscovery,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LockInfoElement, LockOwner, LockScope, LockType};
+    use rustical_xml::XmlDocument;
+
+    #[test]
+    fn test_parse_lockinfo() {
+        let lockinfo = LockInfoElement::parse_str(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+            <D:lockinfo xmlns:D='DAV:'>
+                <D:lockscope><D:exclusive/></D:lockscope>
+                <D:locktype><D:write/></D:locktype>
+                <D:owner>
+                    <D:href>mailto:alice@example.com</D:href>
+                </D:owner>
+            </D:lockinfo>"#,
+        )
+        .unwrap();
+        assert_eq!(lockinfo.lockscope.scope, LockScope::Exclusive);
+        assert_eq!(lockinfo.locktype.locktype, LockType::Write);
+        assert_eq!(
+            lockinfo.owner,
+            Some(LockOwner {
+                href: Some("mailto:alice@example.com".to_owned()),
+                text: String::new(),
+            })
+        );
+    }
+}
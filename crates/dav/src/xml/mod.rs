@@ -1,13 +1,18 @@
+pub mod error;
+pub mod lock;
 pub mod multistatus;
 mod propfind;
 mod resourcetype;
 pub mod tag_list;
+mod text_match;
 use derive_more::derive::From;
 pub use multistatus::MultistatusElement;
 pub use propfind::{PropElement, PropfindElement, PropfindType, Propname};
 pub use resourcetype::{Resourcetype, ResourcetypeInner};
 use rustical_xml::{XmlDeserialize, XmlSerialize};
 pub use tag_list::TagList;
+pub use text_match::{MatchType, NegateCondition, TextCollation, TextMatchElement};
+pub mod sharing;
 pub mod sync_collection;
 
 #[derive(XmlDeserialize, XmlSerialize, Debug, Clone, From, PartialEq)]
@@ -21,3 +26,39 @@ impl HrefElement {
         Self { href }
     }
 }
+
+// A set of principals, e.g. DAV:group-member-set
+#[derive(XmlDeserialize, XmlSerialize, Debug, Clone, Default, From, PartialEq)]
+pub struct HrefListElement {
+    #[xml(ns = "crate::namespace::NS_DAV", flatten)]
+    pub href: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rustical_xml::{XmlRootTag, XmlSerialize, XmlSerializeRoot};
+
+    use super::HrefListElement;
+
+    #[derive(XmlSerialize, XmlRootTag)]
+    #[xml(root = b"document")]
+    struct Document {
+        group_member_set: HrefListElement,
+    }
+
+    #[test]
+    fn test_serialize_href_list() {
+        let mut buf = Vec::new();
+        let mut writer = quick_xml::Writer::new(&mut buf);
+        Document {
+            group_member_set: vec!["/user/alice".to_owned(), "/user/bob".to_owned()].into(),
+        }
+        .serialize_root(&mut writer)
+        .unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            out,
+            "<document><group-member-set><href xmlns=\"DAV:\">/user/alice</href><href xmlns=\"DAV:\">/user/bob</href></group-member-set></document>"
+        )
+    }
+}
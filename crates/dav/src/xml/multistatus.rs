@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::xml::TagList;
+use crate::xml::{lock::LockProp, TagList};
 use actix_web::{
     body::BoxBody,
     http::{header::ContentType, StatusCode},
@@ -10,7 +10,11 @@ use quick_xml::name::Namespace;
 use rustical_xml::{XmlRootTag, XmlSerialize, XmlSerializeRoot};
 
 #[derive(XmlSerialize)]
-pub struct PropTagWrapper<T: XmlSerialize>(#[xml(flatten, ty = "untagged")] pub Vec<T>);
+pub struct PropTagWrapper<T: XmlSerialize>(
+    #[xml(flatten, ty = "untagged")] pub Vec<T>,
+    // WebDAV locking properties which every resource has
+    #[xml(flatten, ty = "untagged")] pub Vec<LockProp>,
+);
 
 // RFC 2518
 // <!ELEMENT propstat (prop, status, responsedescription?) >
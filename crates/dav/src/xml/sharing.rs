@@ -0,0 +1,221 @@
+use super::HrefElement;
+use rustical_store::ShareAccess;
+use rustical_xml::{XmlDeserialize, XmlDocument, XmlSerialize};
+
+// Sharing as implemented by Apple's calendarserver
+// https://github.com/apple/ccs-calendarserver/blob/master/doc/Extensions/caldav-sharing.txt
+
+// <!ELEMENT set (DAV:href, common-name?, summary?, (read | read-write))>
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+#[xml(allow_invalid)]
+pub struct ShareSetElement {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub href: String,
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER")]
+    pub read_write: Option<()>,
+}
+
+// <!ELEMENT remove (DAV:href)>
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+pub struct ShareRemoveElement {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub href: String,
+}
+
+// <!ELEMENT share (set | remove)*>
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+pub struct ShareElement {
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER", flatten)]
+    pub set: Vec<ShareSetElement>,
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER", flatten)]
+    pub remove: Vec<ShareRemoveElement>,
+}
+
+// WebDAV Resource Sharing
+// https://datatracker.ietf.org/doc/html/draft-pot-webdav-resource-sharing-04#section-5.3.2
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+pub enum ShareAccessValue {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    NotShared,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    NoAccess,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    Read,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    ReadWrite,
+}
+
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+pub struct ShareAccessElement {
+    #[xml(ty = "untagged")]
+    pub access: ShareAccessValue,
+}
+
+// <!ELEMENT sharee (href, prop?, comment?, share-access)>
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+#[xml(allow_invalid)]
+pub struct ShareeElement {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub href: String,
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub share_access: ShareAccessElement,
+}
+
+// <!ELEMENT share-resource (sharee*)>
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+pub struct ShareResourceElement {
+    #[xml(ns = "crate::namespace::NS_DAV", flatten)]
+    pub sharee: Vec<ShareeElement>,
+}
+
+// POST body changing the sharees of a collection
+#[derive(XmlDeserialize, XmlDocument, Clone, Debug, PartialEq)]
+pub enum ShareRequest {
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER")]
+    Share(ShareElement),
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    ShareResource(ShareResourceElement),
+}
+
+impl ShareRequest {
+    // The requested access per sharee href, None revokes the share
+    pub fn into_changes(self) -> Vec<(String, Option<ShareAccess>)> {
+        match self {
+            Self::Share(ShareElement { set, remove }) => set
+                .into_iter()
+                .map(|set| {
+                    let access = if set.read_write.is_some() {
+                        ShareAccess::ReadWrite
+                    } else {
+                        ShareAccess::Read
+                    };
+                    (set.href, Some(access))
+                })
+                .chain(remove.into_iter().map(|remove| (remove.href, None)))
+                .collect(),
+            Self::ShareResource(ShareResourceElement { sharee }) => sharee
+                .into_iter()
+                .map(|sharee| {
+                    let access = match sharee.share_access.access {
+                        ShareAccessValue::NotShared | ShareAccessValue::NoAccess => None,
+                        ShareAccessValue::Read => Some(ShareAccess::Read),
+                        ShareAccessValue::ReadWrite => Some(ShareAccess::ReadWrite),
+                    };
+                    (sharee.href, access)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(XmlSerialize, Clone, Debug, PartialEq)]
+pub enum InviteAccess {
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER")]
+    Read,
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER")]
+    ReadWrite,
+}
+
+impl From<ShareAccess> for InviteAccess {
+    fn from(access: ShareAccess) -> Self {
+        match access {
+            ShareAccess::Read => Self::Read,
+            ShareAccess::ReadWrite => Self::ReadWrite,
+        }
+    }
+}
+
+// <!ELEMENT user (DAV:href, common-name?, (invite-noresponse | invite-accepted | ...), access)>
+#[derive(XmlSerialize, Clone, Debug, PartialEq)]
+pub struct InviteUserElement {
+    #[xml(ns = "crate::namespace::NS_DAV")]
+    pub href: String,
+    // Shares don't have to be accepted by the sharee
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER")]
+    pub invite_accepted: (),
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER")]
+    pub access: InviteAccess,
+}
+
+// <!ELEMENT invite (organizer?, user*)>
+#[derive(XmlSerialize, Clone, Debug, PartialEq)]
+pub struct InviteElement {
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER")]
+    pub organizer: HrefElement,
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER", flatten)]
+    pub user: Vec<InviteUserElement>,
+}
+
+// <!ELEMENT allowed-sharing-modes (can-be-shared?, can-be-published?)>
+#[derive(XmlSerialize, Clone, Debug, PartialEq)]
+pub struct AllowedSharingModes {
+    #[xml(ns = "crate::namespace::NS_CALENDARSERVER")]
+    pub can_be_shared: Option<()>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShareRequest;
+    use rustical_store::ShareAccess;
+    use rustical_xml::XmlDocument;
+
+    #[test]
+    fn test_xml_share_request() {
+        let request = ShareRequest::parse_str(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+            <CS:share xmlns:D="DAV:" xmlns:CS="http://calendarserver.org/ns/">
+                <CS:set>
+                    <D:href>/caldav/user/bob</D:href>
+                    <CS:common-name>Bob</CS:common-name>
+                    <CS:summary>Team calendar</CS:summary>
+                    <CS:read-write />
+                </CS:set>
+                <CS:set>
+                    <D:href>/caldav/user/carol</D:href>
+                    <CS:read />
+                </CS:set>
+                <CS:remove>
+                    <D:href>/caldav/user/dave</D:href>
+                </CS:remove>
+            </CS:share>
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.into_changes(),
+            vec![
+                ("/caldav/user/bob".to_owned(), Some(ShareAccess::ReadWrite)),
+                ("/caldav/user/carol".to_owned(), Some(ShareAccess::Read)),
+                ("/caldav/user/dave".to_owned(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_xml_share_resource_request() {
+        let request = ShareRequest::parse_str(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+            <D:share-resource xmlns:D="DAV:">
+                <D:sharee>
+                    <D:href>/carddav/user/bob</D:href>
+                    <D:prop><D:displayname>Bob</D:displayname></D:prop>
+                    <D:share-access><D:read /></D:share-access>
+                </D:sharee>
+                <D:sharee>
+                    <D:href>/carddav/user/carol</D:href>
+                    <D:share-access><D:no-access /></D:share-access>
+                </D:sharee>
+            </D:share-resource>
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.into_changes(),
+            vec![
+                ("/carddav/user/bob".to_owned(), Some(ShareAccess::Read)),
+                ("/carddav/user/carol".to_owned(), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use rustical_xml::{ValueDeserialize, XmlDeserialize, XmlError};
+
+// https://datatracker.ietf.org/doc/html/rfc4790
+// The default differs: i;ascii-casemap for CalDAV, i;unicode-casemap for CardDAV
+// https://datatracker.ietf.org/doc/html/rfc4791#section-7.5.1
+// https://datatracker.ietf.org/doc/html/rfc6352#section-10.5.4
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextCollation {
+    AsciiCasemap,
+    UnicodeCasemap,
+    Octet,
+}
+
+impl TextCollation {
+    pub fn matches(&self, needle: &str, text: &str, match_type: &MatchType) -> bool {
+        let (needle, text) = match self {
+            Self::AsciiCasemap => (needle.to_ascii_lowercase(), text.to_ascii_lowercase()),
+            Self::UnicodeCasemap => (needle.to_lowercase(), text.to_lowercase()),
+            Self::Octet => (needle.to_owned(), text.to_owned()),
+        };
+        match match_type {
+            MatchType::Equals => text == needle,
+            MatchType::Contains => text.contains(&needle),
+            MatchType::StartsWith => text.starts_with(&needle),
+            MatchType::EndsWith => text.ends_with(&needle),
+        }
+    }
+}
+
+impl ValueDeserialize for TextCollation {
+    fn deserialize(val: &str) -> Result<Self, XmlError> {
+        match <String as ValueDeserialize>::deserialize(val)?.as_str() {
+            "i;ascii-casemap" => Ok(Self::AsciiCasemap),
+            "i;unicode-casemap" => Ok(Self::UnicodeCasemap),
+            "i;octet" => Ok(Self::Octet),
+            _ => Err(XmlError::Other(format!("Unsupported collation: {val}"))),
+        }
+    }
+}
+
+// Only CardDAV specifies the match-type attribute, CalDAV always uses contains
+// https://datatracker.ietf.org/doc/html/rfc6352#section-10.5.4
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum MatchType {
+    Equals,
+    #[default]
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+impl ValueDeserialize for MatchType {
+    fn deserialize(val: &str) -> Result<Self, XmlError> {
+        match <String as ValueDeserialize>::deserialize(val)?.as_str() {
+            "equals" => Ok(Self::Equals),
+            "contains" => Ok(Self::Contains),
+            "starts-with" => Ok(Self::StartsWith),
+            "ends-with" => Ok(Self::EndsWith),
+            _ => Err(XmlError::Other(format!("Invalid match-type: {val}"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NegateCondition(pub bool);
+
+impl ValueDeserialize for NegateCondition {
+    fn deserialize(val: &str) -> Result<Self, XmlError> {
+        match <String as ValueDeserialize>::deserialize(val)?.as_str() {
+            "yes" => Ok(Self(true)),
+            "no" => Ok(Self(false)),
+            _ => Err(XmlError::Other(format!(
+                "Invalid negate-condition: {val}, must be yes or no"
+            ))),
+        }
+    }
+}
+
+// <!ELEMENT text-match (#PCDATA)>
+// https://datatracker.ietf.org/doc/html/rfc4791#section-9.7.5
+#[derive(XmlDeserialize, Clone, Debug, PartialEq)]
+pub struct TextMatchElement {
+    #[xml(ty = "attr")]
+    pub collation: Option<TextCollation>,
+    #[xml(ty = "attr", default = "Default::default")]
+    pub negate_condition: NegateCondition,
+    #[xml(ty = "attr", default = "Default::default")]
+    pub match_type: MatchType,
+    #[xml(ty = "text", default = "Default::default")]
+    pub needle: String,
+}
+
+impl TextMatchElement {
+    // Matches against a TEXT value from an iCalendar or vCard property
+    // default_collation is the protocol's collation used if the request doesn't specify one
+    pub fn matches(&self, text: &str, default_collation: TextCollation) -> bool {
+        self.collation.unwrap_or(default_collation).matches(
+            &self.needle,
+            &unescape_text(text),
+            &self.match_type,
+        ) ^ self.negate_condition.0
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc5545#section-3.3.11
+fn unescape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(escaped) => out.push(escaped),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchType, NegateCondition, TextCollation, TextMatchElement};
+
+    #[test]
+    fn test_text_match() {
+        let text_match = TextMatchElement {
+            collation: None,
+            negate_condition: NegateCondition(false),
+            match_type: MatchType::Contains,
+            needle: "meeting".to_owned(),
+        };
+        assert!(text_match.matches("Weekly MEETING", TextCollation::AsciiCasemap));
+        assert!(!text_match.matches("Lunch", TextCollation::AsciiCasemap));
+
+        let text_match = TextMatchElement {
+            needle: "Doe, J".to_owned(),
+            ..text_match
+        };
+        assert!(text_match.matches("Doe\\, Jane", TextCollation::AsciiCasemap));
+
+        // Without a collation attribute CardDAV compares Unicode case-insensitively
+        let text_match = TextMatchElement {
+            needle: "ärger".to_owned(),
+            ..text_match
+        };
+        assert!(!text_match.matches("ÄRGER", TextCollation::AsciiCasemap));
+        assert!(text_match.matches("ÄRGER", TextCollation::UnicodeCasemap));
+
+        let text_match = TextMatchElement {
+            collation: Some(TextCollation::Octet),
+            needle: "meeting".to_owned(),
+            ..text_match
+        };
+        assert!(!text_match.matches("Weekly MEETING", TextCollation::UnicodeCasemap));
+
+        let text_match = TextMatchElement {
+            collation: Some(TextCollation::UnicodeCasemap),
+            negate_condition: NegateCondition(true),
+            match_type: MatchType::Equals,
+            needle: "ÄRGER".to_owned(),
+        };
+        assert!(!text_match.matches("ärger", TextCollation::AsciiCasemap));
+        assert!(text_match.matches("ärgerlich", TextCollation::AsciiCasemap));
+    }
+}
@@ -4,6 +4,36 @@ fn default_enabled() -> bool {
     true
 }
 
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".to_owned(), "profile".to_owned()]
+}
+
+// The ID token claim that is used as the principal id
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserIdClaim {
+    // Stable, but usually not human-readable
+    #[default]
+    Sub,
+    // Many providers let users change it, so it's only safe if the provider controls usernames
+    // https://openid.net/specs/openid-connect-core-1_0.html#ClaimStability
+    PreferredUsername,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OidcConfig {
+    // Shown on the login button
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub claim_userid: UserIdClaim,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct FrontendConfig {
@@ -12,4 +42,6 @@ pub struct FrontendConfig {
     pub secret_key: [u8; 64],
     #[serde(default = "default_enabled")]
     pub enabled: bool,
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
 }
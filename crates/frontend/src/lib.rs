@@ -14,11 +14,16 @@ use askama_actix::TemplateToResponse;
 use assets::{Assets, EmbedService};
 use routes::{
     addressbook::{route_addressbook, route_addressbook_restore},
-    calendar::{route_calendar, route_calendar_restore},
+    app_token::{route_delete_app_token, route_post_app_token},
+    calendar::{
+        route_calendar, route_calendar_restore, route_delete_calendar_feed,
+        route_post_calendar_feed,
+    },
     login::{route_get_login, route_post_login},
+    oidc::{route_get_oidc, route_get_oidc_callback, OidcProviderCache},
 };
 use rustical_store::{
-    auth::{AuthenticationMiddleware, AuthenticationProvider, User},
+    auth::{AppToken, AuthenticationMiddleware, AuthenticationProvider, User},
     Addressbook, AddressbookStore, Calendar, CalendarStore,
 };
 use std::sync::Arc;
@@ -27,7 +32,7 @@ mod assets;
 mod config;
 mod routes;
 
-pub use config::FrontendConfig;
+pub use config::{FrontendConfig, OidcConfig, UserIdClaim};
 
 #[derive(Template)]
 #[template(path = "pages/user.html")]
@@ -37,28 +42,33 @@ struct UserPage {
     pub deleted_calendars: Vec<Calendar>,
     pub addressbooks: Vec<Addressbook>,
     pub deleted_addressbooks: Vec<Addressbook>,
+    pub app_tokens: Vec<AppToken>,
+    pub manage_app_tokens: bool,
 }
 
-async fn route_user<CS: CalendarStore, AS: AddressbookStore>(
+async fn route_user<AP: AuthenticationProvider, CS: CalendarStore, AS: AddressbookStore>(
     path: Path<String>,
+    auth_provider: Data<AP>,
     cal_store: Data<CS>,
     addr_store: Data<AS>,
     user: User,
-) -> impl Responder {
+) -> Result<HttpResponse, rustical_store::Error> {
     // TODO: Check for authorization
     let user_id = path.into_inner();
     if user_id != user.id {
-        return actix_web::HttpResponse::Unauthorized().body("Unauthorized");
+        return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
     }
 
-    UserPage {
-        calendars: cal_store.get_calendars(&user.id).await.unwrap(),
-        deleted_calendars: cal_store.get_deleted_calendars(&user.id).await.unwrap(),
-        addressbooks: addr_store.get_addressbooks(&user.id).await.unwrap(),
-        deleted_addressbooks: addr_store.get_deleted_addressbooks(&user.id).await.unwrap(),
+    Ok(UserPage {
+        calendars: cal_store.get_calendars(&user.id).await?,
+        deleted_calendars: cal_store.get_deleted_calendars(&user.id).await?,
+        addressbooks: addr_store.get_addressbooks(&user.id).await?,
+        deleted_addressbooks: addr_store.get_deleted_addressbooks(&user.id).await?,
+        app_tokens: auth_provider.get_app_tokens(&user.id).await?,
+        manage_app_tokens: auth_provider.supports_app_tokens(),
         user_id: user.id,
     }
-    .to_response()
+    .to_response())
 }
 
 async fn route_root(user: Option<User>, req: HttpRequest) -> impl Responder {
@@ -116,23 +126,43 @@ pub fn configure_frontend<AP: AuthenticationProvider, CS: CalendarStore, AS: Add
                     Key::from(&frontend_config.secret_key),
                 )
                 .cookie_secure(true)
-                .cookie_same_site(SameSite::Strict)
+                // The OIDC callback is a cross-site navigation that needs the session
+                .cookie_same_site(SameSite::Lax)
                 .cookie_content_security(CookieContentSecurity::Private)
                 .build(),
             )
             .app_data(Data::from(auth_provider))
+            .app_data(Data::new(frontend_config.clone()))
+            .app_data(Data::new(OidcProviderCache::default()))
             .app_data(Data::from(cal_store.clone()))
             .app_data(Data::from(addr_store.clone()))
             .service(EmbedService::<Assets>::new("/assets".to_owned()))
             .service(web::resource("").route(web::method(Method::GET).to(route_root)))
             .service(
                 web::resource("/user/{user}")
-                    .route(web::method(Method::GET).to(route_user::<CS, AS>))
+                    .route(web::method(Method::GET).to(route_user::<AP, CS, AS>))
                     .name("frontend_user"),
             )
+            .service(
+                web::resource("/user/{user}/app_token")
+                    .route(web::method(Method::POST).to(route_post_app_token::<AP>)),
+            )
+            .service(
+                web::resource("/user/{user}/app_token/{id}/delete")
+                    .route(web::method(Method::POST).to(route_delete_app_token::<AP>)),
+            )
             .service(
                 web::resource("/user/{user}/calendar/{calendar}")
-                    .route(web::method(Method::GET).to(route_calendar::<CS>)),
+                    .route(web::method(Method::GET).to(route_calendar::<CS>))
+                    .name("frontend_calendar"),
+            )
+            .service(
+                web::resource("/user/{user}/calendar/{calendar}/feed")
+                    .route(web::method(Method::POST).to(route_post_calendar_feed::<CS>)),
+            )
+            .service(
+                web::resource("/user/{user}/calendar/{calendar}/feed/{token}/delete")
+                    .route(web::method(Method::POST).to(route_delete_calendar_feed::<CS>)),
             )
             .service(
                 web::resource("/user/{user}/calendar/{calendar}/restore")
@@ -151,6 +181,16 @@ pub fn configure_frontend<AP: AuthenticationProvider, CS: CalendarStore, AS: Add
                     .name("frontend_login")
                     .route(web::method(Method::GET).to(route_get_login))
                     .route(web::method(Method::POST).to(route_post_login::<AP>)),
+            )
+            .service(
+                web::resource("/login/oidc")
+                    .name("frontend_login_oidc")
+                    .route(web::method(Method::GET).to(route_get_oidc)),
+            )
+            .service(
+                web::resource("/login/oidc/callback")
+                    .name("frontend_oidc_callback")
+                    .route(web::method(Method::GET).to(route_get_oidc_callback::<AP>)),
             ),
     );
 }
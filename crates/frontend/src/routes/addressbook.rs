@@ -19,7 +19,7 @@ pub async fn route_addressbook<AS: AddressbookStore>(
     user: User,
 ) -> Result<impl Responder, rustical_store::Error> {
     let (owner, addrbook_id) = path.into_inner();
-    if owner != user.id {
+    if !user.is_principal(&owner) {
         return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
     }
     Ok(AddressbookPage {
@@ -35,7 +35,7 @@ pub async fn route_addressbook_restore<AS: AddressbookStore>(
     user: User,
 ) -> Result<impl Responder, rustical_store::Error> {
     let (owner, addressbook_id) = path.into_inner();
-    if owner != user.id {
+    if !user.is_principal(&owner) {
         return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
     }
     store.restore_addressbook(&owner, &addressbook_id).await?;
@@ -0,0 +1,74 @@
+use actix_web::{
+    http::StatusCode,
+    web::{self, Data, Form, Path},
+    HttpRequest, HttpResponse, Responder,
+};
+use askama::Template;
+use askama_actix::TemplateToResponse;
+use rustical_store::auth::{generate_app_token, AuthenticationProvider, RateLimiter, User};
+use serde::Deserialize;
+
+#[derive(Template)]
+#[template(path = "pages/app_token.html")]
+struct AppTokenPage {
+    user_id: String,
+    name: String,
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct PostAppTokenForm {
+    name: String,
+}
+
+pub async fn route_post_app_token<AP: AuthenticationProvider>(
+    path: Path<String>,
+    form: Form<PostAppTokenForm>,
+    auth_provider: Data<AP>,
+    user: User,
+) -> Result<impl Responder, rustical_store::Error> {
+    let user_id = path.into_inner();
+    if user_id != user.id {
+        return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
+    }
+    let name = form.into_inner().name;
+    if name.trim().is_empty() {
+        return Err(rustical_store::Error::InvalidData(
+            "App token name must not be empty".to_owned(),
+        ));
+    }
+
+    // Only the hash is stored, so the token can only be shown now
+    let (token, token_hash) = generate_app_token();
+    auth_provider
+        .add_app_token(&user.id, name.to_owned(), token_hash)
+        .await?;
+    Ok(AppTokenPage {
+        user_id: user.id,
+        name,
+        token,
+    }
+    .to_response())
+}
+
+pub async fn route_delete_app_token<AP: AuthenticationProvider>(
+    path: Path<(String, String)>,
+    req: HttpRequest,
+    auth_provider: Data<AP>,
+    user: User,
+) -> Result<impl Responder, rustical_store::Error> {
+    let (user_id, token_id) = path.into_inner();
+    if user_id != user.id {
+        return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
+    }
+    auth_provider.remove_app_token(&user.id, &token_id).await?;
+    // The token must not keep working from the verification cache
+    if let Some(rate_limiter) = req.app_data::<Data<RateLimiter>>() {
+        rate_limiter.invalidate_user(&user.id);
+    }
+    let user_url = req.url_for("frontend_user", [&user.id]).unwrap();
+    Ok(web::Redirect::to(user_url.to_string())
+        .using_status_code(StatusCode::FOUND)
+        .respond_to(&req)
+        .map_into_boxed_body())
+}
@@ -1,33 +1,132 @@
 use actix_web::{
     http::{header, StatusCode},
-    web::{self, Data, Path},
+    web::{self, Data, Form, Path},
     HttpRequest, HttpResponse, Responder,
 };
 use askama::Template;
 use askama_actix::TemplateToResponse;
-use rustical_store::{auth::User, Calendar, CalendarStore};
+use rustical_store::{
+    auth::User,
+    calendar::{CalendarFeed, FeedPrivacy, WebcalStatus},
+    Calendar, CalendarStore,
+};
+use serde::Deserialize;
+
+struct FeedLink {
+    feed: CalendarFeed,
+    url: String,
+    webcal_url: String,
+}
 
 #[derive(Template)]
 #[template(path = "pages/calendar.html")]
 struct CalendarPage {
     calendar: Calendar,
+    feeds: Vec<FeedLink>,
+    can_write: bool,
+    webcal_status: Option<WebcalStatus>,
 }
 
 pub async fn route_calendar<C: CalendarStore>(
     path: Path<(String, String)>,
+    req: HttpRequest,
     store: Data<C>,
     user: User,
 ) -> Result<impl Responder, rustical_store::Error> {
     let (owner, cal_id) = path.into_inner();
-    if owner != user.id {
+    if !user.can_read_calendars(&owner) {
         return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
     }
+    let can_write = user.can_write_calendars(&owner);
+    // Feed tokens are credentials, so only show them to users who could revoke them
+    let feeds = if can_write {
+        store.get_calendar_feeds(&owner, &cal_id).await?
+    } else {
+        vec![]
+    };
+    let feeds = feeds
+        .into_iter()
+        .map(|feed| {
+            let url = req
+                .url_for("calendar_feed", [format!("{}.ics", feed.token)])
+                .unwrap()
+                .to_string();
+            let webcal_url = format!(
+                "webcal{}",
+                url.trim_start_matches("https").trim_start_matches("http")
+            );
+            FeedLink {
+                feed,
+                url,
+                webcal_url,
+            }
+        })
+        .collect();
+    let calendar = store.get_calendar(&owner, &cal_id).await?;
+    let webcal_status = match calendar.subscription_url {
+        Some(_) => Some(store.get_webcal_status(&owner, &cal_id).await?),
+        None => None,
+    };
     Ok(CalendarPage {
-        calendar: store.get_calendar(&owner, &cal_id).await?,
+        calendar,
+        feeds,
+        can_write,
+        webcal_status,
     }
     .to_response())
 }
 
+fn redirect_to_calendar(req: &HttpRequest, owner: &str, cal_id: &str) -> HttpResponse {
+    let calendar_url = req.url_for("frontend_calendar", [owner, cal_id]).unwrap();
+    web::Redirect::to(calendar_url.to_string())
+        .using_status_code(StatusCode::FOUND)
+        .respond_to(req)
+        .map_into_boxed_body()
+}
+
+#[derive(Deserialize)]
+pub struct PostCalendarFeedForm {
+    #[serde(default)]
+    privacy: FeedPrivacy,
+}
+
+pub async fn route_post_calendar_feed<C: CalendarStore>(
+    path: Path<(String, String)>,
+    form: Form<PostCalendarFeedForm>,
+    req: HttpRequest,
+    store: Data<C>,
+    user: User,
+) -> Result<impl Responder, rustical_store::Error> {
+    let (owner, cal_id) = path.into_inner();
+    if !user.can_write_calendars(&owner) {
+        return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
+    }
+    // Make sure the calendar exists
+    store.get_calendar(&owner, &cal_id).await?;
+    store
+        .insert_calendar_feed(CalendarFeed::new(
+            owner.to_owned(),
+            cal_id.to_owned(),
+            form.privacy,
+        ))
+        .await?;
+    Ok(redirect_to_calendar(&req, &owner, &cal_id))
+}
+
+pub async fn route_delete_calendar_feed<C: CalendarStore>(
+    path: Path<(String, String, String)>,
+    req: HttpRequest,
+    store: Data<C>,
+    user: User,
+) -> Result<impl Responder, rustical_store::Error> {
+    let (owner, cal_id, token) = path.into_inner();
+    if !user.can_write_calendars(&owner) {
+        return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
+    }
+    store.delete_calendar_feed(&owner, &cal_id, &token).await?;
+    Ok(redirect_to_calendar(&req, &owner, &cal_id))
+}
+
 pub async fn route_calendar_restore<CS: CalendarStore>(
     path: Path<(String, String)>,
     req: HttpRequest,
@@ -35,7 +134,7 @@ pub async fn route_calendar_restore<CS: CalendarStore>(
     user: User,
 ) -> Result<impl Responder, rustical_store::Error> {
     let (owner, cal_id) = path.into_inner();
-    if owner != user.id {
+    if !user.can_write_calendars(&owner) {
         return Ok(HttpResponse::Unauthorized().body("Unauthorized"));
     }
     store.restore_calendar(&owner, &cal_id).await?;
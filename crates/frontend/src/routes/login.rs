@@ -1,19 +1,24 @@
+use crate::config::FrontendConfig;
 use actix_session::Session;
 use actix_web::{
     error::ErrorUnauthorized,
     web::{Data, Form, Redirect},
-    HttpRequest, HttpResponse, Responder,
+    HttpRequest, HttpResponse, Responder, ResponseError,
 };
 use askama::Template;
-use rustical_store::auth::AuthenticationProvider;
+use rustical_store::auth::{AuthenticationProvider, RateLimiter};
 use serde::Deserialize;
 
 #[derive(Template)]
 #[template(path = "pages/login.html")]
-struct LoginPage;
+struct LoginPage {
+    oidc_name: Option<String>,
+}
 
-pub async fn route_get_login() -> impl Responder {
-    LoginPage
+pub async fn route_get_login(config: Data<FrontendConfig>) -> impl Responder {
+    LoginPage {
+        oidc_name: config.oidc.as_ref().map(|oidc| oidc.name.to_owned()),
+    }
 }
 
 #[derive(Deserialize)]
@@ -28,10 +33,28 @@ pub async fn route_post_login<AP: AuthenticationProvider>(
     session: Session,
     auth_provider: Data<AP>,
 ) -> HttpResponse {
-    if let Ok(Some(user)) = auth_provider
-        .validate_user_token(&form.username, &form.password)
-        .await
-    {
+    let validation = match req.app_data::<Data<RateLimiter>>() {
+        Some(rate_limiter) => {
+            match rate_limiter
+                .validate_user_token(
+                    auth_provider.as_ref(),
+                    rate_limiter.client_ip(&req),
+                    &form.username,
+                    &form.password,
+                )
+                .await
+            {
+                Ok(validation) => validation,
+                Err(err) => return err.error_response(),
+            }
+        }
+        None => auth_provider
+            .validate_user_token(&form.username, &form.password)
+            .await
+            .ok()
+            .flatten(),
+    };
+    if let Some(user) = validation {
         session.insert("user", user).unwrap();
         Redirect::to(format!("/frontend/user/{}", &form.username))
             .see_other()
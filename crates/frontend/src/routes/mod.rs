@@ -1,4 +1,6 @@
 pub mod addressbook;
+pub mod app_token;
 pub mod calendar;
 pub mod login;
+pub mod oidc;
 
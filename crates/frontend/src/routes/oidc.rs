@@ -0,0 +1,207 @@
+use crate::config::{FrontendConfig, OidcConfig, UserIdClaim};
+use actix_session::Session;
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound, ErrorUnauthorized},
+    web::{Data, Query, Redirect},
+    HttpRequest, HttpResponse, Responder,
+};
+use openidconnect::{
+    core::{CoreClient, CoreProviderMetadata, CoreResponseType},
+    reqwest, AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
+    EndpointMaybeSet, EndpointNotSet, EndpointSet, IssuerUrl, Nonce, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, Scope,
+};
+use rustical_store::auth::{AuthenticationProvider, User};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const SESSION_KEY_OIDC_STATE: &str = "oidc_state";
+// The metadata includes the provider's signing keys, so it's only kept for a while to pick up
+// rotated keys
+const PROVIDER_METADATA_TTL: Duration = Duration::from_secs(3600);
+
+// Authorization code flow with PKCE
+// https://openid.net/specs/openid-connect-core-1_0.html#CodeFlowAuth
+#[derive(Serialize, Deserialize)]
+struct OidcState {
+    state: CsrfToken,
+    nonce: Nonce,
+    pkce_verifier: PkceCodeVerifier,
+}
+
+type OidcClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+// Discovered provider metadata, shared between the login and the callback route
+#[derive(Default)]
+pub struct OidcProviderCache {
+    metadata: Mutex<Option<(Instant, CoreProviderMetadata)>>,
+}
+
+impl OidcProviderCache {
+    async fn get(
+        &self,
+        oidc_config: &OidcConfig,
+        http_client: &reqwest::Client,
+    ) -> actix_web::Result<CoreProviderMetadata> {
+        if let Some((fetched_at, metadata)) = self.metadata.lock().unwrap().as_ref() {
+            if fetched_at.elapsed() < PROVIDER_METADATA_TTL {
+                return Ok(metadata.to_owned());
+            }
+        }
+        let issuer_url =
+            IssuerUrl::new(oidc_config.issuer.to_owned()).map_err(ErrorInternalServerError)?;
+        let metadata = CoreProviderMetadata::discover_async(issuer_url, http_client)
+            .await
+            .map_err(ErrorInternalServerError)?;
+        *self.metadata.lock().unwrap() = Some((Instant::now(), metadata.to_owned()));
+        Ok(metadata)
+    }
+
+    fn invalidate(&self) {
+        *self.metadata.lock().unwrap() = None;
+    }
+}
+
+fn get_http_client() -> actix_web::Result<reqwest::Client> {
+    reqwest::ClientBuilder::new()
+        // Following redirects opens the client up to SSRF vulnerabilities
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(ErrorInternalServerError)
+}
+
+fn get_oidc_config(config: &FrontendConfig) -> actix_web::Result<&OidcConfig> {
+    config
+        .oidc
+        .as_ref()
+        .ok_or_else(|| ErrorNotFound("OIDC is not configured"))
+}
+
+async fn get_oidc_client(
+    req: &HttpRequest,
+    oidc_config: &OidcConfig,
+    provider_cache: &OidcProviderCache,
+    http_client: &reqwest::Client,
+) -> actix_web::Result<OidcClient> {
+    let provider_metadata = provider_cache.get(oidc_config, http_client).await?;
+    let redirect_url = req
+        .url_for_static("frontend_oidc_callback")
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(CoreClient::from_provider_metadata(
+        provider_metadata,
+        ClientId::new(oidc_config.client_id.to_owned()),
+        oidc_config.client_secret.to_owned().map(ClientSecret::new),
+    )
+    .set_redirect_uri(RedirectUrl::from_url(redirect_url)))
+}
+
+pub async fn route_get_oidc(
+    req: HttpRequest,
+    session: Session,
+    config: Data<FrontendConfig>,
+    provider_cache: Data<OidcProviderCache>,
+) -> actix_web::Result<impl Responder> {
+    let oidc_config = get_oidc_config(&config)?;
+    let http_client = get_http_client()?;
+    let client = get_oidc_client(&req, oidc_config, &provider_cache, &http_client).await?;
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (auth_url, state, nonce) = client
+        .authorize_url(
+            AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .add_scopes(oidc_config.scopes.iter().cloned().map(Scope::new))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+
+    session.insert(
+        SESSION_KEY_OIDC_STATE,
+        OidcState {
+            state,
+            nonce,
+            pkce_verifier,
+        },
+    )?;
+    Ok(Redirect::to(auth_url.to_string()).see_other())
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    code: String,
+    state: String,
+}
+
+pub async fn route_get_oidc_callback<AP: AuthenticationProvider>(
+    req: HttpRequest,
+    query: Query<OidcCallbackQuery>,
+    session: Session,
+    config: Data<FrontendConfig>,
+    provider_cache: Data<OidcProviderCache>,
+    auth_provider: Data<AP>,
+) -> actix_web::Result<HttpResponse> {
+    let oidc_config = get_oidc_config(&config)?;
+    let OidcCallbackQuery { code, state } = query.into_inner();
+
+    let oidc_state = session
+        .remove_as::<OidcState>(SESSION_KEY_OIDC_STATE)
+        .and_then(Result::ok)
+        .ok_or_else(|| ErrorBadRequest("No OIDC login in progress"))?;
+    if &state != oidc_state.state.secret() {
+        return Err(ErrorBadRequest("OIDC state mismatch"));
+    }
+
+    let http_client = get_http_client()?;
+    let client = get_oidc_client(&req, oidc_config, &provider_cache, &http_client).await?;
+    let token_response = client
+        .exchange_code(AuthorizationCode::new(code))
+        .map_err(ErrorInternalServerError)?
+        .set_pkce_verifier(oidc_state.pkce_verifier)
+        .request_async(&http_client)
+        .await
+        .map_err(ErrorUnauthorized)?;
+
+    let id_token = token_response
+        .extra_fields()
+        .id_token()
+        .ok_or_else(|| ErrorUnauthorized("OIDC provider did not return an ID token"))?;
+    let claims = id_token
+        .claims(&client.id_token_verifier(), &oidc_state.nonce)
+        .map_err(|err| {
+            // The provider might have rotated its keys
+            provider_cache.invalidate();
+            ErrorUnauthorized(err)
+        })?;
+
+    let user_id = match oidc_config.claim_userid {
+        UserIdClaim::Sub => claims.subject().to_string(),
+        UserIdClaim::PreferredUsername => claims
+            .preferred_username()
+            .ok_or_else(|| ErrorUnauthorized("ID token contains no preferred_username"))?
+            .to_string(),
+    };
+    // Only principals known to the authentication provider may log in
+    let user = auth_provider
+        .get_principal(&user_id)
+        .await?
+        .filter(User::can_log_in)
+        .ok_or_else(|| ErrorUnauthorized("Unauthorized"))?;
+
+    session.insert("user", &user)?;
+    Ok(Redirect::to(format!("/frontend/user/{}", user.id))
+        .see_other()
+        .respond_to(&req)
+        .map_into_boxed_body())
+}
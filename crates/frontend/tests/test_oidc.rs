@@ -0,0 +1,239 @@
+use actix_web::{
+    cookie::Cookie,
+    http::{header, StatusCode},
+    test, web, App, HttpResponse, HttpServer,
+};
+use chrono::{Duration, Utc};
+use openidconnect::{
+    core::{
+        CoreIdToken, CoreIdTokenClaims, CoreIdTokenFields, CoreJsonWebKeySet,
+        CoreJwsSigningAlgorithm, CoreProviderMetadata, CoreResponseType, CoreRsaPrivateSigningKey,
+        CoreSubjectIdentifierType, CoreTokenResponse, CoreTokenType,
+    },
+    AccessToken, Audience, AuthUrl, EmptyAdditionalClaims, EmptyAdditionalProviderMetadata,
+    EmptyExtraTokenFields, EndUserUsername, IssuerUrl, JsonWebKeySetUrl, Nonce, PrivateSigningKey,
+    ResponseTypes, StandardClaims, SubjectIdentifier, TokenUrl,
+};
+use rustical_frontend::{configure_frontend, FrontendConfig, OidcConfig, UserIdClaim};
+use rustical_store::auth::{
+    static_user_store::UserEntry, PrincipalType, StaticUserStore, StaticUserStoreConfig, User,
+};
+use rustical_store_sqlite::{
+    addressbook_store::SqliteAddressbookStore, calendar_store::SqliteCalendarStore, create_test_db,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+const SIGNING_KEY: &str = include_str!("oidc_test_key.pem");
+const CLIENT_ID: &str = "rustical";
+
+struct Issuer {
+    url: String,
+    discoveries: AtomicUsize,
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    code: String,
+}
+
+fn signing_key() -> CoreRsaPrivateSigningKey {
+    CoreRsaPrivateSigningKey::from_pem(SIGNING_KEY, None).unwrap()
+}
+
+async fn discovery(issuer: web::Data<Issuer>) -> HttpResponse {
+    issuer.discoveries.fetch_add(1, Ordering::SeqCst);
+    let metadata = CoreProviderMetadata::new(
+        IssuerUrl::new(issuer.url.to_owned()).unwrap(),
+        AuthUrl::new(format!("{}/authorize", issuer.url)).unwrap(),
+        JsonWebKeySetUrl::new(format!("{}/jwks", issuer.url)).unwrap(),
+        vec![ResponseTypes::new(vec![CoreResponseType::Code])],
+        vec![CoreSubjectIdentifierType::Public],
+        vec![CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256],
+        EmptyAdditionalProviderMetadata {},
+    )
+    .set_token_endpoint(Some(
+        TokenUrl::new(format!("{}/token", issuer.url)).unwrap(),
+    ));
+    HttpResponse::Ok().json(metadata)
+}
+
+async fn jwks() -> HttpResponse {
+    HttpResponse::Ok().json(CoreJsonWebKeySet::new(vec![
+        signing_key().as_verification_key()
+    ]))
+}
+
+// The test passes the nonce as the authorization code.
+// The user "alice" has renamed herself to "bob" at the provider.
+async fn token(issuer: web::Data<Issuer>, form: web::Form<TokenRequest>) -> HttpResponse {
+    let claims = CoreIdTokenClaims::new(
+        IssuerUrl::new(issuer.url.to_owned()).unwrap(),
+        vec![Audience::new(CLIENT_ID.to_owned())],
+        Utc::now() + Duration::minutes(5),
+        Utc::now(),
+        StandardClaims::new(SubjectIdentifier::new("alice".to_owned()))
+            .set_preferred_username(Some(EndUserUsername::new("bob".to_owned()))),
+        EmptyAdditionalClaims {},
+    )
+    .set_nonce(Some(Nonce::new(form.code.to_owned())));
+    let id_token = CoreIdToken::new(
+        claims,
+        &signing_key(),
+        CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+        None,
+        None,
+    )
+    .unwrap();
+    HttpResponse::Ok().json(CoreTokenResponse::new(
+        AccessToken::new("access_token".to_owned()),
+        CoreTokenType::Bearer,
+        CoreIdTokenFields::new(Some(id_token), EmptyExtraTokenFields {}),
+    ))
+}
+
+async fn start_issuer() -> web::Data<Issuer> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let issuer = web::Data::new(Issuer {
+        url: format!("http://{}", listener.local_addr().unwrap()),
+        discoveries: AtomicUsize::new(0),
+    });
+    let app_issuer = issuer.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(app_issuer.clone())
+            .route(
+                "/.well-known/openid-configuration",
+                web::get().to(discovery),
+            )
+            .route("/jwks", web::get().to(jwks))
+            .route("/token", web::post().to(token))
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_web::rt::spawn(server);
+    issuer
+}
+
+fn user(id: &str) -> UserEntry {
+    UserEntry {
+        user: User {
+            id: id.to_owned(),
+            displayname: None,
+            principal_type: PrincipalType::Individual,
+            password: None,
+            memberships: vec![],
+            calendar_proxy_read_for: vec![],
+            calendar_proxy_write_for: vec![],
+        },
+        app_tokens: vec![],
+    }
+}
+
+async fn oidc_login(
+    claim_userid: UserIdClaim,
+    users: Vec<UserEntry>,
+) -> (StatusCode, Option<String>, usize) {
+    let issuer = start_issuer().await;
+    let db = create_test_db().await.unwrap();
+    let (send, _recv) = tokio::sync::mpsc::channel(100);
+    let auth_provider = Arc::new(StaticUserStore::new(StaticUserStoreConfig { users }));
+    let cal_store = Arc::new(SqliteCalendarStore::new(db.clone(), send.clone()));
+    let addr_store = Arc::new(SqliteAddressbookStore::new(db, send));
+    let frontend_config = FrontendConfig {
+        secret_key: [0; 64],
+        enabled: true,
+        oidc: Some(OidcConfig {
+            name: "Test".to_owned(),
+            issuer: issuer.url.to_owned(),
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: None,
+            scopes: vec!["openid".to_owned()],
+            claim_userid,
+        }),
+    };
+    let app = test::init_service(App::new().service(web::scope("/frontend").configure(|cfg| {
+        configure_frontend(cfg, auth_provider, cal_store, addr_store, frontend_config)
+    })))
+    .await;
+
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/frontend/login/oidc")
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let session: Cookie = response.response().cookies().next().unwrap().into_owned();
+    let auth_url = openidconnect::url::Url::parse(
+        response
+            .headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap(),
+    )
+    .unwrap();
+    let params: HashMap<_, _> = auth_url.query_pairs().into_owned().collect();
+
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!(
+                "/frontend/login/oidc/callback?code={}&state={}",
+                params["nonce"], params["state"]
+            ))
+            .cookie(session)
+            .to_request(),
+    )
+    .await;
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .map(|location| location.to_str().unwrap().to_owned());
+    (
+        response.status(),
+        location,
+        issuer.discoveries.load(Ordering::SeqCst),
+    )
+}
+
+#[actix_web::test]
+async fn test_oidc_login() {
+    let (status, location, discoveries) =
+        oidc_login(UserIdClaim::default(), vec![user("alice"), user("bob")]).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    // The stable subject decides the user, not the username chosen at the provider
+    assert_eq!(location.as_deref(), Some("/frontend/user/alice"));
+    // The provider metadata is only discovered once
+    assert_eq!(discoveries, 1);
+}
+
+#[actix_web::test]
+async fn test_oidc_login_preferred_username() {
+    let (status, location, _) = oidc_login(
+        UserIdClaim::PreferredUsername,
+        vec![user("alice"), user("bob")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/frontend/user/bob"));
+}
+
+#[actix_web::test]
+async fn test_oidc_login_group() {
+    let mut group = user("alice");
+    group.user.principal_type = PrincipalType::Group;
+    let (status, location, _) = oidc_login(UserIdClaim::default(), vec![group]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(location.is_none());
+}
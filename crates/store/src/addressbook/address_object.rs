@@ -38,17 +38,29 @@ impl AddressObject {
         &self.id
     }
 
+    pub fn get_uid(&self) -> Option<&str> {
+        self.vcard
+            .get_property("UID")
+            .and_then(|prop| prop.value.as_deref())
+    }
+
     pub fn get_etag(&self) -> String {
         let mut hasher = Sha256::new();
         hasher.update(&self.id);
         hasher.update(self.get_vcf());
-        format!("{:x}", hasher.finalize())
+        // Entity tags are quoted strings
+        // https://datatracker.ietf.org/doc/html/rfc7232#section-2.3
+        format!("\"{:x}\"", hasher.finalize())
     }
 
     pub fn get_vcf(&self) -> &str {
         &self.vcf
     }
 
+    pub fn get_vcard(&self) -> &VcardContact {
+        &self.vcard
+    }
+
     pub fn get_anniversary(&self) -> Option<CalDateTime> {
         let prop = self.vcard.get_property("ANNIVERSARY")?;
         CalDateTime::parse_prop(prop, &HashMap::default()).unwrap_or(None)
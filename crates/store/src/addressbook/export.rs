@@ -0,0 +1,11 @@
+use super::AddressObject;
+
+// vCard files can simply hold one vCard after another
+pub fn export_addressbook(objects: &[AddressObject]) -> String {
+    let mut vcf = String::new();
+    for object in objects {
+        vcf.push_str(object.get_vcf().trim_end());
+        vcf.push_str("\r\n");
+    }
+    vcf
+}
@@ -0,0 +1,66 @@
+use crate::Error;
+use ical::{parser::vcard::component::VcardContact, property::Property};
+use std::io::BufReader;
+
+// Splits a file with multiple vCards into one vCard per UID
+// vCards without UID (common in vCard 3 exports) get a random one
+pub fn split_addressbook(vcf: &str) -> Result<Vec<(String, VcardContact)>, Error> {
+    let mut split = vec![];
+    for vcard in ical::VcardParser::new(BufReader::new(vcf.as_bytes())) {
+        let mut vcard = vcard?;
+        let uid = match vcard
+            .properties
+            .iter()
+            .find(|prop| prop.name == "UID")
+            .and_then(|prop| prop.value.to_owned())
+        {
+            Some(uid) => uid,
+            None => {
+                let uid = uuid::Uuid::new_v4().to_string();
+                set_vcard_uid(&mut vcard, &uid);
+                uid
+            }
+        };
+        split.push((uid, vcard));
+    }
+    Ok(split)
+}
+
+pub fn set_vcard_uid(vcard: &mut VcardContact, uid: &str) {
+    vcard.properties.retain(|prop| prop.name != "UID");
+    vcard.properties.push(Property {
+        name: "UID".to_owned(),
+        params: None,
+        value: Some(uid.to_owned()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_addressbook;
+    use ical::generator::Emitter;
+
+    const VCF: &str = "BEGIN:VCARD\r
+VERSION:4.0\r
+UID:alice\r
+FN:Alice\r
+END:VCARD\r
+BEGIN:VCARD\r
+VERSION:3.0\r
+FN:Bob\r
+END:VCARD\r
+";
+
+    #[test]
+    fn test_split_addressbook() {
+        let split = split_addressbook(VCF).unwrap();
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].0, "alice");
+        assert!(split[0].1.generate().contains("FN:Alice\r\n"));
+        // Bob got a UID
+        assert!(split[1]
+            .1
+            .generate()
+            .contains(&format!("UID:{}\r\n", split[1].0)));
+    }
+}
@@ -1,5 +1,10 @@
 pub mod address_object;
+#[allow(clippy::module_inception)]
 pub mod addressbook;
+mod export;
+mod import;
 
 pub use address_object::*;
 pub use addressbook::*;
+pub use export::*;
+pub use import::*;
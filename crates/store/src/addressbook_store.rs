@@ -1,6 +1,6 @@
 use crate::{
     addressbook::{AddressObject, Addressbook},
-    Error,
+    Error, Share,
 };
 use async_trait::async_trait;
 
@@ -63,4 +63,38 @@ pub trait AddressbookStore: Send + Sync + 'static {
         addressbook_id: &str,
         object_id: &str,
     ) -> Result<(), Error>;
+    // COPY and MOVE between addressbooks of the same principal
+    async fn copy_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error>;
+    async fn move_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error>;
+
+    // Addressbooks shared with other principals
+    async fn get_addressbook_shares(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Vec<Share>, Error>;
+    async fn get_received_addressbook_shares(&self, sharee: &str) -> Result<Vec<Share>, Error>;
+    async fn put_addressbook_share(&self, share: Share) -> Result<(), Error>;
+    async fn delete_addressbook_share(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        sharee: &str,
+    ) -> Result<(), Error>;
 }
@@ -0,0 +1,52 @@
+use chrono::NaiveDateTime;
+use password_hash::{PasswordHasher, SaltString};
+use pbkdf2::{password_hash, Params, Pbkdf2};
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+
+// A named password for a DAV client, only its hash is stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppToken {
+    pub id: String,
+    pub name: String,
+    pub token: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub last_used: Option<NaiveDateTime>,
+}
+
+/// Returns a new random app token and its hash
+pub fn generate_app_token() -> (String, String) {
+    let token: String = rand::thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    // App tokens have enough entropy to afford a cheaper hash than passwords
+    let salt = SaltString::generate(OsRng);
+    let token_hash = Pbkdf2
+        .hash_password_customized(
+            token.as_bytes(),
+            None,
+            None,
+            Params {
+                rounds: 1000,
+                ..Default::default()
+            },
+            &salt,
+        )
+        .expect("pbkdf2 parameters are valid")
+        .to_string();
+    (token, token_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_app_token;
+
+    #[test]
+    fn test_generate_app_token() {
+        let (token, token_hash) = generate_app_token();
+        assert_eq!(token.len(), 32);
+        assert!(password_auth::verify_password(&token, &token_hash).is_ok());
+        assert!(password_auth::verify_password("wrong", &token_hash).is_err());
+    }
+}
@@ -0,0 +1,478 @@
+use super::{AppToken, AuthenticationProvider, PrincipalType, User};
+use crate::error::Error;
+use async_trait::async_trait;
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+fn default_user_filter() -> String {
+    "(objectClass=person)".to_owned()
+}
+
+fn default_id_attribute() -> String {
+    "uid".to_owned()
+}
+
+fn default_displayname_attribute() -> String {
+    "cn".to_owned()
+}
+
+fn default_cache_ttl() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LdapUserStoreConfig {
+    // ldap:// or ldaps:// URL of the directory server
+    pub url: String,
+    #[serde(default)]
+    pub starttls: bool,
+    // Service account to search for users, binds anonymously if not set
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    pub user_base_dn: String,
+    #[serde(default = "default_user_filter")]
+    pub user_filter: String,
+    #[serde(default = "default_id_attribute")]
+    pub id_attribute: String,
+    #[serde(default = "default_displayname_attribute")]
+    pub displayname_attribute: String,
+    // Seconds a successful bind is remembered, 0 disables the cache
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl: u64,
+}
+
+struct CachedBind {
+    token_hash: [u8; 32],
+    user: User,
+    expires_at: Instant,
+}
+
+// The directory operations the store needs, so that tests can run without an LDAP server
+#[async_trait]
+trait LdapSession: Send {
+    async fn search(
+        &mut self,
+        base: &str,
+        filter: &str,
+        attributes: Vec<&str>,
+    ) -> Result<Vec<SearchEntry>, Error>;
+    // Whether the credentials are valid
+    async fn bind(&mut self, dn: &str, password: &str) -> Result<bool, Error>;
+    async fn unbind(&mut self);
+}
+
+#[async_trait]
+trait LdapConnector: Send + Sync {
+    async fn connect(&self, config: &LdapUserStoreConfig) -> Result<Box<dyn LdapSession>, Error>;
+}
+
+struct Ldap3Connector;
+
+#[async_trait]
+impl LdapConnector for Ldap3Connector {
+    async fn connect(&self, config: &LdapUserStoreConfig) -> Result<Box<dyn LdapSession>, Error> {
+        let settings = LdapConnSettings::new()
+            .set_starttls(config.starttls)
+            .set_conn_timeout(Duration::from_secs(10));
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &config.url)
+            .await
+            .map_err(ldap_error)?;
+        ldap3::drive!(conn);
+
+        if let Some(bind_dn) = &config.bind_dn {
+            ldap.simple_bind(bind_dn, config.bind_password.as_deref().unwrap_or_default())
+                .await
+                .and_then(|result| result.success())
+                .map_err(ldap_error)?;
+        }
+        Ok(Box::new(ldap))
+    }
+}
+
+#[async_trait]
+impl LdapSession for Ldap {
+    async fn search(
+        &mut self,
+        base: &str,
+        filter: &str,
+        attributes: Vec<&str>,
+    ) -> Result<Vec<SearchEntry>, Error> {
+        let (entries, _result) = Ldap::search(self, base, Scope::Subtree, filter, attributes)
+            .await
+            .and_then(|result| result.success())
+            .map_err(ldap_error)?;
+        Ok(entries.into_iter().map(SearchEntry::construct).collect())
+    }
+
+    async fn bind(&mut self, dn: &str, password: &str) -> Result<bool, Error> {
+        let result = self.simple_bind(dn, password).await.map_err(ldap_error)?;
+        Ok(result.rc == 0)
+    }
+
+    async fn unbind(&mut self) {
+        let _ = Ldap::unbind(self).await;
+    }
+}
+
+pub struct LdapUserStore {
+    config: LdapUserStoreConfig,
+    bind_cache: RwLock<HashMap<String, CachedBind>>,
+    connector: Box<dyn LdapConnector>,
+}
+
+fn ldap_error(err: ldap3::LdapError) -> Error {
+    Error::Other(err.into())
+}
+
+fn hash_token(user_id: &str, token: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(user_id.as_bytes());
+    hasher.update([0]);
+    hasher.update(token.as_bytes());
+    hasher.finalize().into()
+}
+
+impl LdapUserStore {
+    pub fn new(config: LdapUserStoreConfig) -> Self {
+        Self {
+            config,
+            bind_cache: RwLock::new(HashMap::new()),
+            connector: Box::new(Ldap3Connector),
+        }
+    }
+
+    fn user_filter(&self, user_id: Option<&str>) -> String {
+        match user_id {
+            Some(user_id) => format!(
+                "(&{}({}={}))",
+                self.config.user_filter,
+                self.config.id_attribute,
+                ldap_escape(user_id)
+            ),
+            None => self.config.user_filter.to_owned(),
+        }
+    }
+
+    fn user_from_entry(&self, entry: &SearchEntry) -> Option<User> {
+        let first_value = |attribute: &str| {
+            entry
+                .attrs
+                .get(attribute)
+                .and_then(|values| values.first())
+                .cloned()
+        };
+        Some(User {
+            id: first_value(&self.config.id_attribute)?,
+            displayname: first_value(&self.config.displayname_attribute),
+            principal_type: PrincipalType::Individual,
+            password: None,
+            memberships: vec![],
+            calendar_proxy_read_for: vec![],
+            calendar_proxy_write_for: vec![],
+        })
+    }
+
+    async fn connect(&self) -> Result<Box<dyn LdapSession>, Error> {
+        self.connector.connect(&self.config).await
+    }
+
+    async fn search_users(
+        &self,
+        ldap: &mut Box<dyn LdapSession>,
+        user_id: Option<&str>,
+    ) -> Result<Vec<(String, User)>, Error> {
+        let entries = ldap
+            .search(
+                &self.config.user_base_dn,
+                &self.user_filter(user_id),
+                vec![
+                    self.config.id_attribute.as_str(),
+                    self.config.displayname_attribute.as_str(),
+                ],
+            )
+            .await?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|entry| {
+                let user = self.user_from_entry(&entry)?;
+                Some((entry.dn, user))
+            })
+            .collect())
+    }
+
+    fn get_cached_bind(&self, user_id: &str, token: &str) -> Option<User> {
+        let cache = self.bind_cache.read().unwrap();
+        let cached = cache.get(user_id)?;
+        (cached.expires_at > Instant::now() && cached.token_hash == hash_token(user_id, token))
+            .then(|| cached.user.clone())
+    }
+
+    fn cache_bind(&self, user_id: &str, token: &str, user: &User) {
+        if self.config.cache_ttl == 0 {
+            return;
+        }
+        let mut cache = self.bind_cache.write().unwrap();
+        let now = Instant::now();
+        cache.retain(|_, cached| cached.expires_at > now);
+        cache.insert(
+            user_id.to_owned(),
+            CachedBind {
+                token_hash: hash_token(user_id, token),
+                user: user.clone(),
+                expires_at: now + Duration::from_secs(self.config.cache_ttl),
+            },
+        );
+    }
+}
+
+#[async_trait]
+impl AuthenticationProvider for LdapUserStore {
+    async fn validate_user_token(&self, user_id: &str, token: &str) -> Result<Option<User>, Error> {
+        // An empty password would result in an unauthenticated bind that always succeeds
+        // https://datatracker.ietf.org/doc/html/rfc4513#section-5.1.2
+        if token.is_empty() {
+            return Ok(None);
+        }
+        if let Some(user) = self.get_cached_bind(user_id, token) {
+            return Ok(Some(user));
+        }
+
+        // Search for the user's DN and then bind as the user to check the password
+        let mut ldap = self.connect().await?;
+        let mut users = self.search_users(&mut ldap, Some(user_id)).await?;
+        let (dn, user) = match (users.pop(), users.is_empty()) {
+            (Some(user), true) => user,
+            (Some(_), false) => {
+                warn!("LDAP search for {user_id} returned multiple entries");
+                return Ok(None);
+            }
+            (None, _) => return Ok(None),
+        };
+        let bound = ldap.bind(&dn, token).await?;
+        ldap.unbind().await;
+        if !bound {
+            return Ok(None);
+        }
+
+        self.cache_bind(user_id, token, &user);
+        Ok(Some(user))
+    }
+
+    async fn get_principal(&self, id: &str) -> Result<Option<User>, Error> {
+        let mut ldap = self.connect().await?;
+        let users = self.search_users(&mut ldap, Some(id)).await?;
+        ldap.unbind().await;
+        Ok(users.into_iter().next().map(|(_dn, user)| user))
+    }
+
+    async fn list_principals(&self) -> Result<Vec<User>, Error> {
+        let mut ldap = self.connect().await?;
+        let users = self.search_users(&mut ldap, None).await?;
+        ldap.unbind().await;
+        Ok(users.into_iter().map(|(_dn, user)| user).collect())
+    }
+
+    // Only individual principals are read from the directory
+    async fn get_group_members(&self, _group: &str) -> Result<Vec<String>, Error> {
+        Ok(vec![])
+    }
+
+    // Users authenticate with their directory password
+    fn supports_app_tokens(&self) -> bool {
+        false
+    }
+
+    async fn get_app_tokens(&self, _user_id: &str) -> Result<Vec<AppToken>, Error> {
+        Ok(vec![])
+    }
+
+    async fn add_app_token(
+        &self,
+        _user_id: &str,
+        _name: String,
+        _token_hash: String,
+    ) -> Result<String, Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn remove_app_token(&self, _user_id: &str, _token_id: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LdapConnector, LdapSession, LdapUserStore, LdapUserStoreConfig};
+    use crate::{
+        auth::{AuthenticationProvider, PrincipalType, User},
+        Error,
+    };
+    use async_trait::async_trait;
+    use ldap3::SearchEntry;
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    // Entries of a fake directory as (dn, uid, password)
+    #[derive(Clone, Default)]
+    struct MockDirectory {
+        entries: Vec<(&'static str, &'static str, &'static str)>,
+        connections: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl LdapConnector for MockDirectory {
+        async fn connect(
+            &self,
+            _config: &LdapUserStoreConfig,
+        ) -> Result<Box<dyn LdapSession>, Error> {
+            self.connections.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    #[async_trait]
+    impl LdapSession for MockDirectory {
+        async fn search(
+            &mut self,
+            base: &str,
+            filter: &str,
+            _attributes: Vec<&str>,
+        ) -> Result<Vec<SearchEntry>, Error> {
+            Ok(self
+                .entries
+                .iter()
+                .filter(|(_, uid, _)| filter.contains(&format!("(uid={uid})")))
+                .map(|(dn, uid, _)| SearchEntry {
+                    dn: format!("{dn},{base}"),
+                    attrs: HashMap::from([("uid".to_owned(), vec![uid.to_string()])]),
+                    bin_attrs: HashMap::new(),
+                })
+                .collect())
+        }
+
+        async fn bind(&mut self, dn: &str, password: &str) -> Result<bool, Error> {
+            Ok(self.entries.iter().any(|(entry_dn, _, entry_password)| {
+                dn.starts_with(&format!("{entry_dn},")) && *entry_password == password
+            }))
+        }
+
+        async fn unbind(&mut self) {}
+    }
+
+    fn ldap_store(cache_ttl: u64) -> LdapUserStore {
+        LdapUserStore::new(LdapUserStoreConfig {
+            url: "ldap://localhost".to_owned(),
+            starttls: false,
+            bind_dn: None,
+            bind_password: None,
+            user_base_dn: "ou=people,dc=example,dc=com".to_owned(),
+            user_filter: "(objectClass=person)".to_owned(),
+            id_attribute: "uid".to_owned(),
+            displayname_attribute: "cn".to_owned(),
+            cache_ttl,
+        })
+    }
+
+    #[test]
+    fn test_ldap_user_filter() {
+        let store = ldap_store(300);
+        assert_eq!(store.user_filter(None), "(objectClass=person)");
+        assert_eq!(
+            store.user_filter(Some("alice")),
+            "(&(objectClass=person)(uid=alice))"
+        );
+        // User input must not be able to change the filter
+        assert_eq!(
+            store.user_filter(Some("*)(uid=*")),
+            "(&(objectClass=person)(uid=\\2a\\29\\28uid=\\2a))"
+        );
+    }
+
+    #[test]
+    fn test_ldap_bind_cache() {
+        let user = User {
+            id: "alice".to_owned(),
+            displayname: None,
+            principal_type: PrincipalType::Individual,
+            password: None,
+            memberships: vec![],
+            calendar_proxy_read_for: vec![],
+            calendar_proxy_write_for: vec![],
+        };
+
+        let store = ldap_store(300);
+        store.cache_bind("alice", "secret", &user);
+        assert!(store.get_cached_bind("alice", "secret").is_some());
+        assert!(store.get_cached_bind("alice", "wrong").is_none());
+        assert!(store.get_cached_bind("bob", "secret").is_none());
+
+        let store = ldap_store(0);
+        store.cache_bind("alice", "secret", &user);
+        assert!(store.get_cached_bind("alice", "secret").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ldap_validate_user_token() {
+        let directory = MockDirectory {
+            entries: vec![
+                ("uid=alice", "alice", "secret"),
+                ("uid=bob,ou=staff", "bob", "bob1"),
+                ("uid=bob,ou=interns", "bob", "bob2"),
+            ],
+            ..Default::default()
+        };
+        let store = LdapUserStore {
+            connector: Box::new(directory.clone()),
+            ..ldap_store(300)
+        };
+
+        let user = store
+            .validate_user_token("alice", "secret")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.id, "alice");
+        assert!(store
+            .validate_user_token("alice", "wrong")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store
+            .validate_user_token("carol", "secret")
+            .await
+            .unwrap()
+            .is_none());
+        // An ambiguous search must not let the password of any of the entries through
+        assert!(store
+            .validate_user_token("bob", "bob1")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(directory.connections.load(Ordering::SeqCst), 4);
+
+        // Empty passwords are rejected without asking the directory
+        assert!(store
+            .validate_user_token("alice", "")
+            .await
+            .unwrap()
+            .is_none());
+        // Successful binds are cached
+        assert!(store
+            .validate_user_token("alice", "secret")
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(directory.connections.load(Ordering::SeqCst), 4);
+    }
+}
@@ -1,8 +1,9 @@
-use super::{AuthenticationProvider, User};
+use super::{AuthenticationProvider, ProxyAuth, RateLimiter, User};
 use actix_session::Session;
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     http::header::Header,
+    web::Data,
     FromRequest, HttpMessage,
 };
 use actix_web_httpauth::headers::authorization::{Authorization, Basic};
@@ -11,7 +12,7 @@ use std::{
     pin::Pin,
     sync::Arc,
 };
-use tracing::{info_span, Instrument};
+use tracing::{debug, info_span, Instrument};
 
 pub struct AuthenticationMiddleware<AP: AuthenticationProvider> {
     auth_provider: Arc<AP>,
@@ -66,14 +67,47 @@ where
         let auth_provider = Arc::clone(&self.auth_provider);
 
         Box::pin(async move {
+            // Principal from a trusted authenticating reverse proxy
+            if let Some(proxy_auth) = req.app_data::<Data<ProxyAuth>>() {
+                if let Some(principal) = proxy_auth.principal_from_request(req.request()) {
+                    match auth_provider.get_principal(&principal).await {
+                        Ok(Some(user)) if !user.can_log_in() => {
+                            debug!("Proxy principal {principal} is a group")
+                        }
+                        Ok(Some(user)) => {
+                            proxy_auth.provision(&user.id).await;
+                            req.extensions_mut().insert(user);
+                            return service.call(req).await;
+                        }
+                        Ok(None) => debug!("Proxy principal {principal} does not exist"),
+                        Err(err) => debug!("Could not get proxy principal {principal}: {err}"),
+                    }
+                }
+            }
+
             if let Ok(auth) = Authorization::<Basic>::parse(req.request()) {
                 let user_id = auth.as_ref().user_id();
                 if let Some(password) = auth.as_ref().password() {
-                    if let Ok(Some(user)) = auth_provider
-                        .validate_user_token(user_id, password)
-                        .instrument(info_span!("validate_user_token"))
-                        .await
-                    {
+                    let validation = match req.app_data::<Data<RateLimiter>>() {
+                        Some(rate_limiter) => {
+                            rate_limiter
+                                .validate_user_token(
+                                    auth_provider.as_ref(),
+                                    rate_limiter.client_ip(req.request()),
+                                    user_id,
+                                    password,
+                                )
+                                .instrument(info_span!("validate_user_token"))
+                                .await?
+                        }
+                        None => auth_provider
+                            .validate_user_token(user_id, password)
+                            .instrument(info_span!("validate_user_token"))
+                            .await
+                            .ok()
+                            .flatten(),
+                    };
+                    if let Some(user) = validation {
                         req.extensions_mut().insert(user);
                     }
                 }
@@ -95,3 +129,87 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AuthenticationMiddleware;
+    use crate::{
+        auth::{
+            static_user_store::UserEntry, PrincipalProvisioner, PrincipalType, ProxyAuth,
+            ProxyAuthConfig, StaticUserStore, StaticUserStoreConfig, User,
+        },
+        Error,
+    };
+    use actix_web::{test, web, App};
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct RecordingProvisioner(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl PrincipalProvisioner for RecordingProvisioner {
+        async fn provision(&self, principal: &str) -> Result<(), Error> {
+            self.0.lock().unwrap().push(principal.to_owned());
+            Ok(())
+        }
+    }
+
+    fn user(id: &str, principal_type: PrincipalType) -> UserEntry {
+        UserEntry {
+            user: User {
+                id: id.to_owned(),
+                displayname: None,
+                principal_type,
+                password: None,
+                memberships: vec![],
+                calendar_proxy_read_for: vec![],
+                calendar_proxy_write_for: vec![],
+            },
+            app_tokens: vec![],
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_proxy_auth_group() {
+        let auth_provider = Arc::new(StaticUserStore::new(StaticUserStoreConfig {
+            users: vec![
+                user("alice", PrincipalType::Individual),
+                user("team", PrincipalType::Group),
+            ],
+        }));
+        let provisioner = Arc::new(RecordingProvisioner::default());
+        let proxy_auth = ProxyAuth::new(
+            ProxyAuthConfig {
+                header: "Remote-User".to_owned(),
+                trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+                auto_provision: true,
+            },
+            provisioner.clone(),
+        )
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(proxy_auth))
+                .wrap(AuthenticationMiddleware::new(auth_provider))
+                .route(
+                    "/",
+                    web::get().to(|user: Option<User>| async move {
+                        user.map(|user| user.id).unwrap_or_default()
+                    }),
+                ),
+        )
+        .await;
+
+        for (principal, expected) in [("alice", "alice"), ("team", "")] {
+            let req = test::TestRequest::get()
+                .uri("/")
+                .peer_addr("10.1.2.3:1234".parse().unwrap())
+                .insert_header(("Remote-User", principal))
+                .to_request();
+            let body = test::call_and_read_body(&app, req).await;
+            assert_eq!(body, expected.as_bytes());
+        }
+        assert_eq!(*provisioner.0.lock().unwrap(), vec!["alice".to_owned()]);
+    }
+}
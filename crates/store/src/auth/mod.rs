@@ -1,4 +1,8 @@
+pub mod app_token;
+pub mod ldap_user_store;
 pub mod middleware;
+pub mod proxy;
+pub mod rate_limit;
 pub mod static_user_store;
 pub mod user;
 pub mod user_store;
@@ -8,8 +12,31 @@ use async_trait::async_trait;
 #[async_trait]
 pub trait AuthenticationProvider: 'static {
     async fn validate_user_token(&self, user_id: &str, token: &str) -> Result<Option<User>, Error>;
+    async fn get_principal(&self, id: &str) -> Result<Option<User>, Error>;
+    async fn list_principals(&self) -> Result<Vec<User>, Error>;
+    /// Ids of the principals that are members of a group
+    async fn get_group_members(&self, group: &str) -> Result<Vec<String>, Error>;
+
+    /// Whether app tokens can be created and revoked, false for read-only providers
+    fn supports_app_tokens(&self) -> bool {
+        true
+    }
+    async fn get_app_tokens(&self, user_id: &str) -> Result<Vec<AppToken>, Error>;
+    /// Stores the hash of a new app token and returns its id
+    async fn add_app_token(
+        &self,
+        user_id: &str,
+        name: String,
+        token_hash: String,
+    ) -> Result<String, Error>;
+    async fn remove_app_token(&self, user_id: &str, token_id: &str) -> Result<(), Error>;
 }
 
+pub use app_token::{generate_app_token, AppToken};
+pub use ldap_user_store::{LdapUserStore, LdapUserStoreConfig};
 pub use middleware::AuthenticationMiddleware;
+pub use proxy::{CollectionProvisioner, PrincipalProvisioner, ProxyAuth, ProxyAuthConfig};
+pub use rate_limit::{RateLimitConfig, RateLimited, RateLimiter};
 pub use static_user_store::{StaticUserStore, StaticUserStoreConfig};
-pub use user::User;
+pub use user::{PrincipalType, User};
+pub use user_store::UserStore;
@@ -0,0 +1,211 @@
+use crate::{
+    addressbook::Addressbook,
+    calendar::{Calendar, CalendarObjectType},
+    error::Error,
+    AddressbookStore, CalendarStore,
+};
+use actix_web::{http::header::HeaderName, HttpRequest};
+use async_trait::async_trait;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use tracing::{error, warn};
+
+fn default_header() -> String {
+    "Remote-User".to_owned()
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyAuthConfig {
+    #[serde(default = "default_header")]
+    pub header: String,
+    // Only requests coming directly from these networks may set the header
+    pub trusted_proxies: Vec<IpNet>,
+    // Create a default calendar and addressbook for new principals
+    #[serde(default)]
+    pub auto_provision: bool,
+}
+
+#[async_trait]
+pub trait PrincipalProvisioner: Send + Sync + 'static {
+    async fn provision(&self, principal: &str) -> Result<(), Error>;
+}
+
+pub struct ProxyAuth {
+    header: HeaderName,
+    trusted_proxies: Vec<IpNet>,
+    provisioner: Option<Arc<dyn PrincipalProvisioner>>,
+    // Principals we already provisioned since startup
+    provisioned: RwLock<HashSet<String>>,
+}
+
+impl ProxyAuth {
+    pub fn new(
+        config: ProxyAuthConfig,
+        provisioner: Arc<dyn PrincipalProvisioner>,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            header: HeaderName::from_str(&config.header).map_err(|err| Error::Other(err.into()))?,
+            trusted_proxies: config.trusted_proxies,
+            provisioner: config.auto_provision.then_some(provisioner),
+            provisioned: RwLock::new(HashSet::new()),
+        })
+    }
+
+    /// Returns the principal set by a trusted proxy
+    pub fn principal_from_request(&self, req: &HttpRequest) -> Option<String> {
+        let value = req.headers().get(&self.header)?;
+        // Use the address of the TCP peer since forwarding headers can be spoofed
+        let peer = req.peer_addr()?.ip();
+        if !self.trusted_proxies.iter().any(|net| net.contains(&peer)) {
+            warn!("Ignoring {} header from untrusted peer {peer}", self.header);
+            return None;
+        }
+        let principal = value.to_str().ok()?.trim();
+        (!principal.is_empty()).then(|| principal.to_owned())
+    }
+
+    pub async fn provision(&self, principal: &str) {
+        let Some(provisioner) = &self.provisioner else {
+            return;
+        };
+        if self.provisioned.read().unwrap().contains(principal) {
+            return;
+        }
+        match provisioner.provision(principal).await {
+            Ok(()) => {
+                self.provisioned
+                    .write()
+                    .unwrap()
+                    .insert(principal.to_owned());
+            }
+            Err(err) => error!("Could not provision collections for {principal}: {err}"),
+        }
+    }
+}
+
+pub struct CollectionProvisioner<CS: CalendarStore, AS: AddressbookStore> {
+    cal_store: Arc<CS>,
+    addr_store: Arc<AS>,
+}
+
+impl<CS: CalendarStore, AS: AddressbookStore> CollectionProvisioner<CS, AS> {
+    pub fn new(cal_store: Arc<CS>, addr_store: Arc<AS>) -> Self {
+        Self {
+            cal_store,
+            addr_store,
+        }
+    }
+}
+
+#[async_trait]
+impl<CS: CalendarStore, AS: AddressbookStore> PrincipalProvisioner
+    for CollectionProvisioner<CS, AS>
+{
+    async fn provision(&self, principal: &str) -> Result<(), Error> {
+        // Principals that deleted all their collections should not get them back
+        if self.cal_store.get_calendars(principal).await?.is_empty()
+            && self
+                .cal_store
+                .get_deleted_calendars(principal)
+                .await?
+                .is_empty()
+        {
+            self.cal_store
+                .insert_calendar(Calendar {
+                    principal: principal.to_owned(),
+                    id: "default".to_owned(),
+                    displayname: Some("Calendar".to_owned()),
+                    push_topic: uuid::Uuid::new_v4().to_string(),
+                    components: vec![
+                        CalendarObjectType::Event,
+                        CalendarObjectType::Todo,
+                        CalendarObjectType::Journal,
+                    ],
+                    ..Default::default()
+                })
+                .await?;
+        }
+
+        if self
+            .addr_store
+            .get_addressbooks(principal)
+            .await?
+            .is_empty()
+            && self
+                .addr_store
+                .get_deleted_addressbooks(principal)
+                .await?
+                .is_empty()
+        {
+            self.addr_store
+                .insert_addressbook(Addressbook {
+                    principal: principal.to_owned(),
+                    id: "default".to_owned(),
+                    displayname: Some("Contacts".to_owned()),
+                    description: None,
+                    deleted_at: None,
+                    synctoken: 0,
+                    push_topic: uuid::Uuid::new_v4().to_string(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PrincipalProvisioner, ProxyAuth, ProxyAuthConfig};
+    use crate::error::Error;
+    use actix_web::test::TestRequest;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    struct NoopProvisioner;
+
+    #[async_trait]
+    impl PrincipalProvisioner for NoopProvisioner {
+        async fn provision(&self, _principal: &str) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_proxy_principal_from_request() {
+        let proxy_auth = ProxyAuth::new(
+            ProxyAuthConfig {
+                header: "Remote-User".to_owned(),
+                trusted_proxies: vec!["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()],
+                auto_provision: false,
+            },
+            Arc::new(NoopProvisioner),
+        )
+        .unwrap();
+
+        let request = |peer: &str, user: Option<&str>| {
+            let mut req = TestRequest::default().peer_addr(peer.parse().unwrap());
+            if let Some(user) = user {
+                req = req.insert_header(("Remote-User", user));
+            }
+            proxy_auth.principal_from_request(&req.to_http_request())
+        };
+
+        assert_eq!(
+            request("10.1.2.3:1234", Some("alice")).as_deref(),
+            Some("alice")
+        );
+        assert_eq!(
+            request("[::1]:1234", Some("alice")).as_deref(),
+            Some("alice")
+        );
+        assert_eq!(request("192.168.1.1:1234", Some("alice")), None);
+        assert_eq!(request("10.1.2.3:1234", None), None);
+        assert_eq!(request("10.1.2.3:1234", Some(" ")), None);
+    }
+}
@@ -0,0 +1,392 @@
+use super::{AuthenticationProvider, User};
+use actix_web::{http::StatusCode, HttpRequest, HttpResponse, ResponseError};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+// Only clean up stale entries once the maps grow beyond this
+const PRUNE_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct RateLimitConfig {
+    // Failed logins before a user or IP address gets locked out
+    pub max_failures_per_user: u32,
+    pub max_failures_per_ip: u32,
+    // The lockout doubles with every further failure up to max_lockout_secs
+    pub lockout_secs: u64,
+    pub max_lockout_secs: u64,
+    // Seconds a successful verification is cached, 0 disables the cache
+    pub cache_ttl: u64,
+    // Reverse proxies whose X-Forwarded-For header determines the client address
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_failures_per_user: 5,
+            max_failures_per_ip: 20,
+            lockout_secs: 10,
+            max_lockout_secs: 900,
+            cache_ttl: 120,
+            trusted_proxies: vec![],
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Too many failed login attempts")]
+pub struct RateLimited {
+    retry_after: Duration,
+}
+
+impl ResponseError for RateLimited {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", self.retry_after.as_secs().max(1).to_string()))
+            .body(self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum FailureKey {
+    User(String),
+    Ip(IpAddr),
+}
+
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+struct VerifiedToken {
+    user: User,
+    expires_at: Instant,
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    failures: Mutex<HashMap<FailureKey, Failures>>,
+    // Successful verifications so that syncing clients don't pay for the password hash every time,
+    // grouped by user id so that they can be dropped when a user's credentials change
+    verified: RwLock<HashMap<String, HashMap<[u8; 32], VerifiedToken>>>,
+}
+
+fn hash_credentials(user_id: &str, token: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(user_id.as_bytes());
+    hasher.update([0]);
+    hasher.update(token.as_bytes());
+    hasher.finalize().into()
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            failures: Mutex::new(HashMap::new()),
+            verified: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Address of the client, taken from X-Forwarded-For if the peer is a trusted proxy
+    pub fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr()?.ip();
+        let is_trusted = |ip: &IpAddr| {
+            self.config
+                .trusted_proxies
+                .iter()
+                .any(|net| net.contains(ip))
+        };
+        if !is_trusted(&peer) {
+            return Some(peer);
+        }
+        // The rightmost untrusted address is the one our proxies saw
+        let forwarded = req
+            .headers()
+            .get_all("X-Forwarded-For")
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|addr| addr.trim().parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+        Some(
+            forwarded
+                .into_iter()
+                .rev()
+                .find(|ip| !is_trusted(ip))
+                .unwrap_or(peer),
+        )
+    }
+
+    pub async fn validate_user_token<AP: AuthenticationProvider>(
+        &self,
+        auth_provider: &AP,
+        client_ip: Option<IpAddr>,
+        user_id: &str,
+        token: &str,
+    ) -> Result<Option<User>, RateLimited> {
+        let credentials = hash_credentials(user_id, token);
+        if let Some(user) = self.get_verified(user_id, &credentials) {
+            return Ok(Some(user));
+        }
+
+        let keys = self.failure_keys(client_ip, user_id);
+        self.check_lockout(&keys)?;
+
+        match auth_provider.validate_user_token(user_id, token).await {
+            Ok(Some(user)) => {
+                self.record_success(user_id);
+                self.cache_verified(user_id, credentials, &user);
+                Ok(Some(user))
+            }
+            Ok(None) => {
+                self.record_failure(&keys);
+                Ok(None)
+            }
+            // Don't lock out users while the backend is unavailable
+            Err(err) => {
+                warn!("Could not validate credentials for {user_id}: {err}");
+                Ok(None)
+            }
+        }
+    }
+
+    fn failure_keys(&self, client_ip: Option<IpAddr>, user_id: &str) -> Vec<(FailureKey, u32)> {
+        let mut keys = vec![(
+            FailureKey::User(user_id.to_owned()),
+            self.config.max_failures_per_user,
+        )];
+        if let Some(ip) = client_ip {
+            keys.push((FailureKey::Ip(ip), self.config.max_failures_per_ip));
+        }
+        keys
+    }
+
+    fn check_lockout(&self, keys: &[(FailureKey, u32)]) -> Result<(), RateLimited> {
+        let failures = self.failures.lock().unwrap();
+        let now = Instant::now();
+        let retry_after = keys
+            .iter()
+            .filter_map(|(key, _)| failures.get(key)?.locked_until)
+            .filter(|locked_until| *locked_until > now)
+            .max();
+        match retry_after {
+            Some(locked_until) => Err(RateLimited {
+                retry_after: locked_until - now,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn record_failure(&self, keys: &[(FailureKey, u32)]) {
+        let mut failures = self.failures.lock().unwrap();
+        let now = Instant::now();
+        let forget_after = Duration::from_secs(self.config.max_lockout_secs);
+        if failures.len() > PRUNE_THRESHOLD {
+            failures.retain(|_, entry| now - entry.last_failure < forget_after);
+        }
+
+        for (key, max_failures) in keys {
+            let entry = failures.entry(key.clone()).or_insert(Failures {
+                count: 0,
+                last_failure: now,
+                locked_until: None,
+            });
+            if now - entry.last_failure >= forget_after {
+                entry.count = 0;
+            }
+            entry.count += 1;
+            entry.last_failure = now;
+            if entry.count >= *max_failures {
+                let exponent = (entry.count - max_failures).min(31);
+                let lockout = self
+                    .config
+                    .lockout_secs
+                    .saturating_mul(1 << exponent)
+                    .min(self.config.max_lockout_secs);
+                entry.locked_until = Some(now + Duration::from_secs(lockout));
+                warn!(
+                    "Locking out {key:?} for {lockout}s after {} failed logins",
+                    entry.count
+                );
+            }
+        }
+    }
+
+    fn record_success(&self, user_id: &str) {
+        // The IP address counter is kept so that one valid account cannot be used to reset it
+        self.failures
+            .lock()
+            .unwrap()
+            .remove(&FailureKey::User(user_id.to_owned()));
+    }
+
+    /// Drops the cached verifications of a user, e.g. after an app token was revoked
+    pub fn invalidate_user(&self, user_id: &str) {
+        self.verified.write().unwrap().remove(user_id);
+    }
+
+    fn get_verified(&self, user_id: &str, credentials: &[u8; 32]) -> Option<User> {
+        let verified = self.verified.read().unwrap();
+        let entry = verified.get(user_id)?.get(credentials)?;
+        (entry.expires_at > Instant::now()).then(|| entry.user.clone())
+    }
+
+    fn cache_verified(&self, user_id: &str, credentials: [u8; 32], user: &User) {
+        if self.config.cache_ttl == 0 {
+            return;
+        }
+        let mut verified = self.verified.write().unwrap();
+        let now = Instant::now();
+        if verified.len() > PRUNE_THRESHOLD {
+            verified.retain(|_, entries| {
+                entries.retain(|_, entry| entry.expires_at > now);
+                !entries.is_empty()
+            });
+        }
+        verified.entry(user_id.to_owned()).or_default().insert(
+            credentials,
+            VerifiedToken {
+                user: user.clone(),
+                expires_at: now + Duration::from_secs(self.config.cache_ttl),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimitConfig, RateLimiter};
+    use crate::auth::{
+        static_user_store::UserEntry, PrincipalType, StaticUserStore, StaticUserStoreConfig, User,
+    };
+    use actix_web::test::TestRequest;
+    use std::net::IpAddr;
+
+    fn user_store() -> StaticUserStore {
+        StaticUserStore::new(StaticUserStoreConfig {
+            users: vec![UserEntry {
+                user: User {
+                    id: "alice".to_owned(),
+                    displayname: None,
+                    principal_type: PrincipalType::Individual,
+                    password: None,
+                    memberships: vec![],
+                    calendar_proxy_read_for: vec![],
+                    calendar_proxy_write_for: vec![],
+                },
+                app_tokens: vec![password_auth::generate_hash("token")],
+            }],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_lockout() {
+        let store = user_store();
+        let limiter = RateLimiter::new(RateLimitConfig {
+            max_failures_per_user: 3,
+            max_failures_per_ip: 5,
+            ..Default::default()
+        });
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let other_ip: IpAddr = "192.0.2.2".parse().unwrap();
+
+        for _ in 0..3 {
+            let result = limiter
+                .validate_user_token(&store, Some(ip), "alice", "wrong")
+                .await;
+            assert!(matches!(result, Ok(None)));
+        }
+        // alice is locked out, even with the correct token and from another address
+        assert!(limiter
+            .validate_user_token(&store, Some(other_ip), "alice", "token")
+            .await
+            .is_err());
+
+        // The address is locked out after guessing other users
+        for _ in 0..2 {
+            let result = limiter
+                .validate_user_token(&store, Some(ip), "bob", "wrong")
+                .await;
+            assert!(matches!(result, Ok(None)));
+        }
+        assert!(limiter
+            .validate_user_token(&store, Some(ip), "carol", "wrong")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_verification_cache() {
+        let store = user_store();
+        let limiter = RateLimiter::new(RateLimitConfig {
+            max_failures_per_user: 1,
+            ..Default::default()
+        });
+
+        assert!(limiter
+            .validate_user_token(&store, None, "alice", "token")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(limiter
+            .validate_user_token(&store, None, "alice", "wrong")
+            .await
+            .unwrap()
+            .is_none());
+        // Cached verifications are not affected by the lockout
+        assert!(limiter
+            .validate_user_token(&store, None, "alice", "token")
+            .await
+            .unwrap()
+            .is_some());
+
+        // Without the cache the lockout applies again
+        limiter.invalidate_user("alice");
+        assert!(limiter
+            .validate_user_token(&store, None, "alice", "token")
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_client_ip() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        });
+        let client_ip = |peer: &str, forwarded: Option<&str>| {
+            let mut req = TestRequest::default().peer_addr(peer.parse().unwrap());
+            if let Some(forwarded) = forwarded {
+                req = req.insert_header(("X-Forwarded-For", forwarded));
+            }
+            limiter
+                .client_ip(&req.to_http_request())
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            client_ip("192.0.2.1:1234", Some("198.51.100.1")),
+            "192.0.2.1"
+        );
+        assert_eq!(client_ip("10.0.0.1:1234", None), "10.0.0.1");
+        assert_eq!(
+            client_ip("10.0.0.1:1234", Some("198.51.100.1, 192.0.2.1, 10.0.0.2")),
+            "192.0.2.1"
+        );
+    }
+}
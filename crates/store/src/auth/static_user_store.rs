@@ -1,4 +1,7 @@
-use crate::{auth::User, error::Error};
+use crate::{
+    auth::{AppToken, User},
+    error::Error,
+};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -43,6 +46,9 @@ impl AuthenticationProvider for StaticUserStore {
             Some(user) => user.clone(),
             None => return Ok(None),
         };
+        if !user_entry.user.can_log_in() {
+            return Ok(None);
+        }
 
         // Try app tokens first since they are cheaper to calculate
         // They can afford less iterations since they can be generated with high entropy
@@ -63,4 +69,62 @@ impl AuthenticationProvider for StaticUserStore {
 
         Ok(None)
     }
+
+    async fn get_principal(&self, id: &str) -> Result<Option<User>, Error> {
+        Ok(self.users.get(id).map(|user_entry| user_entry.user.clone()))
+    }
+
+    async fn list_principals(&self) -> Result<Vec<User>, Error> {
+        Ok(self
+            .users
+            .values()
+            .map(|user_entry| user_entry.user.clone())
+            .collect())
+    }
+
+    async fn get_group_members(&self, group: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .users
+            .values()
+            .filter(|user_entry| user_entry.user.memberships.iter().any(|g| g == group))
+            .map(|user_entry| user_entry.user.id.to_owned())
+            .collect())
+    }
+
+    fn supports_app_tokens(&self) -> bool {
+        false
+    }
+
+    async fn get_app_tokens(&self, user_id: &str) -> Result<Vec<AppToken>, Error> {
+        let user_entry = match self.users.get(user_id) {
+            Some(user_entry) => user_entry,
+            None => return Ok(vec![]),
+        };
+        Ok(user_entry
+            .app_tokens
+            .iter()
+            .enumerate()
+            .map(|(i, token)| AppToken {
+                id: i.to_string(),
+                name: format!("Token {}", i + 1),
+                token: token.to_owned(),
+                created_at: None,
+                last_used: None,
+            })
+            .collect())
+    }
+
+    // App tokens are configured in the config file
+    async fn add_app_token(
+        &self,
+        _user_id: &str,
+        _name: String,
+        _token_hash: String,
+    ) -> Result<String, Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn remove_app_token(&self, _user_id: &str, _token_id: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
 }
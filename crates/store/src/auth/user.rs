@@ -7,11 +7,72 @@ use derive_more::Display;
 use serde::{Deserialize, Serialize};
 use std::future::{ready, Ready};
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrincipalType {
+    #[default]
+    Individual,
+    Group,
+}
+
+impl PrincipalType {
+    // CALDAV:calendar-user-type
+    // https://datatracker.ietf.org/doc/html/rfc6638#section-2.4.2
+    pub fn as_calendar_user_type(&self) -> &'static str {
+        match self {
+            Self::Individual => "INDIVIDUAL",
+            Self::Group => "GROUP",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct User {
     pub id: String,
     pub displayname: Option<String>,
+    #[serde(default)]
+    pub principal_type: PrincipalType,
     pub password: Option<String>,
+    // Group principals this principal is a member of
+    #[serde(default)]
+    pub memberships: Vec<String>,
+    // Principals whose calendars this principal may read or manage as a delegate
+    // https://github.com/apple/ccs-calendarserver/blob/master/doc/Extensions/caldav-proxy.txt
+    #[serde(default)]
+    pub calendar_proxy_read_for: Vec<String>,
+    #[serde(default)]
+    pub calendar_proxy_write_for: Vec<String>,
+}
+
+impl User {
+    // Groups are only shared principals, logging in as one would give access to the
+    // calendars of all its members
+    pub fn can_log_in(&self) -> bool {
+        self.principal_type != PrincipalType::Group
+    }
+
+    // Whether the user may act as the given principal, either as itself or through a group
+    pub fn is_principal(&self, principal: &str) -> bool {
+        self.id == principal || self.memberships.iter().any(|group| group == principal)
+    }
+
+    // Full access to the calendars of a principal
+    pub fn can_write_calendars(&self, principal: &str) -> bool {
+        self.is_principal(principal)
+            || self
+                .calendar_proxy_write_for
+                .iter()
+                .any(|delegator| delegator == principal)
+    }
+
+    // Read access to the calendars of a principal
+    pub fn can_read_calendars(&self, principal: &str) -> bool {
+        self.can_write_calendars(principal)
+            || self
+                .calendar_proxy_read_for
+                .iter()
+                .any(|delegator| delegator == principal)
+    }
 }
 
 #[derive(Clone, Debug, Display)]
@@ -47,3 +108,33 @@ impl FromRequest for User {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{PrincipalType, User};
+
+    #[test]
+    fn test_user_principal_access() {
+        let user = User {
+            id: "assistant".to_owned(),
+            displayname: None,
+            principal_type: PrincipalType::Individual,
+            password: None,
+            memberships: vec!["team".to_owned()],
+            calendar_proxy_read_for: vec!["colleague".to_owned()],
+            calendar_proxy_write_for: vec!["manager".to_owned()],
+        };
+
+        assert!(user.is_principal("assistant"));
+        assert!(user.is_principal("team"));
+        assert!(!user.is_principal("manager"));
+
+        assert!(user.can_write_calendars("team"));
+        assert!(user.can_write_calendars("manager"));
+        assert!(!user.can_write_calendars("colleague"));
+
+        assert!(user.can_read_calendars("manager"));
+        assert!(user.can_read_calendars("colleague"));
+        assert!(!user.can_read_calendars("stranger"));
+    }
+}
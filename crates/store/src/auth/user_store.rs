@@ -4,5 +4,7 @@ use async_trait::async_trait;
 #[async_trait]
 pub trait UserStore: Send + Sync + 'static {
     async fn get_user(&self, id: &str) -> Result<Option<User>, Error>;
+    async fn get_users(&self) -> Result<Vec<User>, Error>;
     async fn put_user(&self, user: User) -> Result<(), Error>;
+    async fn delete_user(&self, id: &str) -> Result<(), Error>;
 }
@@ -1,49 +1,548 @@
-use super::{parse_duration, CalDateTime};
+use super::{parse_duration, CalDateTime, RecurrenceLimit, RecurrenceRule};
 use crate::Error;
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use ical::{
     generator::IcalEvent,
     parser::{ical::component::IcalTimeZone, Component},
     property::Property,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+// Upper bound of instances we compute for a single window
+const MAX_OCCURENCES: usize = 10_000;
 
 #[derive(Debug, Clone)]
 pub struct EventObject {
     pub(crate) event: IcalEvent,
+    // Modified instances of a recurring event (VEVENTs with a RECURRENCE-ID)
+    pub(crate) overrides: Vec<IcalEvent>,
     pub(crate) timezones: HashMap<String, IcalTimeZone>,
 }
 
+#[derive(Debug, Clone)]
+pub struct EventOccurence<'a> {
+    // None if the event is not recurring
+    pub recurrence_id: Option<CalDateTime>,
+    pub start: CalDateTime,
+    pub end: CalDateTime,
+    // Either the main event or the override for this instance
+    pub event: &'a IcalEvent,
+}
+
 impl EventObject {
     pub fn get_first_occurence(&self) -> Result<Option<CalDateTime>, Error> {
-        if let Some(dtstart) = self.event.get_property("DTSTART") {
-            CalDateTime::parse_prop(dtstart, &self.timezones)
+        Self::get_dtstart(&self.event, &self.timezones)
+    }
+
+    pub fn get_last_occurence(&self) -> Result<Option<CalDateTime>, Error> {
+        let rrule = self.get_rrule()?;
+        if rrule.as_ref().is_some_and(|rrule| rrule.limit.is_none()) {
+            // Recurs forever
+            return Ok(None);
+        }
+
+        if rrule.is_none() && self.overrides.is_empty() && self.get_rdates()?.is_empty() {
+            return self.get_end(&self.event);
+        }
+
+        let (occurences, truncated) = self.expand_occurences(None, None)?;
+        if !truncated {
+            return Ok(occurences
+                .into_iter()
+                .map(|occurence| occurence.end)
+                .max_by_key(CalDateTime::utc));
+        }
+
+        // Too many instances to compute them all. The instances of a rule with UNTIL start
+        // before it, so we look for the last one in growing windows reaching back from UNTIL.
+        // With COUNT there's no way around expanding all instances, treat it as unbounded.
+        let Some(RecurrenceLimit::Until(until)) = rrule.and_then(|rrule| rrule.limit) else {
+            return Ok(None);
+        };
+        let first_start = self.get_first_occurence()?.map(|dtstart| dtstart.utc());
+        let mut lookback = Duration::hours(1);
+        loop {
+            let window_start = until.utc() - lookback;
+            let (occurences, truncated) = self.expand_occurences(Some(window_start), None)?;
+            if truncated {
+                return Ok(None);
+            }
+            // Everything outside of the window ends before the instances in it
+            if let Some(last) = occurences
+                .into_iter()
+                .map(|occurence| occurence.end)
+                .max_by_key(CalDateTime::utc)
+            {
+                return Ok(Some(last));
+            }
+            if first_start.is_none_or(|first_start| window_start <= first_start) {
+                return Ok(None);
+            }
+            lookback = lookback * 2;
+        }
+    }
+
+    pub fn is_recurring(&self) -> bool {
+        self.event.get_property("RRULE").is_some() || self.event.get_property("RDATE").is_some()
+    }
+
+    pub fn get_rrule(&self) -> Result<Option<RecurrenceRule>, Error> {
+        self.event
+            .get_property("RRULE")
+            .and_then(|prop| prop.value.as_ref())
+            .map(|rrule| rrule.parse())
+            .transpose()
+    }
+
+    // Returns all instances overlapping with the range [start, end)
+    // https://datatracker.ietf.org/doc/html/rfc4791#section-9.9
+    pub fn get_occurences(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<Vec<EventOccurence<'_>>, Error> {
+        Ok(self.expand_occurences(start, end)?.0)
+    }
+
+    // Like get_occurences but also returns whether the recurrence set had more than
+    // MAX_OCCURENCES instances in the window and was cut short
+    fn expand_occurences(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<(Vec<EventOccurence<'_>>, bool), Error> {
+        let overlaps = |occurence: &EventOccurence| {
+            let (occ_start, occ_end) = (occurence.start.utc(), occurence.end.utc());
+            start
+                .is_none_or(|start| occ_end > start || (occ_start == occ_end && occ_start >= start))
+                && end.is_none_or(|end| occ_start < end)
+        };
+
+        let Some(dtstart) = self.get_first_occurence()? else {
+            return Ok((vec![], false));
+        };
+        let duration = self.get_duration(&self.event)?;
+
+        if !self.is_recurring() {
+            let occurence = EventOccurence {
+                recurrence_id: None,
+                end: add_duration(&dtstart, duration),
+                start: dtstart,
+                event: &self.event,
+            };
+            return Ok((
+                overlaps(&occurence)
+                    .then_some(occurence)
+                    .into_iter()
+                    .collect(),
+                false,
+            ));
+        }
+
+        let overrides = self
+            .overrides
+            .iter()
+            .map(|event| {
+                let recurrence_id = event
+                    .get_property("RECURRENCE-ID")
+                    .map(|prop| CalDateTime::parse_prop(prop, &self.timezones))
+                    .transpose()?
+                    .flatten()
+                    .ok_or(Error::InvalidData("Missing RECURRENCE-ID".to_owned()))?;
+                Ok((recurrence_id, event))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let overridden: HashSet<_> = overrides.iter().map(|(id, _)| id.utc()).collect();
+        let exdates: HashSet<_> = self.get_exdates()?.iter().map(CalDateTime::utc).collect();
+
+        // Instances starting before this can't overlap with the window
+        let min_start = start.map(|start| start - duration);
+        let (mut starts, truncated) = match self.get_rrule()? {
+            Some(rrule) => rrule.between(
+                &dtstart,
+                min_start.as_ref(),
+                end.as_ref(),
+                Some(MAX_OCCURENCES),
+            ),
+            None => (vec![], false),
+        };
+        // DTSTART is always the first instance
+        starts.push(dtstart.clone());
+        starts.extend(self.get_rdates()?);
+        starts.sort_by_key(CalDateTime::utc);
+        starts.dedup_by_key(|start| start.utc());
+
+        let mut occurences: Vec<_> = starts
+            .into_iter()
+            .filter(|start| !exdates.contains(&start.utc()) && !overridden.contains(&start.utc()))
+            .map(|start| EventOccurence {
+                recurrence_id: Some(start.clone()),
+                end: add_duration(&start, duration),
+                start,
+                event: &self.event,
+            })
+            .filter(overlaps)
+            .collect();
+
+        for (recurrence_id, event) in overrides {
+            let Some(start) = Self::get_dtstart(event, &self.timezones)? else {
+                continue;
+            };
+            let occurence = EventOccurence {
+                recurrence_id: Some(recurrence_id),
+                end: add_duration(&start, self.get_duration(event)?),
+                start,
+                event,
+            };
+            if overlaps(&occurence) {
+                occurences.push(occurence);
+            }
+        }
+        occurences.sort_by_key(|occurence| occurence.start.utc());
+
+        Ok((occurences, truncated))
+    }
+
+    // Returns the instances overlapping with [start, end) as standalone components without
+    // recurrence properties and with all times converted to UTC
+    // https://datatracker.ietf.org/doc/html/rfc4791#section-9.6.5
+    pub fn expand_recurrence(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<Vec<IcalEvent>, Error> {
+        Ok(self
+            .get_occurences(start, end)?
+            .into_iter()
+            .map(|occurence| {
+                let mut event = occurence.event.clone();
+                event.properties.retain(|prop| {
+                    !matches!(
+                        prop.name.as_str(),
+                        "RRULE"
+                            | "RDATE"
+                            | "EXDATE"
+                            | "EXRULE"
+                            | "DTSTART"
+                            | "DTEND"
+                            | "DURATION"
+                            | "RECURRENCE-ID"
+                    )
+                });
+                event
+                    .properties
+                    .push(utc_property("DTSTART", &occurence.start));
+                event.properties.push(utc_property("DTEND", &occurence.end));
+                if let Some(recurrence_id) = &occurence.recurrence_id {
+                    event
+                        .properties
+                        .push(utc_property("RECURRENCE-ID", recurrence_id));
+                }
+                event
+            })
+            .collect())
+    }
+
+    // Returns the main event and only the overridden instances overlapping with [start, end)
+    // https://datatracker.ietf.org/doc/html/rfc4791#section-9.6.6
+    pub fn limit_recurrence_set(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<Vec<IcalEvent>, Error> {
+        let occurences = self.get_occurences(start, end)?;
+        let mut events = vec![self.event.clone()];
+        events.extend(
+            self.overrides
+                .iter()
+                .filter(|event| {
+                    occurences
+                        .iter()
+                        .any(|occurence| std::ptr::eq(occurence.event, *event))
+                })
+                .cloned(),
+        );
+        Ok(events)
+    }
+
+    fn get_dtstart(
+        event: &IcalEvent,
+        timezones: &HashMap<String, IcalTimeZone>,
+    ) -> Result<Option<CalDateTime>, Error> {
+        if let Some(dtstart) = event.get_property("DTSTART") {
+            CalDateTime::parse_prop(dtstart, timezones)
         } else {
             Ok(None)
         }
     }
 
-    pub fn get_last_occurence(&self) -> Result<Option<CalDateTime>, Error> {
-        if let Some(_rrule) = self.event.get_property("RRULE") {
-            // TODO: understand recurrence rules
+    fn get_end(&self, event: &IcalEvent) -> Result<Option<CalDateTime>, Error> {
+        let Some(start) = Self::get_dtstart(event, &self.timezones)? else {
             return Ok(None);
-        }
+        };
+        Ok(Some(add_duration(&start, self.get_duration(event)?)))
+    }
 
-        if let Some(dtend) = self.event.get_property("DTEND") {
-            return CalDateTime::parse_prop(dtend, &self.timezones);
+    fn get_duration(&self, event: &IcalEvent) -> Result<Duration, Error> {
+        let dtstart = Self::get_dtstart(event, &self.timezones)?;
+        if let Some(dtend) = event.get_property("DTEND") {
+            if let (Some(dtstart), Some(dtend)) =
+                (&dtstart, CalDateTime::parse_prop(dtend, &self.timezones)?)
+            {
+                return Ok(dtend.utc() - dtstart.utc());
+            }
         };
 
-        let duration = if let Some(Property {
+        if let Some(Property {
             value: Some(duration),
             ..
-        }) = self.event.get_property("DURATION")
+        }) = event.get_property("DURATION")
         {
-            parse_duration(duration)?
-        } else {
-            Duration::days(1)
+            return parse_duration(duration);
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc5545#section-3.6.1
+        // An all-day event without DTEND lasts one day, otherwise it ends at DTSTART
+        Ok(match dtstart {
+            Some(CalDateTime::Date(_)) => Duration::days(1),
+            _ => Duration::zero(),
+        })
+    }
+
+    fn get_exdates(&self) -> Result<Vec<CalDateTime>, Error> {
+        self.get_date_list("EXDATE")
+    }
+
+    fn get_rdates(&self) -> Result<Vec<CalDateTime>, Error> {
+        self.get_date_list("RDATE")
+    }
+
+    fn get_date_list(&self, name: &str) -> Result<Vec<CalDateTime>, Error> {
+        let mut dates = vec![];
+        for prop in self
+            .event
+            .properties
+            .iter()
+            .filter(|prop| prop.name == name)
+        {
+            dates.extend(CalDateTime::parse_prop_list(prop, &self.timezones)?);
+        }
+        Ok(dates)
+    }
+}
+
+fn add_duration(start: &CalDateTime, duration: Duration) -> CalDateTime {
+    match start {
+        // Keep all-day events as dates
+        CalDateTime::Date(date) if duration.num_seconds() % 86400 == 0 => {
+            CalDateTime::Date(*date + duration)
+        }
+        start => start.clone() + duration,
+    }
+}
+
+fn utc_property(name: &str, value: &CalDateTime) -> Property {
+    let (params, value) = match value {
+        CalDateTime::Date(date) => (
+            Some(vec![("VALUE".to_owned(), vec!["DATE".to_owned()])]),
+            CalDateTime::Date(*date).format(),
+        ),
+        value => (None, CalDateTime::Utc(value.utc()).format()),
+    };
+    Property {
+        name: name.to_owned(),
+        params,
+        value: Some(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{calendar::CalendarObjectComponent, CalendarObject};
+    use chrono::{DateTime, NaiveDate, Utc};
+
+    const RECURRING_ICS: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//test//EN
+BEGIN:VEVENT
+UID:recurring
+DTSTAMP:20240101T000000Z
+DTSTART:20240101T100000Z
+DTEND:20240101T110000Z
+RRULE:FREQ=DAILY;COUNT=5
+EXDATE:20240103T100000Z
+END:VEVENT
+BEGIN:VEVENT
+UID:recurring
+DTSTAMP:20240101T000000Z
+RECURRENCE-ID:20240104T100000Z
+DTSTART:20240110T150000Z
+DTEND:20240110T160000Z
+END:VEVENT
+END:VCALENDAR
+"#;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_recurring_event() {
+        let object =
+            CalendarObject::from_ics("recurring".to_owned(), RECURRING_ICS.to_owned()).unwrap();
+        let CalendarObjectComponent::Event(event) = object.get_data() else {
+            panic!("Expected an event");
+        };
+
+        let starts: Vec<_> = event
+            .get_occurences(None, None)
+            .unwrap()
+            .into_iter()
+            .map(|occurence| occurence.start.utc())
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                utc("2024-01-01T10:00:00Z"),
+                utc("2024-01-02T10:00:00Z"),
+                utc("2024-01-05T10:00:00Z"),
+                utc("2024-01-10T15:00:00Z"),
+            ]
+        );
+
+        // The moved instance determines the end
+        assert_eq!(
+            object.get_last_occurence().unwrap().unwrap().utc(),
+            utc("2024-01-10T16:00:00Z")
+        );
+
+        let window: Vec<_> = event
+            .get_occurences(
+                Some(utc("2024-01-02T10:30:00Z")),
+                Some(utc("2024-01-06T00:00:00Z")),
+            )
+            .unwrap()
+            .into_iter()
+            .map(|occurence| occurence.recurrence_id.unwrap().utc())
+            .collect();
+        assert_eq!(
+            window,
+            vec![utc("2024-01-02T10:00:00Z"), utc("2024-01-05T10:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn test_expand_recurrence() {
+        let object =
+            CalendarObject::from_ics("recurring".to_owned(), RECURRING_ICS.to_owned()).unwrap();
+        let expanded = object
+            .expand_recurrence(
+                Some(utc("2024-01-02T00:00:00Z")),
+                Some(utc("2024-01-11T00:00:00Z")),
+            )
+            .unwrap();
+        assert!(!expanded.contains("RRULE"));
+        assert!(!expanded.contains("EXDATE"));
+        assert!(expanded.contains("RECURRENCE-ID:20240102T100000Z"));
+        assert!(expanded.contains("RECURRENCE-ID:20240104T100000Z"));
+        assert!(expanded.contains("DTSTART:20240110T150000Z"));
+
+        // The expanded object is a valid object consisting only of instances
+        let expanded = CalendarObject::from_ics("recurring".to_owned(), expanded).unwrap();
+        let CalendarObjectComponent::Event(event) = expanded.get_data() else {
+            panic!("Expected an event");
+        };
+        assert_eq!(event.get_occurences(None, None).unwrap().len(), 1);
+
+        let limited = object
+            .limit_recurrence_set(
+                Some(utc("2024-01-01T00:00:00Z")),
+                Some(utc("2024-01-02T00:00:00Z")),
+            )
+            .unwrap();
+        assert!(limited.contains("RRULE:FREQ=DAILY;COUNT=5"));
+        assert!(!limited.contains("RECURRENCE-ID"));
+    }
+
+    #[test]
+    fn test_unbounded_event() {
+        let ics = RECURRING_ICS.replace("RRULE:FREQ=DAILY;COUNT=5", "RRULE:FREQ=WEEKLY");
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        assert_eq!(object.get_last_occurence().unwrap(), None);
+
+        let ics = RECURRING_ICS.replace("COUNT=5", "UNTIL=20240201");
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        assert_eq!(
+            object.get_last_occurence().unwrap().unwrap().date(),
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+        );
+
+        // Events that cannot be expanded are not hidden from time-range queries
+        let ics = RECURRING_ICS.replace("FREQ=DAILY", "FREQ=FORTNIGHTLY");
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        assert!(object.occurs_between(
+            Some(utc("2024-01-01T00:00:00Z")),
+            Some(utc("2024-01-02T00:00:00Z"))
+        ));
+
+        // More instances than we expand are treated as unbounded
+        let ics = RECURRING_ICS.replace("COUNT=5", "COUNT=20000");
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        assert_eq!(object.get_last_occurence().unwrap(), None);
+
+        // unless UNTIL tells us where to look for the last one
+        let ics = RECURRING_ICS.replace("DAILY;COUNT=5", "HOURLY;UNTIL=20300101T000000Z");
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        assert_eq!(
+            object.get_last_occurence().unwrap().unwrap().utc(),
+            utc("2030-01-01T01:00:00Z")
+        );
+        let ics = RECURRING_ICS.replace(
+            "DAILY;COUNT=5",
+            "DAILY;BYMONTH=1;BYMONTHDAY=1,2;UNTIL=20991231T000000Z",
+        );
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        assert_eq!(
+            object.get_last_occurence().unwrap().unwrap().utc(),
+            utc("2099-01-02T11:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_frequent_event_in_window() {
+        // Expansion starts at the window and not at DTSTART
+        let ics = RECURRING_ICS.replace("RRULE:FREQ=DAILY;COUNT=5", "RRULE:FREQ=SECONDLY");
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        let CalendarObjectComponent::Event(event) = object.get_data() else {
+            panic!("Expected an event");
         };
+        let occurences = event
+            .get_occurences(
+                Some(utc("2030-01-01T00:00:00Z")),
+                Some(utc("2031-01-01T00:00:00Z")),
+            )
+            .unwrap();
+        assert!(!occurences.is_empty());
+        // The instance that started an hour earlier still overlaps
+        assert_eq!(occurences[0].start.utc(), utc("2029-12-31T23:00:01Z"));
 
-        let first_occurence = self.get_first_occurence()?;
-        Ok(first_occurence.map(|first_occurence| first_occurence + duration))
+        // A start-only window on an old hourly event
+        let ics = RECURRING_ICS.replace("RRULE:FREQ=DAILY;COUNT=5", "RRULE:FREQ=HOURLY");
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        assert!(object.occurs_between(Some(utc("2030-01-01T00:00:00Z")), None));
+
+        // Sparse sub-daily rules are not ended early
+        let ics = RECURRING_ICS.replace(
+            "RRULE:FREQ=DAILY;COUNT=5",
+            "RRULE:FREQ=MINUTELY;BYMONTH=6;BYHOUR=12",
+        );
+        let object = CalendarObject::from_ics("recurring".to_owned(), ics).unwrap();
+        let CalendarObjectComponent::Event(event) = object.get_data() else {
+            panic!("Expected an event");
+        };
+        let occurences = event
+            .get_occurences(Some(utc("2028-01-01T00:00:00Z")), None)
+            .unwrap();
+        assert_eq!(occurences[0].start.utc(), utc("2028-06-01T12:00:00Z"));
     }
 }
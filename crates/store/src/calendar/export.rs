@@ -0,0 +1,101 @@
+use super::{get_timezones, Calendar, CalendarObject};
+use crate::Error;
+use ical::{
+    generator::{Emitter, IcalCalendar},
+    property::Property,
+};
+use std::collections::BTreeMap;
+
+fn property(name: &str, value: String) -> Property {
+    Property {
+        name: name.to_owned(),
+        params: None,
+        value: Some(value),
+    }
+}
+
+// Merges all objects of a calendar into a single VCALENDAR
+// VTIMEZONEs shared between objects are only included once
+pub(crate) fn merge_calendar(
+    calendar: &Calendar,
+    objects: &[CalendarObject],
+) -> Result<IcalCalendar, Error> {
+    let mut cal = IcalCalendar::new();
+    cal.properties = vec![
+        property("VERSION", "2.0".to_owned()),
+        property("PRODID", "-//github.com/lennart-k/rustical//EN".to_owned()),
+    ];
+    // Non-standard properties understood by most calendar clients
+    if let Some(displayname) = &calendar.displayname {
+        cal.properties
+            .push(property("X-WR-CALNAME", displayname.to_owned()));
+    }
+    if let Some(description) = &calendar.description {
+        cal.properties
+            .push(property("X-WR-CALDESC", description.to_owned()));
+    }
+    if let Some(timezone_id) = &calendar.timezone_id {
+        cal.properties
+            .push(property("X-WR-TIMEZONE", timezone_id.to_owned()));
+    }
+
+    let mut timezones = BTreeMap::new();
+    for object in objects {
+        let object_cal = object.get_ical()?;
+        for (tzid, timezone) in get_timezones(&object_cal) {
+            timezones.entry(tzid).or_insert(timezone);
+        }
+        cal.events.extend(object_cal.events);
+        cal.todos.extend(object_cal.todos);
+        cal.journals.extend(object_cal.journals);
+    }
+    cal.timezones = timezones.into_values().collect();
+    Ok(cal)
+}
+
+pub fn export_calendar(calendar: &Calendar, objects: &[CalendarObject]) -> Result<String, Error> {
+    Ok(merge_calendar(calendar, objects)?.generate())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::export_calendar;
+    use crate::{calendar::Calendar, CalendarObject};
+
+    const TIMEZONE: &str = "BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nBEGIN:STANDARD\r\nDTSTART:19701025T030000\r\nTZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\nEND:STANDARD\r\nEND:VTIMEZONE\r\n";
+
+    fn event(uid: &str) -> CalendarObject {
+        CalendarObject::from_ics(
+            uid.to_owned(),
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n{TIMEZONE}BEGIN:VEVENT\r\nUID:{uid}\r\nDTSTAMP:20240101T000000Z\r\nDTSTART;TZID=Europe/Berlin:20240101T100000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+            ),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_export_calendar() {
+        let calendar = Calendar {
+            principal: "alice".to_owned(),
+            id: "work".to_owned(),
+            displayname: Some("Work".to_owned()),
+            ..Default::default()
+        };
+        let ics = export_calendar(&calendar, &[event("a"), event("b")]).unwrap();
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("X-WR-CALNAME:Work\r\n"));
+        assert_eq!(ics.matches("BEGIN:VTIMEZONE").count(), 1);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("UID:a\r\n") && ics.contains("UID:b\r\n"));
+
+        // The merged calendar can be parsed again
+        let cal = ical::IcalParser::new(ics.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(cal.events.len(), 2);
+        assert_eq!(cal.timezones.len(), 1);
+    }
+}
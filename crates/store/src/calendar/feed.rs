@@ -0,0 +1,311 @@
+use super::{merge_calendar, Calendar, CalendarObject};
+use crate::Error;
+use chrono::NaiveDateTime;
+use ical::{
+    generator::{Emitter, IcalEvent},
+    property::Property,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+
+// How much of a calendar a public feed reveals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeedPrivacy {
+    #[default]
+    Full,
+    // Only reveals times and titles, classified events only as busy
+    Private,
+    // Only reveals when the owner is busy
+    BusyOnly,
+}
+
+impl FeedPrivacy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Private => "private",
+            Self::BusyOnly => "busy-only",
+        }
+    }
+}
+
+// Properties kept in private feeds, everything else like DESCRIPTION, LOCATION, ATTENDEE,
+// ORGANIZER, URL, ATTACH and X- properties may reveal more than the time and title
+const PRIVATE_PROPERTIES: &[&str] = &[
+    "UID",
+    "DTSTAMP",
+    "DTSTART",
+    "DTEND",
+    "DURATION",
+    "DUE",
+    "COMPLETED",
+    "RRULE",
+    "RDATE",
+    "EXDATE",
+    "RECURRENCE-ID",
+    "SEQUENCE",
+    "STATUS",
+    "TRANSP",
+    "SUMMARY",
+    "PRIORITY",
+    "PERCENT-COMPLETE",
+];
+
+// Properties kept for events in busy-only feeds
+const BUSY_PROPERTIES: &[&str] = &[
+    "UID",
+    "DTSTAMP",
+    "DTSTART",
+    "DTEND",
+    "DURATION",
+    "RRULE",
+    "RDATE",
+    "EXDATE",
+    "RECURRENCE-ID",
+    "SEQUENCE",
+    "STATUS",
+    "TRANSP",
+];
+
+// An unauthenticated read-only iCalendar feed of a calendar
+// The token is the only credential, so revoking a feed means deleting it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarFeed {
+    pub token: String,
+    pub principal: String,
+    pub cal_id: String,
+    pub privacy: FeedPrivacy,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl CalendarFeed {
+    pub fn new(principal: String, cal_id: String, privacy: FeedPrivacy) -> Self {
+        let token = rand::thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        Self {
+            token,
+            principal,
+            cal_id,
+            privacy,
+            created_at: Some(chrono::Utc::now().naive_utc()),
+        }
+    }
+}
+
+fn get_value<'a>(properties: &'a [Property], name: &str) -> Option<&'a str> {
+    properties
+        .iter()
+        .find(|prop| prop.name == name)
+        .and_then(|prop| prop.value.as_deref())
+}
+
+// CLASS:PRIVATE and CLASS:CONFIDENTIAL components must not reveal their content
+fn is_classified(properties: &[Property]) -> bool {
+    matches!(
+        get_value(properties, "CLASS"),
+        Some("PRIVATE" | "CONFIDENTIAL")
+    )
+}
+
+// Free time and cancelled events don't make the owner busy
+fn blocks_time(properties: &[Property]) -> bool {
+    get_value(properties, "TRANSP") != Some("TRANSPARENT")
+        && get_value(properties, "STATUS") != Some("CANCELLED")
+}
+
+fn make_busy(event: &mut IcalEvent) {
+    event
+        .properties
+        .retain(|prop| BUSY_PROPERTIES.contains(&prop.name.as_str()));
+    event.properties.push(Property {
+        name: "SUMMARY".to_owned(),
+        params: None,
+        value: Some("Busy".to_owned()),
+    });
+    event.alarms.clear();
+}
+
+// Removes events that don't block time
+// Removed overrides of recurring events become exceptions so that the instance doesn't show up again
+fn remove_free_events(events: &mut Vec<IcalEvent>) {
+    let mut exceptions = vec![];
+    events.retain(|event| {
+        if blocks_time(&event.properties) {
+            return true;
+        }
+        if let (Some(uid), Some(recurrence_id)) = (
+            get_value(&event.properties, "UID"),
+            event
+                .properties
+                .iter()
+                .find(|prop| prop.name == "RECURRENCE-ID"),
+        ) {
+            exceptions.push((uid.to_owned(), recurrence_id.to_owned()));
+        }
+        false
+    });
+    for (uid, recurrence_id) in exceptions {
+        let master = events.iter_mut().find(|event| {
+            get_value(&event.properties, "UID") == Some(uid.as_str())
+                && get_value(&event.properties, "RECURRENCE-ID").is_none()
+        });
+        if let Some(master) = master {
+            master.properties.push(Property {
+                name: "EXDATE".to_owned(),
+                ..recurrence_id
+            });
+        }
+    }
+}
+
+// Merges the objects like export_calendar but only reveals what the feed's privacy allows
+pub fn export_feed(
+    calendar: &Calendar,
+    objects: &[CalendarObject],
+    privacy: FeedPrivacy,
+) -> Result<String, Error> {
+    let mut cal = merge_calendar(calendar, objects)?;
+    if privacy != FeedPrivacy::Full {
+        cal.properties.retain(|prop| prop.name != "X-WR-CALDESC");
+    }
+    match privacy {
+        FeedPrivacy::Full => {}
+        FeedPrivacy::Private => {
+            // Alarms are personal to the owner
+            for event in cal.events.iter_mut() {
+                if is_classified(&event.properties) {
+                    make_busy(event);
+                } else {
+                    event
+                        .properties
+                        .retain(|prop| PRIVATE_PROPERTIES.contains(&prop.name.as_str()));
+                    event.alarms.clear();
+                }
+            }
+            // Classified tasks and journals don't block time, so nothing of them is left to show
+            cal.todos.retain(|todo| !is_classified(&todo.properties));
+            for todo in cal.todos.iter_mut() {
+                todo.properties
+                    .retain(|prop| PRIVATE_PROPERTIES.contains(&prop.name.as_str()));
+                todo.alarms.clear();
+            }
+            cal.journals
+                .retain(|journal| !is_classified(&journal.properties));
+            for journal in cal.journals.iter_mut() {
+                journal
+                    .properties
+                    .retain(|prop| PRIVATE_PROPERTIES.contains(&prop.name.as_str()));
+            }
+        }
+        FeedPrivacy::BusyOnly => {
+            // Tasks and journals don't block any time
+            cal.todos.clear();
+            cal.journals.clear();
+            remove_free_events(&mut cal.events);
+            cal.events.iter_mut().for_each(make_busy);
+        }
+    }
+    Ok(cal.generate())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_feed, FeedPrivacy};
+    use crate::{calendar::Calendar, CalendarObject};
+
+    fn calendar() -> Calendar {
+        Calendar {
+            principal: "alice".to_owned(),
+            id: "oncall".to_owned(),
+            displayname: Some("On-call".to_owned()),
+            description: Some("Escalation rota".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn objects() -> Vec<CalendarObject> {
+        vec![
+            CalendarObject::from_ics(
+                "event".to_owned(),
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VEVENT\r\nUID:event\r\nDTSTAMP:20240101T000000Z\r\nDTSTART:20240101T100000Z\r\nDTEND:20240101T110000Z\r\nSUMMARY:Incident review\r\nDESCRIPTION:Secret\r\nLOCATION:Room 1\r\nATTENDEE:mailto:bob@example.com\r\nORGANIZER:mailto:alice@example.com\r\nURL:https://example.com/incident\r\nX-MEETING-LINK:https://example.com/call\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:Reminder\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n".to_owned(),
+            )
+            .unwrap(),
+            CalendarObject::from_ics(
+                "todo".to_owned(),
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VTODO\r\nUID:todo\r\nDTSTAMP:20240101T000000Z\r\nSUMMARY:Rotate keys\r\nEND:VTODO\r\nEND:VCALENDAR\r\n".to_owned(),
+            )
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_export_feed() {
+        let full = export_feed(&calendar(), &objects(), FeedPrivacy::Full).unwrap();
+        assert!(full.contains("DESCRIPTION:Secret\r\n"));
+        assert!(full.contains("BEGIN:VALARM\r\n"));
+
+        let private = export_feed(&calendar(), &objects(), FeedPrivacy::Private).unwrap();
+        assert!(private.contains("SUMMARY:Incident review\r\n"));
+        assert!(private.contains("BEGIN:VTODO\r\n"));
+        for hidden in [
+            "DESCRIPTION",
+            "LOCATION",
+            "ATTENDEE",
+            "ORGANIZER",
+            "URL",
+            "X-MEETING-LINK",
+            "X-WR-CALDESC",
+            "BEGIN:VALARM",
+        ] {
+            assert!(!private.contains(hidden), "{hidden} should be stripped");
+        }
+
+        let busy = export_feed(&calendar(), &objects(), FeedPrivacy::BusyOnly).unwrap();
+        assert!(busy.contains("SUMMARY:Busy\r\n"));
+        assert!(busy.contains("DTSTART:20240101T100000Z\r\n"));
+        assert!(busy.contains("DTEND:20240101T110000Z\r\n"));
+        assert!(!busy.contains("Incident review"));
+        assert!(!busy.contains("BEGIN:VTODO"));
+        assert!(!busy.contains("X-WR-CALDESC"));
+    }
+
+    fn object(id: &str, content: &str) -> CalendarObject {
+        CalendarObject::from_ics(
+            id.to_owned(),
+            format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n{content}END:VCALENDAR\r\n"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_export_feed_classified() {
+        let objects = vec![
+            object("private", "BEGIN:VEVENT\r\nUID:private\r\nDTSTAMP:20240101T000000Z\r\nDTSTART:20240101T100000Z\r\nDTEND:20240101T110000Z\r\nSUMMARY:Doctor\r\nCLASS:PRIVATE\r\nEND:VEVENT\r\n"),
+            object("todo", "BEGIN:VTODO\r\nUID:todo\r\nDTSTAMP:20240101T000000Z\r\nSUMMARY:Buy gift\r\nCLASS:CONFIDENTIAL\r\nEND:VTODO\r\n"),
+        ];
+        let private = export_feed(&calendar(), &objects, FeedPrivacy::Private).unwrap();
+        assert!(private.contains("SUMMARY:Busy\r\n"));
+        assert!(private.contains("DTSTART:20240101T100000Z\r\n"));
+        assert!(!private.contains("Doctor"));
+        assert!(!private.contains("Buy gift"));
+    }
+
+    #[test]
+    fn test_export_feed_busy_free_events() {
+        let objects = vec![
+            object("free", "BEGIN:VEVENT\r\nUID:free\r\nDTSTAMP:20240101T000000Z\r\nDTSTART:20240101T100000Z\r\nDTEND:20240101T110000Z\r\nTRANSP:TRANSPARENT\r\nEND:VEVENT\r\n"),
+            object("cancelled", "BEGIN:VEVENT\r\nUID:cancelled\r\nDTSTAMP:20240101T000000Z\r\nDTSTART:20240102T100000Z\r\nDTEND:20240102T110000Z\r\nSTATUS:CANCELLED\r\nEND:VEVENT\r\n"),
+            object("weekly", "BEGIN:VEVENT\r\nUID:weekly\r\nDTSTAMP:20240101T000000Z\r\nDTSTART;TZID=Europe/Berlin:20240103T100000\r\nDTEND;TZID=Europe/Berlin:20240103T110000\r\nRRULE:FREQ=WEEKLY\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nUID:weekly\r\nDTSTAMP:20240101T000000Z\r\nRECURRENCE-ID;TZID=Europe/Berlin:20240110T100000\r\nDTSTART;TZID=Europe/Berlin:20240110T100000\r\nDTEND;TZID=Europe/Berlin:20240110T110000\r\nSTATUS:CANCELLED\r\nEND:VEVENT\r\n"),
+        ];
+        let busy = export_feed(&calendar(), &objects, FeedPrivacy::BusyOnly).unwrap();
+        assert!(!busy.contains("UID:free"));
+        assert!(!busy.contains("UID:cancelled"));
+        assert!(!busy.contains("RECURRENCE-ID"));
+        // The cancelled instance must not show up as busy through the recurrence rule
+        assert!(busy.contains("EXDATE;TZID=Europe/Berlin:20240110T100000\r\n"));
+    }
+}
@@ -0,0 +1,262 @@
+use super::{CalDateTime, CalendarObject, CalendarObjectComponent, EventObject};
+use crate::Error;
+use chrono::{DateTime, Utc};
+use ical::{
+    generator::{Emitter, IcalCalendar},
+    parser::{ical::component::IcalFreeBusy, Component},
+    property::Property,
+};
+
+// https://datatracker.ietf.org/doc/html/rfc5545#section-3.2.9
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FreeBusyType {
+    Busy,
+    BusyUnavailable,
+    BusyTentative,
+}
+
+impl FreeBusyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Busy => "BUSY",
+            Self::BusyUnavailable => "BUSY-UNAVAILABLE",
+            Self::BusyTentative => "BUSY-TENTATIVE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreeBusyPeriod {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub fbtype: FreeBusyType,
+}
+
+impl EventObject {
+    // Returns the busy time of all instances within [start, end)
+    // https://datatracker.ietf.org/doc/html/rfc4791#section-7.10
+    pub fn get_busy_periods(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<FreeBusyPeriod>, Error> {
+        Ok(self
+            .get_occurences(Some(start), Some(end))?
+            .into_iter()
+            .filter_map(|occurence| {
+                let value = |name| {
+                    occurence
+                        .event
+                        .get_property(name)
+                        .and_then(|prop| prop.value.as_deref())
+                        .map(str::to_ascii_uppercase)
+                };
+                // Transparent events don't take up any time
+                if value("TRANSP").as_deref() == Some("TRANSPARENT") {
+                    return None;
+                }
+                let fbtype = match value("STATUS").as_deref() {
+                    Some("CANCELLED") => return None,
+                    Some("TENTATIVE") => FreeBusyType::BusyTentative,
+                    _ => FreeBusyType::Busy,
+                };
+                let period = FreeBusyPeriod {
+                    start: occurence.start.utc().max(start),
+                    end: occurence.end.utc().min(end),
+                    fbtype,
+                };
+                (period.start < period.end).then_some(period)
+            })
+            .collect())
+    }
+}
+
+// Aggregates the busy time of all events into non-overlapping periods per FBTYPE
+pub fn get_busy_periods(
+    objects: &[CalendarObject],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<FreeBusyPeriod>, Error> {
+    let mut periods = vec![];
+    for object in objects {
+        if let CalendarObjectComponent::Event(event) = object.get_data() {
+            periods.extend(event.get_busy_periods(start, end)?);
+        }
+    }
+    periods.sort_by_key(|period| (period.fbtype, period.start));
+
+    let mut merged: Vec<FreeBusyPeriod> = vec![];
+    for period in periods {
+        match merged.last_mut() {
+            Some(last) if last.fbtype == period.fbtype && last.end >= period.start => {
+                last.end = last.end.max(period.end);
+            }
+            _ => merged.push(period),
+        }
+    }
+    merged.sort_by_key(|period| period.start);
+    Ok(merged)
+}
+
+fn property(name: &str, params: Option<Vec<(String, Vec<String>)>>, value: String) -> Property {
+    Property {
+        name: name.to_owned(),
+        params,
+        value: Some(value),
+    }
+}
+
+// A VCALENDAR containing a single VFREEBUSY for the range [start, end)
+fn freebusy_calendar(
+    periods: &[FreeBusyPeriod],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> IcalCalendar {
+    let utc = |value: DateTime<Utc>| CalDateTime::Utc(value).format();
+
+    let mut freebusy = IcalFreeBusy::new();
+    freebusy.properties = vec![
+        property("DTSTAMP", None, utc(Utc::now())),
+        property("DTSTART", None, utc(start)),
+        property("DTEND", None, utc(end)),
+    ];
+    freebusy.properties.extend(periods.iter().map(|period| {
+        property(
+            "FREEBUSY",
+            Some(vec![(
+                "FBTYPE".to_owned(),
+                vec![period.fbtype.as_str().to_owned()],
+            )]),
+            format!("{}/{}", utc(period.start), utc(period.end)),
+        )
+    }));
+
+    let mut cal = IcalCalendar::new();
+    cal.properties = vec![
+        property("VERSION", None, "2.0".to_owned()),
+        property(
+            "PRODID",
+            None,
+            "-//github.com/lennart-k/rustical//EN".to_owned(),
+        ),
+    ];
+    cal.free_busys.push(freebusy);
+    cal
+}
+
+pub fn generate_freebusy(
+    periods: &[FreeBusyPeriod],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> String {
+    freebusy_calendar(periods, start, end).generate()
+}
+
+// Generates an attendee's answer to a VFREEBUSY request
+// https://datatracker.ietf.org/doc/html/rfc5546#section-3.3.3
+pub fn generate_freebusy_reply(
+    periods: &[FreeBusyPeriod],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    request: &IcalFreeBusy,
+    attendee: &Property,
+) -> String {
+    let mut cal = freebusy_calendar(periods, start, end);
+    cal.properties
+        .push(property("METHOD", None, "REPLY".to_owned()));
+    let freebusy = &mut cal.free_busys[0];
+    freebusy.properties.extend(
+        ["UID", "ORGANIZER"]
+            .into_iter()
+            .filter_map(|name| request.get_property(name).cloned()),
+    );
+    freebusy.properties.push(attendee.clone());
+    cal.generate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_freebusy, get_busy_periods, FreeBusyPeriod, FreeBusyType};
+    use crate::CalendarObject;
+    use chrono::{DateTime, Utc};
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    fn event(uid: &str, props: &str) -> CalendarObject {
+        CalendarObject::from_ics(
+            uid.to_owned(),
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VEVENT\r\nUID:{uid}\r\nDTSTAMP:20240101T000000Z\r\n{props}END:VEVENT\r\nEND:VCALENDAR\r\n"
+            ),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_busy_periods() {
+        let objects = vec![
+            event(
+                "daily",
+                "DTSTART:20240101T100000Z\r\nDTEND:20240101T110000Z\r\nRRULE:FREQ=DAILY;COUNT=3\r\n",
+            ),
+            event(
+                "overlap",
+                "DTSTART:20240101T103000Z\r\nDTEND:20240101T120000Z\r\n",
+            ),
+            event(
+                "tentative",
+                "DTSTART:20240102T140000Z\r\nDTEND:20240102T150000Z\r\nSTATUS:TENTATIVE\r\n",
+            ),
+            event(
+                "cancelled",
+                "DTSTART:20240102T080000Z\r\nDTEND:20240102T090000Z\r\nSTATUS:CANCELLED\r\n",
+            ),
+            event(
+                "transparent",
+                "DTSTART:20240102T080000Z\r\nDTEND:20240102T090000Z\r\nTRANSP:TRANSPARENT\r\n",
+            ),
+        ];
+        let (start, end) = (utc("2024-01-01T00:00:00Z"), utc("2024-01-03T10:30:00Z"));
+        let periods = get_busy_periods(&objects, start, end).unwrap();
+        let period = |start, end, fbtype| FreeBusyPeriod {
+            start: utc(start),
+            end: utc(end),
+            fbtype,
+        };
+        assert_eq!(
+            periods,
+            vec![
+                period(
+                    "2024-01-01T10:00:00Z",
+                    "2024-01-01T12:00:00Z",
+                    FreeBusyType::Busy
+                ),
+                period(
+                    "2024-01-02T10:00:00Z",
+                    "2024-01-02T11:00:00Z",
+                    FreeBusyType::Busy
+                ),
+                period(
+                    "2024-01-02T14:00:00Z",
+                    "2024-01-02T15:00:00Z",
+                    FreeBusyType::BusyTentative
+                ),
+                period(
+                    "2024-01-03T10:00:00Z",
+                    "2024-01-03T10:30:00Z",
+                    FreeBusyType::Busy
+                ),
+            ]
+        );
+
+        let ics = generate_freebusy(&periods, start, end);
+        assert!(ics.contains("BEGIN:VFREEBUSY\r\n"));
+        assert!(ics.contains("DTSTART:20240101T000000Z\r\n"));
+        assert!(ics.contains("FREEBUSY;FBTYPE=BUSY:20240101T100000Z/20240101T120000Z\r\n"));
+        assert!(
+            ics.contains("FREEBUSY;FBTYPE=BUSY-TENTATIVE:20240102T140000Z/20240102T150000Z\r\n")
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use super::get_timezones;
+use crate::Error;
+use ical::{generator::IcalCalendar, property::Property};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::BufReader,
+};
+
+// UIDs may contain characters that don't belong into a URL
+pub fn object_id_from_uid(uid: &str) -> String {
+    if !uid.is_empty()
+        && uid
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@'))
+    {
+        return uid.to_owned();
+    }
+    format!("{:x}", Sha256::digest(uid))
+}
+
+fn get_uid(properties: &[Property]) -> Option<String> {
+    properties
+        .iter()
+        .find(|prop| prop.name == "UID")
+        .and_then(|prop| prop.value.to_owned())
+}
+
+fn set_uid_property(properties: &mut [Property], uid: &str) {
+    for prop in properties.iter_mut().filter(|prop| prop.name == "UID") {
+        prop.value = Some(uid.to_owned());
+    }
+}
+
+// TZIDs referenced by the properties of a component
+fn get_tzids<'a>(properties: &'a [Property], tzids: &mut HashSet<&'a str>) {
+    tzids.extend(
+        properties
+            .iter()
+            .flat_map(|prop| prop.params.iter().flatten())
+            .filter(|(name, _)| name.eq_ignore_ascii_case("TZID"))
+            .flat_map(|(_, values)| values.iter().map(String::as_str)),
+    );
+}
+
+// Splits an iCalendar file into one VCALENDAR per UID, ordered by UID
+// Components sharing a UID (overridden instances) stay together
+// Components without UID can't be told apart, they are left out and only counted
+pub fn split_calendar(ics: &str) -> Result<(Vec<(String, IcalCalendar)>, usize), Error> {
+    let mut split = vec![];
+    let mut without_uid = 0;
+    for cal in ical::IcalParser::new(BufReader::new(ics.as_bytes())) {
+        let cal = cal?;
+        let timezones = get_timezones(&cal);
+
+        let mut groups: BTreeMap<String, IcalCalendar> = BTreeMap::new();
+        for event in cal.events {
+            match get_uid(&event.properties) {
+                Some(uid) => groups.entry(uid).or_default().events.push(event),
+                None => without_uid += 1,
+            }
+        }
+        for todo in cal.todos {
+            match get_uid(&todo.properties) {
+                Some(uid) => groups.entry(uid).or_default().todos.push(todo),
+                None => without_uid += 1,
+            }
+        }
+        for journal in cal.journals {
+            match get_uid(&journal.properties) {
+                Some(uid) => groups.entry(uid).or_default().journals.push(journal),
+                None => without_uid += 1,
+            }
+        }
+
+        for (uid, mut group) in groups {
+            group.properties = cal
+                .properties
+                .iter()
+                .filter(|prop| matches!(prop.name.as_str(), "VERSION" | "PRODID" | "CALSCALE"))
+                .cloned()
+                .collect();
+
+            // Only include the VTIMEZONEs the object actually uses
+            let mut tzids = HashSet::new();
+            for event in &group.events {
+                get_tzids(&event.properties, &mut tzids);
+            }
+            for todo in &group.todos {
+                get_tzids(&todo.properties, &mut tzids);
+            }
+            for journal in &group.journals {
+                get_tzids(&journal.properties, &mut tzids);
+            }
+            let mut tzids: Vec<_> = tzids.into_iter().collect();
+            tzids.sort();
+            group.timezones = tzids
+                .into_iter()
+                .filter_map(|tzid| timezones.get(tzid).cloned())
+                .collect();
+
+            split.push((uid, group));
+        }
+    }
+    Ok((split, without_uid))
+}
+
+// Gives all components of a calendar split by split_calendar a new UID
+pub fn set_calendar_uid(cal: &mut IcalCalendar, uid: &str) {
+    for event in &mut cal.events {
+        set_uid_property(&mut event.properties, uid);
+    }
+    for todo in &mut cal.todos {
+        set_uid_property(&mut todo.properties, uid);
+    }
+    for journal in &mut cal.journals {
+        set_uid_property(&mut journal.properties, uid);
+    }
+}
+
+// The X-WR-CALNAME many clients put into exported calendars
+pub fn get_calendar_name(ics: &str) -> Option<String> {
+    let cal = ical::IcalParser::new(BufReader::new(ics.as_bytes()))
+        .next()?
+        .ok()?;
+    cal.properties
+        .into_iter()
+        .find(|prop| prop.name == "X-WR-CALNAME")?
+        .value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_calendar_name, set_calendar_uid, split_calendar};
+    use ical::generator::Emitter;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//test//EN\r
+X-WR-CALNAME:Holidays\r
+BEGIN:VEVENT\r
+UID:b\r
+DTSTAMP:20240101T000000Z\r
+DTSTART;VALUE=DATE:20240101\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:a\r
+DTSTAMP:20240101T000000Z\r
+DTSTART;VALUE=DATE:20240201\r
+RRULE:FREQ=YEARLY\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:a\r
+RECURRENCE-ID;VALUE=DATE:20250201\r
+DTSTAMP:20240101T000000Z\r
+DTSTART;VALUE=DATE:20250202\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn test_split_calendar() {
+        assert_eq!(get_calendar_name(ICS).as_deref(), Some("Holidays"));
+
+        let (mut split, without_uid) = split_calendar(ICS).unwrap();
+        assert_eq!(without_uid, 0);
+        assert_eq!(
+            split
+                .iter()
+                .map(|(uid, _)| uid.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert_eq!(split[0].1.events.len(), 2);
+
+        let (_, cal) = &mut split[0];
+        set_calendar_uid(cal, "c");
+        let ics = cal.generate();
+        assert_eq!(ics.matches("UID:c\r\n").count(), 2);
+        assert!(!ics.contains("X-WR-CALNAME"));
+    }
+
+    #[test]
+    fn test_split_calendar_without_uid() {
+        let (split, without_uid) = split_calendar(&ICS.replace("UID:b\r\n", "")).unwrap();
+        assert_eq!(without_uid, 1);
+        assert_eq!(split.len(), 1);
+        assert_eq!(split[0].0, "a");
+    }
+}
@@ -0,0 +1,440 @@
+use super::{CalendarObject, CalendarObjectComponent, EventObject};
+use crate::Error;
+use ical::{
+    generator::{Emitter, IcalCalendar, IcalEvent},
+    parser::Component,
+    property::Property,
+};
+
+// Properties an attendee includes in a REPLY
+// https://datatracker.ietf.org/doc/html/rfc5546#section-3.2.3
+const REPLY_PROPERTIES: &[&str] = &[
+    "UID",
+    "DTSTAMP",
+    "ORGANIZER",
+    "RECURRENCE-ID",
+    "SEQUENCE",
+    "DTSTART",
+    "DTEND",
+    "DURATION",
+    "SUMMARY",
+];
+
+// https://datatracker.ietf.org/doc/html/rfc5546#section-1.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItipMethod {
+    Request,
+    Reply,
+    Cancel,
+}
+
+impl ItipMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Request => "REQUEST",
+            Self::Reply => "REPLY",
+            Self::Cancel => "CANCEL",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ItipMessage {
+    pub method: ItipMethod,
+    // Calendar user addresses of the recipients
+    pub recipients: Vec<String>,
+    pub ics: String,
+}
+
+fn get_param<'a>(prop: &'a Property, name: &str) -> Option<&'a str> {
+    prop.params
+        .iter()
+        .flatten()
+        .find(|(param, _)| param.eq_ignore_ascii_case(name))
+        .and_then(|(_, values)| values.first())
+        .map(String::as_str)
+}
+
+// Whether the server is responsible for scheduling messages to this calendar user
+// https://datatracker.ietf.org/doc/html/rfc6638#section-7.1
+fn is_server_scheduled(prop: &Property) -> bool {
+    get_param(prop, "SCHEDULE-AGENT").is_none_or(|agent| agent.eq_ignore_ascii_case("SERVER"))
+}
+
+fn set_property(event: &mut IcalEvent, name: &str, value: &str) {
+    event.properties.retain(|prop| prop.name != name);
+    event.properties.push(Property {
+        name: name.to_owned(),
+        params: None,
+        value: Some(value.to_owned()),
+    });
+}
+
+impl EventObject {
+    fn components(&self) -> impl Iterator<Item = &IcalEvent> {
+        std::iter::once(&self.event).chain(&self.overrides)
+    }
+
+    pub fn get_organizer(&self) -> Option<&str> {
+        let organizer = self.event.get_property("ORGANIZER")?;
+        if !is_server_scheduled(organizer) {
+            return None;
+        }
+        organizer.value.as_deref()
+    }
+
+    // The attendees of all instances the server schedules for
+    pub fn get_attendees(&self) -> Vec<&str> {
+        let mut attendees = vec![];
+        for prop in self.components().flat_map(|event| &event.properties) {
+            if prop.name != "ATTENDEE" || !is_server_scheduled(prop) {
+                continue;
+            }
+            if let Some(attendee) = prop.value.as_deref() {
+                if !attendees.contains(&attendee) {
+                    attendees.push(attendee);
+                }
+            }
+        }
+        attendees
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.event
+            .get_property("STATUS")
+            .and_then(|prop| prop.value.as_deref())
+            .is_some_and(|status| status.eq_ignore_ascii_case("CANCELLED"))
+    }
+
+    // PARTSTAT of the matching attendee for every instance
+    fn get_partstats(&self, is_attendee: impl Fn(&str) -> bool) -> Vec<Option<String>> {
+        self.components()
+            .map(|event| {
+                event
+                    .properties
+                    .iter()
+                    .filter(|prop| prop.name == "ATTENDEE")
+                    .find(|prop| prop.value.as_deref().is_some_and(&is_attendee))
+                    .map(|prop| {
+                        get_param(prop, "PARTSTAT")
+                            .unwrap_or("NEEDS-ACTION")
+                            .to_ascii_uppercase()
+                    })
+            })
+            .collect()
+    }
+
+    fn itip_calendar(&self, method: ItipMethod, events: Vec<IcalEvent>) -> String {
+        let property = |name: &str, value: &str| Property {
+            name: name.to_owned(),
+            params: None,
+            value: Some(value.to_owned()),
+        };
+        let mut cal = IcalCalendar::new();
+        cal.properties = vec![
+            property("VERSION", "2.0"),
+            property("PRODID", "-//github.com/lennart-k/rustical//EN"),
+            property("METHOD", method.as_str()),
+        ];
+        cal.timezones = self.timezones.values().cloned().collect();
+        cal.events = events;
+        cal.generate()
+    }
+
+    fn cancel_message(&self, recipients: Vec<String>) -> ItipMessage {
+        let mut event = self.event.clone();
+        event.properties.retain(|prop| {
+            prop.name != "ATTENDEE"
+                || prop
+                    .value
+                    .as_ref()
+                    .is_some_and(|attendee| recipients.contains(attendee))
+        });
+        set_property(&mut event, "STATUS", "CANCELLED");
+        ItipMessage {
+            method: ItipMethod::Cancel,
+            ics: self.itip_calendar(ItipMethod::Cancel, vec![event]),
+            recipients,
+        }
+    }
+
+    // partstat overrides the participation status of the attendee, i.e. to decline
+    fn reply_message(
+        &self,
+        organizer: &str,
+        is_attendee: impl Fn(&str) -> bool,
+        partstat: Option<&str>,
+    ) -> ItipMessage {
+        let events = self
+            .components()
+            .map(|event| {
+                let mut event = event.clone();
+                event.properties.retain(|prop| {
+                    REPLY_PROPERTIES.contains(&prop.name.as_str())
+                        || (prop.name == "ATTENDEE"
+                            && prop.value.as_deref().is_some_and(&is_attendee))
+                });
+                if let Some(partstat) = partstat {
+                    for prop in event
+                        .properties
+                        .iter_mut()
+                        .filter(|prop| prop.name == "ATTENDEE")
+                    {
+                        let mut params = prop.params.take().unwrap_or_default();
+                        params.retain(|(param, _)| !param.eq_ignore_ascii_case("PARTSTAT"));
+                        params.push(("PARTSTAT".to_owned(), vec![partstat.to_owned()]));
+                        prop.params = Some(params);
+                    }
+                }
+                event
+            })
+            .collect();
+        ItipMessage {
+            method: ItipMethod::Reply,
+            recipients: vec![organizer.to_owned()],
+            ics: self.itip_calendar(ItipMethod::Reply, events),
+        }
+    }
+}
+
+fn as_event(object: &CalendarObject) -> Option<&EventObject> {
+    match object.get_data() {
+        CalendarObjectComponent::Event(event) => Some(event),
+        _ => None,
+    }
+}
+
+// Deleting an event cancels it as the organizer and declines it as an attendee
+// https://datatracker.ietf.org/doc/html/rfc6638#section-3.2.2.3
+// https://datatracker.ietf.org/doc/html/rfc6638#section-3.2.3.3
+fn deleted_event_messages(
+    event: &EventObject,
+    is_own_address: impl Fn(&str) -> bool,
+) -> Vec<ItipMessage> {
+    let Some(organizer) = event.get_organizer() else {
+        return vec![];
+    };
+    // The attendees already know about cancelled events
+    if event.is_cancelled() {
+        return vec![];
+    }
+    if is_own_address(organizer) {
+        let attendees: Vec<String> = event
+            .get_attendees()
+            .into_iter()
+            .filter(|attendee| !is_own_address(attendee))
+            .map(str::to_owned)
+            .collect();
+        if attendees.is_empty() {
+            return vec![];
+        }
+        return vec![event.cancel_message(attendees)];
+    }
+    // Nothing to decline if we aren't invited or declined already
+    let declined = event
+        .get_partstats(&is_own_address)
+        .iter()
+        .flatten()
+        .all(|partstat| partstat == "DECLINED");
+    if declined {
+        return vec![];
+    }
+    vec![event.reply_message(organizer, &is_own_address, Some("DECLINED"))]
+}
+
+// Determines the scheduling messages caused by a calendar user writing an object,
+// new is None if the object is deleted
+// https://datatracker.ietf.org/doc/html/rfc6638#section-3.2
+pub fn get_itip_messages(
+    old: Option<&CalendarObject>,
+    new: Option<&CalendarObject>,
+    is_own_address: impl Fn(&str) -> bool,
+) -> Result<Vec<ItipMessage>, Error> {
+    let old_event = old.and_then(as_event);
+    let Some(new) = new else {
+        return Ok(old_event
+            .map(|old| deleted_event_messages(old, &is_own_address))
+            .unwrap_or_default());
+    };
+    let Some(event) = as_event(new) else {
+        return Ok(vec![]);
+    };
+    let Some(organizer) = event.get_organizer() else {
+        return Ok(vec![]);
+    };
+
+    if !is_own_address(organizer) {
+        // As an attendee only changes to our participation status are sent to the organizer
+        let partstats = event.get_partstats(&is_own_address);
+        let old_partstats = old_event
+            .map(|old| old.get_partstats(&is_own_address))
+            .unwrap_or_default();
+        let responded = partstats
+            .iter()
+            .flatten()
+            .any(|partstat| partstat != "NEEDS-ACTION");
+        if !responded || partstats == old_partstats {
+            return Ok(vec![]);
+        }
+        return Ok(vec![event.reply_message(organizer, &is_own_address, None)]);
+    }
+
+    let attendees: Vec<String> = event
+        .get_attendees()
+        .into_iter()
+        .filter(|attendee| !is_own_address(attendee))
+        .map(str::to_owned)
+        .collect();
+    let removed: Vec<String> = old_event
+        .map(EventObject::get_attendees)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|attendee| !is_own_address(attendee) && !attendees.iter().any(|a| a == attendee))
+        .map(str::to_owned)
+        .collect();
+
+    let mut messages = vec![];
+    if !removed.is_empty() {
+        messages.push(event.cancel_message(removed));
+    }
+    if attendees.is_empty() || old.is_some_and(|old| old.get_ics() == new.get_ics()) {
+        return Ok(messages);
+    }
+    if event.is_cancelled() {
+        messages.push(event.cancel_message(attendees));
+    } else {
+        let mut cal = new.get_ical()?;
+        cal.properties.retain(|prop| prop.name != "METHOD");
+        cal.properties.push(Property {
+            name: "METHOD".to_owned(),
+            params: None,
+            value: Some(ItipMethod::Request.as_str().to_owned()),
+        });
+        messages.push(ItipMessage {
+            method: ItipMethod::Request,
+            recipients: attendees,
+            ics: cal.generate(),
+        });
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_itip_messages, ItipMethod};
+    use crate::CalendarObject;
+
+    const ALICE: &str = "/caldav/user/alice";
+    const BOB: &str = "/caldav/user/bob";
+    const CAROL: &str = "/caldav/user/carol";
+
+    fn meeting(attendees: &str, status: &str) -> CalendarObject {
+        CalendarObject::from_ics(
+            "meeting".to_owned(),
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VEVENT\r\nUID:meeting\r\nDTSTAMP:20240101T000000Z\r\nDTSTART:20240101T100000Z\r\nDTEND:20240101T110000Z\r\nSUMMARY:Meeting\r\nSTATUS:{status}\r\nORGANIZER:{ALICE}\r\nATTENDEE;PARTSTAT=ACCEPTED:{ALICE}\r\n{attendees}END:VEVENT\r\nEND:VCALENDAR\r\n"
+            ),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_organizer_messages() {
+        let is_alice = |address: &str| address == ALICE;
+        let invite = meeting(
+            &format!("ATTENDEE:{BOB}\r\nATTENDEE;SCHEDULE-AGENT=CLIENT:{CAROL}\r\n"),
+            "CONFIRMED",
+        );
+        let messages = get_itip_messages(None, Some(&invite), is_alice).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method, ItipMethod::Request);
+        assert_eq!(messages[0].recipients, vec![BOB.to_owned()]);
+        assert!(messages[0].ics.contains("METHOD:REQUEST\r\n"));
+
+        // Unchanged objects don't cause messages
+        assert!(get_itip_messages(Some(&invite), Some(&invite), is_alice)
+            .unwrap()
+            .is_empty());
+
+        let uninvited = meeting("", "CONFIRMED");
+        let messages = get_itip_messages(Some(&invite), Some(&uninvited), is_alice).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method, ItipMethod::Cancel);
+        assert_eq!(messages[0].recipients, vec![BOB.to_owned()]);
+        assert!(messages[0].ics.contains("STATUS:CANCELLED\r\n"));
+
+        let cancelled = meeting(&format!("ATTENDEE:{BOB}\r\n"), "CANCELLED");
+        let messages = get_itip_messages(Some(&invite), Some(&cancelled), is_alice).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method, ItipMethod::Cancel);
+    }
+
+    #[test]
+    fn test_attendee_reply() {
+        let is_bob = |address: &str| address == BOB;
+        let invite = meeting(
+            &format!("ATTENDEE;PARTSTAT=NEEDS-ACTION:{BOB}\r\n"),
+            "CONFIRMED",
+        );
+        assert!(get_itip_messages(None, Some(&invite), is_bob)
+            .unwrap()
+            .is_empty());
+
+        let accepted = meeting(
+            &format!("ATTENDEE;PARTSTAT=ACCEPTED:{BOB}\r\n"),
+            "CONFIRMED",
+        );
+        let messages = get_itip_messages(Some(&invite), Some(&accepted), is_bob).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method, ItipMethod::Reply);
+        assert_eq!(messages[0].recipients, vec![ALICE.to_owned()]);
+        assert!(messages[0]
+            .ics
+            .contains(&format!("ATTENDEE;PARTSTAT=ACCEPTED:{BOB}\r\n")));
+        assert!(!messages[0].ics.contains("STATUS:"));
+        assert!(!messages[0]
+            .ics
+            .contains(&format!("ATTENDEE;PARTSTAT=ACCEPTED:{ALICE}")));
+    }
+
+    #[test]
+    fn test_delete_messages() {
+        let is_alice = |address: &str| address == ALICE;
+        let is_bob = |address: &str| address == BOB;
+        let invite = meeting(
+            &format!("ATTENDEE;PARTSTAT=ACCEPTED:{BOB}\r\n"),
+            "CONFIRMED",
+        );
+
+        let messages = get_itip_messages(Some(&invite), None, is_alice).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method, ItipMethod::Cancel);
+        assert_eq!(messages[0].recipients, vec![BOB.to_owned()]);
+
+        let messages = get_itip_messages(Some(&invite), None, is_bob).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method, ItipMethod::Reply);
+        assert_eq!(messages[0].recipients, vec![ALICE.to_owned()]);
+        assert!(messages[0]
+            .ics
+            .contains(&format!("ATTENDEE;PARTSTAT=DECLINED:{BOB}\r\n")));
+
+        // Cancelled or already declined events don't cause messages
+        let cancelled = meeting(&format!("ATTENDEE:{BOB}\r\n"), "CANCELLED");
+        assert!(get_itip_messages(Some(&cancelled), None, is_alice)
+            .unwrap()
+            .is_empty());
+        let declined = meeting(
+            &format!("ATTENDEE;PARTSTAT=DECLINED:{BOB}\r\n"),
+            "CONFIRMED",
+        );
+        assert!(get_itip_messages(Some(&declined), None, is_bob)
+            .unwrap()
+            .is_empty());
+        assert!(
+            get_itip_messages(Some(&invite), None, |address| address == CAROL)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,6 +1,31 @@
-use ical::parser::ical::component::IcalJournal;
+use super::CalDateTime;
+use crate::Error;
+use chrono::Duration;
+use ical::parser::ical::component::{IcalJournal, IcalTimeZone};
+use ical::parser::Component;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct JournalObject {
     pub journal: IcalJournal,
+    pub(crate) timezones: HashMap<String, IcalTimeZone>,
+}
+
+impl JournalObject {
+    pub fn get_first_occurence(&self) -> Result<Option<CalDateTime>, Error> {
+        self.journal
+            .get_property("DTSTART")
+            .map(|prop| CalDateTime::parse_prop(prop, &self.timezones))
+            .transpose()
+            .map(Option::flatten)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc4791#section-9.9
+    // An entry for a date lasts the whole day
+    pub fn get_last_occurence(&self) -> Result<Option<CalDateTime>, Error> {
+        Ok(self.get_first_occurence()?.map(|dtstart| match dtstart {
+            CalDateTime::Date(_) => dtstart + Duration::days(1),
+            dtstart => dtstart,
+        }))
+    }
 }
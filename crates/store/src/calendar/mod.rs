@@ -1,13 +1,28 @@
+#[allow(clippy::module_inception)]
 mod calendar;
 mod event;
+mod export;
+mod feed;
+mod freebusy;
+mod import;
+mod itip;
 mod journal;
 mod object;
+mod rrule;
 mod timestamp;
 mod todo;
+mod webcal;
 
 pub use calendar::*;
 pub use event::*;
+pub use export::*;
+pub use feed::*;
+pub use freebusy::*;
+pub use import::*;
+pub use itip::*;
 pub use journal::*;
 pub use object::*;
+pub use rrule::*;
 pub use timestamp::*;
 pub use todo::*;
+pub use webcal::*;
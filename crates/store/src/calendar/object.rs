@@ -1,10 +1,17 @@
 use super::{CalDateTime, EventObject, JournalObject, TodoObject};
 use crate::Error;
 use anyhow::Result;
-use ical::parser::{ical::component::IcalTimeZone, Component};
+use chrono::{DateTime, Utc};
+use ical::{
+    generator::{Emitter, IcalCalendar},
+    parser::{ical::component::IcalTimeZone, Component},
+};
 use serde::Serialize;
 use sha2::{Digest, Sha256};
-use std::{collections::HashMap, io::BufReader};
+use std::{
+    collections::{HashMap, HashSet},
+    io::BufReader,
+};
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
 // specified in https://datatracker.ietf.org/doc/html/rfc5545#section-3.6
@@ -39,6 +46,28 @@ impl rustical_xml::ValueDeserialize for CalendarObjectType {
     }
 }
 
+// Parses a comma-separated component set like VEVENT,VTODO
+pub fn parse_components(components: &str) -> Vec<CalendarObjectType> {
+    components
+        .split(',')
+        .filter_map(|component| {
+            <CalendarObjectType as rustical_xml::ValueDeserialize>::deserialize(component.trim())
+                .ok()
+        })
+        .collect()
+}
+
+// Maps TZIDs to their VTIMEZONE components
+pub fn get_timezones(cal: &IcalCalendar) -> HashMap<String, IcalTimeZone> {
+    cal.timezones
+        .iter()
+        .filter_map(|timezone| {
+            let tzid = timezone.get_property("TZID")?.value.to_owned()?;
+            Some((tzid, timezone.to_owned()))
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone)]
 pub enum CalendarObjectComponent {
     Event(EventObject),
@@ -62,7 +91,37 @@ impl CalendarObject {
                 "multiple calendars, only one allowed".to_owned(),
             ));
         }
-        if cal.events.len()
+        // Multiple VEVENTs are allowed if they are instances of the same recurring event
+        // https://datatracker.ietf.org/doc/html/rfc4791#section-4.1
+        let (mut events, overrides): (Vec<_>, Vec<_>) = cal
+            .events
+            .iter()
+            .cloned()
+            .partition(|event| event.get_property("RECURRENCE-ID").is_none());
+        let uids: HashSet<_> = cal
+            .events
+            .iter()
+            .map(|event| {
+                event
+                    .get_property("UID")
+                    .and_then(|prop| prop.value.to_owned())
+            })
+            .collect();
+        if uids.len() > 1 || events.len() > 1 {
+            return Err(Error::InvalidData(
+                "iCalendar object is only allowed to have exactly one component".to_owned(),
+            ));
+        }
+        // An object may also consist only of overridden instances
+        let (event, overrides) = match events.pop() {
+            Some(event) => (Some(event), overrides),
+            None => {
+                let mut overrides = overrides.into_iter();
+                (overrides.next(), overrides.collect())
+            }
+        };
+
+        if event.iter().len()
             + cal.alarms.len()
             + cal.todos.len()
             + cal.journals.len()
@@ -75,23 +134,15 @@ impl CalendarObject {
             ));
         }
 
-        let timezones: HashMap<String, IcalTimeZone> = cal
-            .timezones
-            .clone()
-            .into_iter()
-            .filter_map(|timezone| {
-                let timezone_prop = timezone.get_property("TZID")?.to_owned();
-                let tzid = timezone_prop.value?;
-                Some((tzid, timezone))
-            })
-            .collect();
+        let timezones = get_timezones(&cal);
 
-        if let Some(event) = cal.events.first() {
+        if let Some(event) = event {
             return Ok(CalendarObject {
                 id: object_id,
                 ics,
                 data: CalendarObjectComponent::Event(EventObject {
-                    event: event.clone(),
+                    event,
+                    overrides,
                     timezones,
                 }),
             });
@@ -100,7 +151,10 @@ impl CalendarObject {
             return Ok(CalendarObject {
                 id: object_id,
                 ics,
-                data: CalendarObjectComponent::Todo(TodoObject { todo: todo.clone() }),
+                data: CalendarObjectComponent::Todo(TodoObject {
+                    todo: todo.clone(),
+                    timezones,
+                }),
             });
         }
         if let Some(journal) = cal.journals.first() {
@@ -109,6 +163,7 @@ impl CalendarObject {
                 ics,
                 data: CalendarObjectComponent::Journal(JournalObject {
                     journal: journal.clone(),
+                    timezones,
                 }),
             });
         }
@@ -121,17 +176,33 @@ impl CalendarObject {
     pub fn get_id(&self) -> &str {
         &self.id
     }
+
+    pub fn get_uid(&self) -> Option<&str> {
+        let uid = match &self.data {
+            CalendarObjectComponent::Event(event) => event.event.get_property("UID"),
+            CalendarObjectComponent::Todo(todo) => todo.todo.get_property("UID"),
+            CalendarObjectComponent::Journal(journal) => journal.journal.get_property("UID"),
+        };
+        uid.and_then(|prop| prop.value.as_deref())
+    }
+
     pub fn get_etag(&self) -> String {
         let mut hasher = Sha256::new();
         hasher.update(&self.id);
         hasher.update(self.get_ics());
-        format!("{:x}", hasher.finalize())
+        // Entity tags are quoted strings
+        // https://datatracker.ietf.org/doc/html/rfc7232#section-2.3
+        format!("\"{:x}\"", hasher.finalize())
     }
 
     pub fn get_ics(&self) -> &str {
         &self.ics
     }
 
+    pub fn get_data(&self) -> &CalendarObjectComponent {
+        &self.data
+    }
+
     pub fn get_component_name(&self) -> &str {
         match self.data {
             CalendarObjectComponent::Todo(_) => "VTODO",
@@ -148,17 +219,83 @@ impl CalendarObject {
         }
     }
 
+    pub fn get_ical(&self) -> Result<IcalCalendar, Error> {
+        ical::IcalParser::new(BufReader::new(self.ics.as_bytes()))
+            .next()
+            .ok_or(Error::NotFound)?
+            .map_err(Error::from)
+    }
+
+    // Returns the iCalendar data with recurring events expanded to instances in [start, end)
+    pub fn expand_recurrence(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<String, Error> {
+        let CalendarObjectComponent::Event(event) = &self.data else {
+            return Ok(self.ics.to_owned());
+        };
+        let mut cal = self.get_ical()?;
+        // Expanded instances only use UTC
+        cal.timezones = vec![];
+        cal.events = event.expand_recurrence(start, end)?;
+        Ok(cal.generate())
+    }
+
+    // Returns the iCalendar data with only the overridden instances in [start, end)
+    pub fn limit_recurrence_set(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<String, Error> {
+        let CalendarObjectComponent::Event(event) = &self.data else {
+            return Ok(self.ics.to_owned());
+        };
+        let mut cal = self.get_ical()?;
+        cal.events = event.limit_recurrence_set(start, end)?;
+        Ok(cal.generate())
+    }
+
+    // Whether any instance of the object overlaps with [start, end)
+    pub fn occurs_between(&self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> bool {
+        if let CalendarObjectComponent::Event(event) = &self.data {
+            // Recurring events match if any of their instances overlaps with the range,
+            // events we cannot expand might match
+            return event
+                .get_occurences(start, end)
+                .map(|occurences| !occurences.is_empty())
+                .unwrap_or(true);
+        }
+        if let Some(start) = start {
+            if let Some(last_occurence) = self.get_last_occurence().unwrap_or(None) {
+                if start > last_occurence.utc() {
+                    return false;
+                }
+            };
+        }
+        if let Some(end) = end {
+            if let Some(first_occurence) = self.get_first_occurence().unwrap_or(None) {
+                if end < first_occurence.utc() {
+                    return false;
+                }
+            };
+        }
+        true
+    }
+
     pub fn get_first_occurence(&self) -> Result<Option<CalDateTime>, Error> {
         match &self.data {
             CalendarObjectComponent::Event(event) => event.get_first_occurence(),
-            _ => Ok(None),
+            CalendarObjectComponent::Todo(todo) => todo.get_first_occurence(),
+            CalendarObjectComponent::Journal(journal) => journal.get_first_occurence(),
         }
     }
 
     pub fn get_last_occurence(&self) -> Result<Option<CalDateTime>, Error> {
         match &self.data {
             CalendarObjectComponent::Event(event) => event.get_last_occurence(),
-            _ => Ok(None),
+            CalendarObjectComponent::Todo(todo) => todo.get_last_occurence(),
+            CalendarObjectComponent::Journal(journal) => journal.get_last_occurence(),
         }
     }
 }
@@ -0,0 +1,828 @@
+use super::CalDateTime;
+use crate::Error;
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc,
+    Weekday,
+};
+use std::{collections::VecDeque, str::FromStr};
+
+// Upper bound of periods we check in a single expansion. This also ends rules that never produce
+// an instance (e.g. FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30)
+const MAX_CHECKED_PERIODS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RecurrenceFrequency {
+    Secondly,
+    Minutely,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl FromStr for RecurrenceFrequency {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "SECONDLY" => Self::Secondly,
+            "MINUTELY" => Self::Minutely,
+            "HOURLY" => Self::Hourly,
+            "DAILY" => Self::Daily,
+            "WEEKLY" => Self::Weekly,
+            "MONTHLY" => Self::Monthly,
+            "YEARLY" => Self::Yearly,
+            _ => {
+                return Err(Error::InvalidData(format!(
+                    "Invalid recurrence frequency {value}"
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecurrenceLimit {
+    Count(usize),
+    Until(CalDateTime),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// https://datatracker.ietf.org/doc/html/rfc5545#section-3.3.10
+// Empty BY* vectors mean that the rule part is not specified
+pub struct RecurrenceRule {
+    pub frequency: RecurrenceFrequency,
+    pub limit: Option<RecurrenceLimit>,
+    pub interval: u32,
+    pub by_second: Vec<u32>,
+    pub by_minute: Vec<u32>,
+    pub by_hour: Vec<u32>,
+    // (nth occurence of weekday within month/year, weekday)
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    pub by_month_day: Vec<i32>,
+    pub by_year_day: Vec<i32>,
+    pub by_week_no: Vec<i32>,
+    pub by_month: Vec<u32>,
+    pub by_set_pos: Vec<i32>,
+    pub week_start: Weekday,
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, Error> {
+    Ok(match value {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return Err(Error::InvalidData(format!("Invalid weekday {value}"))),
+    })
+}
+
+fn parse_list<T: FromStr>(
+    value: &str,
+    name: &str,
+    valid: impl Fn(&T) -> bool,
+) -> Result<Vec<T>, Error> {
+    value
+        .split(',')
+        .map(|item| {
+            item.trim_start_matches('+')
+                .parse::<T>()
+                .ok()
+                .filter(&valid)
+                .ok_or(Error::InvalidData(format!(
+                    "Invalid value {item} for {name}"
+                )))
+        })
+        .collect()
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = Error;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let mut frequency = None;
+        let mut limit = None;
+        let mut interval = 1;
+        let mut by_second = vec![];
+        let mut by_minute = vec![];
+        let mut by_hour = vec![];
+        let mut by_day = vec![];
+        let mut by_month_day = vec![];
+        let mut by_year_day = vec![];
+        let mut by_week_no = vec![];
+        let mut by_month = vec![];
+        let mut by_set_pos = vec![];
+        let mut week_start = Weekday::Mon;
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or(Error::InvalidData(format!("Invalid RRULE part {part}")))?;
+            // Enumerated values are case-insensitive as well
+            let value = value.to_uppercase();
+            let value = value.as_str();
+            match name.to_uppercase().as_str() {
+                "FREQ" => frequency = Some(value.parse()?),
+                "COUNT" => {
+                    let count = value
+                        .parse()
+                        .map_err(|_| Error::InvalidData(format!("Invalid COUNT {value}")))?;
+                    limit = Some(RecurrenceLimit::Count(count));
+                }
+                "UNTIL" => limit = Some(RecurrenceLimit::Until(CalDateTime::parse(value, None)?)),
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or(Error::InvalidData(format!("Invalid INTERVAL {value}")))?;
+                }
+                "BYSECOND" => by_second = parse_list(value, name, |x| *x <= 60)?,
+                "BYMINUTE" => by_minute = parse_list(value, name, |x| *x <= 59)?,
+                "BYHOUR" => by_hour = parse_list(value, name, |x| *x <= 23)?,
+                "BYMONTHDAY" => {
+                    by_month_day = parse_list(value, name, |x: &i32| (1..=31).contains(&x.abs()))?
+                }
+                "BYYEARDAY" => {
+                    by_year_day = parse_list(value, name, |x: &i32| (1..=366).contains(&x.abs()))?
+                }
+                "BYWEEKNO" => {
+                    by_week_no = parse_list(value, name, |x: &i32| (1..=53).contains(&x.abs()))?
+                }
+                "BYMONTH" => by_month = parse_list(value, name, |x| (1..=12).contains(x))?,
+                "BYSETPOS" => {
+                    by_set_pos = parse_list(value, name, |x: &i32| (1..=366).contains(&x.abs()))?
+                }
+                "WKST" => week_start = parse_weekday(value)?,
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(|item| {
+                            let (nth, weekday) = item.split_at(item.len().saturating_sub(2));
+                            let nth = if nth.is_empty() {
+                                None
+                            } else {
+                                Some(
+                                    nth.trim_start_matches('+')
+                                        .parse::<i32>()
+                                        .ok()
+                                        .filter(|nth| (1..=53).contains(&nth.abs()))
+                                        .ok_or(Error::InvalidData(format!(
+                                            "Invalid BYDAY value {item}"
+                                        )))?,
+                                )
+                            };
+                            Ok((nth, parse_weekday(weekday)?))
+                        })
+                        .collect::<Result<_, Error>>()?
+                }
+                // Ignore unknown rule parts
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            frequency: frequency.ok_or(Error::InvalidData("RRULE without FREQ".to_owned()))?,
+            limit,
+            interval,
+            by_second,
+            by_minute,
+            by_hour,
+            by_day,
+            by_month_day,
+            by_year_day,
+            by_week_no,
+            by_month,
+            by_set_pos,
+            week_start,
+        })
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|next| next.pred_opt())
+        .map(|last| last.day())
+        .unwrap_or(31)
+}
+
+fn days_in_year(year: i32) -> u32 {
+    if NaiveDate::from_ymd_opt(year, 2, 29).is_some() {
+        366
+    } else {
+        365
+    }
+}
+
+// First day of week 1 where week 1 is the first week containing at least four days of the year
+fn first_week_start(year: i32, week_start: Weekday) -> NaiveDate {
+    let jan1 = NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or_default();
+    let offset = (7 + jan1.weekday().num_days_from_monday() as i64
+        - week_start.num_days_from_monday() as i64)
+        % 7;
+    if offset <= 3 {
+        jan1 - Duration::days(offset)
+    } else {
+        jan1 + Duration::days(7 - offset)
+    }
+}
+
+// Returns (week number, number of weeks in that week-numbering year)
+fn week_number(date: NaiveDate, week_start: Weekday) -> (i32, i32) {
+    let mut year = date.year();
+    if date >= first_week_start(year + 1, week_start) {
+        year += 1;
+    } else if date < first_week_start(year, week_start) {
+        year -= 1;
+    }
+    let start = first_week_start(year, week_start);
+    let weeks = (first_week_start(year + 1, week_start) - start).num_days() / 7;
+    ((date - start).num_days() as i32 / 7 + 1, weeks as i32)
+}
+
+impl RecurrenceRule {
+    // Fills in the implicit rule parts that come from DTSTART, e.g. FREQ=MONTHLY repeats on the
+    // DTSTART's day of month
+    fn normalized(&self, dtstart: &NaiveDateTime) -> Self {
+        let mut rule = self.clone();
+        if rule.by_week_no.is_empty()
+            && rule.by_year_day.is_empty()
+            && rule.by_month_day.is_empty()
+            && rule.by_day.is_empty()
+        {
+            match rule.frequency {
+                RecurrenceFrequency::Yearly => {
+                    if rule.by_month.is_empty() {
+                        rule.by_month = vec![dtstart.month()];
+                    }
+                    rule.by_month_day = vec![dtstart.day() as i32];
+                }
+                RecurrenceFrequency::Monthly => {
+                    rule.by_month_day = vec![dtstart.day() as i32];
+                }
+                RecurrenceFrequency::Weekly => {
+                    rule.by_day = vec![(None, dtstart.weekday())];
+                }
+                _ => {}
+            }
+        }
+        if rule.frequency > RecurrenceFrequency::Hourly && rule.by_hour.is_empty() {
+            rule.by_hour = vec![dtstart.hour()];
+        }
+        if rule.frequency > RecurrenceFrequency::Minutely && rule.by_minute.is_empty() {
+            rule.by_minute = vec![dtstart.minute()];
+        }
+        if rule.frequency > RecurrenceFrequency::Secondly && rule.by_second.is_empty() {
+            rule.by_second = vec![dtstart.second()];
+        }
+        rule
+    }
+
+    fn matches_day(&self, date: &NaiveDate) -> bool {
+        if !self.by_month.is_empty() && !self.by_month.contains(&date.month()) {
+            return false;
+        }
+        if !self.by_week_no.is_empty() {
+            let (week, weeks) = week_number(*date, self.week_start);
+            if !self.by_week_no.contains(&week) && !self.by_week_no.contains(&(week - weeks - 1)) {
+                return false;
+            }
+        }
+        if !self.by_year_day.is_empty() {
+            let day = date.ordinal() as i32;
+            let negative = day - days_in_year(date.year()) as i32 - 1;
+            if !self.by_year_day.contains(&day) && !self.by_year_day.contains(&negative) {
+                return false;
+            }
+        }
+        if !self.by_month_day.is_empty() {
+            let day = date.day() as i32;
+            let negative = day - days_in_month(date.year(), date.month()) as i32 - 1;
+            if !self.by_month_day.contains(&day) && !self.by_month_day.contains(&negative) {
+                return false;
+            }
+        }
+        if !self.by_day.is_empty() {
+            // nth weekdays are only meaningful for MONTHLY and YEARLY rules
+            let (position, count) = match self.frequency {
+                RecurrenceFrequency::Monthly => (
+                    date.day() as i32,
+                    days_in_month(date.year(), date.month()) as i32,
+                ),
+                RecurrenceFrequency::Yearly if self.by_month.is_empty() => {
+                    (date.ordinal() as i32, days_in_year(date.year()) as i32)
+                }
+                RecurrenceFrequency::Yearly => (
+                    date.day() as i32,
+                    days_in_month(date.year(), date.month()) as i32,
+                ),
+                _ => (0, 0),
+            };
+            let nth = (position - 1) / 7 + 1;
+            let nth_last = -((count - position) / 7 + 1);
+            if !self.by_day.iter().any(|(n, weekday)| {
+                *weekday == date.weekday()
+                    && match n {
+                        Some(n) if count > 0 => *n == nth || *n == nth_last,
+                        _ => true,
+                    }
+            }) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn period_days(&self, dtstart: &NaiveDateTime, period: i64) -> Vec<NaiveDate> {
+        let interval = self.interval as i64;
+        let start = dtstart.date();
+        match self.frequency {
+            RecurrenceFrequency::Yearly => {
+                let year = start.year() + (period * interval) as i32;
+                let first = NaiveDate::from_ymd_opt(year, 1, 1);
+                first
+                    .map(|first| first.iter_days().take_while(|d| d.year() == year).collect())
+                    .unwrap_or_default()
+            }
+            RecurrenceFrequency::Monthly => {
+                let month_index =
+                    start.year() as i64 * 12 + start.month0() as i64 + period * interval;
+                let (year, month) = ((month_index / 12) as i32, (month_index % 12) as u32 + 1);
+                NaiveDate::from_ymd_opt(year, month, 1)
+                    .map(|first| {
+                        first
+                            .iter_days()
+                            .take_while(|d| d.month() == month)
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            }
+            RecurrenceFrequency::Weekly => {
+                let offset = (7 + start.weekday().num_days_from_monday() as i64
+                    - self.week_start.num_days_from_monday() as i64)
+                    % 7;
+                let week = start - Duration::days(offset) + Duration::weeks(period * interval);
+                week.iter_days().take(7).collect()
+            }
+            RecurrenceFrequency::Daily => vec![start + Duration::days(period * interval)],
+            // Sub-daily frequencies are handled in period_instances
+            _ => vec![],
+        }
+    }
+
+    fn period_instances(&self, dtstart: &NaiveDateTime, period: i64) -> Vec<NaiveDateTime> {
+        let mut instances: Vec<NaiveDateTime> = match self.frequency {
+            RecurrenceFrequency::Hourly
+            | RecurrenceFrequency::Minutely
+            | RecurrenceFrequency::Secondly => {
+                let time = *dtstart + Duration::seconds(self.period_seconds() * period);
+                if !self.matches_day(&time.date())
+                    || (!self.by_hour.is_empty() && !self.by_hour.contains(&time.hour()))
+                    || (self.frequency < RecurrenceFrequency::Hourly
+                        && !self.by_minute.is_empty()
+                        && !self.by_minute.contains(&time.minute()))
+                    || (self.frequency < RecurrenceFrequency::Minutely
+                        && !self.by_second.is_empty()
+                        && !self.by_second.contains(&time.second()))
+                {
+                    vec![]
+                } else {
+                    let minutes = if self.frequency == RecurrenceFrequency::Hourly {
+                        self.by_minute.clone()
+                    } else {
+                        vec![time.minute()]
+                    };
+                    let seconds = if self.frequency == RecurrenceFrequency::Secondly {
+                        vec![time.second()]
+                    } else {
+                        self.by_second.clone()
+                    };
+                    minutes
+                        .iter()
+                        .flat_map(|minute| seconds.iter().map(move |second| (*minute, *second)))
+                        .filter_map(|(minute, second)| {
+                            time.date().and_hms_opt(time.hour(), minute, second.min(59))
+                        })
+                        .collect()
+                }
+            }
+            _ => self
+                .period_days(dtstart, period)
+                .into_iter()
+                .filter(|date| self.matches_day(date))
+                .flat_map(|date| {
+                    self.by_hour.iter().flat_map(move |hour| {
+                        self.by_minute.iter().flat_map(move |minute| {
+                            self.by_second.iter().filter_map(move |second| {
+                                NaiveTime::from_hms_opt(*hour, *minute, (*second).min(59))
+                                    .map(|time| date.and_time(time))
+                            })
+                        })
+                    })
+                })
+                .collect(),
+        };
+        instances.sort();
+        instances.dedup();
+
+        if self.by_set_pos.is_empty() {
+            return instances;
+        }
+        let len = instances.len() as i32;
+        let mut selected: Vec<_> = self
+            .by_set_pos
+            .iter()
+            .filter_map(|pos| {
+                let index = if *pos > 0 { pos - 1 } else { len + pos };
+                (0..len).contains(&index).then(|| instances[index as usize])
+            })
+            .collect();
+        selected.sort();
+        selected.dedup();
+        selected
+    }
+
+    // Length of a period of a sub-daily rule
+    fn period_seconds(&self) -> i64 {
+        let step = match self.frequency {
+            RecurrenceFrequency::Hourly => 3600,
+            RecurrenceFrequency::Minutely => 60,
+            _ => 1,
+        };
+        step * self.interval as i64
+    }
+
+    // First period of a sub-daily rule starting at or after time
+    fn period_at(&self, dtstart: &NaiveDateTime, time: NaiveDateTime) -> i64 {
+        let seconds = (time - *dtstart).num_seconds();
+        (seconds + self.period_seconds() - 1).div_euclid(self.period_seconds())
+    }
+
+    // The next period that can contain an instance. Sub-daily rules skip whole days and hours
+    // that are excluded by the BY* rule parts.
+    fn next_period(&self, dtstart: &NaiveDateTime, period: i64) -> i64 {
+        if self.frequency > RecurrenceFrequency::Hourly {
+            return period + 1;
+        }
+        let time = *dtstart + Duration::seconds(self.period_seconds() * period);
+        let skip_to = if !self.matches_day(&time.date()) {
+            time.date()
+                .succ_opt()
+                .map(|date| date.and_time(NaiveTime::default()))
+        } else if self.frequency < RecurrenceFrequency::Hourly
+            && !self.by_hour.is_empty()
+            && !self.by_hour.contains(&time.hour())
+        {
+            time.with_minute(0)
+                .and_then(|time| time.with_second(0))
+                .map(|time| time + Duration::hours(1))
+        } else {
+            None
+        };
+        skip_to
+            .map(|skip_to| self.period_at(dtstart, skip_to))
+            .unwrap_or_default()
+            .max(period + 1)
+    }
+
+    // Lower bound for the year a period starts in
+    fn period_year(&self, dtstart: &NaiveDateTime, period: i64) -> i64 {
+        let interval = self.interval as i64;
+        let year = dtstart.year() as i64;
+        match self.frequency {
+            RecurrenceFrequency::Yearly => year + period * interval,
+            RecurrenceFrequency::Monthly => {
+                year + (dtstart.month0() as i64 + period * interval) / 12
+            }
+            RecurrenceFrequency::Weekly => year + period * interval * 7 / 366,
+            RecurrenceFrequency::Daily => year + period * interval / 366,
+            _ => year + period * self.period_seconds() / (366 * 86400),
+        }
+    }
+
+    // A period that ends before the given local time. Starting there skips all instances
+    // before it, which is only possible if the rule has no COUNT.
+    fn period_before(&self, dtstart: &NaiveDateTime, time: NaiveDateTime) -> i64 {
+        let interval = self.interval as i64;
+        let period = match self.frequency {
+            RecurrenceFrequency::Yearly => (time.year() - dtstart.year()) as i64 / interval,
+            RecurrenceFrequency::Monthly => {
+                let months = (time.year() - dtstart.year()) as i64 * 12 + time.month() as i64
+                    - dtstart.month() as i64;
+                months / interval
+            }
+            RecurrenceFrequency::Weekly => (time - *dtstart).num_weeks() / interval,
+            RecurrenceFrequency::Daily => (time - *dtstart).num_days() / interval,
+            _ => (time - *dtstart).num_seconds() / self.period_seconds(),
+        };
+        (period - 1).max(0)
+    }
+
+    // Iterates over the instances of this rule in the local time of DTSTART, starting at the
+    // given period.
+    // COUNT is respected but UNTIL is left to the caller since it has to be compared in the
+    // timezone of DTSTART.
+    fn iter_local(&self, dtstart: NaiveDateTime, period: i64) -> RecurrenceIter {
+        RecurrenceIter {
+            rule: self.normalized(&dtstart),
+            dtstart,
+            period,
+            checked_periods: 0,
+            truncated: false,
+            emitted: 0,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    // Returns the instances of this rule in the timezone of dtstart that start in [start, end),
+    // at most limit instances.
+    // The second value is true if the expansion was cut short by the limit or because too many
+    // periods had to be checked.
+    pub fn between(
+        &self,
+        dtstart: &CalDateTime,
+        start: Option<&DateTime<Utc>>,
+        end: Option<&DateTime<Utc>>,
+        limit: Option<usize>,
+    ) -> (Vec<CalDateTime>, bool) {
+        let until = match &self.limit {
+            Some(RecurrenceLimit::Until(until)) => Some(until.utc()),
+            _ => None,
+        };
+        let until_date = match &self.limit {
+            Some(RecurrenceLimit::Until(until)) => Some(until.date()),
+            _ => None,
+        };
+
+        let local_dtstart = dtstart.naive_local();
+        let first_period = match (start, &self.limit) {
+            (_, Some(RecurrenceLimit::Count(_))) | (None, _) => 0,
+            // A day of margin covers any UTC offset of dtstart
+            (Some(start), _) => {
+                self.period_before(&local_dtstart, start.naive_utc() - Duration::days(1))
+            }
+        };
+
+        let mut instances = vec![];
+        let mut iter = self.iter_local(local_dtstart, first_period);
+        let mut truncated = false;
+        for local in iter.by_ref() {
+            let Some(instance) = dtstart.with_naive_local(local) else {
+                continue;
+            };
+            let is_after_until = match (&instance, until, until_date) {
+                (CalDateTime::Date(date), _, Some(until_date)) => date > &until_date,
+                (instance, Some(until), _) => instance.utc() > until,
+                _ => false,
+            };
+            if is_after_until {
+                break;
+            }
+            if end.is_some_and(|end| &instance.utc() >= end) {
+                break;
+            }
+            if start.is_some_and(|start| &instance.utc() < start) {
+                continue;
+            }
+            if limit.is_some_and(|limit| instances.len() >= limit) {
+                truncated = true;
+                break;
+            }
+            instances.push(instance);
+        }
+        (instances, truncated || iter.truncated)
+    }
+}
+
+struct RecurrenceIter {
+    rule: RecurrenceRule,
+    dtstart: NaiveDateTime,
+    period: i64,
+    checked_periods: usize,
+    // Set if we stopped because of MAX_CHECKED_PERIODS
+    truncated: bool,
+    emitted: usize,
+    buffer: VecDeque<NaiveDateTime>,
+}
+
+impl Iterator for RecurrenceIter {
+    type Item = NaiveDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(RecurrenceLimit::Count(count)) = self.rule.limit {
+            if self.emitted >= count {
+                return None;
+            }
+        }
+        while self.buffer.is_empty() {
+            if self.rule.period_year(&self.dtstart, self.period) >= 9999 {
+                return None;
+            }
+            if self.checked_periods >= MAX_CHECKED_PERIODS {
+                self.truncated = true;
+                return None;
+            }
+            let instances = self.rule.period_instances(&self.dtstart, self.period);
+            self.period = self.rule.next_period(&self.dtstart, self.period);
+            self.checked_periods += 1;
+            self.buffer.extend(
+                instances
+                    .into_iter()
+                    .filter(|instance| instance >= &self.dtstart),
+            );
+            if self.buffer.back().is_some_and(|last| last.year() >= 9999) {
+                return None;
+            }
+        }
+        self.emitted += 1;
+        self.buffer.pop_front()
+    }
+}
+
+impl CalDateTime {
+    // The wall clock time in this value's timezone
+    pub fn naive_local(&self) -> NaiveDateTime {
+        match self {
+            Self::Local(datetime) => datetime.to_owned(),
+            Self::Utc(datetime) => datetime.naive_utc(),
+            Self::OlsonTZ(datetime) => datetime.naive_local(),
+            Self::Date(date) => date.and_time(NaiveTime::default()),
+        }
+    }
+
+    // Creates a value with the same kind and timezone as self
+    pub fn with_naive_local(&self, local: NaiveDateTime) -> Option<Self> {
+        Some(match self {
+            Self::Local(_) => Self::Local(local),
+            Self::Utc(_) => Self::Utc(local.and_utc()),
+            Self::OlsonTZ(datetime) => {
+                let tz = datetime.timezone();
+                // Nonexistent local times (DST gaps) are shifted by the length of the gap
+                Self::OlsonTZ(tz.from_local_datetime(&local).earliest().or_else(|| {
+                    tz.from_local_datetime(&(local + Duration::hours(1)))
+                        .earliest()
+                })?)
+            }
+            Self::Date(_) => Self::Date(local.date()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecurrenceFrequency, RecurrenceLimit, RecurrenceRule};
+    use crate::calendar::CalDateTime;
+    use chrono::Weekday;
+
+    fn expand(rule: &str, dtstart: &str, limit: usize) -> Vec<String> {
+        let rule: RecurrenceRule = rule.parse().unwrap();
+        let dtstart = CalDateTime::parse(dtstart, None).unwrap();
+        rule.between(&dtstart, None, None, Some(limit))
+            .0
+            .iter()
+            .map(CalDateTime::format)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_rrule() {
+        let rule: RecurrenceRule = "FREQ=MONTHLY;INTERVAL=2;BYDAY=-1FR,2MO;COUNT=10;WKST=SU"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.frequency, RecurrenceFrequency::Monthly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(rule.limit, Some(RecurrenceLimit::Count(10)));
+        assert_eq!(
+            rule.by_day,
+            vec![(Some(-1), Weekday::Fri), (Some(2), Weekday::Mon)]
+        );
+        assert_eq!(rule.week_start, Weekday::Sun);
+
+        let rule: RecurrenceRule = "freq=weekly;byday=mo,we;until=20240201t000000z"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.frequency, RecurrenceFrequency::Weekly);
+        assert_eq!(
+            rule.by_day,
+            vec![(None, Weekday::Mon), (None, Weekday::Wed)]
+        );
+        assert!(matches!(rule.limit, Some(RecurrenceLimit::Until(_))));
+
+        assert!("INTERVAL=2".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=DAILY;BYMONTH=13".parse::<RecurrenceRule>().is_err());
+    }
+
+    #[test]
+    fn test_expand_daily() {
+        assert_eq!(
+            expand("FREQ=DAILY;COUNT=3", "19970902T090000", 10),
+            vec!["19970902T090000", "19970903T090000", "19970904T090000"]
+        );
+        assert_eq!(
+            expand(
+                "FREQ=DAILY;INTERVAL=10;UNTIL=19971001T000000Z",
+                "19970902T090000Z",
+                10
+            ),
+            vec!["19970902T090000Z", "19970912T090000Z", "19970922T090000Z"]
+        );
+    }
+
+    #[test]
+    fn test_expand_weekly() {
+        assert_eq!(
+            expand(
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,TH;COUNT=4",
+                "19970902T090000",
+                10
+            ),
+            vec![
+                "19970902T090000",
+                "19970904T090000",
+                "19970916T090000",
+                "19970918T090000"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_monthly() {
+        // Last weekday of the month
+        assert_eq!(
+            expand(
+                "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+                "19970930T090000",
+                3
+            ),
+            vec!["19970930T090000", "19971031T090000", "19971128T090000"]
+        );
+        // Months without a 31st are skipped
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYMONTHDAY=31", "20240131", 3),
+            vec!["20240131", "20240331", "20240531"]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=1FR", "19970905T090000", 3),
+            vec!["19970905T090000", "19971003T090000", "19971107T090000"]
+        );
+    }
+
+    #[test]
+    fn test_expand_yearly() {
+        assert_eq!(
+            expand("FREQ=YEARLY", "20240229", 3),
+            vec!["20240229", "20280229", "20320229"]
+        );
+        assert_eq!(
+            expand(
+                "FREQ=YEARLY;BYMONTH=1;BYDAY=SU;COUNT=3",
+                "19980104T090000",
+                10
+            ),
+            vec!["19980104T090000", "19980111T090000", "19980118T090000"]
+        );
+        assert_eq!(
+            expand("FREQ=YEARLY;BYWEEKNO=20;BYDAY=MO", "19970512T090000", 3),
+            vec!["19970512T090000", "19980511T090000", "19990517T090000"]
+        );
+        // This rule never produces an instance
+        assert!(expand("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30", "20240101", 3).is_empty());
+    }
+
+    #[test]
+    fn test_expand_hourly() {
+        assert_eq!(
+            expand(
+                "FREQ=HOURLY;INTERVAL=3;UNTIL=19970902T170000Z",
+                "19970902T090000Z",
+                10
+            ),
+            vec!["19970902T090000Z", "19970902T120000Z", "19970902T150000Z"]
+        );
+    }
+
+    #[test]
+    fn test_expand_timezone() {
+        let rule: RecurrenceRule = "FREQ=DAILY;COUNT=2".parse().unwrap();
+        let dtstart =
+            CalDateTime::parse("20241026T120000", Some(chrono_tz::Europe::Berlin)).unwrap();
+        let (instances, _) = rule.between(&dtstart, None, None, None);
+        // The wall clock time stays the same across the DST change
+        assert_eq!(
+            instances
+                .iter()
+                .map(|i| i.utc().to_rfc3339())
+                .collect::<Vec<_>>(),
+            vec!["2024-10-26T10:00:00+00:00", "2024-10-27T11:00:00+00:00"]
+        );
+    }
+}
@@ -78,6 +78,34 @@ impl CalDateTime {
             return Ok(None);
         };
 
+        let timezone = Self::parse_prop_timezone(prop, timezones)?;
+        Self::parse(&prop_value, timezone).map(Some)
+    }
+
+    // For properties like RDATE and EXDATE that may contain a list of values
+    pub fn parse_prop_list(
+        prop: &Property,
+        timezones: &HashMap<String, IcalTimeZone>,
+    ) -> Result<Vec<Self>, Error> {
+        let prop_value = if let Some(value) = &prop.value {
+            value.to_owned()
+        } else {
+            return Ok(vec![]);
+        };
+
+        let timezone = Self::parse_prop_timezone(prop, timezones)?;
+        prop_value
+            .split(',')
+            // PERIOD values (start/end) only need their start
+            .map(|value| value.split('/').next().unwrap_or(value))
+            .map(|value| Self::parse(value, timezone))
+            .collect()
+    }
+
+    fn parse_prop_timezone(
+        prop: &Property,
+        timezones: &HashMap<String, IcalTimeZone>,
+    ) -> Result<Option<Tz>, Error> {
         // Use the TZID parameter from the property
         let timezone = if let Some(tzid) = &prop
             .params
@@ -107,13 +135,9 @@ impl CalDateTime {
                 } else {
                     // If the TZID matches a name from the Olson database (e.g. Europe/Berlin) we
                     // guess that we can just use it
-                    if let Ok(tz) = tzid.parse::<Tz>() {
-                        Some(tz)
-                    } else {
-                        // TODO: Too bad, we need to manually parse it
-                        // For now it's just treated as localtime
-                        None
-                    }
+                    // TODO: If it doesn't we need to manually parse it
+                    // For now it's just treated as localtime
+                    tzid.parse::<Tz>().ok()
                 }
             } else {
                 // TZID refers to timezone that does not exist
@@ -127,7 +151,7 @@ impl CalDateTime {
             None
         };
 
-        Self::parse(&prop_value, timezone).map(Some)
+        Ok(timezone)
     }
 
     pub fn format(&self) -> String {
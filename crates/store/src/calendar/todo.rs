@@ -1,6 +1,88 @@
-use ical::parser::ical::component::IcalTodo;
+use super::{parse_duration, CalDateTime};
+use crate::Error;
+use ical::parser::ical::component::{IcalTimeZone, IcalTodo};
+use ical::parser::Component;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct TodoObject {
     pub todo: IcalTodo,
+    pub(crate) timezones: HashMap<String, IcalTimeZone>,
+}
+
+impl TodoObject {
+    fn get_date_prop(&self, name: &str) -> Result<Option<CalDateTime>, Error> {
+        self.todo
+            .get_property(name)
+            .map(|prop| CalDateTime::parse_prop(prop, &self.timezones))
+            .transpose()
+            .map(Option::flatten)
+    }
+
+    // A task without DTSTART starts at its due date
+    pub fn get_first_occurence(&self) -> Result<Option<CalDateTime>, Error> {
+        match self.get_date_prop("DTSTART")? {
+            Some(dtstart) => Ok(Some(dtstart)),
+            None => self.get_date_prop("DUE"),
+        }
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc4791#section-9.9
+    pub fn get_last_occurence(&self) -> Result<Option<CalDateTime>, Error> {
+        if let Some(due) = self.get_date_prop("DUE")? {
+            return Ok(Some(due));
+        }
+        let Some(dtstart) = self.get_date_prop("DTSTART")? else {
+            return Ok(None);
+        };
+        match self
+            .todo
+            .get_property("DURATION")
+            .and_then(|prop| prop.value.as_ref())
+        {
+            Some(duration) => Ok(Some(dtstart + parse_duration(duration)?)),
+            None => Ok(Some(dtstart)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CalendarObject;
+    use chrono::{DateTime, Utc};
+
+    const TODO_ICS: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//test//EN
+BEGIN:VTODO
+UID:todo
+DTSTAMP:20240101T000000Z
+DTSTART:20240110T100000Z
+DUE:20240112T100000Z
+SUMMARY:Task
+END:VTODO
+END:VCALENDAR
+"#;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_todo_time_range() {
+        let object = CalendarObject::from_ics("todo".to_owned(), TODO_ICS.to_owned()).unwrap();
+        assert!(object.occurs_between(
+            Some(utc("2024-01-11T00:00:00Z")),
+            Some(utc("2024-01-12T00:00:00Z"))
+        ));
+        assert!(!object.occurs_between(Some(utc("2024-02-01T00:00:00Z")), None));
+        assert!(!object.occurs_between(None, Some(utc("2024-01-01T00:00:00Z"))));
+
+        // Tasks without any dates match every range
+        let ics = TODO_ICS
+            .replace("DTSTART:20240110T100000Z\n", "")
+            .replace("DUE:20240112T100000Z\n", "");
+        let object = CalendarObject::from_ics("todo".to_owned(), ics).unwrap();
+        assert!(object.occurs_between(Some(utc("2024-02-01T00:00:00Z")), None));
+    }
 }
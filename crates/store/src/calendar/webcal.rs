@@ -0,0 +1,92 @@
+use super::{object_id_from_uid, split_calendar, CalendarObject};
+use crate::Error;
+use chrono::NaiveDateTime;
+use ical::generator::Emitter;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+// Outcome of the last refresh of a subscribed calendar
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WebcalStatus {
+    // Validators of the last downloaded feed for conditional requests
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_refresh: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+}
+
+// Splits a subscribed iCalendar feed into one object per UID
+pub fn split_webcal_feed(ics: &str) -> Result<Vec<CalendarObject>, Error> {
+    let mut objects = vec![];
+    let (split, without_uid) = split_calendar(ics)?;
+    if without_uid > 0 {
+        warn!("Skipping {without_uid} components without UID in subscribed feed");
+    }
+    for (uid, cal) in split {
+        match CalendarObject::from_ics(object_id_from_uid(&uid), cal.generate()) {
+            Ok(object) => objects.push(object),
+            // One broken component shouldn't take down the whole subscription
+            Err(err) => warn!("Skipping {uid} in subscribed feed: {err}"),
+        }
+    }
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_webcal_feed;
+
+    const FEED: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//test//EN\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:STANDARD\r
+DTSTART:19701025T030000\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:weekly\r
+DTSTAMP:20240101T000000Z\r
+DTSTART;TZID=Europe/Berlin:20240101T100000\r
+RRULE:FREQ=WEEKLY\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:holiday@example.com\r
+DTSTAMP:20240101T000000Z\r
+DTSTART;VALUE=DATE:20240101\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:weekly\r
+DTSTAMP:20240101T000000Z\r
+RECURRENCE-ID;TZID=Europe/Berlin:20240108T100000\r
+DTSTART;TZID=Europe/Berlin:20240108T120000\r
+END:VEVENT\r
+BEGIN:VTODO\r
+UID:a/b c\r
+DTSTAMP:20240101T000000Z\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn test_split_webcal_feed() {
+        let objects = split_webcal_feed(FEED).unwrap();
+        let ids: Vec<_> = objects.iter().map(|object| object.get_id()).collect();
+        assert_eq!(ids.len(), 3);
+        // UIDs that aren't URL-safe are hashed
+        assert_eq!(ids[0].len(), 64);
+        assert_eq!(objects[0].get_component_name(), "VTODO");
+        assert_eq!(&ids[1..], ["holiday@example.com", "weekly"]);
+
+        let holiday = objects[1].get_ics();
+        assert!(!holiday.contains("BEGIN:VTIMEZONE"));
+
+        let weekly = objects[2].get_ics();
+        assert_eq!(weekly.matches("BEGIN:VEVENT").count(), 2);
+        assert_eq!(weekly.matches("BEGIN:VTIMEZONE").count(), 1);
+        assert!(weekly.contains("PRODID:-//test//EN"));
+    }
+}
@@ -1,5 +1,6 @@
-use crate::calendar::{Calendar, CalendarObject};
+use crate::calendar::{Calendar, CalendarFeed, CalendarObject, WebcalStatus};
 use crate::error::Error;
+use crate::Share;
 use async_trait::async_trait;
 use chrono::NaiveDate;
 
@@ -79,6 +80,77 @@ pub trait CalendarStore: Send + Sync + 'static {
         cal_id: &str,
         object_id: &str,
     ) -> Result<(), Error>;
+    // COPY and MOVE between calendars of the same principal
+    async fn copy_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error>;
+    async fn move_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error>;
+
+    // Scheduling inbox of a principal (RFC 6638)
+    async fn get_inbox_objects(&self, principal: &str) -> Result<Vec<CalendarObject>, Error>;
+    async fn get_inbox_object(
+        &self,
+        principal: &str,
+        object_id: &str,
+    ) -> Result<CalendarObject, Error>;
+    async fn put_inbox_object(
+        &self,
+        principal: String,
+        object: CalendarObject,
+    ) -> Result<(), Error>;
+    async fn delete_inbox_object(&self, principal: &str, object_id: &str) -> Result<(), Error>;
+
+    // Calendars shared with other principals
+    async fn get_calendar_shares(&self, principal: &str, cal_id: &str)
+        -> Result<Vec<Share>, Error>;
+    async fn get_received_calendar_shares(&self, sharee: &str) -> Result<Vec<Share>, Error>;
+    async fn put_calendar_share(&self, share: Share) -> Result<(), Error>;
+    async fn delete_calendar_share(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        sharee: &str,
+    ) -> Result<(), Error>;
+
+    // Public read-only feeds of calendars
+    async fn get_calendar_feeds(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<CalendarFeed>, Error>;
+    async fn get_calendar_feed(&self, token: &str) -> Result<CalendarFeed, Error>;
+    async fn insert_calendar_feed(&self, feed: CalendarFeed) -> Result<(), Error>;
+    async fn delete_calendar_feed(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        token: &str,
+    ) -> Result<(), Error>;
+
+    // Calendars of all principals that mirror a webcal subscription
+    async fn get_subscribed_calendars(&self) -> Result<Vec<Calendar>, Error>;
+    async fn get_webcal_status(&self, principal: &str, cal_id: &str)
+        -> Result<WebcalStatus, Error>;
+    async fn put_webcal_status(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        status: WebcalStatus,
+    ) -> Result<(), Error>;
 
     fn is_read_only(&self) -> bool;
 }
@@ -1,8 +1,9 @@
 use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    calendar::CalendarObjectType, AddressObject, Addressbook, AddressbookStore, Calendar,
-    CalendarObject, CalendarStore, Error,
+    calendar::{CalendarFeed, CalendarObjectType, WebcalStatus},
+    AddressObject, Addressbook, AddressbookStore, Calendar, CalendarObject, CalendarStore, Error,
+    Share,
 };
 use async_trait::async_trait;
 use derive_more::derive::Constructor;
@@ -159,6 +160,125 @@ impl<AS: AddressbookStore> CalendarStore for ContactBirthdayStore<AS> {
         Err(Error::ReadOnly)
     }
 
+    async fn copy_object(
+        &self,
+        _principal: &str,
+        _cal_id: &str,
+        _object_id: &str,
+        _dest_cal_id: &str,
+        _dest_object_id: &str,
+        _overwrite: bool,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn move_object(
+        &self,
+        _principal: &str,
+        _cal_id: &str,
+        _object_id: &str,
+        _dest_cal_id: &str,
+        _dest_object_id: &str,
+        _overwrite: bool,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn get_inbox_objects(&self, _principal: &str) -> Result<Vec<CalendarObject>, Error> {
+        Ok(vec![])
+    }
+
+    async fn get_inbox_object(
+        &self,
+        _principal: &str,
+        _object_id: &str,
+    ) -> Result<CalendarObject, Error> {
+        Err(Error::NotFound)
+    }
+
+    async fn put_inbox_object(
+        &self,
+        _principal: String,
+        _object: CalendarObject,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn delete_inbox_object(&self, _principal: &str, _object_id: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn get_calendar_shares(
+        &self,
+        _principal: &str,
+        _cal_id: &str,
+    ) -> Result<Vec<Share>, Error> {
+        Ok(vec![])
+    }
+
+    async fn get_received_calendar_shares(&self, _sharee: &str) -> Result<Vec<Share>, Error> {
+        Ok(vec![])
+    }
+
+    async fn put_calendar_share(&self, _share: Share) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn delete_calendar_share(
+        &self,
+        _principal: &str,
+        _cal_id: &str,
+        _sharee: &str,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn get_calendar_feeds(
+        &self,
+        _principal: &str,
+        _cal_id: &str,
+    ) -> Result<Vec<CalendarFeed>, Error> {
+        Ok(vec![])
+    }
+
+    async fn get_calendar_feed(&self, _token: &str) -> Result<CalendarFeed, Error> {
+        Err(Error::NotFound)
+    }
+
+    async fn insert_calendar_feed(&self, _feed: CalendarFeed) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn delete_calendar_feed(
+        &self,
+        _principal: &str,
+        _cal_id: &str,
+        _token: &str,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    async fn get_subscribed_calendars(&self) -> Result<Vec<Calendar>, Error> {
+        Ok(vec![])
+    }
+
+    async fn get_webcal_status(
+        &self,
+        _principal: &str,
+        _cal_id: &str,
+    ) -> Result<WebcalStatus, Error> {
+        Ok(WebcalStatus::default())
+    }
+
+    async fn put_webcal_status(
+        &self,
+        _principal: &str,
+        _cal_id: &str,
+        _status: WebcalStatus,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
     fn is_read_only(&self) -> bool {
         true
     }
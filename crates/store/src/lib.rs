@@ -6,12 +6,14 @@ pub use error::Error;
 pub mod auth;
 pub mod calendar;
 mod contact_birthday_store;
+mod share;
 mod subscription_store;
 pub mod synctoken;
 
 pub use addressbook_store::AddressbookStore;
 pub use calendar_store::CalendarStore;
 pub use contact_birthday_store::ContactBirthdayStore;
+pub use share::{Share, ShareAccess};
 pub use subscription_store::*;
 
 pub use addressbook::{AddressObject, Addressbook};
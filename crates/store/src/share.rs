@@ -0,0 +1,53 @@
+// Access a sharee has to a shared collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareAccess {
+    Read,
+    ReadWrite,
+}
+
+// A calendar or addressbook shared with another principal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    // Owner of the collection
+    pub principal: String,
+    pub collection_id: String,
+    pub sharee: String,
+    pub access: ShareAccess,
+}
+
+// Separates the collection id from its owner in mount ids
+const MOUNT_ID_SEPARATOR: &str = "_shared_by_";
+
+impl Share {
+    // Shared collections show up in the sharee's home under this id
+    pub fn mount_id(&self) -> String {
+        format!(
+            "{}{MOUNT_ID_SEPARATOR}{}",
+            self.collection_id, self.principal
+        )
+    }
+
+    // Collections must not look like mounted shares,
+    // otherwise they could shadow them or make mount ids ambiguous
+    pub fn is_valid_collection_id(collection_id: &str) -> bool {
+        !collection_id.contains(MOUNT_ID_SEPARATOR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Share, ShareAccess};
+
+    #[test]
+    fn test_mount_id() {
+        let share = Share {
+            principal: "alice".to_owned(),
+            collection_id: "work".to_owned(),
+            sharee: "bob".to_owned(),
+            access: ShareAccess::Read,
+        };
+        assert_eq!(share.mount_id(), "work_shared_by_alice");
+        assert!(Share::is_valid_collection_id("work"));
+        assert!(!Share::is_valid_collection_id(&share.mount_id()));
+    }
+}
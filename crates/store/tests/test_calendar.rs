@@ -1,6 +1,9 @@
 use rstest::rstest;
 use rstest_reuse::{self, apply, template};
-use rustical_store::{CalendarObject, CalendarStore};
+use rustical_store::{
+    calendar::{CalendarFeed, FeedPrivacy, WebcalStatus},
+    CalendarObject, CalendarStore, Share, ShareAccess,
+};
 use rustical_store_sqlite::{calendar_store::SqliteCalendarStore, create_test_db};
 
 const TIMEZONE: &str = include_str!("examples/timezone.ics");
@@ -43,3 +46,301 @@ async fn test_create_event<CS: CalendarStore>(store: CS) {
     assert_eq!(event.get_ics(), EVENT);
     assert_eq!(event.get_id(), "asd");
 }
+
+#[apply(cal_store)]
+#[tokio::test]
+async fn test_schedule_inbox<CS: CalendarStore>(store: CS) {
+    let object = CalendarObject::from_ics("message".to_owned(), EVENT.to_owned()).unwrap();
+    store
+        .put_inbox_object("testuser".to_owned(), object)
+        .await
+        .unwrap();
+
+    let objects = store.get_inbox_objects("testuser").await.unwrap();
+    assert_eq!(objects.len(), 1);
+    assert_eq!(objects[0].get_id(), "message");
+    assert!(store
+        .get_inbox_objects("otheruser")
+        .await
+        .unwrap()
+        .is_empty());
+
+    store
+        .delete_inbox_object("testuser", "message")
+        .await
+        .unwrap();
+    assert!(store.get_inbox_object("testuser", "message").await.is_err());
+}
+
+#[apply(cal_store)]
+#[tokio::test]
+async fn test_calendar_shares<CS: CalendarStore>(store: CS) {
+    store
+        .insert_calendar(rustical_store::Calendar {
+            id: "test".to_owned(),
+            principal: "testuser".to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let share = Share {
+        principal: "testuser".to_owned(),
+        collection_id: "test".to_owned(),
+        sharee: "otheruser".to_owned(),
+        access: ShareAccess::Read,
+    };
+    store.put_calendar_share(share.clone()).await.unwrap();
+    // Sharing again replaces the access
+    let share = Share {
+        access: ShareAccess::ReadWrite,
+        ..share
+    };
+    store.put_calendar_share(share.clone()).await.unwrap();
+
+    assert_eq!(
+        store.get_calendar_shares("testuser", "test").await.unwrap(),
+        vec![share.clone()]
+    );
+    assert_eq!(
+        store
+            .get_received_calendar_shares("otheruser")
+            .await
+            .unwrap(),
+        vec![share]
+    );
+
+    store
+        .delete_calendar_share("testuser", "test", "otheruser")
+        .await
+        .unwrap();
+    assert!(store
+        .get_received_calendar_shares("otheruser")
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[apply(cal_store)]
+#[tokio::test]
+async fn test_calendar_feeds<CS: CalendarStore>(store: CS) {
+    store
+        .insert_calendar(rustical_store::Calendar {
+            id: "test".to_owned(),
+            principal: "testuser".to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let feed = CalendarFeed::new(
+        "testuser".to_owned(),
+        "test".to_owned(),
+        FeedPrivacy::BusyOnly,
+    );
+    store.insert_calendar_feed(feed.clone()).await.unwrap();
+
+    let stored = store.get_calendar_feed(&feed.token).await.unwrap();
+    assert_eq!(
+        (stored.principal.as_str(), stored.cal_id.as_str()),
+        ("testuser", "test")
+    );
+    assert_eq!(stored.privacy, FeedPrivacy::BusyOnly);
+    assert_eq!(
+        store.get_calendar_feeds("testuser", "test").await.unwrap()[0].token,
+        feed.token
+    );
+
+    // Feeds can only be revoked through their calendar
+    store
+        .delete_calendar_feed("otheruser", "test", &feed.token)
+        .await
+        .unwrap();
+    assert!(store.get_calendar_feed(&feed.token).await.is_ok());
+    store
+        .delete_calendar_feed("testuser", "test", &feed.token)
+        .await
+        .unwrap();
+    assert!(store.get_calendar_feed(&feed.token).await.is_err());
+    assert!(store
+        .get_calendar_feeds("testuser", "test")
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[apply(cal_store)]
+#[tokio::test]
+async fn test_webcal_status<CS: CalendarStore>(store: CS) {
+    store
+        .insert_calendar(rustical_store::Calendar {
+            id: "holidays".to_owned(),
+            principal: "testuser".to_owned(),
+            subscription_url: Some("webcal://example.com/holidays.ics".to_owned()),
+            push_topic: "holidays".to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    store
+        .insert_calendar(rustical_store::Calendar {
+            id: "local".to_owned(),
+            principal: "testuser".to_owned(),
+            push_topic: "local".to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let subscribed = store.get_subscribed_calendars().await.unwrap();
+    assert_eq!(subscribed.len(), 1);
+    assert_eq!(subscribed[0].id, "holidays");
+
+    // Calendars that were never refreshed have an empty status
+    assert_eq!(
+        store
+            .get_webcal_status("testuser", "holidays")
+            .await
+            .unwrap(),
+        WebcalStatus::default()
+    );
+    let status = WebcalStatus {
+        etag: Some("\"abc\"".to_owned()),
+        last_modified: None,
+        last_refresh: Some(chrono::Utc::now().naive_utc()),
+        last_error: Some("connection refused".to_owned()),
+    };
+    store
+        .put_webcal_status("testuser", "holidays", status.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .get_webcal_status("testuser", "holidays")
+            .await
+            .unwrap(),
+        status
+    );
+}
+
+#[apply(cal_store)]
+#[tokio::test]
+async fn test_copy_move_object<CS: CalendarStore>(store: CS) {
+    for id in ["home", "work"] {
+        store
+            .insert_calendar(rustical_store::Calendar {
+                id: id.to_owned(),
+                principal: "testuser".to_owned(),
+                push_topic: id.to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+    let object = CalendarObject::from_ics("event".to_owned(), EVENT.to_owned()).unwrap();
+    store
+        .put_object("testuser".to_owned(), "home".to_owned(), object, false)
+        .await
+        .unwrap();
+
+    store
+        .copy_object("testuser", "home", "event", "work", "copy", false)
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .get_object("testuser", "work", "copy")
+            .await
+            .unwrap()
+            .get_ics(),
+        EVENT
+    );
+    // The destination already exists
+    assert!(store
+        .copy_object("testuser", "home", "event", "work", "copy", false)
+        .await
+        .is_err());
+
+    let (_, _, home_synctoken) = store.sync_changes("testuser", "home", 0).await.unwrap();
+    let (_, _, work_synctoken) = store.sync_changes("testuser", "work", 0).await.unwrap();
+    store
+        .move_object("testuser", "home", "event", "work", "moved", false)
+        .await
+        .unwrap();
+    assert!(store.get_object("testuser", "home", "event").await.is_err());
+    assert!(store.get_object("testuser", "work", "moved").await.is_ok());
+
+    // Both changelogs contain the move
+    let (_, deleted, _) = store
+        .sync_changes("testuser", "home", home_synctoken)
+        .await
+        .unwrap();
+    assert_eq!(deleted, vec!["event".to_owned()]);
+    let (changed, _, _) = store
+        .sync_changes("testuser", "work", work_synctoken)
+        .await
+        .unwrap();
+    assert_eq!(
+        changed.iter().map(|obj| obj.get_id()).collect::<Vec<_>>(),
+        vec!["moved"]
+    );
+}
+
+// Objects and changes of calendars with the same id but another owner must not leak
+#[apply(cal_store)]
+#[tokio::test]
+async fn test_calendar_scoping<CS: CalendarStore>(store: CS) {
+    for principal in ["alice", "bob"] {
+        store
+            .insert_calendar(rustical_store::Calendar {
+                id: "test".to_owned(),
+                principal: principal.to_owned(),
+                push_topic: format!("{principal}-test"),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+    store
+        .insert_calendar(rustical_store::Calendar {
+            id: "other".to_owned(),
+            principal: "alice".to_owned(),
+            push_topic: "alice-other".to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let synctoken = store.get_calendar("alice", "test").await.unwrap().synctoken;
+
+    for (principal, cal_id, object_id) in [
+        ("alice", "test", "event"),
+        ("bob", "test", "event"),
+        ("bob", "test", "gone"),
+        ("alice", "other", "gone"),
+    ] {
+        let object = CalendarObject::from_ics(object_id.to_owned(), EVENT.to_owned()).unwrap();
+        store
+            .put_object(principal.to_owned(), cal_id.to_owned(), object, true)
+            .await
+            .unwrap();
+    }
+    for (principal, cal_id, object_id) in [
+        ("bob", "test", "event"),
+        ("bob", "test", "gone"),
+        ("alice", "other", "gone"),
+    ] {
+        store
+            .delete_object(principal, cal_id, object_id, false)
+            .await
+            .unwrap();
+    }
+
+    assert!(store.get_object("alice", "test", "event").await.is_ok());
+    let (objects, deleted, _) = store
+        .sync_changes("alice", "test", synctoken)
+        .await
+        .unwrap();
+    assert_eq!(objects.len(), 1);
+    assert_eq!(objects[0].get_id(), "event");
+    assert!(deleted.is_empty());
+}
@@ -0,0 +1,35 @@
+use rustical_store::{
+    auth::{CollectionProvisioner, PrincipalProvisioner},
+    AddressbookStore, CalendarStore,
+};
+use rustical_store_sqlite::{
+    addressbook_store::SqliteAddressbookStore, calendar_store::SqliteCalendarStore, create_test_db,
+};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_provision_collections() {
+    let db = create_test_db().await.unwrap();
+    let (send, _recv) = tokio::sync::mpsc::channel(100);
+    let cal_store = Arc::new(SqliteCalendarStore::new(db.clone(), send.clone()));
+    let addr_store = Arc::new(SqliteAddressbookStore::new(db, send));
+    let provisioner = CollectionProvisioner::new(cal_store.clone(), addr_store.clone());
+
+    provisioner.provision("alice").await.unwrap();
+    let calendars = cal_store.get_calendars("alice").await.unwrap();
+    assert_eq!(calendars.len(), 1);
+    assert_eq!(calendars[0].id, "default");
+    assert_eq!(addr_store.get_addressbooks("alice").await.unwrap().len(), 1);
+
+    // Provisioning again must not create duplicates
+    provisioner.provision("alice").await.unwrap();
+    assert_eq!(cal_store.get_calendars("alice").await.unwrap().len(), 1);
+
+    // Deleted collections are not recreated
+    cal_store
+        .delete_calendar("alice", "default", true)
+        .await
+        .unwrap();
+    provisioner.provision("alice").await.unwrap();
+    assert!(cal_store.get_calendars("alice").await.unwrap().is_empty());
+}
@@ -0,0 +1,148 @@
+use rstest::rstest;
+use rstest_reuse::{self, apply, template};
+use rustical_store::auth::{AuthenticationProvider, PrincipalType, User, UserStore};
+use rustical_store_sqlite::{create_test_db, user_store::SqliteUserStore};
+
+#[template]
+#[rstest]
+#[case::sqlite(async {
+     SqliteUserStore::new(create_test_db().await.unwrap())
+ })]
+async fn user_store<US: UserStore + AuthenticationProvider>(
+    #[future(awt)]
+    #[case]
+    mut store: US,
+) {
+}
+
+fn user(id: &str) -> User {
+    User {
+        id: id.to_owned(),
+        displayname: None,
+        principal_type: PrincipalType::Individual,
+        password: None,
+        memberships: vec![],
+        calendar_proxy_read_for: vec![],
+        calendar_proxy_write_for: vec![],
+    }
+}
+
+#[apply(user_store)]
+#[tokio::test]
+async fn test_put_user<US: UserStore + AuthenticationProvider>(store: US) {
+    store
+        .put_user(User {
+            principal_type: PrincipalType::Group,
+            ..user("team")
+        })
+        .await
+        .unwrap();
+    store
+        .put_user(User {
+            displayname: Some("Assistant".to_owned()),
+            memberships: vec!["team".to_owned()],
+            calendar_proxy_write_for: vec!["manager".to_owned()],
+            ..user("assistant")
+        })
+        .await
+        .unwrap();
+
+    let assistant = store.get_user("assistant").await.unwrap().unwrap();
+    assert_eq!(assistant.displayname.as_deref(), Some("Assistant"));
+    assert_eq!(assistant.memberships, vec!["team".to_owned()]);
+    assert_eq!(
+        assistant.calendar_proxy_write_for,
+        vec!["manager".to_owned()]
+    );
+    assert!(assistant.calendar_proxy_read_for.is_empty());
+    assert_eq!(
+        store
+            .get_principal("team")
+            .await
+            .unwrap()
+            .unwrap()
+            .principal_type,
+        PrincipalType::Group
+    );
+    assert_eq!(store.list_principals().await.unwrap().len(), 2);
+    assert_eq!(
+        store.get_group_members("team").await.unwrap(),
+        vec!["assistant".to_owned()]
+    );
+    assert!(store
+        .get_group_members("assistant")
+        .await
+        .unwrap()
+        .is_empty());
+
+    store.delete_user("assistant").await.unwrap();
+    assert!(store.get_user("assistant").await.unwrap().is_none());
+    assert!(store.delete_user("assistant").await.is_err());
+}
+
+#[apply(user_store)]
+#[tokio::test]
+async fn test_group_cannot_log_in<US: UserStore + AuthenticationProvider>(store: US) {
+    store
+        .put_user(User {
+            principal_type: PrincipalType::Group,
+            password: Some(password_auth::generate_hash("password")),
+            ..user("team")
+        })
+        .await
+        .unwrap();
+    assert!(store
+        .validate_user_token("team", "password")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[apply(user_store)]
+#[tokio::test]
+async fn test_app_tokens<US: UserStore + AuthenticationProvider>(store: US) {
+    store
+        .put_user(User {
+            password: Some(password_auth::generate_hash("password")),
+            ..user("testuser")
+        })
+        .await
+        .unwrap();
+    let token_id = store
+        .add_app_token(
+            "testuser",
+            "phone".to_owned(),
+            password_auth::generate_hash("token"),
+        )
+        .await
+        .unwrap();
+
+    let app_tokens = store.get_app_tokens("testuser").await.unwrap();
+    assert_eq!(app_tokens.len(), 1);
+    assert_eq!(app_tokens[0].id, token_id);
+    assert_eq!(app_tokens[0].name, "phone");
+    assert!(app_tokens[0].last_used.is_none());
+
+    for token in ["password", "token"] {
+        assert!(store
+            .validate_user_token("testuser", token)
+            .await
+            .unwrap()
+            .is_some());
+    }
+    assert!(store
+        .validate_user_token("testuser", "wrong")
+        .await
+        .unwrap()
+        .is_none());
+    assert!(store.get_app_tokens("testuser").await.unwrap()[0]
+        .last_used
+        .is_some());
+
+    store.remove_app_token("testuser", &token_id).await.unwrap();
+    assert!(store
+        .validate_user_token("testuser", "token")
+        .await
+        .unwrap()
+        .is_none());
+}
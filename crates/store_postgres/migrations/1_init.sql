@@ -0,0 +1,164 @@
+-- Same schema as the sqlite store, principals are managed by the auth backend
+-- Timestamps are stored in UTC
+
+CREATE TABLE calendars (
+    principal TEXT NOT NULL,
+    id TEXT NOT NULL,
+    synctoken BIGINT DEFAULT 0 NOT NULL,
+    displayname TEXT,
+    description TEXT,
+    "order" BIGINT DEFAULT 0 NOT NULL,
+    color TEXT,
+    timezone TEXT,
+    timezone_id TEXT,
+    deleted_at TIMESTAMP,
+    subscription_url TEXT,
+    push_topic TEXT UNIQUE NOT NULL,
+    comp_event BOOLEAN NOT NULL,
+    comp_todo BOOLEAN NOT NULL,
+    comp_journal BOOLEAN NOT NULL,
+    PRIMARY KEY (principal, id)
+);
+
+CREATE TABLE calendarobjects (
+    principal TEXT NOT NULL,
+    cal_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    ics TEXT NOT NULL,
+    updated_at TIMESTAMP DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    deleted_at TIMESTAMP,
+
+    -- For more efficient calendar-queries
+    first_occurence DATE,
+    last_occurence DATE,
+    etag TEXT,
+    object_type SMALLINT NOT NULL,  -- VEVENT(0)/VTODO(1)/VJOURNAL(2)
+
+    PRIMARY KEY (principal, cal_id, id),
+    FOREIGN KEY (principal, cal_id)
+    REFERENCES calendars (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_calobjs_first_occurence ON calendarobjects (first_occurence);
+CREATE INDEX idx_calobjs_last_occurence ON calendarobjects (last_occurence);
+CREATE INDEX idx_calobjs_etag ON calendarobjects (etag);
+CREATE INDEX idx_calobjs_obj_type ON calendarobjects (object_type);
+
+CREATE TABLE calendarobjectchangelog (
+    principal TEXT NOT NULL,
+    cal_id TEXT NOT NULL,
+    object_id TEXT NOT NULL,
+    "operation" SMALLINT NOT NULL,
+    synctoken BIGINT DEFAULT 0 NOT NULL,
+    created_at TIMESTAMP DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    PRIMARY KEY (principal, cal_id, synctoken, created_at),
+    FOREIGN KEY (principal, cal_id)
+    REFERENCES calendars (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_calobj_log_cal ON calendarobjectchangelog (cal_id);
+
+CREATE TABLE addressbooks (
+    principal TEXT NOT NULL,
+    id TEXT NOT NULL,
+    synctoken BIGINT DEFAULT 0 NOT NULL,
+    displayname TEXT,
+    description TEXT,
+    deleted_at TIMESTAMP,
+    push_topic TEXT UNIQUE NOT NULL,
+    PRIMARY KEY (principal, id)
+);
+
+CREATE TABLE addressobjects (
+    principal TEXT NOT NULL,
+    addressbook_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    vcf TEXT NOT NULL,
+    updated_at TIMESTAMP DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    deleted_at TIMESTAMP,
+    PRIMARY KEY (principal, addressbook_id, id),
+    FOREIGN KEY (principal, addressbook_id)
+    REFERENCES addressbooks (principal, id) ON DELETE CASCADE
+);
+
+CREATE TABLE addressobjectchangelog (
+    principal TEXT NOT NULL,
+    addressbook_id TEXT NOT NULL,
+    object_id TEXT NOT NULL,
+    "operation" SMALLINT NOT NULL,
+    synctoken BIGINT DEFAULT 0 NOT NULL,
+    created_at TIMESTAMP DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    PRIMARY KEY (principal, addressbook_id, synctoken, created_at),
+    FOREIGN KEY (principal, addressbook_id)
+    REFERENCES addressbooks (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_addrobj_log_cal ON addressobjectchangelog (addressbook_id);
+
+CREATE TABLE subscriptions (
+    id TEXT NOT NULL,
+    topic TEXT NOT NULL,
+    expiration TIMESTAMP NOT NULL,
+    push_resource TEXT NOT NULL,
+    PRIMARY KEY (id)
+);
+
+-- Scheduling messages delivered to a principal (RFC 6638)
+CREATE TABLE scheduleinbox (
+    principal TEXT NOT NULL,
+    id TEXT NOT NULL,
+    ics TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    PRIMARY KEY (principal, id)
+);
+
+-- Collections shared with other principals
+CREATE TABLE calendarshares (
+    principal TEXT NOT NULL,
+    cal_id TEXT NOT NULL,
+    sharee TEXT NOT NULL,
+    read_write BOOLEAN NOT NULL,
+    PRIMARY KEY (principal, cal_id, sharee),
+    FOREIGN KEY (principal, cal_id)
+    REFERENCES calendars (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_calshares_sharee ON calendarshares (sharee);
+
+CREATE TABLE addressbookshares (
+    principal TEXT NOT NULL,
+    addressbook_id TEXT NOT NULL,
+    sharee TEXT NOT NULL,
+    read_write BOOLEAN NOT NULL,
+    PRIMARY KEY (principal, addressbook_id, sharee),
+    FOREIGN KEY (principal, addressbook_id)
+    REFERENCES addressbooks (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_addrshares_sharee ON addressbookshares (sharee);
+
+-- Public read-only feeds of calendars, the token is the only credential
+CREATE TABLE calendarfeeds (
+    token TEXT NOT NULL PRIMARY KEY,
+    principal TEXT NOT NULL,
+    cal_id TEXT NOT NULL,
+    privacy TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (principal, cal_id)
+    REFERENCES calendars (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_calfeeds_calendar ON calendarfeeds (principal, cal_id);
+
+-- Refresh state of calendars subscribed to a webcal feed
+CREATE TABLE webcalstatus (
+    principal TEXT NOT NULL,
+    cal_id TEXT NOT NULL,
+    etag TEXT,
+    last_modified TEXT,
+    last_refresh TIMESTAMP,
+    last_error TEXT,
+    PRIMARY KEY (principal, cal_id),
+    FOREIGN KEY (principal, cal_id)
+    REFERENCES calendars (principal, id) ON DELETE CASCADE
+);
@@ -0,0 +1,821 @@
+use super::{ChangeOperation, ShareRow};
+use async_trait::async_trait;
+use derive_more::derive::Constructor;
+use rustical_store::{
+    synctoken::format_synctoken, AddressObject, Addressbook, AddressbookStore, CollectionOperation,
+    CollectionOperationDomain, CollectionOperationType, Error, Share, ShareAccess,
+};
+use sqlx::{Acquire, Executor, PgPool, Postgres, Transaction};
+use tokio::sync::mpsc::Sender;
+use tracing::{error, instrument};
+
+#[derive(Debug, Clone)]
+struct AddressObjectRow {
+    id: String,
+    vcf: String,
+}
+
+impl TryFrom<AddressObjectRow> for AddressObject {
+    type Error = crate::Error;
+
+    fn try_from(value: AddressObjectRow) -> Result<Self, Self::Error> {
+        Ok(Self::from_vcf(value.id, value.vcf)?)
+    }
+}
+
+#[derive(Debug, Constructor)]
+pub struct PostgresAddressbookStore {
+    db: PgPool,
+    sender: Sender<CollectionOperation>,
+}
+
+impl PostgresAddressbookStore {
+    async fn _get_addressbook<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        id: &str,
+    ) -> Result<Addressbook, rustical_store::Error> {
+        let addressbook = sqlx::query_as!(
+            Addressbook,
+            r#"SELECT principal, id, synctoken, displayname, description, deleted_at, push_topic
+                FROM addressbooks
+                WHERE (principal, id) = ($1, $2)"#,
+            principal,
+            id
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(addressbook)
+    }
+
+    async fn _get_addressbooks<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+    ) -> Result<Vec<Addressbook>, rustical_store::Error> {
+        let addressbooks = sqlx::query_as!(
+            Addressbook,
+            r#"SELECT principal, id, synctoken, displayname, description, deleted_at, push_topic
+                FROM addressbooks
+                WHERE principal = $1 AND deleted_at IS NULL"#,
+            principal
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(addressbooks)
+    }
+
+    async fn _get_deleted_addressbooks<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+    ) -> Result<Vec<Addressbook>, rustical_store::Error> {
+        let addressbooks = sqlx::query_as!(
+            Addressbook,
+            r#"SELECT principal, id, synctoken, displayname, description, deleted_at, push_topic
+                FROM addressbooks
+                WHERE principal = $1 AND deleted_at IS NOT NULL"#,
+            principal
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(addressbooks)
+    }
+
+    async fn _update_addressbook<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: String,
+        id: String,
+        addressbook: Addressbook,
+    ) -> Result<(), rustical_store::Error> {
+        let result = sqlx::query!(
+            r#"UPDATE addressbooks SET principal = $1, id = $2, displayname = $3, description = $4, push_topic = $5
+                WHERE (principal, id) = ($6, $7)"#,
+            addressbook.principal,
+            addressbook.id,
+            addressbook.displayname,
+            addressbook.description,
+            addressbook.push_topic,
+            principal,
+            id
+        )
+        .execute(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        if result.rows_affected() == 0 {
+            return Err(rustical_store::Error::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn _insert_addressbook<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        addressbook: Addressbook,
+    ) -> Result<(), rustical_store::Error> {
+        sqlx::query!(
+            r#"INSERT INTO addressbooks (principal, id, displayname, description, push_topic)
+                VALUES ($1, $2, $3, $4, $5)"#,
+            addressbook.principal,
+            addressbook.id,
+            addressbook.displayname,
+            addressbook.description,
+            addressbook.push_topic,
+        )
+        .execute(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    async fn _delete_addressbook<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        addressbook_id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), rustical_store::Error> {
+        match use_trashbin {
+            true => {
+                sqlx::query!(
+                    r#"UPDATE addressbooks SET deleted_at = (NOW() AT TIME ZONE 'UTC') WHERE (principal, id) = ($1, $2)"#,
+                    principal, addressbook_id
+                )
+                .execute(executor)
+                .await.map_err(crate::Error::from)?;
+            }
+            false => {
+                sqlx::query!(
+                    r#"DELETE FROM addressbooks WHERE (principal, id) = ($1, $2)"#,
+                    principal,
+                    addressbook_id
+                )
+                .execute(executor)
+                .await
+                .map_err(crate::Error::from)?;
+            }
+        };
+
+        Ok(())
+    }
+
+    async fn _restore_addressbook<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<(), rustical_store::Error> {
+        sqlx::query!(
+            r"UPDATE addressbooks SET deleted_at = NULL WHERE (principal, id) = ($1, $2)",
+            principal,
+            addressbook_id
+        )
+        .execute(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    async fn _sync_changes<'a, A: Acquire<'a, Database = Postgres>>(
+        acquire: A,
+        principal: &str,
+        addressbook_id: &str,
+        synctoken: i64,
+    ) -> Result<(Vec<AddressObject>, Vec<String>, i64), rustical_store::Error> {
+        struct Row {
+            object_id: String,
+            synctoken: i64,
+        }
+
+        let mut conn = acquire.acquire().await.map_err(crate::Error::from)?;
+
+        let changes = sqlx::query_as!(
+            Row,
+            r#"
+                SELECT DISTINCT object_id, synctoken from addressobjectchangelog
+                WHERE (principal, addressbook_id) = ($1, $2) AND synctoken > $3
+                ORDER BY synctoken ASC
+            "#,
+            principal,
+            addressbook_id,
+            synctoken
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(crate::Error::from)?;
+
+        let mut objects = vec![];
+        let mut deleted_objects = vec![];
+
+        let new_synctoken = changes
+            .last()
+            .map(|&Row { synctoken, .. }| synctoken)
+            .unwrap_or(0);
+
+        for Row { object_id, .. } in changes {
+            match Self::_get_object(&mut *conn, principal, addressbook_id, &object_id).await {
+                Ok(object) => objects.push(object),
+                Err(rustical_store::Error::NotFound) => deleted_objects.push(object_id),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok((objects, deleted_objects, new_synctoken))
+    }
+
+    async fn _get_objects<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Vec<AddressObject>, rustical_store::Error> {
+        sqlx::query_as!(
+            AddressObjectRow,
+            "SELECT id, vcf FROM addressobjects WHERE principal = $1 AND addressbook_id = $2 AND deleted_at IS NULL",
+            principal,
+            addressbook_id
+        )
+        .fetch_all(executor)
+        .await.map_err(crate::Error::from)?
+        .into_iter()
+        .map(|row| row.try_into().map_err(rustical_store::Error::from))
+        .collect()
+    }
+
+    async fn _get_object<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+    ) -> Result<AddressObject, rustical_store::Error> {
+        Ok(sqlx::query_as!(
+            AddressObjectRow,
+            "SELECT id, vcf FROM addressobjects WHERE (principal, addressbook_id, id) = ($1, $2, $3)",
+            principal,
+            addressbook_id,
+            object_id
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(crate::Error::from)?
+        .try_into()?)
+    }
+
+    async fn _put_object<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: String,
+        addressbook_id: String,
+        object: AddressObject,
+        overwrite: bool,
+    ) -> Result<(), rustical_store::Error> {
+        let (object_id, vcf) = (object.get_id(), object.get_vcf());
+
+        (if overwrite {
+            sqlx::query!(
+            r#"INSERT INTO addressobjects (principal, addressbook_id, id, vcf) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (principal, addressbook_id, id) DO UPDATE
+                SET vcf = excluded.vcf, updated_at = excluded.updated_at, deleted_at = NULL"#,
+            principal,
+            addressbook_id,
+            object_id,
+            vcf
+        )
+        } else {
+            // If the object already exists a database error is thrown and handled in error.rs
+            sqlx::query!(
+            "INSERT INTO addressobjects (principal, addressbook_id, id, vcf) VALUES ($1, $2, $3, $4)",
+            principal,
+            addressbook_id,
+            object_id,
+            vcf
+        )
+        })
+        .execute(executor)
+        .await
+        .map_err(crate::Error::from)?;
+
+        Ok(())
+    }
+
+    async fn _delete_object<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), rustical_store::Error> {
+        match use_trashbin {
+            true => {
+                sqlx::query!(
+                    "UPDATE addressobjects SET deleted_at = (NOW() AT TIME ZONE 'UTC'), updated_at = (NOW() AT TIME ZONE 'UTC') WHERE (principal, addressbook_id, id) = ($1, $2, $3)",
+                    principal,
+                    addressbook_id,
+                    object_id
+                )
+                .execute(executor)
+                .await.map_err(crate::Error::from)?;
+            }
+            false => {
+                sqlx::query!(
+                    "DELETE FROM addressobjects WHERE (principal, addressbook_id, id) = ($1, $2, $3)",
+                    principal,
+                    addressbook_id,
+                    object_id
+                )
+                .execute(executor)
+                .await
+                .map_err(crate::Error::from)?;
+            }
+        };
+        Ok(())
+    }
+
+    async fn _restore_object<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+    ) -> Result<(), rustical_store::Error> {
+        sqlx::query!(
+            r#"UPDATE addressobjects SET deleted_at = NULL, updated_at = (NOW() AT TIME ZONE 'UTC') WHERE (principal, addressbook_id, id) = ($1, $2, $3)"#,
+            principal,
+            addressbook_id,
+            object_id
+        )
+        .execute(executor)
+        .await.map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    // Both changelogs are written in the same transaction
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_or_move_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+        remove_source: bool,
+    ) -> Result<(), Error> {
+        if (addressbook_id, object_id) == (dest_addressbook_id, dest_object_id) {
+            return Err(Error::AlreadyExists);
+        }
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        let object = Self::_get_object(&mut *tx, principal, addressbook_id, object_id).await?;
+        let object =
+            AddressObject::from_vcf(dest_object_id.to_owned(), object.get_vcf().to_owned())?;
+        Self::_put_object(
+            &mut *tx,
+            principal.to_owned(),
+            dest_addressbook_id.to_owned(),
+            object,
+            overwrite,
+        )
+        .await?;
+        let mut synctokens = vec![(
+            dest_addressbook_id,
+            log_object_operation(
+                &mut tx,
+                principal,
+                dest_addressbook_id,
+                dest_object_id,
+                ChangeOperation::Add,
+            )
+            .await
+            .map_err(crate::Error::from)?,
+        )];
+
+        if remove_source {
+            Self::_delete_object(&mut *tx, principal, addressbook_id, object_id, false).await?;
+            synctokens.push((
+                addressbook_id,
+                log_object_operation(
+                    &mut tx,
+                    principal,
+                    addressbook_id,
+                    object_id,
+                    ChangeOperation::Delete,
+                )
+                .await
+                .map_err(crate::Error::from)?,
+            ));
+        }
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        for (addressbook_id, synctoken) in synctokens {
+            if let Err(err) = self.sender.try_send(CollectionOperation {
+                r#type: CollectionOperationType::Object,
+                domain: CollectionOperationDomain::Addressbook,
+                topic: self
+                    .get_addressbook(principal, addressbook_id)
+                    .await?
+                    .push_topic,
+                sync_token: Some(synctoken),
+            }) {
+                error!("Push notification about changed addressbook failed: {err}");
+            };
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AddressbookStore for PostgresAddressbookStore {
+    #[instrument]
+    async fn get_addressbook(
+        &self,
+        principal: &str,
+        id: &str,
+    ) -> Result<Addressbook, rustical_store::Error> {
+        Self::_get_addressbook(&self.db, principal, id).await
+    }
+
+    #[instrument]
+    async fn get_addressbooks(
+        &self,
+        principal: &str,
+    ) -> Result<Vec<Addressbook>, rustical_store::Error> {
+        Self::_get_addressbooks(&self.db, principal).await
+    }
+
+    #[instrument]
+    async fn get_deleted_addressbooks(
+        &self,
+        principal: &str,
+    ) -> Result<Vec<Addressbook>, rustical_store::Error> {
+        Self::_get_deleted_addressbooks(&self.db, principal).await
+    }
+
+    #[instrument]
+    async fn update_addressbook(
+        &self,
+        principal: String,
+        id: String,
+        addressbook: Addressbook,
+    ) -> Result<(), rustical_store::Error> {
+        Self::_update_addressbook(&self.db, principal, id, addressbook).await
+    }
+
+    #[instrument]
+    async fn insert_addressbook(
+        &self,
+        addressbook: Addressbook,
+    ) -> Result<(), rustical_store::Error> {
+        Self::_insert_addressbook(&self.db, addressbook).await
+    }
+
+    #[instrument]
+    async fn delete_addressbook(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), rustical_store::Error> {
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        let addressbook = match Self::_get_addressbook(&mut *tx, principal, addressbook_id).await {
+            Ok(addressbook) => Some(addressbook),
+            Err(Error::NotFound) => None,
+            Err(err) => return Err(err),
+        };
+
+        Self::_delete_addressbook(&mut *tx, principal, addressbook_id, use_trashbin).await?;
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        if let Some(addressbook) = addressbook {
+            if let Err(err) = self.sender.try_send(CollectionOperation {
+                r#type: CollectionOperationType::Delete,
+                domain: CollectionOperationDomain::Addressbook,
+                topic: addressbook.push_topic,
+                sync_token: None,
+            }) {
+                error!("Push notification about deleted addressbook failed: {err}");
+            };
+        }
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn restore_addressbook(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<(), rustical_store::Error> {
+        Self::_restore_addressbook(&self.db, principal, addressbook_id).await
+    }
+
+    #[instrument]
+    async fn sync_changes(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        synctoken: i64,
+    ) -> Result<(Vec<AddressObject>, Vec<String>, i64), rustical_store::Error> {
+        Self::_sync_changes(&self.db, principal, addressbook_id, synctoken).await
+    }
+
+    #[instrument]
+    async fn get_objects(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Vec<AddressObject>, rustical_store::Error> {
+        Self::_get_objects(&self.db, principal, addressbook_id).await
+    }
+
+    #[instrument]
+    async fn get_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+    ) -> Result<AddressObject, rustical_store::Error> {
+        Self::_get_object(&self.db, principal, addressbook_id, object_id).await
+    }
+
+    #[instrument]
+    async fn put_object(
+        &self,
+        principal: String,
+        addressbook_id: String,
+        object: AddressObject,
+        overwrite: bool,
+    ) -> Result<(), rustical_store::Error> {
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        let object_id = object.get_id().to_owned();
+
+        Self::_put_object(
+            &mut *tx,
+            principal.to_owned(),
+            addressbook_id.to_owned(),
+            object,
+            overwrite,
+        )
+        .await?;
+
+        let synctoken = log_object_operation(
+            &mut tx,
+            &principal,
+            &addressbook_id,
+            &object_id,
+            ChangeOperation::Add,
+        )
+        .await
+        .map_err(crate::Error::from)?;
+
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        if let Err(err) = self.sender.try_send(CollectionOperation {
+            r#type: CollectionOperationType::Object,
+            domain: CollectionOperationDomain::Addressbook,
+            topic: self
+                .get_addressbook(&principal, &addressbook_id)
+                .await?
+                .push_topic,
+            sync_token: Some(synctoken),
+        }) {
+            error!("Push notification about deleted addressbook failed: {err}");
+        };
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), rustical_store::Error> {
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        Self::_delete_object(&mut *tx, principal, addressbook_id, object_id, use_trashbin).await?;
+
+        let synctoken = log_object_operation(
+            &mut tx,
+            principal,
+            addressbook_id,
+            object_id,
+            ChangeOperation::Delete,
+        )
+        .await
+        .map_err(crate::Error::from)?;
+
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        // TODO: Watch for errors here?
+        let _ = self.sender.try_send(CollectionOperation {
+            r#type: CollectionOperationType::Object,
+            domain: CollectionOperationDomain::Addressbook,
+            topic: self
+                .get_addressbook(principal, addressbook_id)
+                .await?
+                .push_topic,
+            sync_token: Some(synctoken),
+        });
+        Ok(())
+    }
+
+    #[instrument]
+    async fn copy_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            addressbook_id,
+            object_id,
+            dest_addressbook_id,
+            dest_object_id,
+            overwrite,
+            false,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn move_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            addressbook_id,
+            object_id,
+            dest_addressbook_id,
+            dest_object_id,
+            overwrite,
+            true,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn restore_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+    ) -> Result<(), rustical_store::Error> {
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        Self::_restore_object(&mut *tx, principal, addressbook_id, object_id).await?;
+
+        let synctoken = log_object_operation(
+            &mut tx,
+            principal,
+            addressbook_id,
+            object_id,
+            ChangeOperation::Add,
+        )
+        .await
+        .map_err(crate::Error::from)?;
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        // TODO: Watch for errors here?
+        let _ = self.sender.try_send(CollectionOperation {
+            r#type: CollectionOperationType::Object,
+            domain: CollectionOperationDomain::Addressbook,
+            topic: self
+                .get_addressbook(principal, addressbook_id)
+                .await?
+                .push_topic,
+            sync_token: Some(synctoken),
+        });
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_addressbook_shares(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Vec<Share>, Error> {
+        Ok(sqlx::query_as!(
+            ShareRow,
+            r#"SELECT principal, addressbook_id AS collection_id, sharee, read_write
+                FROM addressbookshares
+                WHERE (principal, addressbook_id) = ($1, $2)
+                ORDER BY sharee"#,
+            principal,
+            addressbook_id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(Share::from)
+        .collect())
+    }
+
+    #[instrument]
+    async fn get_received_addressbook_shares(&self, sharee: &str) -> Result<Vec<Share>, Error> {
+        // Shares of addressbooks in the trash bin are hidden
+        Ok(sqlx::query_as!(
+            ShareRow,
+            r#"SELECT addressbookshares.principal, addressbook_id AS collection_id, sharee, read_write
+                FROM addressbookshares
+                INNER JOIN addressbooks
+                    ON (addressbooks.principal, addressbooks.id) = (addressbookshares.principal, addressbook_id)
+                WHERE sharee = $1 AND addressbooks.deleted_at IS NULL
+                ORDER BY addressbookshares.principal, addressbook_id"#,
+            sharee
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(Share::from)
+        .collect())
+    }
+
+    #[instrument]
+    async fn put_addressbook_share(&self, share: Share) -> Result<(), Error> {
+        let read_write = share.access == ShareAccess::ReadWrite;
+        sqlx::query!(
+            r#"INSERT INTO addressbookshares (principal, addressbook_id, sharee, read_write)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (principal, addressbook_id, sharee) DO UPDATE SET read_write = excluded.read_write"#,
+            share.principal,
+            share.collection_id,
+            share.sharee,
+            read_write
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_addressbook_share(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        sharee: &str,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM addressbookshares WHERE (principal, addressbook_id, sharee) = ($1, $2, $3)",
+            principal,
+            addressbook_id,
+            sharee
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+}
+
+// Logs an operation to an address object
+async fn log_object_operation(
+    tx: &mut Transaction<'_, Postgres>,
+    principal: &str,
+    addressbook_id: &str,
+    object_id: &str,
+    operation: ChangeOperation,
+) -> Result<String, sqlx::Error> {
+    struct Synctoken {
+        synctoken: i64,
+    }
+    let Synctoken { synctoken } = sqlx::query_as!(
+        Synctoken,
+        r#"
+        UPDATE addressbooks
+        SET synctoken = synctoken + 1
+        WHERE (principal, id) = ($1, $2)
+        RETURNING synctoken"#,
+        principal,
+        addressbook_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO addressobjectchangelog (principal, addressbook_id, object_id, "operation", synctoken)
+        VALUES ($1, $2, $3, $4, $5)"#,
+        principal,
+        addressbook_id,
+        object_id,
+        operation as i16,
+        synctoken
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(format_synctoken(synctoken))
+}
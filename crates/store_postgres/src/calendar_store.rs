@@ -0,0 +1,1150 @@
+use super::{ChangeOperation, ShareRow};
+use async_trait::async_trait;
+use chrono::TimeDelta;
+use derive_more::derive::Constructor;
+use rustical_store::calendar::{
+    CalDateTime, CalendarFeed, CalendarObjectType, FeedPrivacy, WebcalStatus,
+};
+use rustical_store::calendar_store::CalendarQuery;
+use rustical_store::synctoken::format_synctoken;
+use rustical_store::{Calendar, CalendarObject, CalendarStore, Error, Share, ShareAccess};
+use rustical_store::{CollectionOperation, CollectionOperationType};
+use sqlx::types::chrono::NaiveDateTime;
+use sqlx::{Acquire, Executor, PgPool, Postgres, Transaction};
+use tokio::sync::mpsc::Sender;
+use tracing::{error, instrument};
+
+#[derive(Debug, Clone)]
+struct CalendarFeedRow {
+    token: String,
+    principal: String,
+    cal_id: String,
+    privacy: String,
+    created_at: NaiveDateTime,
+}
+
+impl From<CalendarFeedRow> for CalendarFeed {
+    fn from(value: CalendarFeedRow) -> Self {
+        Self {
+            token: value.token,
+            principal: value.principal,
+            cal_id: value.cal_id,
+            privacy: match value.privacy.as_str() {
+                "private" => FeedPrivacy::Private,
+                "busy-only" => FeedPrivacy::BusyOnly,
+                _ => FeedPrivacy::Full,
+            },
+            created_at: Some(value.created_at),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CalendarObjectRow {
+    id: String,
+    ics: String,
+}
+
+impl TryFrom<CalendarObjectRow> for CalendarObject {
+    type Error = rustical_store::Error;
+
+    fn try_from(value: CalendarObjectRow) -> Result<Self, Self::Error> {
+        CalendarObject::from_ics(value.id, value.ics)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct CalendarRow {
+    principal: String,
+    id: String,
+    displayname: Option<String>,
+    order: i64,
+    description: Option<String>,
+    color: Option<String>,
+    timezone: Option<String>,
+    timezone_id: Option<String>,
+    deleted_at: Option<NaiveDateTime>,
+    synctoken: i64,
+    subscription_url: Option<String>,
+    push_topic: String,
+    comp_event: bool,
+    comp_todo: bool,
+    comp_journal: bool,
+}
+
+impl From<CalendarRow> for Calendar {
+    fn from(value: CalendarRow) -> Self {
+        let mut components = vec![];
+        if value.comp_event {
+            components.push(CalendarObjectType::Event);
+        }
+        if value.comp_todo {
+            components.push(CalendarObjectType::Todo);
+        }
+        if value.comp_journal {
+            components.push(CalendarObjectType::Journal);
+        }
+        Self {
+            principal: value.principal,
+            id: value.id,
+            displayname: value.displayname,
+            order: value.order,
+            description: value.description,
+            color: value.color,
+            timezone: value.timezone,
+            timezone_id: value.timezone_id,
+            deleted_at: value.deleted_at,
+            synctoken: value.synctoken,
+            subscription_url: value.subscription_url,
+            push_topic: value.push_topic,
+            components,
+        }
+    }
+}
+
+#[derive(Debug, Constructor)]
+pub struct PostgresCalendarStore {
+    db: PgPool,
+    sender: Sender<CollectionOperation>,
+}
+
+impl PostgresCalendarStore {
+    async fn _get_calendar<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        id: &str,
+    ) -> Result<Calendar, Error> {
+        let cal = sqlx::query_as!(
+            CalendarRow,
+            r#"SELECT *
+                FROM calendars
+                WHERE (principal, id) = ($1, $2)"#,
+            principal,
+            id
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(cal.into())
+    }
+
+    async fn _get_calendars<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+    ) -> Result<Vec<Calendar>, Error> {
+        let cals = sqlx::query_as!(
+            CalendarRow,
+            r#"SELECT *
+                FROM calendars
+                WHERE principal = $1 AND deleted_at IS NULL"#,
+            principal
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(cals.into_iter().map(Calendar::from).collect())
+    }
+
+    async fn _get_deleted_calendars<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+    ) -> Result<Vec<Calendar>, Error> {
+        let cals = sqlx::query_as!(
+            CalendarRow,
+            r#"SELECT *
+                FROM calendars
+                WHERE principal = $1 AND deleted_at IS NOT NULL"#,
+            principal
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(cals.into_iter().map(Calendar::from).collect())
+    }
+
+    async fn _insert_calendar<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        calendar: Calendar,
+    ) -> Result<(), Error> {
+        let comp_event = calendar.components.contains(&CalendarObjectType::Event);
+        let comp_todo = calendar.components.contains(&CalendarObjectType::Todo);
+        let comp_journal = calendar.components.contains(&CalendarObjectType::Journal);
+
+        sqlx::query!(
+            r#"INSERT INTO calendars (principal, id, displayname, description, "order", color, timezone, timezone_id, push_topic, comp_event, comp_todo, comp_journal, subscription_url)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
+            calendar.principal,
+            calendar.id,
+            calendar.displayname,
+            calendar.description,
+            calendar.order,
+            calendar.color,
+            calendar.timezone,
+            calendar.timezone_id,
+            calendar.push_topic,
+            comp_event, comp_todo, comp_journal,
+            calendar.subscription_url
+        )
+        .execute(executor)
+        .await.map_err(crate::Error::from)?;
+
+        Ok(())
+    }
+
+    async fn _update_calendar<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: String,
+        id: String,
+        calendar: Calendar,
+    ) -> Result<(), Error> {
+        let comp_event = calendar.components.contains(&CalendarObjectType::Event);
+        let comp_todo = calendar.components.contains(&CalendarObjectType::Todo);
+        let comp_journal = calendar.components.contains(&CalendarObjectType::Journal);
+
+        let result = sqlx::query!(
+            r#"UPDATE calendars SET principal = $1, id = $2, displayname = $3, description = $4, "order" = $5, color = $6, timezone = $7, timezone_id = $8, push_topic = $9, comp_event = $10, comp_todo = $11, comp_journal = $12, subscription_url = $13
+                WHERE (principal, id) = ($14, $15)"#,
+            calendar.principal,
+            calendar.id,
+            calendar.displayname,
+            calendar.description,
+            calendar.order,
+            calendar.color,
+            calendar.timezone,
+            calendar.timezone_id,
+            calendar.push_topic,
+            comp_event, comp_todo, comp_journal,
+            calendar.subscription_url,
+            principal,
+            id
+        ).execute(executor).await.map_err(crate::Error::from)?;
+        if result.rows_affected() == 0 {
+            return Err(rustical_store::Error::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn _delete_calendar<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        match use_trashbin {
+            true => sqlx::query!(
+                r#"UPDATE calendars SET deleted_at = (NOW() AT TIME ZONE 'UTC') WHERE (principal, id) = ($1, $2)"#,
+                principal,
+                id
+            )
+            .execute(executor)
+            .await
+            .map_err(crate::Error::from)?,
+            false => sqlx::query!(
+                r#"DELETE FROM calendars WHERE (principal, id) = ($1, $2)"#,
+                principal,
+                id
+            )
+            .execute(executor)
+            .await
+            .map_err(crate::Error::from)?,
+        };
+        Ok(())
+    }
+
+    async fn _restore_calendar<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        id: &str,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r"UPDATE calendars SET deleted_at = NULL WHERE (principal, id) = ($1, $2)",
+            principal,
+            id
+        )
+        .execute(executor)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    async fn _get_objects<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<CalendarObject>, Error> {
+        sqlx::query_as!(
+            CalendarObjectRow,
+            "SELECT id, ics FROM calendarobjects WHERE principal = $1 AND cal_id = $2 AND deleted_at IS NULL",
+            principal,
+            cal_id
+        )
+        .fetch_all(executor)
+        .await.map_err(crate::Error::from)?
+        .into_iter()
+        .map(|row| row.try_into())
+        .collect()
+    }
+
+    async fn _calendar_query<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        cal_id: &str,
+        query: CalendarQuery,
+    ) -> Result<Vec<CalendarObject>, Error> {
+        // We extend our query interval by one day in each direction since we really don't want to
+        // miss any objects because of timezone differences
+        let start = query.time_start.map(|start| start - TimeDelta::days(1));
+        let end = query.time_end.map(|end| end + TimeDelta::days(1));
+
+        sqlx::query_as!(
+            CalendarObjectRow,
+            r"SELECT id, ics FROM calendarobjects
+                WHERE principal = $1 AND cal_id = $2 AND deleted_at IS NULL
+                    AND (last_occurence IS NULL OR $3::DATE IS NULL OR last_occurence >= $3)
+                    AND (first_occurence IS NULL OR $4::DATE IS NULL OR first_occurence <= $4)
+            ",
+            principal,
+            cal_id,
+            start,
+            end,
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(|row| row.try_into())
+        .collect()
+    }
+
+    async fn _get_object<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+    ) -> Result<CalendarObject, Error> {
+        sqlx::query_as!(
+            CalendarObjectRow,
+            "SELECT id, ics FROM calendarobjects WHERE (principal, cal_id, id) = ($1, $2, $3)",
+            principal,
+            cal_id,
+            object_id
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(crate::Error::from)?
+        .try_into()
+    }
+
+    #[instrument]
+    async fn _put_object<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: String,
+        cal_id: String,
+        object: CalendarObject,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        // TODO: Prevent objects from being commited to a subscription calendar
+        let (object_id, ics) = (object.get_id(), object.get_ics());
+
+        let first_occurence = object
+            .get_first_occurence()
+            .ok()
+            .flatten()
+            .as_ref()
+            .map(CalDateTime::date);
+        let last_occurence = object
+            .get_last_occurence()
+            .ok()
+            .flatten()
+            .as_ref()
+            .map(CalDateTime::date);
+        let etag = object.get_etag();
+        let object_type = object.get_object_type() as i16;
+
+        (if overwrite {
+            sqlx::query!(
+                r#"INSERT INTO calendarobjects (principal, cal_id, id, ics, first_occurence, last_occurence, etag, object_type) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                    ON CONFLICT (principal, cal_id, id) DO UPDATE
+                    SET ics = excluded.ics, first_occurence = excluded.first_occurence, last_occurence = excluded.last_occurence,
+                        etag = excluded.etag, object_type = excluded.object_type,
+                        updated_at = excluded.updated_at, deleted_at = NULL"#,
+                principal,
+                cal_id,
+                object_id,
+                ics,
+                first_occurence,
+                last_occurence,
+                etag,
+                object_type,
+            )
+        } else {
+            // If the object already exists a database error is thrown and handled in error.rs
+            sqlx::query!(
+                "INSERT INTO calendarobjects (principal, cal_id, id, ics, first_occurence, last_occurence, etag, object_type) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                principal,
+                cal_id,
+                object_id,
+                ics,
+                first_occurence,
+                last_occurence,
+                etag,
+                object_type,
+            )
+        })
+        .execute(executor)
+        .await
+        .map_err(crate::Error::from)?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn _delete_object<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        cal_id: &str,
+        id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        match use_trashbin {
+            true => {
+                sqlx::query!(
+                    "UPDATE calendarobjects SET deleted_at = (NOW() AT TIME ZONE 'UTC'), updated_at = (NOW() AT TIME ZONE 'UTC') WHERE (principal, cal_id, id) = ($1, $2, $3)",
+                    principal,
+                    cal_id,
+                    id
+                )
+                .execute(executor)
+                .await.map_err(crate::Error::from)?;
+            }
+            false => {
+                sqlx::query!(
+                    "DELETE FROM calendarobjects WHERE (principal, cal_id, id) = ($1, $2, $3)",
+                    principal,
+                    cal_id,
+                    id
+                )
+                .execute(executor)
+                .await
+                .map_err(crate::Error::from)?;
+            }
+        };
+        Ok(())
+    }
+
+    #[instrument]
+    async fn _restore_object<'e, E: Executor<'e, Database = Postgres>>(
+        executor: E,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"UPDATE calendarobjects SET deleted_at = NULL, updated_at = (NOW() AT TIME ZONE 'UTC') WHERE (principal, cal_id, id) = ($1, $2, $3)"#,
+            principal,
+            cal_id,
+            object_id
+        )
+        .execute(executor)
+        .await.map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    // Both changelogs are written in the same transaction
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_or_move_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+        remove_source: bool,
+    ) -> Result<(), Error> {
+        if (cal_id, object_id) == (dest_cal_id, dest_object_id) {
+            return Err(Error::AlreadyExists);
+        }
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        let object = Self::_get_object(&mut *tx, principal, cal_id, object_id).await?;
+        let object =
+            CalendarObject::from_ics(dest_object_id.to_owned(), object.get_ics().to_owned())?;
+        Self::_put_object(
+            &mut *tx,
+            principal.to_owned(),
+            dest_cal_id.to_owned(),
+            object,
+            overwrite,
+        )
+        .await?;
+        let mut synctokens = vec![(
+            dest_cal_id,
+            log_object_operation(
+                &mut tx,
+                principal,
+                dest_cal_id,
+                dest_object_id,
+                ChangeOperation::Add,
+            )
+            .await
+            .map_err(crate::Error::from)?,
+        )];
+
+        if remove_source {
+            Self::_delete_object(&mut *tx, principal, cal_id, object_id, false).await?;
+            synctokens.push((
+                cal_id,
+                log_object_operation(
+                    &mut tx,
+                    principal,
+                    cal_id,
+                    object_id,
+                    ChangeOperation::Delete,
+                )
+                .await
+                .map_err(crate::Error::from)?,
+            ));
+        }
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        for (cal_id, synctoken) in synctokens {
+            if let Err(err) = self.sender.try_send(CollectionOperation {
+                r#type: CollectionOperationType::Object,
+                domain: rustical_store::CollectionOperationDomain::Calendar,
+                topic: self.get_calendar(principal, cal_id).await?.push_topic,
+                sync_token: Some(synctoken),
+            }) {
+                error!("Push notification about changed calendar failed: {err}");
+            };
+        }
+        Ok(())
+    }
+
+    async fn _sync_changes<'a, A: Acquire<'a, Database = Postgres>>(
+        acquire: A,
+        principal: &str,
+        cal_id: &str,
+        synctoken: i64,
+    ) -> Result<(Vec<CalendarObject>, Vec<String>, i64), Error> {
+        struct Row {
+            object_id: String,
+            synctoken: i64,
+        }
+
+        let mut conn = acquire.acquire().await.map_err(crate::Error::from)?;
+
+        let changes = sqlx::query_as!(
+            Row,
+            r#"
+                SELECT DISTINCT object_id, synctoken from calendarobjectchangelog
+                WHERE (principal, cal_id) = ($1, $2) AND synctoken > $3
+                ORDER BY synctoken ASC
+            "#,
+            principal,
+            cal_id,
+            synctoken
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(crate::Error::from)?;
+
+        let mut objects = vec![];
+        let mut deleted_objects = vec![];
+
+        let new_synctoken = changes
+            .last()
+            .map(|&Row { synctoken, .. }| synctoken)
+            .unwrap_or(0);
+
+        for Row { object_id, .. } in changes {
+            match Self::_get_object(&mut *conn, principal, cal_id, &object_id).await {
+                Ok(object) => objects.push(object),
+                Err(rustical_store::Error::NotFound) => deleted_objects.push(object_id),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok((objects, deleted_objects, new_synctoken))
+    }
+}
+
+#[async_trait]
+impl CalendarStore for PostgresCalendarStore {
+    #[instrument]
+    async fn get_calendar(&self, principal: &str, id: &str) -> Result<Calendar, Error> {
+        Self::_get_calendar(&self.db, principal, id).await
+    }
+
+    #[instrument]
+    async fn get_calendars(&self, principal: &str) -> Result<Vec<Calendar>, Error> {
+        Self::_get_calendars(&self.db, principal).await
+    }
+
+    #[instrument]
+    async fn get_deleted_calendars(&self, principal: &str) -> Result<Vec<Calendar>, Error> {
+        Self::_get_deleted_calendars(&self.db, principal).await
+    }
+
+    #[instrument]
+    async fn insert_calendar(&self, calendar: Calendar) -> Result<(), Error> {
+        Self::_insert_calendar(&self.db, calendar).await
+    }
+
+    #[instrument]
+    async fn update_calendar(
+        &self,
+        principal: String,
+        id: String,
+        calendar: Calendar,
+    ) -> Result<(), Error> {
+        Self::_update_calendar(&self.db, principal, id, calendar).await
+    }
+
+    // Does not actually delete the calendar but just disables it
+    #[instrument]
+    async fn delete_calendar(
+        &self,
+        principal: &str,
+        id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        let cal = match Self::_get_calendar(&mut *tx, principal, id).await {
+            Ok(cal) => Some(cal),
+            Err(Error::NotFound) => None,
+            Err(err) => return Err(err),
+        };
+
+        Self::_delete_calendar(&mut *tx, principal, id, use_trashbin).await?;
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        if let Some(cal) = cal {
+            if let Err(err) = self.sender.try_send(CollectionOperation {
+                r#type: CollectionOperationType::Delete,
+                domain: rustical_store::CollectionOperationDomain::Calendar,
+                topic: cal.push_topic,
+                sync_token: None,
+            }) {
+                error!("Push notification about deleted calendar failed: {err}");
+            };
+        }
+        Ok(())
+    }
+
+    #[instrument]
+    async fn restore_calendar(&self, principal: &str, id: &str) -> Result<(), Error> {
+        Self::_restore_calendar(&self.db, principal, id).await
+    }
+
+    #[instrument]
+    async fn calendar_query(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        query: CalendarQuery,
+    ) -> Result<Vec<CalendarObject>, Error> {
+        Self::_calendar_query(&self.db, principal, cal_id, query).await
+    }
+
+    #[instrument]
+    async fn get_objects(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<CalendarObject>, Error> {
+        Self::_get_objects(&self.db, principal, cal_id).await
+    }
+
+    #[instrument]
+    async fn get_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+    ) -> Result<CalendarObject, Error> {
+        Self::_get_object(&self.db, principal, cal_id, object_id).await
+    }
+
+    #[instrument]
+    async fn put_object(
+        &self,
+        principal: String,
+        cal_id: String,
+        object: CalendarObject,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        // TODO: Prevent objects from being commited to a subscription calendar
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        let object_id = object.get_id().to_owned();
+
+        Self::_put_object(
+            &mut *tx,
+            principal.to_owned(),
+            cal_id.to_owned(),
+            object,
+            overwrite,
+        )
+        .await?;
+
+        let synctoken = log_object_operation(
+            &mut tx,
+            &principal,
+            &cal_id,
+            &object_id,
+            ChangeOperation::Add,
+        )
+        .await?;
+
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        if let Err(err) = self.sender.try_send(CollectionOperation {
+            r#type: CollectionOperationType::Object,
+            domain: rustical_store::CollectionOperationDomain::Calendar,
+            topic: self.get_calendar(&principal, &cal_id).await?.push_topic,
+            sync_token: Some(synctoken),
+        }) {
+            error!("Push notification about deleted calendar failed: {err}");
+        };
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        Self::_delete_object(&mut *tx, principal, cal_id, id, use_trashbin).await?;
+
+        let synctoken =
+            log_object_operation(&mut tx, principal, cal_id, id, ChangeOperation::Delete).await?;
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        if let Err(err) = self.sender.try_send(CollectionOperation {
+            r#type: CollectionOperationType::Object,
+            domain: rustical_store::CollectionOperationDomain::Calendar,
+            topic: self.get_calendar(principal, cal_id).await?.push_topic,
+            sync_token: Some(synctoken),
+        }) {
+            error!("Push notification about deleted calendar failed: {err}");
+        };
+        Ok(())
+    }
+
+    #[instrument]
+    async fn restore_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+    ) -> Result<(), Error> {
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        Self::_restore_object(&mut *tx, principal, cal_id, object_id).await?;
+
+        let synctoken =
+            log_object_operation(&mut tx, principal, cal_id, object_id, ChangeOperation::Add)
+                .await?;
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        if let Err(err) = self.sender.try_send(CollectionOperation {
+            r#type: CollectionOperationType::Object,
+            domain: rustical_store::CollectionOperationDomain::Calendar,
+            topic: self.get_calendar(principal, cal_id).await?.push_topic,
+            sync_token: Some(synctoken),
+        }) {
+            error!("Push notification about deleted calendar failed: {err}");
+        };
+        Ok(())
+    }
+
+    #[instrument]
+    async fn copy_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            cal_id,
+            object_id,
+            dest_cal_id,
+            dest_object_id,
+            overwrite,
+            false,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn move_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            cal_id,
+            object_id,
+            dest_cal_id,
+            dest_object_id,
+            overwrite,
+            true,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn sync_changes(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        synctoken: i64,
+    ) -> Result<(Vec<CalendarObject>, Vec<String>, i64), Error> {
+        Self::_sync_changes(&self.db, principal, cal_id, synctoken).await
+    }
+
+    #[instrument]
+    async fn get_inbox_objects(&self, principal: &str) -> Result<Vec<CalendarObject>, Error> {
+        sqlx::query_as!(
+            CalendarObjectRow,
+            "SELECT id, ics FROM scheduleinbox WHERE principal = $1 ORDER BY created_at",
+            principal
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(|row| row.try_into())
+        .collect()
+    }
+
+    #[instrument]
+    async fn get_inbox_object(
+        &self,
+        principal: &str,
+        object_id: &str,
+    ) -> Result<CalendarObject, Error> {
+        sqlx::query_as!(
+            CalendarObjectRow,
+            "SELECT id, ics FROM scheduleinbox WHERE (principal, id) = ($1, $2)",
+            principal,
+            object_id
+        )
+        .fetch_one(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .try_into()
+    }
+
+    #[instrument]
+    async fn put_inbox_object(
+        &self,
+        principal: String,
+        object: CalendarObject,
+    ) -> Result<(), Error> {
+        let (object_id, ics) = (object.get_id(), object.get_ics());
+        sqlx::query!(
+            "INSERT INTO scheduleinbox (principal, id, ics) VALUES ($1, $2, $3)",
+            principal,
+            object_id,
+            ics
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_inbox_object(&self, principal: &str, object_id: &str) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM scheduleinbox WHERE (principal, id) = ($1, $2)",
+            principal,
+            object_id
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_calendar_shares(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<Share>, Error> {
+        Ok(sqlx::query_as!(
+            ShareRow,
+            r#"SELECT principal, cal_id AS collection_id, sharee, read_write
+                FROM calendarshares
+                WHERE (principal, cal_id) = ($1, $2)
+                ORDER BY sharee"#,
+            principal,
+            cal_id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(Share::from)
+        .collect())
+    }
+
+    #[instrument]
+    async fn get_received_calendar_shares(&self, sharee: &str) -> Result<Vec<Share>, Error> {
+        // Shares of calendars in the trash bin are hidden
+        Ok(sqlx::query_as!(
+            ShareRow,
+            r#"SELECT calendarshares.principal, cal_id AS collection_id, sharee, read_write
+                FROM calendarshares
+                INNER JOIN calendars
+                    ON (calendars.principal, calendars.id) = (calendarshares.principal, cal_id)
+                WHERE sharee = $1 AND calendars.deleted_at IS NULL
+                ORDER BY calendarshares.principal, cal_id"#,
+            sharee
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(Share::from)
+        .collect())
+    }
+
+    #[instrument]
+    async fn put_calendar_share(&self, share: Share) -> Result<(), Error> {
+        let read_write = share.access == ShareAccess::ReadWrite;
+        sqlx::query!(
+            r#"INSERT INTO calendarshares (principal, cal_id, sharee, read_write)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (principal, cal_id, sharee) DO UPDATE SET read_write = excluded.read_write"#,
+            share.principal,
+            share.collection_id,
+            share.sharee,
+            read_write
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_calendar_share(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        sharee: &str,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM calendarshares WHERE (principal, cal_id, sharee) = ($1, $2, $3)",
+            principal,
+            cal_id,
+            sharee
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_calendar_feeds(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<CalendarFeed>, Error> {
+        Ok(sqlx::query_as!(
+            CalendarFeedRow,
+            r#"SELECT token, principal, cal_id, privacy, created_at
+                FROM calendarfeeds
+                WHERE (principal, cal_id) = ($1, $2)
+                ORDER BY created_at"#,
+            principal,
+            cal_id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(CalendarFeed::from)
+        .collect())
+    }
+
+    #[instrument(skip(token))]
+    async fn get_calendar_feed(&self, token: &str) -> Result<CalendarFeed, Error> {
+        Ok(sqlx::query_as!(
+            CalendarFeedRow,
+            r#"SELECT token, principal, cal_id, privacy, created_at
+                FROM calendarfeeds
+                WHERE token = $1"#,
+            token
+        )
+        .fetch_one(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into())
+    }
+
+    #[instrument(skip(feed))]
+    async fn insert_calendar_feed(&self, feed: CalendarFeed) -> Result<(), Error> {
+        let privacy = feed.privacy.as_str();
+        let created_at = feed
+            .created_at
+            .unwrap_or_else(|| chrono::Utc::now().naive_utc());
+        sqlx::query!(
+            r#"INSERT INTO calendarfeeds (token, principal, cal_id, privacy, created_at)
+                VALUES ($1, $2, $3, $4, $5)"#,
+            feed.token,
+            feed.principal,
+            feed.cal_id,
+            privacy,
+            created_at
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument(skip(token))]
+    async fn delete_calendar_feed(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        token: &str,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM calendarfeeds WHERE (principal, cal_id, token) = ($1, $2, $3)",
+            principal,
+            cal_id,
+            token
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_subscribed_calendars(&self) -> Result<Vec<Calendar>, Error> {
+        let cals = sqlx::query_as!(
+            CalendarRow,
+            r#"SELECT *
+                FROM calendars
+                WHERE subscription_url IS NOT NULL AND deleted_at IS NULL"#,
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(cals.into_iter().map(Calendar::from).collect())
+    }
+
+    #[instrument]
+    async fn get_webcal_status(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<WebcalStatus, Error> {
+        Ok(sqlx::query_as!(
+            WebcalStatus,
+            r#"SELECT etag, last_modified, last_refresh, last_error
+                FROM webcalstatus
+                WHERE (principal, cal_id) = ($1, $2)"#,
+            principal,
+            cal_id
+        )
+        .fetch_optional(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .unwrap_or_default())
+    }
+
+    #[instrument]
+    async fn put_webcal_status(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        status: WebcalStatus,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"INSERT INTO webcalstatus (principal, cal_id, etag, last_modified, last_refresh, last_error)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (principal, cal_id) DO UPDATE
+                SET etag = excluded.etag, last_modified = excluded.last_modified,
+                    last_refresh = excluded.last_refresh, last_error = excluded.last_error"#,
+            principal,
+            cal_id,
+            status.etag,
+            status.last_modified,
+            status.last_refresh,
+            status.last_error
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+// Logs an operation to the events
+async fn log_object_operation(
+    tx: &mut Transaction<'_, Postgres>,
+    principal: &str,
+    cal_id: &str,
+    object_id: &str,
+    operation: ChangeOperation,
+) -> Result<String, Error> {
+    struct Synctoken {
+        synctoken: i64,
+    }
+    let Synctoken { synctoken } = sqlx::query_as!(
+        Synctoken,
+        r#"
+        UPDATE calendars
+        SET synctoken = synctoken + 1
+        WHERE (principal, id) = ($1, $2)
+        RETURNING synctoken"#,
+        principal,
+        cal_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(crate::Error::from)?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO calendarobjectchangelog (principal, cal_id, object_id, "operation", synctoken)
+        VALUES ($1, $2, $3, $4, $5)"#,
+        principal,
+        cal_id,
+        object_id,
+        operation as i16,
+        synctoken
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(crate::Error::from)?;
+    Ok(format_synctoken(synctoken))
+}
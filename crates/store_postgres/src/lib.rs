@@ -0,0 +1,82 @@
+use rustical_store::{Share, ShareAccess};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    Executor, PgPool,
+};
+use std::str::FromStr;
+
+pub mod addressbook_store;
+pub mod calendar_store;
+pub mod error;
+pub mod subscription_store;
+
+pub use error::Error;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(i16)]
+pub(crate) enum ChangeOperation {
+    // There's no distinction between Add and Modify
+    Add = 0,
+    Delete = 1,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ShareRow {
+    principal: String,
+    collection_id: String,
+    sharee: String,
+    read_write: bool,
+}
+
+impl From<ShareRow> for Share {
+    fn from(value: ShareRow) -> Self {
+        Self {
+            principal: value.principal,
+            collection_id: value.collection_id,
+            sharee: value.sharee,
+            access: if value.read_write {
+                ShareAccess::ReadWrite
+            } else {
+                ShareAccess::Read
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PostgresStore {
+    db: PgPool,
+}
+
+impl PostgresStore {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+pub async fn create_db_pool(db_url: &str, migrate: bool) -> Result<PgPool, sqlx::Error> {
+    let db = PgPoolOptions::new().connect(db_url).await?;
+    if migrate {
+        println!("Running database migrations");
+        sqlx::migrate!("./migrations").run(&db).await?;
+    }
+    Ok(db)
+}
+
+// Every test database lives in its own schema so tests can run in parallel
+pub async fn create_test_db(db_url: &str) -> Result<PgPool, sqlx::Error> {
+    let schema = format!("test_{}", uuid::Uuid::new_v4().simple());
+    PgPool::connect(db_url)
+        .await?
+        .execute(format!("CREATE SCHEMA {schema}").as_str())
+        .await?;
+
+    let options = PgConnectOptions::from_str(db_url)?.options([("search_path", schema)]);
+    let db = PgPoolOptions::new().connect_with(options).await?;
+    sqlx::migrate!("./migrations").run(&db).await?;
+    Ok(db)
+}
+
+pub async fn create_test_store(db_url: &str) -> Result<PostgresStore, sqlx::Error> {
+    Ok(PostgresStore::new(create_test_db(db_url).await?))
+}
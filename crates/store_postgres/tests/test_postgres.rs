@@ -0,0 +1,377 @@
+use chrono::NaiveDate;
+use rustical_store::{
+    calendar::{CalendarFeed, FeedPrivacy, WebcalStatus},
+    calendar_store::CalendarQuery,
+    AddressObject, Addressbook, AddressbookStore, Calendar, CalendarObject, CalendarStore, Share,
+    ShareAccess, Subscription, SubscriptionStore,
+};
+use rustical_store_postgres::{
+    addressbook_store::PostgresAddressbookStore, calendar_store::PostgresCalendarStore,
+    create_test_db, create_test_store,
+};
+
+// These tests need a running Postgres and are ignored by default, run them with
+// RUSTICAL_TEST_POSTGRES_URL=postgres://postgres@localhost/postgres cargo test -- --ignored
+fn test_db_url() -> String {
+    std::env::var("RUSTICAL_TEST_POSTGRES_URL").expect("RUSTICAL_TEST_POSTGRES_URL must be set")
+}
+
+fn event(uid: &str, date: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VEVENT\r\nUID:{uid}\r\nDTSTAMP:20240101T000000Z\r\nDTSTART;VALUE=DATE:{date}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+    )
+}
+
+const VCARD: &str = "BEGIN:VCARD\r\nVERSION:4.0\r\nUID:alice\r\nFN:Alice\r\nEND:VCARD\r\n";
+
+async fn cal_store() -> PostgresCalendarStore {
+    let (send, _recv) = tokio::sync::mpsc::channel(100);
+    PostgresCalendarStore::new(create_test_db(&test_db_url()).await.unwrap(), send)
+}
+
+async fn insert_calendar(store: &impl CalendarStore, id: &str) {
+    store
+        .insert_calendar(Calendar {
+            principal: "testuser".to_owned(),
+            id: id.to_owned(),
+            push_topic: uuid::Uuid::new_v4().to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs RUSTICAL_TEST_POSTGRES_URL"]
+async fn test_calendar_sync() {
+    let store = cal_store().await;
+    insert_calendar(&store, "test").await;
+
+    let object = CalendarObject::from_ics("a".to_owned(), event("a", "20240101")).unwrap();
+    store
+        .put_object("testuser".to_owned(), "test".to_owned(), object, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .get_object("testuser", "test", "a")
+            .await
+            .unwrap()
+            .get_ics(),
+        event("a", "20240101")
+    );
+
+    // Objects can only be overwritten explicitly
+    let object = CalendarObject::from_ics("a".to_owned(), event("a", "20240201")).unwrap();
+    assert!(matches!(
+        store
+            .put_object(
+                "testuser".to_owned(),
+                "test".to_owned(),
+                object.clone(),
+                false
+            )
+            .await,
+        Err(rustical_store::Error::AlreadyExists)
+    ));
+    store
+        .put_object("testuser".to_owned(), "test".to_owned(), object, true)
+        .await
+        .unwrap();
+    let object = CalendarObject::from_ics("b".to_owned(), event("b", "20240301")).unwrap();
+    store
+        .put_object("testuser".to_owned(), "test".to_owned(), object, false)
+        .await
+        .unwrap();
+
+    let (changed, deleted, synctoken) = store.sync_changes("testuser", "test", 0).await.unwrap();
+    assert_eq!(synctoken, 3);
+    assert_eq!(changed.len(), 3);
+    assert!(deleted.is_empty());
+    assert_eq!(
+        store
+            .get_calendar("testuser", "test")
+            .await
+            .unwrap()
+            .synctoken,
+        3
+    );
+
+    store
+        .delete_object("testuser", "test", "b", false)
+        .await
+        .unwrap();
+    let (changed, deleted, synctoken) = store
+        .sync_changes("testuser", "test", synctoken)
+        .await
+        .unwrap();
+    assert!(changed.is_empty());
+    assert_eq!(deleted, vec!["b".to_owned()]);
+    assert_eq!(synctoken, 4);
+
+    // Objects in the trash bin are still returned by get_object
+    store
+        .delete_object("testuser", "test", "a", true)
+        .await
+        .unwrap();
+    assert!(store
+        .get_objects("testuser", "test")
+        .await
+        .unwrap()
+        .is_empty());
+    store.restore_object("testuser", "test", "a").await.unwrap();
+    assert_eq!(
+        store.get_objects("testuser", "test").await.unwrap().len(),
+        1
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs RUSTICAL_TEST_POSTGRES_URL"]
+async fn test_calendar_query() {
+    let store = cal_store().await;
+    insert_calendar(&store, "test").await;
+    for (id, date) in [("jan", "20240115"), ("mar", "20240315")] {
+        let object = CalendarObject::from_ics(id.to_owned(), event(id, date)).unwrap();
+        store
+            .put_object("testuser".to_owned(), "test".to_owned(), object, false)
+            .await
+            .unwrap();
+    }
+
+    let objects = store
+        .calendar_query(
+            "testuser",
+            "test",
+            CalendarQuery {
+                time_start: NaiveDate::from_ymd_opt(2024, 3, 1),
+                time_end: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        objects.iter().map(|obj| obj.get_id()).collect::<Vec<_>>(),
+        vec!["mar"]
+    );
+    let objects = store
+        .calendar_query("testuser", "test", CalendarQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(objects.len(), 2);
+}
+
+#[tokio::test]
+#[ignore = "needs RUSTICAL_TEST_POSTGRES_URL"]
+async fn test_calendar_trashbin_and_shares() {
+    let store = cal_store().await;
+    insert_calendar(&store, "home").await;
+    insert_calendar(&store, "work").await;
+    assert!(matches!(
+        store
+            .insert_calendar(Calendar {
+                principal: "testuser".to_owned(),
+                id: "home".to_owned(),
+                ..Default::default()
+            })
+            .await,
+        Err(rustical_store::Error::AlreadyExists)
+    ));
+
+    store
+        .put_calendar_share(Share {
+            principal: "testuser".to_owned(),
+            collection_id: "home".to_owned(),
+            sharee: "bob".to_owned(),
+            access: ShareAccess::Read,
+        })
+        .await
+        .unwrap();
+    // Sharing again updates the access
+    store
+        .put_calendar_share(Share {
+            principal: "testuser".to_owned(),
+            collection_id: "home".to_owned(),
+            sharee: "bob".to_owned(),
+            access: ShareAccess::ReadWrite,
+        })
+        .await
+        .unwrap();
+    let shares = store.get_received_calendar_shares("bob").await.unwrap();
+    assert_eq!(shares.len(), 1);
+    assert_eq!(shares[0].access, ShareAccess::ReadWrite);
+
+    store
+        .delete_calendar("testuser", "home", true)
+        .await
+        .unwrap();
+    assert_eq!(store.get_calendars("testuser").await.unwrap().len(), 1);
+    assert_eq!(
+        store.get_deleted_calendars("testuser").await.unwrap().len(),
+        1
+    );
+    assert!(store
+        .get_received_calendar_shares("bob")
+        .await
+        .unwrap()
+        .is_empty());
+
+    store.restore_calendar("testuser", "home").await.unwrap();
+    assert_eq!(store.get_calendars("testuser").await.unwrap().len(), 2);
+
+    store
+        .delete_calendar("testuser", "home", false)
+        .await
+        .unwrap();
+    assert!(matches!(
+        store.get_calendar("testuser", "home").await,
+        Err(rustical_store::Error::NotFound)
+    ));
+}
+
+#[tokio::test]
+#[ignore = "needs RUSTICAL_TEST_POSTGRES_URL"]
+async fn test_calendar_feeds_and_webcal_status() {
+    let store = cal_store().await;
+    insert_calendar(&store, "test").await;
+
+    let feed = CalendarFeed::new(
+        "testuser".to_owned(),
+        "test".to_owned(),
+        FeedPrivacy::BusyOnly,
+    );
+    store.insert_calendar_feed(feed.clone()).await.unwrap();
+    let stored = store.get_calendar_feed(&feed.token).await.unwrap();
+    assert_eq!(stored.privacy, FeedPrivacy::BusyOnly);
+    assert_eq!(
+        store
+            .get_calendar_feeds("testuser", "test")
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    assert_eq!(
+        store.get_webcal_status("testuser", "test").await.unwrap(),
+        WebcalStatus::default()
+    );
+    for etag in ["\"1\"", "\"2\""] {
+        store
+            .put_webcal_status(
+                "testuser",
+                "test",
+                WebcalStatus {
+                    etag: Some(etag.to_owned()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        store
+            .get_webcal_status("testuser", "test")
+            .await
+            .unwrap()
+            .etag
+            .as_deref(),
+        Some("\"2\"")
+    );
+
+    // Deleting the calendar removes its feeds
+    store
+        .delete_calendar("testuser", "test", false)
+        .await
+        .unwrap();
+    assert!(store.get_calendar_feed(&feed.token).await.is_err());
+}
+
+#[tokio::test]
+#[ignore = "needs RUSTICAL_TEST_POSTGRES_URL"]
+async fn test_copy_move_object() {
+    let store = cal_store().await;
+    insert_calendar(&store, "home").await;
+    insert_calendar(&store, "work").await;
+    let object = CalendarObject::from_ics("event".to_owned(), event("event", "20240101")).unwrap();
+    store
+        .put_object("testuser".to_owned(), "home".to_owned(), object, false)
+        .await
+        .unwrap();
+
+    store
+        .move_object("testuser", "home", "event", "work", "moved", false)
+        .await
+        .unwrap();
+    let (_, deleted, _) = store.sync_changes("testuser", "home", 1).await.unwrap();
+    assert_eq!(deleted, vec!["event".to_owned()]);
+    let (changed, _, _) = store.sync_changes("testuser", "work", 0).await.unwrap();
+    assert_eq!(
+        changed.iter().map(|obj| obj.get_id()).collect::<Vec<_>>(),
+        vec!["moved"]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs RUSTICAL_TEST_POSTGRES_URL"]
+async fn test_addressbook_sync() {
+    let url = test_db_url();
+    let (send, _recv) = tokio::sync::mpsc::channel(100);
+    let store = PostgresAddressbookStore::new(create_test_db(&url).await.unwrap(), send);
+    store
+        .insert_addressbook(Addressbook {
+            principal: "testuser".to_owned(),
+            id: "contacts".to_owned(),
+            displayname: None,
+            description: None,
+            deleted_at: None,
+            synctoken: 0,
+            push_topic: uuid::Uuid::new_v4().to_string(),
+        })
+        .await
+        .unwrap();
+
+    let object = AddressObject::from_vcf("alice".to_owned(), VCARD.to_owned()).unwrap();
+    store
+        .put_object("testuser".to_owned(), "contacts".to_owned(), object, false)
+        .await
+        .unwrap();
+    store
+        .delete_object("testuser", "contacts", "alice", false)
+        .await
+        .unwrap();
+
+    let (changed, deleted, synctoken) =
+        store.sync_changes("testuser", "contacts", 0).await.unwrap();
+    assert!(changed.is_empty());
+    assert!(deleted.contains(&"alice".to_owned()));
+    assert_eq!(synctoken, 2);
+}
+
+#[tokio::test]
+#[ignore = "needs RUSTICAL_TEST_POSTGRES_URL"]
+async fn test_subscriptions() {
+    let url = test_db_url();
+    let store = create_test_store(&url).await.unwrap();
+    let expiration = NaiveDate::from_ymd_opt(2030, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    for push_resource in ["https://push.example.com/1", "https://push.example.com/2"] {
+        store
+            .upsert_subscription(Subscription {
+                id: "sub".to_owned(),
+                topic: "topic".to_owned(),
+                expiration,
+                push_resource: push_resource.to_owned(),
+            })
+            .await
+            .unwrap();
+    }
+    let subscriptions = store.get_subscriptions("topic").await.unwrap();
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(subscriptions[0].push_resource, "https://push.example.com/2");
+
+    store.delete_subscription("sub").await.unwrap();
+    assert!(store.get_subscription("sub").await.is_err());
+}
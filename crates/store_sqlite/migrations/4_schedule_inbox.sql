@@ -0,0 +1,8 @@
+-- Scheduling messages delivered to a principal (RFC 6638)
+CREATE TABLE scheduleinbox (
+    principal TEXT NOT NULL,
+    id TEXT NOT NULL,
+    ics TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (principal, id)
+);
@@ -0,0 +1,24 @@
+-- Collections shared with other principals
+CREATE TABLE calendarshares (
+    principal TEXT NOT NULL,
+    cal_id TEXT NOT NULL,
+    sharee TEXT NOT NULL,
+    read_write BOOLEAN NOT NULL,
+    PRIMARY KEY (principal, cal_id, sharee),
+    FOREIGN KEY (principal, cal_id)
+    REFERENCES calendars (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_calshares_sharee ON calendarshares (sharee);
+
+CREATE TABLE addressbookshares (
+    principal TEXT NOT NULL,
+    addressbook_id TEXT NOT NULL,
+    sharee TEXT NOT NULL,
+    read_write BOOLEAN NOT NULL,
+    PRIMARY KEY (principal, addressbook_id, sharee),
+    FOREIGN KEY (principal, addressbook_id)
+    REFERENCES addressbooks (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_addrshares_sharee ON addressbookshares (sharee);
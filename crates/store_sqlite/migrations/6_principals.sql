@@ -0,0 +1,34 @@
+CREATE TABLE principals (
+    id TEXT NOT NULL PRIMARY KEY,
+    displayname TEXT,
+    principal_type TEXT NOT NULL,
+    password_hash TEXT
+);
+
+-- Groups a principal is a member of
+CREATE TABLE memberships (
+    principal TEXT NOT NULL,
+    membership TEXT NOT NULL,
+    PRIMARY KEY (principal, membership),
+    FOREIGN KEY (principal) REFERENCES principals (id) ON DELETE CASCADE
+);
+
+-- Principals whose calendars a principal manages as a delegate
+CREATE TABLE calendarproxies (
+    principal TEXT NOT NULL,
+    delegator TEXT NOT NULL,
+    read_write BOOLEAN NOT NULL,
+    PRIMARY KEY (principal, delegator),
+    FOREIGN KEY (principal) REFERENCES principals (id) ON DELETE CASCADE
+);
+
+CREATE TABLE apptokens (
+    id TEXT NOT NULL PRIMARY KEY,
+    principal TEXT NOT NULL,
+    name TEXT NOT NULL,
+    token TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (principal) REFERENCES principals (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_apptokens_principal ON apptokens (principal);
@@ -0,0 +1 @@
+ALTER TABLE apptokens ADD COLUMN last_used DATETIME;
@@ -0,0 +1,12 @@
+-- Public read-only feeds of calendars, the token is the only credential
+CREATE TABLE calendarfeeds (
+    token TEXT NOT NULL PRIMARY KEY,
+    principal TEXT NOT NULL,
+    cal_id TEXT NOT NULL,
+    privacy TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (principal, cal_id)
+    REFERENCES calendars (principal, id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_calfeeds_calendar ON calendarfeeds (principal, cal_id);
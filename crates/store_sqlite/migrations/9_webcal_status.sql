@@ -0,0 +1,12 @@
+-- Refresh state of calendars subscribed to a webcal feed
+CREATE TABLE webcalstatus (
+    principal TEXT NOT NULL,
+    cal_id TEXT NOT NULL,
+    etag TEXT,
+    last_modified TEXT,
+    last_refresh DATETIME,
+    last_error TEXT,
+    PRIMARY KEY (principal, cal_id),
+    FOREIGN KEY (principal, cal_id)
+    REFERENCES calendars (principal, id) ON DELETE CASCADE
+);
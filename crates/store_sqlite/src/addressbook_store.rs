@@ -1,9 +1,9 @@
-use super::ChangeOperation;
+use super::{ChangeOperation, ShareRow};
 use async_trait::async_trait;
 use derive_more::derive::Constructor;
 use rustical_store::{
     synctoken::format_synctoken, AddressObject, Addressbook, AddressbookStore, CollectionOperation,
-    CollectionOperationDomain, CollectionOperationType, Error,
+    CollectionOperationDomain, CollectionOperationType, Error, Share, ShareAccess,
 };
 use sqlx::{Acquire, Executor, Sqlite, SqlitePool, Transaction};
 use tokio::sync::mpsc::Sender;
@@ -191,9 +191,11 @@ impl SqliteAddressbookStore {
             Row,
             r#"
                 SELECT DISTINCT object_id, max(0, synctoken) as "synctoken!: i64" from addressobjectchangelog
-                WHERE synctoken > ?
+                WHERE (principal, addressbook_id) = (?1, ?2) AND synctoken > ?3
                 ORDER BY synctoken ASC
             "#,
+            principal,
+            addressbook_id,
             synctoken
         )
         .fetch_all(&mut *conn)
@@ -309,7 +311,8 @@ impl SqliteAddressbookStore {
             }
             false => {
                 sqlx::query!(
-                    "DELETE FROM addressobjects WHERE addressbook_id = ? AND id = ?",
+                    "DELETE FROM addressobjects WHERE (principal, addressbook_id, id) = (?, ?, ?)",
+                    principal,
                     addressbook_id,
                     object_id
                 )
@@ -337,6 +340,80 @@ impl SqliteAddressbookStore {
         .await.map_err(crate::Error::from)?;
         Ok(())
     }
+
+    // Both changelogs are written in the same transaction
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_or_move_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+        remove_source: bool,
+    ) -> Result<(), Error> {
+        if (addressbook_id, object_id) == (dest_addressbook_id, dest_object_id) {
+            return Err(Error::AlreadyExists);
+        }
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        let object = Self::_get_object(&mut *tx, principal, addressbook_id, object_id).await?;
+        let object =
+            AddressObject::from_vcf(dest_object_id.to_owned(), object.get_vcf().to_owned())?;
+        Self::_put_object(
+            &mut *tx,
+            principal.to_owned(),
+            dest_addressbook_id.to_owned(),
+            object,
+            overwrite,
+        )
+        .await?;
+        let mut synctokens = vec![(
+            dest_addressbook_id,
+            log_object_operation(
+                &mut tx,
+                principal,
+                dest_addressbook_id,
+                dest_object_id,
+                ChangeOperation::Add,
+            )
+            .await
+            .map_err(crate::Error::from)?,
+        )];
+
+        if remove_source {
+            Self::_delete_object(&mut *tx, principal, addressbook_id, object_id, false).await?;
+            synctokens.push((
+                addressbook_id,
+                log_object_operation(
+                    &mut tx,
+                    principal,
+                    addressbook_id,
+                    object_id,
+                    ChangeOperation::Delete,
+                )
+                .await
+                .map_err(crate::Error::from)?,
+            ));
+        }
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        for (addressbook_id, synctoken) in synctokens {
+            if let Err(err) = self.sender.try_send(CollectionOperation {
+                r#type: CollectionOperationType::Object,
+                domain: CollectionOperationDomain::Addressbook,
+                topic: self
+                    .get_addressbook(principal, addressbook_id)
+                    .await?
+                    .push_topic,
+                sync_token: Some(synctoken),
+            }) {
+                error!("Push notification about changed addressbook failed: {err}");
+            };
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -539,6 +616,50 @@ impl AddressbookStore for SqliteAddressbookStore {
         Ok(())
     }
 
+    #[instrument]
+    async fn copy_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            addressbook_id,
+            object_id,
+            dest_addressbook_id,
+            dest_object_id,
+            overwrite,
+            false,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn move_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            addressbook_id,
+            object_id,
+            dest_addressbook_id,
+            dest_object_id,
+            overwrite,
+            true,
+        )
+        .await
+    }
+
     #[instrument]
     async fn restore_object(
         &self,
@@ -574,6 +695,86 @@ impl AddressbookStore for SqliteAddressbookStore {
 
         Ok(())
     }
+
+    #[instrument]
+    async fn get_addressbook_shares(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Vec<Share>, Error> {
+        Ok(sqlx::query_as!(
+            ShareRow,
+            r#"SELECT principal, addressbook_id AS collection_id, sharee, read_write
+                FROM addressbookshares
+                WHERE (principal, addressbook_id) = (?, ?)
+                ORDER BY sharee"#,
+            principal,
+            addressbook_id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(Share::from)
+        .collect())
+    }
+
+    #[instrument]
+    async fn get_received_addressbook_shares(&self, sharee: &str) -> Result<Vec<Share>, Error> {
+        // Shares of addressbooks in the trash bin are hidden
+        Ok(sqlx::query_as!(
+            ShareRow,
+            r#"SELECT addressbookshares.principal, addressbook_id AS collection_id, sharee, read_write
+                FROM addressbookshares
+                INNER JOIN addressbooks
+                    ON (addressbooks.principal, addressbooks.id) = (addressbookshares.principal, addressbook_id)
+                WHERE sharee = ? AND addressbooks.deleted_at IS NULL
+                ORDER BY addressbookshares.principal, addressbook_id"#,
+            sharee
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(Share::from)
+        .collect())
+    }
+
+    #[instrument]
+    async fn put_addressbook_share(&self, share: Share) -> Result<(), Error> {
+        let read_write = share.access == ShareAccess::ReadWrite;
+        sqlx::query!(
+            r#"INSERT OR REPLACE INTO addressbookshares (principal, addressbook_id, sharee, read_write)
+                VALUES (?, ?, ?, ?)"#,
+            share.principal,
+            share.collection_id,
+            share.sharee,
+            read_write
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_addressbook_share(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        sharee: &str,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM addressbookshares WHERE (principal, addressbook_id, sharee) = (?, ?, ?)",
+            principal,
+            addressbook_id,
+            sharee
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
 }
 
 // Logs an operation to an address object
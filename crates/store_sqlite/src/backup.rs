@@ -0,0 +1,404 @@
+use crate::Error;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sqlx::{sqlite::SqliteConnectOptions, Connection, Executor, Row, SqliteConnection, SqlitePool};
+use std::{
+    fs::{self, File, TryLockError},
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const DB_EXTENSION: &str = ".db";
+const GZ_EXTENSION: &str = ".db.gz";
+// Connections that are just being closed may still hold a lock for a moment
+const LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+
+fn invalid_backup(message: String) -> Error {
+    rustical_store::Error::InvalidData(message).into()
+}
+
+fn timestamp() -> String {
+    chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string()
+}
+
+// Backups are named <database name>-<timestamp>.db(.gz) so they sort by age
+fn backup_prefix(db_path: &Path) -> String {
+    let name = db_path.file_stem().unwrap_or_default().to_string_lossy();
+    format!("{name}-")
+}
+
+fn is_backup_of(name: &str, prefix: &str) -> bool {
+    name.starts_with(prefix) && (name.ends_with(DB_EXTENSION) || name.ends_with(GZ_EXTENSION))
+}
+
+async fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> Result<T, Error> {
+    Ok(tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)??)
+}
+
+// VACUUM INTO reads a consistent snapshot, so this is safe while the server is writing
+pub async fn backup_db(db: &SqlitePool, dest: &Path) -> Result<(), Error> {
+    sqlx::query("VACUUM INTO ?")
+        .bind(dest.to_string_lossy())
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Writes a new backup into dir and removes all but the newest `keep` backups
+pub async fn create_backup(
+    db: &SqlitePool,
+    db_path: &Path,
+    dir: &Path,
+    keep: usize,
+    compress: bool,
+) -> Result<PathBuf, Error> {
+    fs::create_dir_all(dir)?;
+    let name = format!("{}{}", backup_prefix(db_path), timestamp());
+    // Incomplete backups never carry the final name
+    let tmp_path = dir.join(format!(".{name}.tmp"));
+    backup_db(db, &tmp_path).await?;
+
+    let path = if compress {
+        let path = dir.join(format!("{name}{GZ_EXTENSION}"));
+        let (src, dest) = (tmp_path.to_owned(), path.to_owned());
+        let result = spawn_blocking(move || {
+            let gz_path = dest.with_extension("gz.tmp");
+            let mut encoder = GzEncoder::new(File::create(&gz_path)?, Compression::default());
+            io::copy(&mut File::open(&src)?, &mut encoder)?;
+            encoder.finish()?.sync_all()?;
+            fs::rename(&gz_path, &dest)
+        })
+        .await;
+        fs::remove_file(&tmp_path)?;
+        result?;
+        path
+    } else {
+        let path = dir.join(format!("{name}{DB_EXTENSION}"));
+        fs::rename(&tmp_path, &path)?;
+        path
+    };
+
+    rotate_backups(db_path, dir, keep)?;
+    Ok(path)
+}
+
+// Backups of the database, oldest first
+pub fn list_backups(db_path: &Path, dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let prefix = backup_prefix(db_path);
+    let mut backups = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if is_backup_of(&entry.file_name().to_string_lossy(), &prefix) {
+            backups.push(entry.path());
+        }
+    }
+    backups.sort();
+    Ok(backups)
+}
+
+fn rotate_backups(db_path: &Path, dir: &Path, keep: usize) -> Result<(), Error> {
+    let backups = list_backups(db_path, dir)?;
+    let outdated = backups.len().saturating_sub(keep.max(1));
+    for backup in &backups[..outdated] {
+        fs::remove_file(backup)?;
+    }
+    Ok(())
+}
+
+// A backup must be intact and must not contain migrations this version doesn't know
+async fn check_backup(path: &Path) -> Result<(), Error> {
+    let db = SqlitePool::connect_with(SqliteConnectOptions::new().filename(path).read_only(true))
+        .await?;
+
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&db)
+        .await?;
+    if integrity != "ok" {
+        return Err(invalid_backup(format!("Backup is corrupted: {integrity}")));
+    }
+
+    let migrator = sqlx::migrate!("./migrations");
+    let applied = sqlx::query("SELECT version, checksum, success FROM _sqlx_migrations")
+        .fetch_all(&db)
+        .await
+        .map_err(|_| invalid_backup("Backup has no migration history".to_owned()))?;
+    for row in applied {
+        let version: i64 = row.try_get("version")?;
+        let checksum: Vec<u8> = row.try_get("checksum")?;
+        let success: bool = row.try_get("success")?;
+        let Some(migration) = migrator
+            .iter()
+            .find(|migration| migration.version == version)
+        else {
+            return Err(invalid_backup(format!(
+                "Backup contains migration {version} which is unknown to this version of RustiCal"
+            )));
+        };
+        if *migration.checksum != *checksum || !success {
+            return Err(invalid_backup(format!(
+                "Migration {version} in the backup doesn't match this version of RustiCal"
+            )));
+        }
+    }
+    db.close().await;
+    Ok(())
+}
+
+fn sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn in_use(db_path: &Path) -> Error {
+    invalid_backup(format!(
+        "{} is in use, stop RustiCal before restoring",
+        db_path.display()
+    ))
+}
+
+// Advisory lock on <database>.lock, SQLite's own locks don't show an idle server
+// The server holds it shared for as long as it runs, restoring needs it exclusively
+pub struct DbLock(File);
+
+impl DbLock {
+    fn open(db_path: &Path) -> Result<File, Error> {
+        Ok(File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(sidecar_path(db_path, ".lock"))?)
+    }
+
+    pub fn shared(db_path: &Path) -> Result<Self, Error> {
+        let file = Self::open(db_path)?;
+        match file.try_lock_shared() {
+            Ok(()) => Ok(Self(file)),
+            Err(TryLockError::WouldBlock) => Err(invalid_backup(format!(
+                "{} is being restored",
+                db_path.display()
+            ))),
+            Err(TryLockError::Error(err)) => Err(err.into()),
+        }
+    }
+
+    fn exclusive(db_path: &Path) -> Result<Self, Error> {
+        let file = Self::open(db_path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Self(file)),
+            Err(TryLockError::WouldBlock) => Err(in_use(db_path)),
+            Err(TryLockError::Error(err)) => Err(err.into()),
+        }
+    }
+}
+
+impl Drop for DbLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+// Holds a write lock on the database so that nobody writes to it while it's replaced
+// This catches processes not holding the DbLock, like a running CLI command
+async fn lock_db(db_path: &Path) -> Result<SqliteConnection, Error> {
+    let mut conn = SqliteConnection::connect_with(
+        &SqliteConnectOptions::new()
+            .filename(db_path)
+            .busy_timeout(LOCK_TIMEOUT),
+    )
+    .await?;
+    if conn.execute("BEGIN EXCLUSIVE").await.is_err() {
+        return Err(in_use(db_path));
+    }
+    Ok(conn)
+}
+
+// Replaces the database with a backup, the server must not be running
+// The previous database is kept as <database>.before-restore-<timestamp>, whose path is returned
+pub async fn restore_backup(backup: &Path, db_path: &Path) -> Result<Option<PathBuf>, Error> {
+    let _db_lock = DbLock::exclusive(db_path)?;
+    let lock = match db_path.exists() {
+        true => Some(lock_db(db_path).await?),
+        false => None,
+    };
+
+    let restore_path = sidecar_path(db_path, ".restore");
+    let (src, dest) = (backup.to_owned(), restore_path.to_owned());
+    spawn_blocking(move || {
+        let mut input = File::open(&src)?;
+        let mut output = File::create(&dest)?;
+        if src.to_string_lossy().ends_with(".gz") {
+            io::copy(&mut GzDecoder::new(input), &mut output)?;
+        } else {
+            io::copy(&mut input, &mut output)?;
+        }
+        output.sync_all()
+    })
+    .await?;
+
+    if let Err(err) = check_backup(&restore_path).await {
+        fs::remove_file(&restore_path)?;
+        return Err(err);
+    }
+
+    // The WAL belongs to the old database, so it moves along with it
+    let previous_suffix = format!(".before-restore-{}", timestamp());
+    let previous_path = lock
+        .is_some()
+        .then(|| sidecar_path(db_path, &previous_suffix));
+    for suffix in ["", "-wal", "-shm"] {
+        let path = sidecar_path(db_path, suffix);
+        if path.exists() {
+            fs::rename(
+                &path,
+                sidecar_path(db_path, &format!("{previous_suffix}{suffix}")),
+            )?;
+        }
+    }
+    fs::rename(&restore_path, db_path)?;
+    if let Some(lock) = lock {
+        lock.close().await?;
+    }
+    Ok(previous_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_backup, list_backups, restore_backup, DbLock};
+    use crate::create_db_pool;
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db.sqlite3");
+        let backup_dir = dir.path().join("backups");
+        let db = create_db_pool(&db_path.to_string_lossy(), true)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO principals (id, principal_type) VALUES ('alice', 'individual')")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let mut backups = vec![];
+        for compress in [false, true, true] {
+            backups.push(
+                create_backup(&db, &db_path, &backup_dir, 2, compress)
+                    .await
+                    .unwrap(),
+            );
+        }
+        // Only the newest two are kept
+        assert_eq!(
+            list_backups(&db_path, &backup_dir).unwrap(),
+            backups[1..].to_vec()
+        );
+
+        sqlx::query("DELETE FROM principals")
+            .execute(&db)
+            .await
+            .unwrap();
+        db.close().await;
+
+        let previous = restore_backup(&backups[2], &db_path)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(previous
+            .to_string_lossy()
+            .contains("db.sqlite3.before-restore-"));
+        // A second restore keeps the previous database of the first one
+        let db = create_db_pool(&db_path.to_string_lossy(), false)
+            .await
+            .unwrap();
+        db.close().await;
+        let second = restore_backup(&backups[2], &db_path)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(previous, second);
+        let previous_db = create_db_pool(&previous.to_string_lossy(), false)
+            .await
+            .unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM principals")
+            .fetch_one(&previous_db)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+        previous_db.close().await;
+
+        let db = create_db_pool(&db_path.to_string_lossy(), false)
+            .await
+            .unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM principals")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_restore_unknown_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db_path, backup_path) = (dir.path().join("db"), dir.path().join("backup.db"));
+        let db = create_db_pool(&backup_path.to_string_lossy(), true)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (99999, 'future', TRUE, x'00', 0)")
+            .execute(&db)
+            .await
+            .unwrap();
+        db.close().await;
+
+        assert!(restore_backup(&backup_path, &db_path).await.is_err());
+        assert!(!db_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_database_in_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db_path, backup_dir) = (dir.path().join("db"), dir.path().join("backups"));
+        let db = create_db_pool(&db_path.to_string_lossy(), true)
+            .await
+            .unwrap();
+        let backup = create_backup(&db, &db_path, &backup_dir, 1, false)
+            .await
+            .unwrap();
+
+        // Another writer holds the database
+        let mut conn = db.acquire().await.unwrap();
+        sqlx::query("BEGIN IMMEDIATE")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        assert!(restore_backup(&backup, &db_path).await.is_err());
+        assert!(!dir.path().join("db.restore").exists());
+        sqlx::query("ROLLBACK").execute(&mut *conn).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_while_server_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db_path, backup_dir) = (dir.path().join("db"), dir.path().join("backups"));
+        let db = create_db_pool(&db_path.to_string_lossy(), true)
+            .await
+            .unwrap();
+        let backup = create_backup(&db, &db_path, &backup_dir, 1, false)
+            .await
+            .unwrap();
+
+        // An idle server holds no SQLite locks, only the lock file
+        let server_lock = DbLock::shared(&db_path).unwrap();
+        assert!(restore_backup(&backup, &db_path).await.is_err());
+        assert!(!dir.path().join("db.restore").exists());
+
+        drop(server_lock);
+        db.close().await;
+        assert!(restore_backup(&backup, &db_path).await.unwrap().is_some());
+        assert!(DbLock::shared(&db_path).is_ok());
+    }
+}
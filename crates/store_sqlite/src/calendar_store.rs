@@ -1,17 +1,44 @@
-use super::ChangeOperation;
+use super::{ChangeOperation, ShareRow};
 use async_trait::async_trait;
 use chrono::TimeDelta;
 use derive_more::derive::Constructor;
-use rustical_store::calendar::{CalDateTime, CalendarObjectType};
+use rustical_store::calendar::{
+    CalDateTime, CalendarFeed, CalendarObjectType, FeedPrivacy, WebcalStatus,
+};
 use rustical_store::calendar_store::CalendarQuery;
 use rustical_store::synctoken::format_synctoken;
-use rustical_store::{Calendar, CalendarObject, CalendarStore, Error};
+use rustical_store::{Calendar, CalendarObject, CalendarStore, Error, Share, ShareAccess};
 use rustical_store::{CollectionOperation, CollectionOperationType};
 use sqlx::types::chrono::NaiveDateTime;
 use sqlx::{Acquire, Executor, Sqlite, SqlitePool, Transaction};
 use tokio::sync::mpsc::Sender;
 use tracing::{error, instrument};
 
+#[derive(Debug, Clone)]
+struct CalendarFeedRow {
+    token: String,
+    principal: String,
+    cal_id: String,
+    privacy: String,
+    created_at: NaiveDateTime,
+}
+
+impl From<CalendarFeedRow> for CalendarFeed {
+    fn from(value: CalendarFeedRow) -> Self {
+        Self {
+            token: value.token,
+            principal: value.principal,
+            cal_id: value.cal_id,
+            privacy: match value.privacy.as_str() {
+                "private" => FeedPrivacy::Private,
+                "busy-only" => FeedPrivacy::BusyOnly,
+                _ => FeedPrivacy::Full,
+            },
+            created_at: Some(value.created_at),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct CalendarObjectRow {
     id: String,
@@ -144,8 +171,8 @@ impl SqliteCalendarStore {
         let comp_journal = calendar.components.contains(&CalendarObjectType::Journal);
 
         sqlx::query!(
-            r#"INSERT INTO calendars (principal, id, displayname, description, "order", color, timezone, timezone_id, push_topic, comp_event, comp_todo, comp_journal)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            r#"INSERT INTO calendars (principal, id, displayname, description, "order", color, timezone, timezone_id, push_topic, comp_event, comp_todo, comp_journal, subscription_url)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
             calendar.principal,
             calendar.id,
             calendar.displayname,
@@ -155,7 +182,8 @@ impl SqliteCalendarStore {
             calendar.timezone,
             calendar.timezone_id,
             calendar.push_topic,
-            comp_event, comp_todo, comp_journal
+            comp_event, comp_todo, comp_journal,
+            calendar.subscription_url
         )
         .execute(executor)
         .await.map_err(crate::Error::from)?;
@@ -174,7 +202,7 @@ impl SqliteCalendarStore {
         let comp_journal = calendar.components.contains(&CalendarObjectType::Journal);
 
         let result = sqlx::query!(
-            r#"UPDATE calendars SET principal = ?, id = ?, displayname = ?, description = ?, "order" = ?, color = ?, timezone = ?, timezone_id = ?, push_topic = ?, comp_event = ?, comp_todo = ?, comp_journal = ?
+            r#"UPDATE calendars SET principal = ?, id = ?, displayname = ?, description = ?, "order" = ?, color = ?, timezone = ?, timezone_id = ?, push_topic = ?, comp_event = ?, comp_todo = ?, comp_journal = ?, subscription_url = ?
                 WHERE (principal, id) = (?, ?)"#,
             calendar.principal,
             calendar.id,
@@ -186,6 +214,7 @@ impl SqliteCalendarStore {
             calendar.timezone_id,
             calendar.push_topic,
             comp_event, comp_todo, comp_journal,
+            calendar.subscription_url,
             principal,
             id
         ).execute(executor).await.map_err(crate::Error::from)?;
@@ -252,7 +281,7 @@ impl SqliteCalendarStore {
         .fetch_all(executor)
         .await.map_err(crate::Error::from)?
         .into_iter()
-        .map(|row| row.try_into().map_err(rustical_store::Error::from))
+        .map(|row| row.try_into())
         .collect()
     }
 
@@ -286,7 +315,7 @@ impl SqliteCalendarStore {
         .await
         .map_err(crate::Error::from)?
         .into_iter()
-        .map(|row| row.try_into().map_err(rustical_store::Error::from))
+        .map(|row| row.try_into())
         .collect()
     }
 
@@ -389,7 +418,8 @@ impl SqliteCalendarStore {
             }
             false => {
                 sqlx::query!(
-                    "DELETE FROM calendarobjects WHERE cal_id = ? AND id = ?",
+                    "DELETE FROM calendarobjects WHERE (principal, cal_id, id) = (?, ?, ?)",
+                    principal,
                     cal_id,
                     id
                 )
@@ -419,6 +449,77 @@ impl SqliteCalendarStore {
         Ok(())
     }
 
+    // Both changelogs are written in the same transaction
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_or_move_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+        remove_source: bool,
+    ) -> Result<(), Error> {
+        if (cal_id, object_id) == (dest_cal_id, dest_object_id) {
+            return Err(Error::AlreadyExists);
+        }
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+
+        let object = Self::_get_object(&mut *tx, principal, cal_id, object_id).await?;
+        let object =
+            CalendarObject::from_ics(dest_object_id.to_owned(), object.get_ics().to_owned())?;
+        Self::_put_object(
+            &mut *tx,
+            principal.to_owned(),
+            dest_cal_id.to_owned(),
+            object,
+            overwrite,
+        )
+        .await?;
+        let mut synctokens = vec![(
+            dest_cal_id,
+            log_object_operation(
+                &mut tx,
+                principal,
+                dest_cal_id,
+                dest_object_id,
+                ChangeOperation::Add,
+            )
+            .await
+            .map_err(crate::Error::from)?,
+        )];
+
+        if remove_source {
+            Self::_delete_object(&mut *tx, principal, cal_id, object_id, false).await?;
+            synctokens.push((
+                cal_id,
+                log_object_operation(
+                    &mut tx,
+                    principal,
+                    cal_id,
+                    object_id,
+                    ChangeOperation::Delete,
+                )
+                .await
+                .map_err(crate::Error::from)?,
+            ));
+        }
+        tx.commit().await.map_err(crate::Error::from)?;
+
+        for (cal_id, synctoken) in synctokens {
+            if let Err(err) = self.sender.try_send(CollectionOperation {
+                r#type: CollectionOperationType::Object,
+                domain: rustical_store::CollectionOperationDomain::Calendar,
+                topic: self.get_calendar(principal, cal_id).await?.push_topic,
+                sync_token: Some(synctoken),
+            }) {
+                error!("Push notification about changed calendar failed: {err}");
+            };
+        }
+        Ok(())
+    }
+
     async fn _sync_changes<'a, A: Acquire<'a, Database = Sqlite>>(
         acquire: A,
         principal: &str,
@@ -436,9 +537,11 @@ impl SqliteCalendarStore {
             Row,
             r#"
                 SELECT DISTINCT object_id, max(0, synctoken) as "synctoken!: i64" from calendarobjectchangelog
-                WHERE synctoken > ?
+                WHERE (principal, cal_id) = (?1, ?2) AND synctoken > ?3
                 ORDER BY synctoken ASC
             "#,
+            principal,
+            cal_id,
             synctoken
         )
         .fetch_all(&mut *conn)
@@ -660,6 +763,50 @@ impl CalendarStore for SqliteCalendarStore {
         Ok(())
     }
 
+    #[instrument]
+    async fn copy_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            cal_id,
+            object_id,
+            dest_cal_id,
+            dest_object_id,
+            overwrite,
+            false,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn move_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            cal_id,
+            object_id,
+            dest_cal_id,
+            dest_object_id,
+            overwrite,
+            true,
+        )
+        .await
+    }
+
     #[instrument]
     async fn sync_changes(
         &self,
@@ -670,6 +817,286 @@ impl CalendarStore for SqliteCalendarStore {
         Self::_sync_changes(&self.db, principal, cal_id, synctoken).await
     }
 
+    #[instrument]
+    async fn get_inbox_objects(&self, principal: &str) -> Result<Vec<CalendarObject>, Error> {
+        sqlx::query_as!(
+            CalendarObjectRow,
+            "SELECT id, ics FROM scheduleinbox WHERE principal = ? ORDER BY created_at",
+            principal
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(|row| row.try_into())
+        .collect()
+    }
+
+    #[instrument]
+    async fn get_inbox_object(
+        &self,
+        principal: &str,
+        object_id: &str,
+    ) -> Result<CalendarObject, Error> {
+        sqlx::query_as!(
+            CalendarObjectRow,
+            "SELECT id, ics FROM scheduleinbox WHERE (principal, id) = (?, ?)",
+            principal,
+            object_id
+        )
+        .fetch_one(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .try_into()
+    }
+
+    #[instrument]
+    async fn put_inbox_object(
+        &self,
+        principal: String,
+        object: CalendarObject,
+    ) -> Result<(), Error> {
+        let (object_id, ics) = (object.get_id(), object.get_ics());
+        sqlx::query!(
+            "INSERT INTO scheduleinbox (principal, id, ics) VALUES (?, ?, ?)",
+            principal,
+            object_id,
+            ics
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_inbox_object(&self, principal: &str, object_id: &str) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM scheduleinbox WHERE (principal, id) = (?, ?)",
+            principal,
+            object_id
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_calendar_shares(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<Share>, Error> {
+        Ok(sqlx::query_as!(
+            ShareRow,
+            r#"SELECT principal, cal_id AS collection_id, sharee, read_write
+                FROM calendarshares
+                WHERE (principal, cal_id) = (?, ?)
+                ORDER BY sharee"#,
+            principal,
+            cal_id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(Share::from)
+        .collect())
+    }
+
+    #[instrument]
+    async fn get_received_calendar_shares(&self, sharee: &str) -> Result<Vec<Share>, Error> {
+        // Shares of calendars in the trash bin are hidden
+        Ok(sqlx::query_as!(
+            ShareRow,
+            r#"SELECT calendarshares.principal, cal_id AS collection_id, sharee, read_write
+                FROM calendarshares
+                INNER JOIN calendars
+                    ON (calendars.principal, calendars.id) = (calendarshares.principal, cal_id)
+                WHERE sharee = ? AND calendars.deleted_at IS NULL
+                ORDER BY calendarshares.principal, cal_id"#,
+            sharee
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(Share::from)
+        .collect())
+    }
+
+    #[instrument]
+    async fn put_calendar_share(&self, share: Share) -> Result<(), Error> {
+        let read_write = share.access == ShareAccess::ReadWrite;
+        sqlx::query!(
+            r#"INSERT OR REPLACE INTO calendarshares (principal, cal_id, sharee, read_write)
+                VALUES (?, ?, ?, ?)"#,
+            share.principal,
+            share.collection_id,
+            share.sharee,
+            read_write
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_calendar_share(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        sharee: &str,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM calendarshares WHERE (principal, cal_id, sharee) = (?, ?, ?)",
+            principal,
+            cal_id,
+            sharee
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_calendar_feeds(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<CalendarFeed>, Error> {
+        Ok(sqlx::query_as!(
+            CalendarFeedRow,
+            r#"SELECT token, principal, cal_id, privacy, created_at
+                FROM calendarfeeds
+                WHERE (principal, cal_id) = (?, ?)
+                ORDER BY created_at"#,
+            principal,
+            cal_id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into_iter()
+        .map(CalendarFeed::from)
+        .collect())
+    }
+
+    #[instrument(skip(token))]
+    async fn get_calendar_feed(&self, token: &str) -> Result<CalendarFeed, Error> {
+        Ok(sqlx::query_as!(
+            CalendarFeedRow,
+            r#"SELECT token, principal, cal_id, privacy, created_at
+                FROM calendarfeeds
+                WHERE token = ?"#,
+            token
+        )
+        .fetch_one(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .into())
+    }
+
+    #[instrument(skip(feed))]
+    async fn insert_calendar_feed(&self, feed: CalendarFeed) -> Result<(), Error> {
+        let privacy = feed.privacy.as_str();
+        let created_at = feed
+            .created_at
+            .unwrap_or_else(|| chrono::Utc::now().naive_utc());
+        sqlx::query!(
+            r#"INSERT INTO calendarfeeds (token, principal, cal_id, privacy, created_at)
+                VALUES (?, ?, ?, ?, ?)"#,
+            feed.token,
+            feed.principal,
+            feed.cal_id,
+            privacy,
+            created_at
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument(skip(token))]
+    async fn delete_calendar_feed(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        token: &str,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM calendarfeeds WHERE (principal, cal_id, token) = (?, ?, ?)",
+            principal,
+            cal_id,
+            token
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_subscribed_calendars(&self) -> Result<Vec<Calendar>, Error> {
+        let cals = sqlx::query_as!(
+            CalendarRow,
+            r#"SELECT *
+                FROM calendars
+                WHERE subscription_url IS NOT NULL AND deleted_at IS NULL"#,
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(cals.into_iter().map(Calendar::from).collect())
+    }
+
+    #[instrument]
+    async fn get_webcal_status(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<WebcalStatus, Error> {
+        Ok(sqlx::query_as!(
+            WebcalStatus,
+            r#"SELECT etag, last_modified, last_refresh, last_error
+                FROM webcalstatus
+                WHERE (principal, cal_id) = (?, ?)"#,
+            principal,
+            cal_id
+        )
+        .fetch_optional(&self.db)
+        .await
+        .map_err(crate::Error::from)?
+        .unwrap_or_default())
+    }
+
+    #[instrument]
+    async fn put_webcal_status(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        status: WebcalStatus,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"INSERT OR REPLACE INTO webcalstatus (principal, cal_id, etag, last_modified, last_refresh, last_error)
+                VALUES (?, ?, ?, ?, ?, ?)"#,
+            principal,
+            cal_id,
+            status.etag,
+            status.last_modified,
+            status.last_refresh,
+            status.last_error
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(())
+    }
+
     fn is_read_only(&self) -> bool {
         false
     }
@@ -5,6 +5,9 @@ pub enum Error {
 
     #[error(transparent)]
     StoreError(rustical_store::Error),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
 }
 
 impl From<sqlx::Error> for Error {
@@ -28,6 +31,7 @@ impl From<Error> for rustical_store::Error {
         match value {
             Error::SqlxError(err) => Self::Other(err.into()),
             Error::StoreError(err) => err,
+            Error::IoError(err) => Self::Other(err.into()),
         }
     }
 }
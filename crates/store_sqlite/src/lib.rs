@@ -1,10 +1,13 @@
+use rustical_store::{Share, ShareAccess};
 use serde::Serialize;
 use sqlx::{sqlite::SqliteConnectOptions, Pool, Sqlite, SqlitePool};
 
 pub mod addressbook_store;
+pub mod backup;
 pub mod calendar_store;
 pub mod error;
 pub mod subscription_store;
+pub mod user_store;
 
 pub use error::Error;
 
@@ -16,6 +19,29 @@ pub(crate) enum ChangeOperation {
     Delete,
 }
 
+#[derive(Debug, Clone)]
+pub(crate) struct ShareRow {
+    principal: String,
+    collection_id: String,
+    sharee: String,
+    read_write: bool,
+}
+
+impl From<ShareRow> for Share {
+    fn from(value: ShareRow) -> Self {
+        Self {
+            principal: value.principal,
+            collection_id: value.collection_id,
+            sharee: value.sharee,
+            access: if value.read_write {
+                ShareAccess::ReadWrite
+            } else {
+                ShareAccess::Read
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SqliteStore {
     db: SqlitePool,
@@ -0,0 +1,293 @@
+use async_trait::async_trait;
+use rustical_store::auth::{AppToken, AuthenticationProvider, PrincipalType, User, UserStore};
+use rustical_store::Error;
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use tracing::instrument;
+
+#[derive(Debug, Clone)]
+struct PrincipalRow {
+    id: String,
+    displayname: Option<String>,
+    principal_type: String,
+    password_hash: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct CalendarProxyRow {
+    delegator: String,
+    read_write: bool,
+}
+
+fn principal_type_to_str(principal_type: PrincipalType) -> &'static str {
+    match principal_type {
+        PrincipalType::Individual => "individual",
+        PrincipalType::Group => "group",
+    }
+}
+
+fn principal_type_from_str(principal_type: &str) -> PrincipalType {
+    match principal_type {
+        "group" => PrincipalType::Group,
+        _ => PrincipalType::Individual,
+    }
+}
+
+#[derive(Debug)]
+pub struct SqliteUserStore {
+    db: SqlitePool,
+}
+
+impl SqliteUserStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    async fn get_user_from_row(&self, row: PrincipalRow) -> Result<User, Error> {
+        let memberships = sqlx::query_scalar!(
+            "SELECT membership FROM memberships WHERE principal = ?",
+            row.id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        let proxies = sqlx::query_as!(
+            CalendarProxyRow,
+            "SELECT delegator, read_write FROM calendarproxies WHERE principal = ?",
+            row.id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+
+        let (proxy_write, proxy_read): (Vec<_>, Vec<_>) =
+            proxies.into_iter().partition(|proxy| proxy.read_write);
+        Ok(User {
+            id: row.id,
+            displayname: row.displayname,
+            principal_type: principal_type_from_str(&row.principal_type),
+            password: row.password_hash,
+            memberships,
+            calendar_proxy_read_for: proxy_read.into_iter().map(|p| p.delegator).collect(),
+            calendar_proxy_write_for: proxy_write.into_iter().map(|p| p.delegator).collect(),
+        })
+    }
+
+    async fn put_user_relations(
+        tx: &mut Transaction<'_, Sqlite>,
+        user: &User,
+    ) -> Result<(), crate::Error> {
+        sqlx::query!("DELETE FROM memberships WHERE principal = ?", user.id)
+            .execute(&mut **tx)
+            .await?;
+        for membership in &user.memberships {
+            sqlx::query!(
+                "INSERT INTO memberships (principal, membership) VALUES (?, ?)",
+                user.id,
+                membership
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        sqlx::query!("DELETE FROM calendarproxies WHERE principal = ?", user.id)
+            .execute(&mut **tx)
+            .await?;
+        let proxies = user
+            .calendar_proxy_read_for
+            .iter()
+            .map(|delegator| (delegator, false))
+            .chain(
+                user.calendar_proxy_write_for
+                    .iter()
+                    .map(|delegator| (delegator, true)),
+            );
+        for (delegator, read_write) in proxies {
+            sqlx::query!(
+                "INSERT OR REPLACE INTO calendarproxies (principal, delegator, read_write) VALUES (?, ?, ?)",
+                user.id,
+                delegator,
+                read_write
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserStore for SqliteUserStore {
+    #[instrument]
+    async fn get_user(&self, id: &str) -> Result<Option<User>, Error> {
+        let row = sqlx::query_as!(
+            PrincipalRow,
+            "SELECT id, displayname, principal_type, password_hash FROM principals WHERE id = ?",
+            id
+        )
+        .fetch_optional(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(match row {
+            Some(row) => Some(self.get_user_from_row(row).await?),
+            None => None,
+        })
+    }
+
+    #[instrument]
+    async fn get_users(&self) -> Result<Vec<User>, Error> {
+        let rows = sqlx::query_as!(
+            PrincipalRow,
+            "SELECT id, displayname, principal_type, password_hash FROM principals ORDER BY id"
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        let mut users = vec![];
+        for row in rows {
+            users.push(self.get_user_from_row(row).await?);
+        }
+        Ok(users)
+    }
+
+    #[instrument(skip(user))]
+    async fn put_user(&self, user: User) -> Result<(), Error> {
+        let mut tx = self.db.begin().await.map_err(crate::Error::from)?;
+        let principal_type = principal_type_to_str(user.principal_type);
+        sqlx::query!(
+            r#"INSERT INTO principals (id, displayname, principal_type, password_hash) VALUES (?, ?, ?, ?)
+                ON CONFLICT(id) DO UPDATE SET displayname = excluded.displayname, principal_type = excluded.principal_type, password_hash = excluded.password_hash"#,
+            user.id,
+            user.displayname,
+            principal_type,
+            user.password
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(crate::Error::from)?;
+        Self::put_user_relations(&mut tx, &user).await?;
+        tx.commit().await.map_err(crate::Error::from)?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_user(&self, id: &str) -> Result<(), Error> {
+        let result = sqlx::query!("DELETE FROM principals WHERE id = ?", id)
+            .execute(&self.db)
+            .await
+            .map_err(crate::Error::from)?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuthenticationProvider for SqliteUserStore {
+    async fn validate_user_token(&self, user_id: &str, token: &str) -> Result<Option<User>, Error> {
+        let user = match self.get_user(user_id).await? {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+        if !user.can_log_in() {
+            return Ok(None);
+        }
+
+        // Try app tokens first since they are cheaper to calculate
+        for app_token in self.get_app_tokens(user_id).await? {
+            if password_auth::verify_password(token, &app_token.token).is_ok() {
+                let last_used = chrono::Utc::now().naive_utc();
+                sqlx::query!(
+                    "UPDATE apptokens SET last_used = ? WHERE id = ?",
+                    last_used,
+                    app_token.id
+                )
+                .execute(&self.db)
+                .await
+                .map_err(crate::Error::from)?;
+                return Ok(Some(user));
+            }
+        }
+
+        let password = match &user.password {
+            Some(password) => password,
+            None => return Ok(None),
+        };
+
+        if password_auth::verify_password(token, password).is_ok() {
+            return Ok(Some(user));
+        }
+
+        Ok(None)
+    }
+
+    async fn get_principal(&self, id: &str) -> Result<Option<User>, Error> {
+        self.get_user(id).await
+    }
+
+    async fn list_principals(&self) -> Result<Vec<User>, Error> {
+        self.get_users().await
+    }
+
+    #[instrument]
+    async fn get_group_members(&self, group: &str) -> Result<Vec<String>, Error> {
+        Ok(sqlx::query_scalar!(
+            "SELECT principal FROM memberships WHERE membership = ? ORDER BY principal",
+            group
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?)
+    }
+
+    #[instrument]
+    async fn get_app_tokens(&self, user_id: &str) -> Result<Vec<AppToken>, Error> {
+        Ok(sqlx::query_as!(
+            AppToken,
+            "SELECT id, name, token, created_at, last_used FROM apptokens WHERE principal = ? ORDER BY created_at",
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(crate::Error::from)?)
+    }
+
+    #[instrument(skip(token_hash))]
+    async fn add_app_token(
+        &self,
+        user_id: &str,
+        name: String,
+        token_hash: String,
+    ) -> Result<String, Error> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let created_at = chrono::Utc::now().naive_utc();
+        sqlx::query!(
+            "INSERT INTO apptokens (id, principal, name, token, created_at) VALUES (?, ?, ?, ?, ?)",
+            id,
+            user_id,
+            name,
+            token_hash,
+            created_at
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        Ok(id)
+    }
+
+    #[instrument]
+    async fn remove_app_token(&self, user_id: &str, token_id: &str) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "DELETE FROM apptokens WHERE principal = ? AND id = ?",
+            user_id,
+            token_id
+        )
+        .execute(&self.db)
+        .await
+        .map_err(crate::Error::from)?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+}
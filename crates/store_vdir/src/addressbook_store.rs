@@ -0,0 +1,586 @@
+use crate::collection::{check_name, list_dirs, Collection, CollectionState, ShareEntry};
+use crate::props::CollectionProps;
+use crate::ChangeOperation;
+use async_trait::async_trait;
+use rustical_store::{
+    synctoken::format_synctoken, AddressObject, Addressbook, AddressbookStore, CollectionOperation,
+    CollectionOperationDomain, CollectionOperationType, Error, Share, ShareAccess,
+};
+use std::path::PathBuf;
+use tokio::{fs, sync::mpsc::Sender, sync::Mutex};
+use tracing::{error, instrument, warn};
+
+fn addressbook_from_props(
+    principal: &str,
+    id: &str,
+    props: CollectionProps,
+    state: CollectionState,
+) -> Addressbook {
+    Addressbook {
+        principal: principal.to_owned(),
+        id: id.to_owned(),
+        displayname: props.displayname,
+        description: props.addressbook_description,
+        deleted_at: state.deleted_at,
+        synctoken: state.synctoken,
+        push_topic: state.push_topic,
+    }
+}
+
+fn update_props(props: &mut CollectionProps, addressbook: &Addressbook) {
+    props.displayname = addressbook.displayname.to_owned();
+    props.addressbook_description = addressbook.description.to_owned();
+}
+
+// Keeps every addressbook in <root>/<principal>/addressbooks/<id>/ with one .vcf file per object
+#[derive(Debug)]
+pub struct VdirAddressbookStore {
+    root: PathBuf,
+    sender: Sender<CollectionOperation>,
+    // The state files are rewritten as a whole, so writes must not interleave
+    lock: Mutex<()>,
+}
+
+impl VdirAddressbookStore {
+    pub fn new(root: PathBuf, sender: Sender<CollectionOperation>) -> Self {
+        Self {
+            root,
+            sender,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn collection(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Collection, crate::Error> {
+        Ok(Collection::new(
+            self.root
+                .join(check_name(principal)?)
+                .join("addressbooks")
+                .join(check_name(addressbook_id)?),
+            "vcf",
+        ))
+    }
+
+    fn notify(&self, r#type: CollectionOperationType, topic: String, synctoken: Option<i64>) {
+        if let Err(err) = self.sender.try_send(CollectionOperation {
+            r#type,
+            domain: CollectionOperationDomain::Addressbook,
+            topic,
+            sync_token: synctoken.map(format_synctoken),
+        }) {
+            error!("Push notification about changed addressbook failed: {err}");
+        }
+    }
+
+    async fn read_addressbook(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Addressbook, Error> {
+        let collection = self.collection(principal, addressbook_id)?;
+        let state = collection.read_state().await?;
+        let props = collection.read_props().await?;
+        Ok(addressbook_from_props(
+            principal,
+            addressbook_id,
+            props,
+            state,
+        ))
+    }
+
+    async fn read_addressbooks(&self, principal: &str) -> Result<Vec<Addressbook>, Error> {
+        let mut addressbooks = vec![];
+        for addressbook_id in
+            list_dirs(&self.root.join(check_name(principal)?).join("addressbooks")).await?
+        {
+            addressbooks.push(self.read_addressbook(principal, &addressbook_id).await?);
+        }
+        Ok(addressbooks)
+    }
+
+    async fn update_state(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        f: impl FnOnce(&mut CollectionState),
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(principal, addressbook_id)?;
+        let mut state = collection.read_state().await?;
+        f(&mut state);
+        collection.write_state(&state).await?;
+        Ok(())
+    }
+
+    // Picks up objects that were changed in the file system
+    async fn rescan(&self, principal: &str, addressbook_id: &str) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(principal, addressbook_id)?;
+        let mut state = collection.read_state().await?;
+        if collection.rescan(&mut state).await? {
+            collection.write_state(&state).await?;
+            self.notify(
+                CollectionOperationType::Object,
+                state.push_topic,
+                Some(state.synctoken),
+            );
+        }
+        Ok(())
+    }
+
+    pub async fn rescan_all(&self) -> Result<(), Error> {
+        for principal in list_dirs(&self.root).await? {
+            for addressbook_id in
+                list_dirs(&self.root.join(&principal).join("addressbooks")).await?
+            {
+                if let Err(err) = self.rescan(&principal, &addressbook_id).await {
+                    warn!("Could not rescan addressbook {principal}/{addressbook_id}: {err}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Both changelogs are written while holding the lock
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_or_move_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+        remove_source: bool,
+    ) -> Result<(), Error> {
+        if (addressbook_id, object_id) == (dest_addressbook_id, dest_object_id) {
+            return Err(Error::AlreadyExists);
+        }
+        let _guard = self.lock.lock().await;
+        let (source, dest) = (
+            self.collection(principal, addressbook_id)?,
+            self.collection(principal, dest_addressbook_id)?,
+        );
+
+        let object = AddressObject::from_vcf(
+            dest_object_id.to_owned(),
+            source.read_object(object_id).await?,
+        )?;
+        let mut dest_state = dest.read_state().await?;
+        dest.write_object(&mut dest_state, dest_object_id, object.get_vcf(), overwrite)
+            .await?;
+        dest.log(&mut dest_state, dest_object_id, ChangeOperation::Add)
+            .await?;
+
+        let mut source_state = None;
+        if remove_source {
+            if addressbook_id == dest_addressbook_id {
+                dest.remove_object(&mut dest_state, object_id, false)
+                    .await?;
+                dest.log(&mut dest_state, object_id, ChangeOperation::Delete)
+                    .await?;
+            } else {
+                let mut state = source.read_state().await?;
+                source.remove_object(&mut state, object_id, false).await?;
+                source
+                    .log(&mut state, object_id, ChangeOperation::Delete)
+                    .await?;
+                source.write_state(&state).await?;
+                source_state = Some(state);
+            }
+        }
+        dest.write_state(&dest_state).await?;
+
+        for state in [Some(dest_state), source_state].into_iter().flatten() {
+            self.notify(
+                CollectionOperationType::Object,
+                state.push_topic,
+                Some(state.synctoken),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AddressbookStore for VdirAddressbookStore {
+    #[instrument]
+    async fn get_addressbook(&self, principal: &str, id: &str) -> Result<Addressbook, Error> {
+        self.read_addressbook(principal, id).await
+    }
+
+    #[instrument]
+    async fn get_addressbooks(&self, principal: &str) -> Result<Vec<Addressbook>, Error> {
+        Ok(self
+            .read_addressbooks(principal)
+            .await?
+            .into_iter()
+            .filter(|addressbook| addressbook.deleted_at.is_none())
+            .collect())
+    }
+
+    #[instrument]
+    async fn get_deleted_addressbooks(&self, principal: &str) -> Result<Vec<Addressbook>, Error> {
+        Ok(self
+            .read_addressbooks(principal)
+            .await?
+            .into_iter()
+            .filter(|addressbook| addressbook.deleted_at.is_some())
+            .collect())
+    }
+
+    #[instrument]
+    async fn update_addressbook(
+        &self,
+        principal: String,
+        id: String,
+        addressbook: Addressbook,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let mut collection = self.collection(&principal, &id)?;
+        let mut state = collection.read_state().await?;
+
+        if (addressbook.principal.as_str(), addressbook.id.as_str()) != (&principal, &id) {
+            let dest = self.collection(&addressbook.principal, &addressbook.id)?;
+            if dest.exists().await {
+                return Err(Error::AlreadyExists);
+            }
+            if let Some(parent) = dest.path.parent() {
+                fs::create_dir_all(parent)
+                    .await
+                    .map_err(crate::Error::from)?;
+            }
+            fs::rename(&collection.path, &dest.path)
+                .await
+                .map_err(crate::Error::from)?;
+            collection = dest;
+        }
+
+        let mut props: CollectionProps = collection.read_props().await?;
+        update_props(&mut props, &addressbook);
+        collection.write_props(&props).await?;
+        state.push_topic = addressbook.push_topic;
+        collection.write_state(&state).await?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn insert_addressbook(&self, addressbook: Addressbook) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(&addressbook.principal, &addressbook.id)?;
+        collection.create().await?;
+
+        let mut props = CollectionProps::default();
+        update_props(&mut props, &addressbook);
+        collection.write_props(&props).await?;
+        collection
+            .write_state(&CollectionState {
+                push_topic: addressbook.push_topic,
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_addressbook(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        let addressbook = match self.read_addressbook(principal, addressbook_id).await {
+            Ok(addressbook) => addressbook,
+            Err(Error::NotFound) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        if use_trashbin {
+            self.update_state(principal, addressbook_id, |state| {
+                state.deleted_at = Some(chrono::Utc::now().naive_utc());
+            })
+            .await?;
+        } else {
+            let _guard = self.lock.lock().await;
+            fs::remove_dir_all(&self.collection(principal, addressbook_id)?.path)
+                .await
+                .map_err(crate::Error::from)?;
+        }
+
+        self.notify(
+            CollectionOperationType::Delete,
+            addressbook.push_topic,
+            None,
+        );
+        Ok(())
+    }
+
+    #[instrument]
+    async fn restore_addressbook(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<(), Error> {
+        self.update_state(principal, addressbook_id, |state| state.deleted_at = None)
+            .await
+    }
+
+    #[instrument]
+    async fn sync_changes(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        synctoken: i64,
+    ) -> Result<(Vec<AddressObject>, Vec<String>, i64), Error> {
+        self.rescan(principal, addressbook_id).await?;
+        let changes = self
+            .collection(principal, addressbook_id)?
+            .changes_since(synctoken)
+            .await?;
+
+        let mut objects = vec![];
+        let mut deleted_objects = vec![];
+
+        let new_synctoken = changes.last().map(|(_, synctoken)| *synctoken).unwrap_or(0);
+
+        for (object_id, _) in changes {
+            match self.get_object(principal, addressbook_id, &object_id).await {
+                Ok(object) => objects.push(object),
+                Err(Error::NotFound) => deleted_objects.push(object_id),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok((objects, deleted_objects, new_synctoken))
+    }
+
+    #[instrument]
+    async fn get_objects(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Vec<AddressObject>, Error> {
+        let collection = self.collection(principal, addressbook_id)?;
+        let mut objects = vec![];
+        for (object_id, vcf) in collection.list_objects().await? {
+            match AddressObject::from_vcf(object_id.to_owned(), vcf) {
+                Ok(object) => objects.push(object),
+                // A file broken by hand shouldn't hide the rest of the addressbook
+                Err(err) => warn!("Skipping {principal}/{addressbook_id}/{object_id}: {err}"),
+            }
+        }
+        Ok(objects)
+    }
+
+    #[instrument]
+    async fn get_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+    ) -> Result<AddressObject, Error> {
+        let vcf = self
+            .collection(principal, addressbook_id)?
+            .read_object(object_id)
+            .await?;
+        AddressObject::from_vcf(object_id.to_owned(), vcf)
+    }
+
+    #[instrument]
+    async fn put_object(
+        &self,
+        principal: String,
+        addressbook_id: String,
+        object: AddressObject,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(&principal, &addressbook_id)?;
+        let mut state = collection.read_state().await?;
+        collection
+            .write_object(&mut state, object.get_id(), object.get_vcf(), overwrite)
+            .await?;
+        let synctoken = collection
+            .log(&mut state, object.get_id(), ChangeOperation::Add)
+            .await?;
+        collection.write_state(&state).await?;
+
+        self.notify(
+            CollectionOperationType::Object,
+            state.push_topic,
+            Some(synctoken),
+        );
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(principal, addressbook_id)?;
+        let mut state = collection.read_state().await?;
+        collection
+            .remove_object(&mut state, object_id, use_trashbin)
+            .await?;
+        let synctoken = collection
+            .log(&mut state, object_id, ChangeOperation::Delete)
+            .await?;
+        collection.write_state(&state).await?;
+
+        self.notify(
+            CollectionOperationType::Object,
+            state.push_topic,
+            Some(synctoken),
+        );
+        Ok(())
+    }
+
+    #[instrument]
+    async fn restore_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(principal, addressbook_id)?;
+        let mut state = collection.read_state().await?;
+        collection.restore_object(&mut state, object_id).await?;
+        let synctoken = collection
+            .log(&mut state, object_id, ChangeOperation::Add)
+            .await?;
+        collection.write_state(&state).await?;
+
+        self.notify(
+            CollectionOperationType::Object,
+            state.push_topic,
+            Some(synctoken),
+        );
+        Ok(())
+    }
+
+    #[instrument]
+    async fn copy_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            addressbook_id,
+            object_id,
+            dest_addressbook_id,
+            dest_object_id,
+            overwrite,
+            false,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn move_object(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        object_id: &str,
+        dest_addressbook_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            addressbook_id,
+            object_id,
+            dest_addressbook_id,
+            dest_object_id,
+            overwrite,
+            true,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn get_addressbook_shares(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+    ) -> Result<Vec<Share>, Error> {
+        let mut shares: Vec<_> = self
+            .collection(principal, addressbook_id)?
+            .read_state()
+            .await?
+            .shares
+            .into_iter()
+            .map(|share| share.into_share(principal, addressbook_id))
+            .collect();
+        shares.sort_by(|a, b| a.sharee.cmp(&b.sharee));
+        Ok(shares)
+    }
+
+    #[instrument]
+    async fn get_received_addressbook_shares(&self, sharee: &str) -> Result<Vec<Share>, Error> {
+        let mut shares = vec![];
+        for principal in list_dirs(&self.root).await? {
+            for addressbook_id in
+                list_dirs(&self.root.join(&principal).join("addressbooks")).await?
+            {
+                let state = self
+                    .collection(&principal, &addressbook_id)?
+                    .read_state()
+                    .await?;
+                // Shares of addressbooks in the trash bin are hidden
+                if state.deleted_at.is_some() {
+                    continue;
+                }
+                shares.extend(
+                    state
+                        .shares
+                        .into_iter()
+                        .filter(|share| share.sharee == sharee)
+                        .map(|share| share.into_share(&principal, &addressbook_id)),
+                );
+            }
+        }
+        Ok(shares)
+    }
+
+    #[instrument]
+    async fn put_addressbook_share(&self, share: Share) -> Result<(), Error> {
+        let entry = ShareEntry {
+            sharee: share.sharee.to_owned(),
+            read_write: share.access == ShareAccess::ReadWrite,
+        };
+        self.update_state(&share.principal, &share.collection_id, |state| {
+            state
+                .shares
+                .retain(|existing| existing.sharee != entry.sharee);
+            state.shares.push(entry);
+        })
+        .await
+    }
+
+    #[instrument]
+    async fn delete_addressbook_share(
+        &self,
+        principal: &str,
+        addressbook_id: &str,
+        sharee: &str,
+    ) -> Result<(), Error> {
+        self.update_state(principal, addressbook_id, |state| {
+            state.shares.retain(|share| share.sharee != sharee)
+        })
+        .await
+    }
+}
@@ -0,0 +1,811 @@
+use crate::collection::{
+    check_name, list_dirs, write_atomic, Collection, CollectionState, FeedEntry, ShareEntry,
+};
+use crate::props::CollectionProps;
+use crate::ChangeOperation;
+use async_trait::async_trait;
+use chrono::TimeDelta;
+use rustical_store::calendar::{CalendarFeed, WebcalStatus};
+use rustical_store::calendar_store::CalendarQuery;
+use rustical_store::synctoken::format_synctoken;
+use rustical_store::{
+    Calendar, CalendarObject, CalendarStore, CollectionOperation, CollectionOperationDomain,
+    CollectionOperationType, Error, Share, ShareAccess,
+};
+use std::path::PathBuf;
+use tokio::{fs, sync::mpsc::Sender, sync::Mutex};
+use tracing::{error, instrument, warn};
+
+fn calendar_from_props(
+    principal: &str,
+    id: &str,
+    props: CollectionProps,
+    state: CollectionState,
+) -> Calendar {
+    Calendar {
+        principal: principal.to_owned(),
+        id: id.to_owned(),
+        components: props.get_components(),
+        displayname: props.displayname,
+        order: props
+            .order
+            .and_then(|order| order.parse().ok())
+            .unwrap_or_default(),
+        description: props.calendar_description,
+        color: props.color,
+        timezone: props.timezone,
+        timezone_id: props.timezone_id,
+        deleted_at: state.deleted_at,
+        synctoken: state.synctoken,
+        subscription_url: props.source,
+        push_topic: state.push_topic,
+    }
+}
+
+fn update_props(props: &mut CollectionProps, calendar: &Calendar) {
+    props.displayname = calendar.displayname.to_owned();
+    props.order = Some(calendar.order.to_string());
+    props.calendar_description = calendar.description.to_owned();
+    props.color = calendar.color.to_owned();
+    props.timezone = calendar.timezone.to_owned();
+    props.timezone_id = calendar.timezone_id.to_owned();
+    props.source = calendar.subscription_url.to_owned();
+    props.set_components(&calendar.components);
+}
+
+// Keeps every calendar in <root>/<principal>/calendars/<id>/ with one .ics file per object
+#[derive(Debug)]
+pub struct VdirCalendarStore {
+    root: PathBuf,
+    sender: Sender<CollectionOperation>,
+    // The state files are rewritten as a whole, so writes must not interleave
+    lock: Mutex<()>,
+}
+
+impl VdirCalendarStore {
+    pub fn new(root: PathBuf, sender: Sender<CollectionOperation>) -> Self {
+        Self {
+            root,
+            sender,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn collection(&self, principal: &str, cal_id: &str) -> Result<Collection, crate::Error> {
+        Ok(Collection::new(
+            self.root
+                .join(check_name(principal)?)
+                .join("calendars")
+                .join(check_name(cal_id)?),
+            "ics",
+        ))
+    }
+
+    fn inbox_path(&self, principal: &str, object_id: &str) -> Result<PathBuf, crate::Error> {
+        Ok(self
+            .root
+            .join(check_name(principal)?)
+            .join("inbox")
+            .join(format!("{}.ics", check_name(object_id)?)))
+    }
+
+    fn notify(&self, r#type: CollectionOperationType, topic: String, synctoken: Option<i64>) {
+        if let Err(err) = self.sender.try_send(CollectionOperation {
+            r#type,
+            domain: CollectionOperationDomain::Calendar,
+            topic,
+            sync_token: synctoken.map(format_synctoken),
+        }) {
+            error!("Push notification about changed calendar failed: {err}");
+        }
+    }
+
+    async fn read_calendar(&self, principal: &str, cal_id: &str) -> Result<Calendar, Error> {
+        let collection = self.collection(principal, cal_id)?;
+        let state = collection.read_state().await?;
+        let props = collection.read_props().await?;
+        Ok(calendar_from_props(principal, cal_id, props, state))
+    }
+
+    // Calendars of all principals including deleted ones
+    async fn all_calendars(&self) -> Result<Vec<(Calendar, CollectionState)>, Error> {
+        let mut calendars = vec![];
+        for principal in list_dirs(&self.root).await? {
+            for cal_id in list_dirs(&self.root.join(&principal).join("calendars")).await? {
+                let collection = self.collection(&principal, &cal_id)?;
+                let state = collection.read_state().await?;
+                let props = collection.read_props().await?;
+                calendars.push((
+                    calendar_from_props(&principal, &cal_id, props, state.clone()),
+                    state,
+                ));
+            }
+        }
+        Ok(calendars)
+    }
+
+    async fn update_state<T>(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        f: impl FnOnce(&mut CollectionState) -> T,
+    ) -> Result<T, Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(principal, cal_id)?;
+        let mut state = collection.read_state().await?;
+        let result = f(&mut state);
+        collection.write_state(&state).await?;
+        Ok(result)
+    }
+
+    // Picks up objects that were changed in the file system
+    async fn rescan(&self, principal: &str, cal_id: &str) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(principal, cal_id)?;
+        let mut state = collection.read_state().await?;
+        if collection.rescan(&mut state).await? {
+            collection.write_state(&state).await?;
+            self.notify(
+                CollectionOperationType::Object,
+                state.push_topic,
+                Some(state.synctoken),
+            );
+        }
+        Ok(())
+    }
+
+    pub async fn rescan_all(&self) -> Result<(), Error> {
+        for principal in list_dirs(&self.root).await? {
+            for cal_id in list_dirs(&self.root.join(&principal).join("calendars")).await? {
+                if let Err(err) = self.rescan(&principal, &cal_id).await {
+                    warn!("Could not rescan calendar {principal}/{cal_id}: {err}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Both changelogs are written while holding the lock
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_or_move_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+        remove_source: bool,
+    ) -> Result<(), Error> {
+        if (cal_id, object_id) == (dest_cal_id, dest_object_id) {
+            return Err(Error::AlreadyExists);
+        }
+        let _guard = self.lock.lock().await;
+        let (source, dest) = (
+            self.collection(principal, cal_id)?,
+            self.collection(principal, dest_cal_id)?,
+        );
+
+        let object = CalendarObject::from_ics(
+            dest_object_id.to_owned(),
+            source.read_object(object_id).await?,
+        )?;
+        let mut dest_state = dest.read_state().await?;
+        dest.write_object(&mut dest_state, dest_object_id, object.get_ics(), overwrite)
+            .await?;
+        dest.log(&mut dest_state, dest_object_id, ChangeOperation::Add)
+            .await?;
+
+        let mut source_state = None;
+        if remove_source {
+            if cal_id == dest_cal_id {
+                dest.remove_object(&mut dest_state, object_id, false)
+                    .await?;
+                dest.log(&mut dest_state, object_id, ChangeOperation::Delete)
+                    .await?;
+            } else {
+                let mut state = source.read_state().await?;
+                source.remove_object(&mut state, object_id, false).await?;
+                source
+                    .log(&mut state, object_id, ChangeOperation::Delete)
+                    .await?;
+                source.write_state(&state).await?;
+                source_state = Some(state);
+            }
+        }
+        dest.write_state(&dest_state).await?;
+
+        for state in [Some(dest_state), source_state].into_iter().flatten() {
+            self.notify(
+                CollectionOperationType::Object,
+                state.push_topic,
+                Some(state.synctoken),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CalendarStore for VdirCalendarStore {
+    #[instrument]
+    async fn get_calendar(&self, principal: &str, id: &str) -> Result<Calendar, Error> {
+        self.read_calendar(principal, id).await
+    }
+
+    #[instrument]
+    async fn get_calendars(&self, principal: &str) -> Result<Vec<Calendar>, Error> {
+        let mut calendars = vec![];
+        for cal_id in list_dirs(&self.root.join(check_name(principal)?).join("calendars")).await? {
+            let calendar = self.read_calendar(principal, &cal_id).await?;
+            if calendar.deleted_at.is_none() {
+                calendars.push(calendar);
+            }
+        }
+        Ok(calendars)
+    }
+
+    #[instrument]
+    async fn get_deleted_calendars(&self, principal: &str) -> Result<Vec<Calendar>, Error> {
+        let mut calendars = vec![];
+        for cal_id in list_dirs(&self.root.join(check_name(principal)?).join("calendars")).await? {
+            let calendar = self.read_calendar(principal, &cal_id).await?;
+            if calendar.deleted_at.is_some() {
+                calendars.push(calendar);
+            }
+        }
+        Ok(calendars)
+    }
+
+    #[instrument]
+    async fn insert_calendar(&self, calendar: Calendar) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(&calendar.principal, &calendar.id)?;
+        collection.create().await?;
+
+        let mut props = CollectionProps::default();
+        update_props(&mut props, &calendar);
+        collection.write_props(&props).await?;
+        collection
+            .write_state(&CollectionState {
+                push_topic: calendar.push_topic,
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn update_calendar(
+        &self,
+        principal: String,
+        id: String,
+        calendar: Calendar,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let mut collection = self.collection(&principal, &id)?;
+        let mut state = collection.read_state().await?;
+
+        if (calendar.principal.as_str(), calendar.id.as_str()) != (&principal, &id) {
+            let dest = self.collection(&calendar.principal, &calendar.id)?;
+            if dest.exists().await {
+                return Err(Error::AlreadyExists);
+            }
+            if let Some(parent) = dest.path.parent() {
+                fs::create_dir_all(parent)
+                    .await
+                    .map_err(crate::Error::from)?;
+            }
+            fs::rename(&collection.path, &dest.path)
+                .await
+                .map_err(crate::Error::from)?;
+            collection = dest;
+        }
+
+        let mut props: CollectionProps = collection.read_props().await?;
+        update_props(&mut props, &calendar);
+        collection.write_props(&props).await?;
+        state.push_topic = calendar.push_topic;
+        collection.write_state(&state).await?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_calendar(
+        &self,
+        principal: &str,
+        id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        let cal = match self.read_calendar(principal, id).await {
+            Ok(cal) => cal,
+            Err(Error::NotFound) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        if use_trashbin {
+            self.update_state(principal, id, |state| {
+                state.deleted_at = Some(chrono::Utc::now().naive_utc());
+            })
+            .await?;
+        } else {
+            let _guard = self.lock.lock().await;
+            fs::remove_dir_all(&self.collection(principal, id)?.path)
+                .await
+                .map_err(crate::Error::from)?;
+        }
+
+        self.notify(CollectionOperationType::Delete, cal.push_topic, None);
+        Ok(())
+    }
+
+    #[instrument]
+    async fn restore_calendar(&self, principal: &str, id: &str) -> Result<(), Error> {
+        self.update_state(principal, id, |state| state.deleted_at = None)
+            .await
+    }
+
+    #[instrument]
+    async fn calendar_query(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        query: CalendarQuery,
+    ) -> Result<Vec<CalendarObject>, Error> {
+        // We extend our query interval by one day in each direction since we really don't want to
+        // miss any objects because of timezone differences
+        let start = query.time_start.map(|start| start - TimeDelta::days(1));
+        let end = query.time_end.map(|end| end + TimeDelta::days(1));
+
+        Ok(self
+            .get_objects(principal, cal_id)
+            .await?
+            .into_iter()
+            .filter(|object| {
+                let first_occurence = object.get_first_occurence().ok().flatten();
+                let last_occurence = object.get_last_occurence().ok().flatten();
+                last_occurence
+                    .zip(start)
+                    .is_none_or(|(last, start)| last.date() >= start)
+                    && first_occurence
+                        .zip(end)
+                        .is_none_or(|(first, end)| first.date() <= end)
+            })
+            .collect())
+    }
+
+    #[instrument]
+    async fn get_objects(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<CalendarObject>, Error> {
+        let collection = self.collection(principal, cal_id)?;
+        let mut objects = vec![];
+        for (object_id, ics) in collection.list_objects().await? {
+            match CalendarObject::from_ics(object_id.to_owned(), ics) {
+                Ok(object) => objects.push(object),
+                // A file broken by hand shouldn't hide the rest of the calendar
+                Err(err) => warn!("Skipping {principal}/{cal_id}/{object_id}: {err}"),
+            }
+        }
+        Ok(objects)
+    }
+
+    #[instrument]
+    async fn get_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+    ) -> Result<CalendarObject, Error> {
+        let ics = self
+            .collection(principal, cal_id)?
+            .read_object(object_id)
+            .await?;
+        CalendarObject::from_ics(object_id.to_owned(), ics)
+    }
+
+    #[instrument]
+    async fn put_object(
+        &self,
+        principal: String,
+        cal_id: String,
+        object: CalendarObject,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(&principal, &cal_id)?;
+        let mut state = collection.read_state().await?;
+        collection
+            .write_object(&mut state, object.get_id(), object.get_ics(), overwrite)
+            .await?;
+        let synctoken = collection
+            .log(&mut state, object.get_id(), ChangeOperation::Add)
+            .await?;
+        collection.write_state(&state).await?;
+
+        self.notify(
+            CollectionOperationType::Object,
+            state.push_topic,
+            Some(synctoken),
+        );
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(principal, cal_id)?;
+        let mut state = collection.read_state().await?;
+        collection
+            .remove_object(&mut state, id, use_trashbin)
+            .await?;
+        let synctoken = collection
+            .log(&mut state, id, ChangeOperation::Delete)
+            .await?;
+        collection.write_state(&state).await?;
+
+        self.notify(
+            CollectionOperationType::Object,
+            state.push_topic,
+            Some(synctoken),
+        );
+        Ok(())
+    }
+
+    #[instrument]
+    async fn restore_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let collection = self.collection(principal, cal_id)?;
+        let mut state = collection.read_state().await?;
+        collection.restore_object(&mut state, object_id).await?;
+        let synctoken = collection
+            .log(&mut state, object_id, ChangeOperation::Add)
+            .await?;
+        collection.write_state(&state).await?;
+
+        self.notify(
+            CollectionOperationType::Object,
+            state.push_topic,
+            Some(synctoken),
+        );
+        Ok(())
+    }
+
+    #[instrument]
+    async fn copy_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            cal_id,
+            object_id,
+            dest_cal_id,
+            dest_object_id,
+            overwrite,
+            false,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn move_object(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        object_id: &str,
+        dest_cal_id: &str,
+        dest_object_id: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        self.copy_or_move_object(
+            principal,
+            cal_id,
+            object_id,
+            dest_cal_id,
+            dest_object_id,
+            overwrite,
+            true,
+        )
+        .await
+    }
+
+    #[instrument]
+    async fn sync_changes(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        synctoken: i64,
+    ) -> Result<(Vec<CalendarObject>, Vec<String>, i64), Error> {
+        self.rescan(principal, cal_id).await?;
+        let changes = self
+            .collection(principal, cal_id)?
+            .changes_since(synctoken)
+            .await?;
+
+        let mut objects = vec![];
+        let mut deleted_objects = vec![];
+
+        let new_synctoken = changes.last().map(|(_, synctoken)| *synctoken).unwrap_or(0);
+
+        for (object_id, _) in changes {
+            match self.get_object(principal, cal_id, &object_id).await {
+                Ok(object) => objects.push(object),
+                Err(Error::NotFound) => deleted_objects.push(object_id),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok((objects, deleted_objects, new_synctoken))
+    }
+
+    #[instrument]
+    async fn get_inbox_objects(&self, principal: &str) -> Result<Vec<CalendarObject>, Error> {
+        let inbox = self.root.join(check_name(principal)?).join("inbox");
+        let mut entries = match fs::read_dir(&inbox).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(crate::Error::from(err).into()),
+        };
+        let mut objects = vec![];
+        while let Some(entry) = entries.next_entry().await.map_err(crate::Error::from)? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Some(object_id) = name.strip_suffix(".ics") else {
+                continue;
+            };
+            if object_id.starts_with('.') {
+                continue;
+            }
+            let modified = entry
+                .metadata()
+                .await
+                .and_then(|metadata| metadata.modified())
+                .map_err(crate::Error::from)?;
+            let ics = fs::read_to_string(entry.path())
+                .await
+                .map_err(crate::Error::from)?;
+            objects.push((
+                modified,
+                CalendarObject::from_ics(object_id.to_owned(), ics)?,
+            ));
+        }
+        // Oldest messages first
+        objects.sort_by_key(|(modified, _)| *modified);
+        Ok(objects.into_iter().map(|(_, object)| object).collect())
+    }
+
+    #[instrument]
+    async fn get_inbox_object(
+        &self,
+        principal: &str,
+        object_id: &str,
+    ) -> Result<CalendarObject, Error> {
+        let ics = fs::read_to_string(self.inbox_path(principal, object_id)?)
+            .await
+            .map_err(crate::Error::from)?;
+        CalendarObject::from_ics(object_id.to_owned(), ics)
+    }
+
+    #[instrument]
+    async fn put_inbox_object(
+        &self,
+        principal: String,
+        object: CalendarObject,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let path = self.inbox_path(&principal, object.get_id())?;
+        if fs::try_exists(&path).await.map_err(crate::Error::from)? {
+            return Err(Error::AlreadyExists);
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(crate::Error::from)?;
+        }
+        write_atomic(&path, object.get_ics().as_bytes()).await?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete_inbox_object(&self, principal: &str, object_id: &str) -> Result<(), Error> {
+        match fs::remove_file(self.inbox_path(principal, object_id)?).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(crate::Error::from(err).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[instrument]
+    async fn get_calendar_shares(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<Share>, Error> {
+        let mut shares: Vec<_> = self
+            .collection(principal, cal_id)?
+            .read_state()
+            .await?
+            .shares
+            .into_iter()
+            .map(|share| share.into_share(principal, cal_id))
+            .collect();
+        shares.sort_by(|a, b| a.sharee.cmp(&b.sharee));
+        Ok(shares)
+    }
+
+    #[instrument]
+    async fn get_received_calendar_shares(&self, sharee: &str) -> Result<Vec<Share>, Error> {
+        // Shares of calendars in the trash bin are hidden
+        Ok(self
+            .all_calendars()
+            .await?
+            .into_iter()
+            .filter(|(calendar, _)| calendar.deleted_at.is_none())
+            .flat_map(|(calendar, state)| {
+                state
+                    .shares
+                    .into_iter()
+                    .filter(|share| share.sharee == sharee)
+                    .map(move |share| share.into_share(&calendar.principal, &calendar.id))
+            })
+            .collect())
+    }
+
+    #[instrument]
+    async fn put_calendar_share(&self, share: Share) -> Result<(), Error> {
+        let entry = ShareEntry {
+            sharee: share.sharee.to_owned(),
+            read_write: share.access == ShareAccess::ReadWrite,
+        };
+        self.update_state(&share.principal, &share.collection_id, |state| {
+            state
+                .shares
+                .retain(|existing| existing.sharee != entry.sharee);
+            state.shares.push(entry);
+        })
+        .await
+    }
+
+    #[instrument]
+    async fn delete_calendar_share(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        sharee: &str,
+    ) -> Result<(), Error> {
+        self.update_state(principal, cal_id, |state| {
+            state.shares.retain(|share| share.sharee != sharee)
+        })
+        .await
+    }
+
+    #[instrument]
+    async fn get_calendar_feeds(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<Vec<CalendarFeed>, Error> {
+        Ok(self
+            .collection(principal, cal_id)?
+            .read_state()
+            .await?
+            .feeds
+            .into_iter()
+            .map(|feed| feed_from_entry(principal, cal_id, feed))
+            .collect())
+    }
+
+    #[instrument(skip(token))]
+    async fn get_calendar_feed(&self, token: &str) -> Result<CalendarFeed, Error> {
+        self.all_calendars()
+            .await?
+            .into_iter()
+            .find_map(|(calendar, state)| {
+                state
+                    .feeds
+                    .into_iter()
+                    .find(|feed| feed.token == token)
+                    .map(|feed| feed_from_entry(&calendar.principal, &calendar.id, feed))
+            })
+            .ok_or(Error::NotFound)
+    }
+
+    #[instrument(skip(feed))]
+    async fn insert_calendar_feed(&self, feed: CalendarFeed) -> Result<(), Error> {
+        let entry = FeedEntry {
+            token: feed.token,
+            privacy: feed.privacy,
+            created_at: feed
+                .created_at
+                .unwrap_or_else(|| chrono::Utc::now().naive_utc()),
+        };
+        self.update_state(&feed.principal, &feed.cal_id, |state| {
+            state.feeds.push(entry)
+        })
+        .await
+    }
+
+    #[instrument(skip(token))]
+    async fn delete_calendar_feed(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        token: &str,
+    ) -> Result<(), Error> {
+        self.update_state(principal, cal_id, |state| {
+            state.feeds.retain(|feed| feed.token != token)
+        })
+        .await
+    }
+
+    #[instrument]
+    async fn get_subscribed_calendars(&self) -> Result<Vec<Calendar>, Error> {
+        Ok(self
+            .all_calendars()
+            .await?
+            .into_iter()
+            .map(|(calendar, _)| calendar)
+            .filter(|calendar| calendar.subscription_url.is_some() && calendar.deleted_at.is_none())
+            .collect())
+    }
+
+    #[instrument]
+    async fn get_webcal_status(
+        &self,
+        principal: &str,
+        cal_id: &str,
+    ) -> Result<WebcalStatus, Error> {
+        Ok(self
+            .collection(principal, cal_id)?
+            .read_state()
+            .await?
+            .webcal_status
+            .unwrap_or_default())
+    }
+
+    #[instrument]
+    async fn put_webcal_status(
+        &self,
+        principal: &str,
+        cal_id: &str,
+        status: WebcalStatus,
+    ) -> Result<(), Error> {
+        self.update_state(principal, cal_id, |state| {
+            state.webcal_status = Some(status)
+        })
+        .await
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+fn feed_from_entry(principal: &str, cal_id: &str, feed: FeedEntry) -> CalendarFeed {
+    CalendarFeed {
+        token: feed.token,
+        principal: principal.to_owned(),
+        cal_id: cal_id.to_owned(),
+        privacy: feed.privacy,
+        created_at: Some(feed.created_at),
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{ChangeOperation, Error};
+use chrono::NaiveDateTime;
+use rustical_store::{
+    calendar::{FeedPrivacy, WebcalStatus},
+    Share, ShareAccess,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+// Collection properties like the displayname and color
+pub(crate) const PROPS_FILE: &str = ".rustical.props";
+// State that only rustical needs
+const STATE_FILE: &str = ".rustical.json";
+// Append-only log of changes, one JSON object per line
+const CHANGELOG_FILE: &str = ".rustical.changelog";
+const TRASH_DIR: &str = ".trash";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ShareEntry {
+    pub sharee: String,
+    pub read_write: bool,
+}
+
+impl ShareEntry {
+    pub fn into_share(self, principal: &str, collection_id: &str) -> Share {
+        Share {
+            principal: principal.to_owned(),
+            collection_id: collection_id.to_owned(),
+            sharee: self.sharee,
+            access: if self.read_write {
+                ShareAccess::ReadWrite
+            } else {
+                ShareAccess::Read
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FeedEntry {
+    pub token: String,
+    pub privacy: FeedPrivacy,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct CollectionState {
+    pub push_topic: String,
+    pub synctoken: i64,
+    pub deleted_at: Option<NaiveDateTime>,
+    // Content hashes of the objects, used to notice changes made outside of rustical
+    pub objects: BTreeMap<String, String>,
+    pub shares: Vec<ShareEntry>,
+    pub feeds: Vec<FeedEntry>,
+    pub webcal_status: Option<WebcalStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChangelogEntry {
+    synctoken: i64,
+    object_id: String,
+    operation: ChangeOperation,
+}
+
+// Principals, collections and objects become path segments
+pub(crate) fn check_name(name: &str) -> Result<&str, Error> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(rustical_store::Error::InvalidData(format!("Invalid name: {name}")).into());
+    }
+    Ok(name)
+}
+
+fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+// Readers never see half-written files
+pub(crate) async fn write_atomic(path: &Path, content: &[u8]) -> Result<(), Error> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{file_name}.tmp"));
+    fs::write(&tmp_path, content).await?;
+    fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+pub(crate) async fn read_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T, Error> {
+    match fs::read(path).await {
+        Ok(content) => Ok(serde_json::from_slice(&content)?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(crate) async fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    write_atomic(path, &serde_json::to_vec_pretty(value)?).await
+}
+
+// Names of the subdirectories, dotfiles are skipped
+pub(crate) async fn list_dirs(path: &Path) -> Result<Vec<String>, Error> {
+    let mut entries = match fs::read_dir(path).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    let mut names = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') && entry.file_type().await?.is_dir() {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+// A directory with one file per object (a vdir)
+#[derive(Debug, Clone)]
+pub(crate) struct Collection {
+    pub path: PathBuf,
+    extension: &'static str,
+}
+
+impl Collection {
+    pub fn new(path: PathBuf, extension: &'static str) -> Self {
+        Self { path, extension }
+    }
+
+    fn object_path(&self, object_id: &str) -> Result<PathBuf, Error> {
+        Ok(self
+            .path
+            .join(format!("{}.{}", check_name(object_id)?, self.extension)))
+    }
+
+    fn trash_path(&self, object_id: &str) -> Result<PathBuf, Error> {
+        Ok(self
+            .path
+            .join(TRASH_DIR)
+            .join(format!("{}.{}", check_name(object_id)?, self.extension)))
+    }
+
+    pub async fn exists(&self) -> bool {
+        fs::metadata(&self.path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    pub async fn create(&self) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::create_dir(&self.path).await?;
+        Ok(())
+    }
+
+    pub async fn read_props<P: DeserializeOwned + Default>(&self) -> Result<P, Error> {
+        read_json(&self.path.join(PROPS_FILE)).await
+    }
+
+    pub async fn write_props<P: Serialize>(&self, props: &P) -> Result<(), Error> {
+        write_json(&self.path.join(PROPS_FILE), props).await
+    }
+
+    pub async fn read_state(&self) -> Result<CollectionState, Error> {
+        if !self.exists().await {
+            return Err(rustical_store::Error::NotFound.into());
+        }
+        let mut state: CollectionState = read_json(&self.path.join(STATE_FILE)).await?;
+        if state.push_topic.is_empty() {
+            // Collections created by hand get a stable topic until the state is written
+            state.push_topic = content_hash(&self.path.to_string_lossy());
+        }
+        Ok(state)
+    }
+
+    pub async fn write_state(&self, state: &CollectionState) -> Result<(), Error> {
+        write_json(&self.path.join(STATE_FILE), state).await
+    }
+
+    // Objects in the trash bin can still be read
+    pub async fn read_object(&self, object_id: &str) -> Result<String, Error> {
+        match fs::read_to_string(self.object_path(object_id)?).await {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(fs::read_to_string(self.trash_path(object_id)?).await?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    pub async fn list_objects(&self) -> Result<Vec<(String, String)>, Error> {
+        let mut entries = fs::read_dir(&self.path).await?;
+        let suffix = format!(".{}", self.extension);
+        let mut objects = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Some(object_id) = name.strip_suffix(&suffix) else {
+                continue;
+            };
+            if object_id.starts_with('.') || !entry.file_type().await?.is_file() {
+                continue;
+            }
+            objects.push((
+                object_id.to_owned(),
+                fs::read_to_string(entry.path()).await?,
+            ));
+        }
+        objects.sort();
+        Ok(objects)
+    }
+
+    pub async fn write_object(
+        &self,
+        state: &mut CollectionState,
+        object_id: &str,
+        content: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        let (path, trash_path) = (self.object_path(object_id)?, self.trash_path(object_id)?);
+        let exists = fs::try_exists(&path).await? || fs::try_exists(&trash_path).await?;
+        if exists && !overwrite {
+            return Err(rustical_store::Error::AlreadyExists.into());
+        }
+        remove_if_exists(&trash_path).await?;
+        write_atomic(&path, content.as_bytes()).await?;
+        state
+            .objects
+            .insert(object_id.to_owned(), content_hash(content));
+        Ok(())
+    }
+
+    pub async fn remove_object(
+        &self,
+        state: &mut CollectionState,
+        object_id: &str,
+        use_trashbin: bool,
+    ) -> Result<(), Error> {
+        let (path, trash_path) = (self.object_path(object_id)?, self.trash_path(object_id)?);
+        if use_trashbin {
+            fs::create_dir_all(self.path.join(TRASH_DIR)).await?;
+            match fs::rename(&path, &trash_path).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        } else {
+            remove_if_exists(&path).await?;
+            remove_if_exists(&trash_path).await?;
+        }
+        state.objects.remove(object_id);
+        Ok(())
+    }
+
+    pub async fn restore_object(
+        &self,
+        state: &mut CollectionState,
+        object_id: &str,
+    ) -> Result<(), Error> {
+        let path = self.object_path(object_id)?;
+        match fs::rename(self.trash_path(object_id)?, &path).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        if let Ok(content) = fs::read_to_string(&path).await {
+            state
+                .objects
+                .insert(object_id.to_owned(), content_hash(&content));
+        }
+        Ok(())
+    }
+
+    // Increases the sync token, the caller has to write the state afterwards
+    pub async fn log(
+        &self,
+        state: &mut CollectionState,
+        object_id: &str,
+        operation: ChangeOperation,
+    ) -> Result<i64, Error> {
+        state.synctoken += 1;
+        let mut line = serde_json::to_vec(&ChangelogEntry {
+            synctoken: state.synctoken,
+            object_id: object_id.to_owned(),
+            operation,
+        })?;
+        line.push(b'\n');
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.join(CHANGELOG_FILE))
+            .await?;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(state.synctoken)
+    }
+
+    // Objects changed after a sync token in the order of their changes
+    pub async fn changes_since(&self, synctoken: i64) -> Result<Vec<(String, i64)>, Error> {
+        let changelog = match fs::read_to_string(self.path.join(CHANGELOG_FILE)).await {
+            Ok(changelog) => changelog,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut changes = vec![];
+        for line in changelog.lines().filter(|line| !line.trim().is_empty()) {
+            let entry: ChangelogEntry = serde_json::from_str(line)?;
+            if entry.synctoken > synctoken {
+                changes.push((entry.object_id, entry.synctoken));
+            }
+        }
+        changes.sort_by_key(|(_, synctoken)| *synctoken);
+        Ok(changes)
+    }
+
+    // Logs objects that were added, changed or removed outside of rustical
+    // Returns whether anything changed, the caller has to write the state afterwards
+    pub async fn rescan(&self, state: &mut CollectionState) -> Result<bool, Error> {
+        let mut changed = false;
+        let mut seen = HashSet::new();
+        for (object_id, content) in self.list_objects().await? {
+            let hash = content_hash(&content);
+            if state.objects.get(&object_id) != Some(&hash) {
+                state.objects.insert(object_id.to_owned(), hash);
+                self.log(state, &object_id, ChangeOperation::Add).await?;
+                changed = true;
+            }
+            seen.insert(object_id);
+        }
+        let removed: Vec<_> = state
+            .objects
+            .keys()
+            .filter(|object_id| !seen.contains(*object_id))
+            .cloned()
+            .collect();
+        for object_id in removed {
+            state.objects.remove(&object_id);
+            self.log(state, &object_id, ChangeOperation::Delete).await?;
+            changed = true;
+        }
+        Ok(changed)
+    }
+}
+
+async fn remove_if_exists(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path).await {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_name, Collection, CollectionState};
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("event-1@example.com").is_ok());
+        for name in ["", ".trash", "../alice", "a/b", "a\\b"] {
+            assert!(check_name(name).is_err(), "{name} should be rejected");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rescan() {
+        let dir = tempfile::tempdir().unwrap();
+        let collection = Collection::new(dir.path().join("calendar"), "ics");
+        collection.create().await.unwrap();
+        let mut state = CollectionState::default();
+
+        collection
+            .write_object(&mut state, "a", "A", false)
+            .await
+            .unwrap();
+        assert!(!collection.rescan(&mut state).await.unwrap());
+
+        // Changed, added and removed by hand
+        std::fs::write(collection.path.join("a.ics"), "A2").unwrap();
+        std::fs::write(collection.path.join("b.ics"), "B").unwrap();
+        std::fs::write(collection.path.join("notes.txt"), "ignored").unwrap();
+        assert!(collection.rescan(&mut state).await.unwrap());
+        std::fs::remove_file(collection.path.join("a.ics")).unwrap();
+        assert!(collection.rescan(&mut state).await.unwrap());
+
+        assert_eq!(state.synctoken, 3);
+        assert_eq!(
+            collection.changes_since(0).await.unwrap(),
+            vec![
+                ("a".to_owned(), 1),
+                ("b".to_owned(), 2),
+                ("a".to_owned(), 3)
+            ]
+        );
+        assert_eq!(state.objects.keys().collect::<Vec<_>>(), vec!["b"]);
+    }
+}
@@ -0,0 +1,39 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    IoError(std::io::Error),
+
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    StoreError(rustical_store::Error),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        match value.kind() {
+            std::io::ErrorKind::NotFound => Error::StoreError(rustical_store::Error::NotFound),
+            std::io::ErrorKind::AlreadyExists => {
+                Error::StoreError(rustical_store::Error::AlreadyExists)
+            }
+            _ => Error::IoError(value),
+        }
+    }
+}
+
+impl From<Error> for rustical_store::Error {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::IoError(err) => Self::Other(err.into()),
+            Error::JsonError(err) => Self::Other(err.into()),
+            Error::StoreError(err) => err,
+        }
+    }
+}
+
+impl From<rustical_store::Error> for Error {
+    fn from(value: rustical_store::Error) -> Self {
+        Self::StoreError(value)
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+pub mod addressbook_store;
+pub mod calendar_store;
+mod collection;
+pub mod error;
+pub mod props;
+pub mod subscription_store;
+pub mod watcher;
+
+pub use error::Error;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ChangeOperation {
+    // There's no distinction between Add and Modify
+    Add,
+    Delete,
+}
+
+// Stores DAV Push subscriptions next to the collections
+#[derive(Debug)]
+pub struct VdirStore {
+    root: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl VdirStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            lock: Mutex::new(()),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use rustical_store::calendar::{parse_components, CalendarObjectType};
+use rustical_xml::ValueSerialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Radicale's tags telling calendars from addressbooks in its collection-root
+pub const TAG_CALENDAR: &str = "VCALENDAR";
+pub const TAG_ADDRESSBOOK: &str = "VADDRESSBOOK";
+// Radicale's tag for calendars mirroring a webcal feed
+pub const TAG_SUBSCRIBED: &str = "VSUBSCRIBED";
+
+// Collection properties keyed by their DAV names like Radicale's .Radicale.props,
+// so the same struct reads Radicale's collections when migrating
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionProps {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(rename = "D:displayname", skip_serializing_if = "Option::is_none")]
+    pub displayname: Option<String>,
+    #[serde(
+        rename = "C:calendar-description",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub calendar_description: Option<String>,
+    #[serde(
+        rename = "CR:addressbook-description",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub addressbook_description: Option<String>,
+    #[serde(
+        rename = "ICAL:calendar-color",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color: Option<String>,
+    #[serde(
+        rename = "ICAL:calendar-order",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub order: Option<String>,
+    #[serde(
+        rename = "C:calendar-timezone",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timezone: Option<String>,
+    #[serde(
+        rename = "C:calendar-timezone-id",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timezone_id: Option<String>,
+    // Comma-separated like VEVENT,VTODO
+    #[serde(
+        rename = "C:supported-calendar-component-set",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub components: Option<String>,
+    #[serde(rename = "CS:source", skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    // Properties rustical doesn't know are kept as they are
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+impl CollectionProps {
+    // All components if the set is missing like in Radicale
+    pub fn get_components(&self) -> Vec<CalendarObjectType> {
+        match &self.components {
+            Some(components) => parse_components(components),
+            None => vec![
+                CalendarObjectType::Event,
+                CalendarObjectType::Todo,
+                CalendarObjectType::Journal,
+            ],
+        }
+    }
+
+    pub fn set_components(&mut self, components: &[CalendarObjectType]) {
+        self.components = Some(
+            components
+                .iter()
+                .map(ValueSerialize::serialize)
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CollectionProps;
+    use rustical_store::calendar::CalendarObjectType;
+
+    #[test]
+    fn test_radicale_props() {
+        let props: CollectionProps = serde_json::from_str(
+            r##"{"C:supported-calendar-component-set": "VEVENT,VTODO", "D:displayname": "Work", "ICAL:calendar-color": "#ff0000ff", "tag": "VCALENDAR", "X:unknown": "kept"}"##,
+        )
+        .unwrap();
+        assert_eq!(props.displayname.as_deref(), Some("Work"));
+        assert_eq!(
+            props.get_components(),
+            vec![CalendarObjectType::Event, CalendarObjectType::Todo]
+        );
+
+        let json = serde_json::to_value(&props).unwrap();
+        assert_eq!(json["X:unknown"], "kept");
+        assert!(json.get("CS:source").is_none());
+    }
+}
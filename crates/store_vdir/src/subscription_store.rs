@@ -0,0 +1,87 @@
+use crate::collection::{read_json, write_json};
+use crate::VdirStore;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use rustical_store::{Error, Subscription, SubscriptionStore};
+use serde::{Deserialize, Serialize};
+
+const SUBSCRIPTIONS_FILE: &str = ".subscriptions.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SubscriptionEntry {
+    id: String,
+    topic: String,
+    expiration: NaiveDateTime,
+    push_resource: String,
+}
+
+impl From<SubscriptionEntry> for Subscription {
+    fn from(value: SubscriptionEntry) -> Self {
+        Self {
+            id: value.id,
+            topic: value.topic,
+            expiration: value.expiration,
+            push_resource: value.push_resource,
+        }
+    }
+}
+
+impl VdirStore {
+    async fn read_subscriptions(&self) -> Result<Vec<SubscriptionEntry>, crate::Error> {
+        read_json(&self.root.join(SUBSCRIPTIONS_FILE)).await
+    }
+
+    async fn write_subscriptions(
+        &self,
+        subscriptions: &[SubscriptionEntry],
+    ) -> Result<(), crate::Error> {
+        tokio::fs::create_dir_all(&self.root).await?;
+        write_json(&self.root.join(SUBSCRIPTIONS_FILE), &subscriptions).await
+    }
+}
+
+#[async_trait]
+impl SubscriptionStore for VdirStore {
+    async fn get_subscriptions(&self, topic: &str) -> Result<Vec<Subscription>, Error> {
+        Ok(self
+            .read_subscriptions()
+            .await?
+            .into_iter()
+            .filter(|sub| sub.topic == topic)
+            .map(Subscription::from)
+            .collect())
+    }
+
+    async fn get_subscription(&self, id: &str) -> Result<Subscription, Error> {
+        self.read_subscriptions()
+            .await?
+            .into_iter()
+            .find(|sub| sub.id == id)
+            .map(Subscription::from)
+            .ok_or(Error::NotFound)
+    }
+
+    async fn upsert_subscription(&self, sub: Subscription) -> Result<bool, Error> {
+        let _guard = self.lock.lock().await;
+        let mut subscriptions = self.read_subscriptions().await?;
+        let len = subscriptions.len();
+        subscriptions.retain(|existing| existing.id != sub.id);
+        let existed = subscriptions.len() != len;
+        subscriptions.push(SubscriptionEntry {
+            id: sub.id,
+            topic: sub.topic,
+            expiration: sub.expiration,
+            push_resource: sub.push_resource,
+        });
+        self.write_subscriptions(&subscriptions).await?;
+        Ok(existed)
+    }
+
+    async fn delete_subscription(&self, id: &str) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let mut subscriptions = self.read_subscriptions().await?;
+        subscriptions.retain(|sub| sub.id != id);
+        self.write_subscriptions(&subscriptions).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{addressbook_store::VdirAddressbookStore, calendar_store::VdirCalendarStore};
+use std::{sync::Arc, time::Duration};
+use tracing::error;
+
+// Polls the collections for files changed outside of rustical, e.g. by vdirsyncer,
+// so that sync tokens advance and DAV Push subscribers get notified
+pub async fn vdir_watcher(
+    cal_store: Arc<VdirCalendarStore>,
+    addr_store: Arc<VdirAddressbookStore>,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if let Err(err) = cal_store.rescan_all().await {
+            error!("Error rescanning calendars: {err}");
+        }
+        if let Err(err) = addr_store.rescan_all().await {
+            error!("Error rescanning addressbooks: {err}");
+        }
+    }
+}
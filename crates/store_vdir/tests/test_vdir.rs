@@ -0,0 +1,404 @@
+use chrono::NaiveDate;
+use rustical_store::{
+    calendar::{CalendarFeed, FeedPrivacy, WebcalStatus},
+    synctoken::format_synctoken,
+    AddressObject, Addressbook, AddressbookStore, Calendar, CalendarObject, CalendarStore,
+    CollectionOperation, Share, ShareAccess, Subscription, SubscriptionStore,
+};
+use rustical_store_vdir::{
+    addressbook_store::VdirAddressbookStore, calendar_store::VdirCalendarStore, VdirStore,
+};
+use tempfile::TempDir;
+use tokio::sync::mpsc::Receiver;
+
+fn event(uid: &str, date: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VEVENT\r\nUID:{uid}\r\nDTSTAMP:20240101T000000Z\r\nDTSTART;VALUE=DATE:{date}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+    )
+}
+
+const VCARD: &str = "BEGIN:VCARD\r\nVERSION:4.0\r\nUID:alice\r\nFN:Alice\r\nEND:VCARD\r\n";
+
+fn cal_store() -> (TempDir, VdirCalendarStore, Receiver<CollectionOperation>) {
+    let dir = tempfile::tempdir().unwrap();
+    let (send, recv) = tokio::sync::mpsc::channel(100);
+    let store = VdirCalendarStore::new(dir.path().to_owned(), send);
+    (dir, store, recv)
+}
+
+async fn insert_calendar(store: &impl CalendarStore, id: &str) {
+    store
+        .insert_calendar(Calendar {
+            principal: "testuser".to_owned(),
+            id: id.to_owned(),
+            push_topic: format!("topic-{id}"),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_calendar_sync() {
+    let (_dir, store, _recv) = cal_store();
+    insert_calendar(&store, "test").await;
+
+    let object = CalendarObject::from_ics("a".to_owned(), event("a", "20240101")).unwrap();
+    store
+        .put_object("testuser".to_owned(), "test".to_owned(), object, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .get_object("testuser", "test", "a")
+            .await
+            .unwrap()
+            .get_ics(),
+        event("a", "20240101")
+    );
+
+    // Objects can only be overwritten explicitly
+    let object = CalendarObject::from_ics("a".to_owned(), event("a", "20240201")).unwrap();
+    assert!(matches!(
+        store
+            .put_object(
+                "testuser".to_owned(),
+                "test".to_owned(),
+                object.clone(),
+                false
+            )
+            .await,
+        Err(rustical_store::Error::AlreadyExists)
+    ));
+    store
+        .put_object("testuser".to_owned(), "test".to_owned(), object, true)
+        .await
+        .unwrap();
+    let object = CalendarObject::from_ics("b".to_owned(), event("b", "20240301")).unwrap();
+    store
+        .put_object("testuser".to_owned(), "test".to_owned(), object, false)
+        .await
+        .unwrap();
+
+    let (changed, deleted, synctoken) = store.sync_changes("testuser", "test", 0).await.unwrap();
+    assert_eq!(synctoken, 3);
+    assert_eq!(changed.len(), 3);
+    assert!(deleted.is_empty());
+    assert_eq!(
+        store
+            .get_calendar("testuser", "test")
+            .await
+            .unwrap()
+            .synctoken,
+        3
+    );
+
+    store
+        .delete_object("testuser", "test", "b", false)
+        .await
+        .unwrap();
+    let (changed, deleted, synctoken) = store
+        .sync_changes("testuser", "test", synctoken)
+        .await
+        .unwrap();
+    assert!(changed.is_empty());
+    assert_eq!(deleted, vec!["b".to_owned()]);
+    assert_eq!(synctoken, 4);
+
+    // Objects in the trash bin are still returned by get_object
+    store
+        .delete_object("testuser", "test", "a", true)
+        .await
+        .unwrap();
+    assert!(store
+        .get_objects("testuser", "test")
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(store.get_object("testuser", "test", "a").await.is_ok());
+    store.restore_object("testuser", "test", "a").await.unwrap();
+    assert_eq!(
+        store.get_objects("testuser", "test").await.unwrap().len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_external_changes() {
+    let (dir, store, mut recv) = cal_store();
+    insert_calendar(&store, "test").await;
+    let object = CalendarObject::from_ics("a".to_owned(), event("a", "20240101")).unwrap();
+    store
+        .put_object("testuser".to_owned(), "test".to_owned(), object, false)
+        .await
+        .unwrap();
+    recv.try_recv().unwrap();
+
+    // Edited and added by another program, e.g. vdirsyncer
+    let path = dir.path().join("testuser/calendars/test");
+    std::fs::write(path.join("a.ics"), event("a", "20240301")).unwrap();
+    std::fs::write(path.join("b.ics"), event("b", "20240401")).unwrap();
+
+    store.rescan_all().await.unwrap();
+    let operation = recv.try_recv().unwrap();
+    assert_eq!(operation.topic, "topic-test");
+    assert_eq!(operation.sync_token, Some(format_synctoken(3)));
+
+    let (changed, deleted, synctoken) = store.sync_changes("testuser", "test", 1).await.unwrap();
+    assert_eq!(
+        changed.iter().map(|obj| obj.get_id()).collect::<Vec<_>>(),
+        vec!["a", "b"]
+    );
+    assert!(deleted.is_empty());
+    assert_eq!(synctoken, 3);
+
+    // sync_changes picks up changes on its own
+    std::fs::remove_file(path.join("b.ics")).unwrap();
+    let (_, deleted, synctoken) = store
+        .sync_changes("testuser", "test", synctoken)
+        .await
+        .unwrap();
+    assert_eq!(deleted, vec!["b".to_owned()]);
+    assert_eq!(synctoken, 4);
+}
+
+#[tokio::test]
+async fn test_hand_made_collection() {
+    let (dir, store, _recv) = cal_store();
+    let path = dir.path().join("testuser/calendars/tasks");
+    std::fs::create_dir_all(&path).unwrap();
+    std::fs::write(
+        path.join(".rustical.props"),
+        r##"{"C:supported-calendar-component-set": "VTODO", "D:displayname": "Tasks", "ICAL:calendar-color": "#00ff00ff", "X:unknown": "kept"}"##,
+    )
+    .unwrap();
+    std::fs::write(path.join("todo.ics"), event("todo", "20240101")).unwrap();
+
+    let calendar = store.get_calendar("testuser", "tasks").await.unwrap();
+    assert_eq!(calendar.displayname.as_deref(), Some("Tasks"));
+    assert_eq!(calendar.color.as_deref(), Some("#00ff00ff"));
+    assert_eq!(
+        store.get_objects("testuser", "tasks").await.unwrap().len(),
+        1
+    );
+
+    // Updating keeps properties rustical doesn't know
+    store
+        .update_calendar(
+            "testuser".to_owned(),
+            "tasks".to_owned(),
+            Calendar {
+                displayname: Some("Todos".to_owned()),
+                ..calendar
+            },
+        )
+        .await
+        .unwrap();
+    let props: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(path.join(".rustical.props")).unwrap()).unwrap();
+    assert_eq!(props["D:displayname"], "Todos");
+    assert_eq!(props["C:supported-calendar-component-set"], "VTODO");
+    assert_eq!(props["X:unknown"], "kept");
+}
+
+#[tokio::test]
+async fn test_calendar_trashbin_and_shares() {
+    let (_dir, store, _recv) = cal_store();
+    insert_calendar(&store, "home").await;
+    insert_calendar(&store, "work").await;
+    assert!(matches!(
+        store
+            .insert_calendar(Calendar {
+                principal: "testuser".to_owned(),
+                id: "home".to_owned(),
+                ..Default::default()
+            })
+            .await,
+        Err(rustical_store::Error::AlreadyExists)
+    ));
+
+    for access in [ShareAccess::Read, ShareAccess::ReadWrite] {
+        store
+            .put_calendar_share(Share {
+                principal: "testuser".to_owned(),
+                collection_id: "home".to_owned(),
+                sharee: "bob".to_owned(),
+                access,
+            })
+            .await
+            .unwrap();
+    }
+    let shares = store.get_received_calendar_shares("bob").await.unwrap();
+    assert_eq!(shares.len(), 1);
+    assert_eq!(shares[0].access, ShareAccess::ReadWrite);
+
+    store
+        .delete_calendar("testuser", "home", true)
+        .await
+        .unwrap();
+    assert_eq!(store.get_calendars("testuser").await.unwrap().len(), 1);
+    assert_eq!(
+        store.get_deleted_calendars("testuser").await.unwrap().len(),
+        1
+    );
+    assert!(store
+        .get_received_calendar_shares("bob")
+        .await
+        .unwrap()
+        .is_empty());
+
+    store.restore_calendar("testuser", "home").await.unwrap();
+    assert_eq!(store.get_calendars("testuser").await.unwrap().len(), 2);
+
+    store
+        .delete_calendar("testuser", "home", false)
+        .await
+        .unwrap();
+    assert!(matches!(
+        store.get_calendar("testuser", "home").await,
+        Err(rustical_store::Error::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_calendar_feeds_and_webcal_status() {
+    let (_dir, store, _recv) = cal_store();
+    insert_calendar(&store, "test").await;
+
+    let feed = CalendarFeed::new(
+        "testuser".to_owned(),
+        "test".to_owned(),
+        FeedPrivacy::BusyOnly,
+    );
+    store.insert_calendar_feed(feed.clone()).await.unwrap();
+    let stored = store.get_calendar_feed(&feed.token).await.unwrap();
+    assert_eq!(stored.privacy, FeedPrivacy::BusyOnly);
+
+    store
+        .put_webcal_status(
+            "testuser",
+            "test",
+            WebcalStatus {
+                etag: Some("\"1\"".to_owned()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .get_webcal_status("testuser", "test")
+            .await
+            .unwrap()
+            .etag
+            .as_deref(),
+        Some("\"1\"")
+    );
+
+    // Deleting the calendar removes its feeds
+    store
+        .delete_calendar("testuser", "test", false)
+        .await
+        .unwrap();
+    assert!(store.get_calendar_feed(&feed.token).await.is_err());
+}
+
+#[tokio::test]
+async fn test_copy_move_object() {
+    let (_dir, store, _recv) = cal_store();
+    insert_calendar(&store, "home").await;
+    insert_calendar(&store, "work").await;
+    let object = CalendarObject::from_ics("event".to_owned(), event("event", "20240101")).unwrap();
+    store
+        .put_object("testuser".to_owned(), "home".to_owned(), object, false)
+        .await
+        .unwrap();
+
+    store
+        .move_object("testuser", "home", "event", "work", "moved", false)
+        .await
+        .unwrap();
+    let (_, deleted, _) = store.sync_changes("testuser", "home", 1).await.unwrap();
+    assert_eq!(deleted, vec!["event".to_owned()]);
+    let (changed, _, _) = store.sync_changes("testuser", "work", 0).await.unwrap();
+    assert_eq!(
+        changed.iter().map(|obj| obj.get_id()).collect::<Vec<_>>(),
+        vec!["moved"]
+    );
+}
+
+#[tokio::test]
+async fn test_addressbook_sync() {
+    let dir = tempfile::tempdir().unwrap();
+    let (send, _recv) = tokio::sync::mpsc::channel(100);
+    let store = VdirAddressbookStore::new(dir.path().to_owned(), send);
+    store
+        .insert_addressbook(Addressbook {
+            principal: "testuser".to_owned(),
+            id: "contacts".to_owned(),
+            displayname: Some("Contacts".to_owned()),
+            description: None,
+            deleted_at: None,
+            synctoken: 0,
+            push_topic: "topic".to_owned(),
+        })
+        .await
+        .unwrap();
+    assert!(dir
+        .path()
+        .join("testuser/addressbooks/contacts/.rustical.props")
+        .exists());
+
+    let object = AddressObject::from_vcf("alice".to_owned(), VCARD.to_owned()).unwrap();
+    store
+        .put_object("testuser".to_owned(), "contacts".to_owned(), object, false)
+        .await
+        .unwrap();
+    assert!(dir
+        .path()
+        .join("testuser/addressbooks/contacts/alice.vcf")
+        .exists());
+    store
+        .delete_object("testuser", "contacts", "alice", false)
+        .await
+        .unwrap();
+
+    let (changed, deleted, synctoken) =
+        store.sync_changes("testuser", "contacts", 0).await.unwrap();
+    assert!(changed.is_empty());
+    assert!(deleted.contains(&"alice".to_owned()));
+    assert_eq!(synctoken, 2);
+}
+
+#[tokio::test]
+async fn test_subscriptions() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = VdirStore::new(dir.path().to_owned());
+    let expiration = NaiveDate::from_ymd_opt(2030, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    for (push_resource, existed) in [
+        ("https://push.example.com/1", false),
+        ("https://push.example.com/2", true),
+    ] {
+        assert_eq!(
+            store
+                .upsert_subscription(Subscription {
+                    id: "sub".to_owned(),
+                    topic: "topic".to_owned(),
+                    expiration,
+                    push_resource: push_resource.to_owned(),
+                })
+                .await
+                .unwrap(),
+            existed
+        );
+    }
+    let subscriptions = store.get_subscriptions("topic").await.unwrap();
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(subscriptions[0].push_resource, "https://push.example.com/2");
+
+    store.delete_subscription("sub").await.unwrap();
+    assert!(store.get_subscription("sub").await.is_err());
+}
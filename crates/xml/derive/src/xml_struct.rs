@@ -8,7 +8,12 @@ use syn::{DataStruct, DeriveInput};
 fn invalid_field_branch(ident: &syn::Ident, allow: bool) -> proc_macro2::TokenStream {
     let ident = ident.to_string();
     if allow {
-        quote! {}
+        quote! {
+            // Skip the invalid element including its children
+            if !empty {
+                reader.read_to_end_into(start.name(), &mut Vec::new())?;
+            }
+        }
     } else {
         quote! {
         return Err(XmlError::InvalidFieldName(#ident, format!("[{ns:?}]{tag}", tag = String::from_utf8_lossy(tag)))) }
@@ -197,6 +197,7 @@ fn test_struct_attr() {
 fn test_struct_generics() {
     #[derive(XmlDeserialize, XmlRootTag)]
     #[xml(root = b"document")]
+    #[allow(dead_code)]
     struct Document<T: XmlDeserialize> {
         child: T,
     }
@@ -217,6 +218,7 @@ fn test_struct_generics() {
 fn test_struct_unparsed() {
     #[derive(XmlDeserialize, XmlRootTag)]
     #[xml(root = b"document")]
+    #[allow(dead_code)]
     struct Document {
         child: Unparsed,
     }
@@ -311,3 +313,29 @@ fn test_struct_tuple() {
         }
     );
 }
+
+#[test]
+fn test_struct_allow_invalid() {
+    #[derive(Debug, XmlDeserialize, XmlRootTag, PartialEq)]
+    #[xml(root = b"document", allow_invalid)]
+    struct Document {
+        child: Option<String>,
+    }
+
+    let doc = Document::parse_str(
+        r#"
+    <?xml version="1.0" encoding="utf-8"?>
+    <document>
+        <invalid><child>Nested</child></invalid>
+        <invalid />
+        <child>Hello!</child>
+    </document>"#,
+    )
+    .unwrap();
+    assert_eq!(
+        doc,
+        Document {
+            child: Some("Hello!".to_owned())
+        }
+    );
+}
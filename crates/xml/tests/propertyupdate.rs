@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use rustical_xml::{Unparsed, XmlDeserialize, XmlDocument, XmlRootTag};
 
 #[test]
@@ -37,7 +39,7 @@ fn test_propertyupdate() {
         operations: Vec<Operation<T>>,
     }
 
-    let doc = PropertyupdateElement::<Unparsed>::parse_str(
+    let _doc = PropertyupdateElement::<Unparsed>::parse_str(
         r#"
          <propertyupdate>
             <set>
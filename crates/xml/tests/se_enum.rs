@@ -9,6 +9,7 @@ fn test_struct_value_tagged() {
     }
 
     #[derive(Debug, XmlSerialize, PartialEq)]
+    #[allow(dead_code)]
     enum Prop {
         Test(String),
         Hello(usize),
@@ -0,0 +1,48 @@
+use crate::config::BackupConfig;
+use actix_web::{web::Data, HttpResponse};
+use rustical_store::auth::User;
+use rustical_store_sqlite::backup::create_backup;
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+// Backups of the SQLite data store triggered through POST /admin/backup
+pub struct BackupService {
+    db: SqlitePool,
+    db_path: PathBuf,
+    config: BackupConfig,
+}
+
+impl BackupService {
+    pub fn new(db: SqlitePool, db_path: PathBuf, config: BackupConfig) -> Self {
+        Self {
+            db,
+            db_path,
+            config,
+        }
+    }
+}
+
+pub async fn route_backup(user: User, backup: Data<BackupService>) -> HttpResponse {
+    if !backup.config.admins.contains(&user.id) {
+        return HttpResponse::Forbidden().finish();
+    }
+    match create_backup(
+        &backup.db,
+        &backup.db_path,
+        Path::new(&backup.config.path),
+        backup.config.keep,
+        backup.config.compress,
+    )
+    .await
+    {
+        Ok(path) => {
+            info!("{} created the backup {}", user.id, path.display());
+            HttpResponse::Ok().body(path.to_string_lossy().into_owned())
+        }
+        Err(err) => {
+            error!("Backup failed: {err}");
+            HttpResponse::InternalServerError().body("Backup failed")
+        }
+    }
+}
@@ -1,19 +1,30 @@
+use crate::admin::{route_backup, BackupService};
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
 use actix_web::middleware::NormalizePath;
 use actix_web::{web, App};
+use rustical_caldav::scheduling::SchedulingConfig;
+use rustical_dav::lock::LockManager;
 use rustical_frontend::{configure_frontend, FrontendConfig};
-use rustical_store::auth::AuthenticationProvider;
+use rustical_store::auth::{
+    AuthenticationMiddleware, AuthenticationProvider, ProxyAuth, RateLimiter,
+};
 use rustical_store::{AddressbookStore, CalendarStore, SubscriptionStore};
 use std::sync::Arc;
 use tracing_actix_web::TracingLogger;
 
+#[allow(clippy::too_many_arguments)]
 pub fn make_app<AS: AddressbookStore, CS: CalendarStore, S: SubscriptionStore>(
     addr_store: Arc<AS>,
     cal_store: Arc<CS>,
     subscription_store: Arc<S>,
     auth_provider: Arc<impl AuthenticationProvider>,
     frontend_config: FrontendConfig,
+    proxy_auth: Option<web::Data<ProxyAuth>>,
+    rate_limiter: web::Data<RateLimiter>,
+    lock_manager: web::Data<LockManager>,
+    backup: Option<web::Data<BackupService>>,
+    scheduling: SchedulingConfig,
 ) -> App<
     impl ServiceFactory<
         ServiceRequest,
@@ -24,6 +35,8 @@ pub fn make_app<AS: AddressbookStore, CS: CalendarStore, S: SubscriptionStore>(
     >,
 > {
     let mut app = App::new()
+        .app_data(rate_limiter)
+        .app_data(lock_manager)
         // .wrap(Logger::new("[%s] %r"))
         .wrap(TracingLogger::default())
         .wrap(NormalizePath::trim())
@@ -34,6 +47,7 @@ pub fn make_app<AS: AddressbookStore, CS: CalendarStore, S: SubscriptionStore>(
                 cal_store.clone(),
                 addr_store.clone(),
                 subscription_store.clone(),
+                scheduling,
             )
         }))
         .service(web::scope("/carddav").configure(|cfg| {
@@ -52,6 +66,19 @@ pub fn make_app<AS: AddressbookStore, CS: CalendarStore, S: SubscriptionStore>(
                 }),
         );
 
+    if let Some(backup) = backup {
+        app = app.service(
+            web::scope("/admin")
+                .wrap(AuthenticationMiddleware::new(auth_provider.clone()))
+                .app_data(backup)
+                .route("/backup", web::post().to(route_backup)),
+        );
+    }
+
+    if let Some(proxy_auth) = proxy_auth {
+        app = app.app_data(proxy_auth);
+    }
+
     if frontend_config.enabled {
         app = app
             .service(web::scope("/frontend").configure(|cfg| {
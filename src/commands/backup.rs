@@ -0,0 +1,72 @@
+use crate::config::{default_backup_keep, Config, DataStoreConfig, SqliteDataStoreConfig};
+use clap::Parser;
+use rustical_store_sqlite::{
+    backup::{create_backup, restore_backup},
+    create_db_pool,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Parser)]
+pub struct BackupArgs {
+    #[arg(
+        long,
+        help = "Directory to write the backup to, defaults to path in [backup]"
+    )]
+    path: Option<PathBuf>,
+    #[arg(long, help = "Number of backups to keep, defaults to keep in [backup]")]
+    keep: Option<usize>,
+    #[arg(long, help = "Compress the backup with gzip")]
+    compress: bool,
+    #[arg(long, help = "SQLite database to back up, defaults to the data store")]
+    db_url: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct RestoreArgs {
+    #[arg(help = "Backup created by rustical backup")]
+    backup: PathBuf,
+    #[arg(long, help = "SQLite database to replace, defaults to the data store")]
+    db_url: Option<String>,
+}
+
+fn sqlite_db_url(db_url: Option<String>, config: &Config) -> anyhow::Result<String> {
+    match (db_url, &config.data_store) {
+        (Some(db_url), _) => Ok(db_url),
+        (None, DataStoreConfig::Sqlite(SqliteDataStoreConfig { db_url })) => Ok(db_url.to_owned()),
+        _ => anyhow::bail!(
+            "Backups are only supported for SQLite, use the tools of your database instead"
+        ),
+    }
+}
+
+pub async fn cmd_backup(args: BackupArgs, config: &Config) -> anyhow::Result<()> {
+    let db_url = sqlite_db_url(args.db_url, config)?;
+    let backup_config = config.backup.as_ref();
+    let dir = args
+        .path
+        .or_else(|| backup_config.map(|backup_config| PathBuf::from(&backup_config.path)))
+        .ok_or_else(|| anyhow::anyhow!("Specify --path or configure [backup]"))?;
+    let keep = args
+        .keep
+        .or(backup_config.map(|backup_config| backup_config.keep))
+        .unwrap_or_else(default_backup_keep);
+    let compress =
+        args.compress || backup_config.is_some_and(|backup_config| backup_config.compress);
+
+    let db = create_db_pool(&db_url, false).await?;
+    let path = create_backup(&db, Path::new(&db_url), &dir, keep, compress).await?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+pub async fn cmd_restore(args: RestoreArgs, config: &Config) -> anyhow::Result<()> {
+    let db_url = sqlite_db_url(args.db_url, config)?;
+    match restore_backup(&args.backup, Path::new(&db_url)).await? {
+        Some(previous) => println!(
+            "Restored {db_url}, the previous database was kept as {}",
+            previous.display()
+        ),
+        None => println!("Restored {db_url}"),
+    }
+    Ok(())
+}
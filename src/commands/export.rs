@@ -0,0 +1,44 @@
+use clap::Parser;
+use rustical_store::{
+    addressbook::export_addressbook, calendar::export_calendar, AddressbookStore, CalendarStore,
+};
+use std::{fs, path::PathBuf};
+
+#[derive(Debug, Parser)]
+pub struct ExportArgs {
+    principal: String,
+    #[arg(
+        long,
+        short = 'o',
+        default_value = ".",
+        help = "Directory to write <calendar>.ics and <addressbook>.vcf to"
+    )]
+    output: PathBuf,
+}
+
+pub async fn cmd_export(
+    args: ExportArgs,
+    cal_store: &impl CalendarStore,
+    addr_store: &impl AddressbookStore,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(&args.output)?;
+
+    for calendar in cal_store.get_calendars(&args.principal).await? {
+        let objects = cal_store
+            .get_objects(&calendar.principal, &calendar.id)
+            .await?;
+        let path = args.output.join(format!("{}.ics", calendar.id));
+        fs::write(&path, export_calendar(&calendar, &objects)?)?;
+        println!("{}\t{} objects", path.display(), objects.len());
+    }
+
+    for addressbook in addr_store.get_addressbooks(&args.principal).await? {
+        let objects = addr_store
+            .get_objects(&addressbook.principal, &addressbook.id)
+            .await?;
+        let path = args.output.join(format!("{}.vcf", addressbook.id));
+        fs::write(&path, export_addressbook(&objects))?;
+        println!("{}\t{} objects", path.display(), objects.len());
+    }
+    Ok(())
+}
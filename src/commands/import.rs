@@ -0,0 +1,310 @@
+use clap::{Parser, ValueEnum};
+use ical::generator::Emitter;
+use rustical_store::{
+    addressbook::{set_vcard_uid, split_addressbook},
+    calendar::{
+        get_calendar_name, object_id_from_uid, set_calendar_uid, split_calendar, CalendarObjectType,
+    },
+    AddressObject, Addressbook, AddressbookStore, Calendar, CalendarObject, CalendarStore, Error,
+    Share,
+};
+use std::{collections::HashMap, fs, path::PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub(crate) enum ConflictStrategy {
+    #[value(help = "Keep the existing object")]
+    Skip,
+    #[value(help = "Replace the existing object")]
+    Overwrite,
+    #[value(help = "Import the object as a copy with a new UID")]
+    Rename,
+}
+
+#[derive(Debug, Parser)]
+pub struct ImportArgs {
+    principal: String,
+    #[arg(help = "An .ics or .vcf file, may contain many objects")]
+    file: PathBuf,
+    #[arg(
+        long,
+        help = "Calendar or addressbook to import into, defaults to the file name. Created if missing"
+    )]
+    collection: Option<String>,
+    #[arg(long, value_enum, default_value_t = ConflictStrategy::Skip, help = "What to do with objects that already exist")]
+    conflict: ConflictStrategy,
+}
+
+#[derive(Debug, Default)]
+struct ImportStats {
+    imported: usize,
+    skipped: usize,
+    renamed: usize,
+    failed: usize,
+}
+
+async fn import_calendar(
+    principal: &str,
+    cal_id: &str,
+    ics: &str,
+    conflict: ConflictStrategy,
+    cal_store: &impl CalendarStore,
+) -> anyhow::Result<ImportStats> {
+    match cal_store.get_calendar(principal, cal_id).await {
+        Ok(calendar) if calendar.deleted_at.is_some() => {
+            anyhow::bail!("Calendar {cal_id} is in the trash bin")
+        }
+        Ok(calendar) if calendar.subscription_url.is_some() => {
+            anyhow::bail!("Calendar {cal_id} is a read-only subscription")
+        }
+        Ok(_) => {}
+        Err(Error::NotFound) if !Share::is_valid_collection_id(cal_id) => {
+            anyhow::bail!("Invalid calendar id: {cal_id}")
+        }
+        Err(Error::NotFound) => {
+            cal_store
+                .insert_calendar(Calendar {
+                    principal: principal.to_owned(),
+                    id: cal_id.to_owned(),
+                    displayname: get_calendar_name(ics),
+                    push_topic: uuid::Uuid::new_v4().to_string(),
+                    components: vec![
+                        CalendarObjectType::Event,
+                        CalendarObjectType::Todo,
+                        CalendarObjectType::Journal,
+                    ],
+                    ..Default::default()
+                })
+                .await?;
+            println!("Created calendar {cal_id}");
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    let mut stats = ImportStats::default();
+    let (split, without_uid) = split_calendar(ics)?;
+    if without_uid > 0 {
+        eprintln!("Skipping {without_uid} components without UID");
+        stats.failed += without_uid;
+    }
+    // Objects are identified by their UID, the resource name can be anything
+    let mut existing: HashMap<String, String> = cal_store
+        .get_objects(principal, cal_id)
+        .await?
+        .iter()
+        .filter_map(|object| Some((object.get_uid()?.to_owned(), object.get_id().to_owned())))
+        .collect();
+    for (mut uid, mut cal) in split {
+        let mut renamed = false;
+        let (object_id, overwrite) = match existing.get(&uid) {
+            None => (object_id_from_uid(&uid), false),
+            Some(_) if conflict == ConflictStrategy::Skip => {
+                stats.skipped += 1;
+                continue;
+            }
+            Some(object_id) if conflict == ConflictStrategy::Overwrite => {
+                (object_id.to_owned(), true)
+            }
+            Some(_) => {
+                let new_uid = uuid::Uuid::new_v4().to_string();
+                set_calendar_uid(&mut cal, &new_uid);
+                println!("Importing {uid} as {new_uid}");
+                uid = new_uid;
+                renamed = true;
+                (uid.to_owned(), false)
+            }
+        };
+        let ics = cal.generate();
+        let object = match CalendarObject::from_ics(object_id, ics.to_owned()) {
+            Ok(object) => object,
+            Err(err) => {
+                eprintln!("Skipping invalid object {uid}: {err}");
+                stats.failed += 1;
+                continue;
+            }
+        };
+        let object_id = match cal_store
+            .put_object(
+                principal.to_owned(),
+                cal_id.to_owned(),
+                object.clone(),
+                overwrite,
+            )
+            .await
+        {
+            Ok(()) => object.get_id().to_owned(),
+            // The resource name is taken by an object with another UID
+            Err(Error::AlreadyExists) => {
+                let object = CalendarObject::from_ics(uuid::Uuid::new_v4().to_string(), ics)?;
+                cal_store
+                    .put_object(
+                        principal.to_owned(),
+                        cal_id.to_owned(),
+                        object.clone(),
+                        false,
+                    )
+                    .await?;
+                object.get_id().to_owned()
+            }
+            Err(err) => return Err(err.into()),
+        };
+        existing.insert(uid, object_id);
+        match renamed {
+            true => stats.renamed += 1,
+            false => stats.imported += 1,
+        }
+    }
+    Ok(stats)
+}
+
+async fn import_addressbook(
+    principal: &str,
+    addressbook_id: &str,
+    vcf: &str,
+    conflict: ConflictStrategy,
+    addr_store: &impl AddressbookStore,
+) -> anyhow::Result<ImportStats> {
+    match addr_store.get_addressbook(principal, addressbook_id).await {
+        Ok(addressbook) if addressbook.deleted_at.is_some() => {
+            anyhow::bail!("Addressbook {addressbook_id} is in the trash bin")
+        }
+        Ok(_) => {}
+        Err(Error::NotFound) if !Share::is_valid_collection_id(addressbook_id) => {
+            anyhow::bail!("Invalid addressbook id: {addressbook_id}")
+        }
+        Err(Error::NotFound) => {
+            addr_store
+                .insert_addressbook(Addressbook {
+                    principal: principal.to_owned(),
+                    id: addressbook_id.to_owned(),
+                    displayname: None,
+                    description: None,
+                    deleted_at: None,
+                    synctoken: 0,
+                    push_topic: uuid::Uuid::new_v4().to_string(),
+                })
+                .await?;
+            println!("Created addressbook {addressbook_id}");
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    let mut stats = ImportStats::default();
+    // Objects are identified by their UID, the resource name can be anything
+    let mut existing: HashMap<String, String> = addr_store
+        .get_objects(principal, addressbook_id)
+        .await?
+        .iter()
+        .filter_map(|object| Some((object.get_uid()?.to_owned(), object.get_id().to_owned())))
+        .collect();
+    for (mut uid, mut vcard) in split_addressbook(vcf)? {
+        let mut renamed = false;
+        let (object_id, overwrite) = match existing.get(&uid) {
+            None => (object_id_from_uid(&uid), false),
+            Some(_) if conflict == ConflictStrategy::Skip => {
+                stats.skipped += 1;
+                continue;
+            }
+            Some(object_id) if conflict == ConflictStrategy::Overwrite => {
+                (object_id.to_owned(), true)
+            }
+            Some(_) => {
+                let new_uid = uuid::Uuid::new_v4().to_string();
+                set_vcard_uid(&mut vcard, &new_uid);
+                println!("Importing {uid} as {new_uid}");
+                uid = new_uid;
+                renamed = true;
+                (uid.to_owned(), false)
+            }
+        };
+        let vcf = vcard.generate();
+        let object = match AddressObject::from_vcf(object_id, vcf.to_owned()) {
+            Ok(object) => object,
+            Err(err) => {
+                eprintln!("Skipping invalid object {uid}: {err}");
+                stats.failed += 1;
+                continue;
+            }
+        };
+        let object_id = match addr_store
+            .put_object(
+                principal.to_owned(),
+                addressbook_id.to_owned(),
+                object.clone(),
+                overwrite,
+            )
+            .await
+        {
+            Ok(()) => object.get_id().to_owned(),
+            // The resource name is taken by an object with another UID
+            Err(Error::AlreadyExists) => {
+                let object = AddressObject::from_vcf(uuid::Uuid::new_v4().to_string(), vcf)?;
+                addr_store
+                    .put_object(
+                        principal.to_owned(),
+                        addressbook_id.to_owned(),
+                        object.clone(),
+                        false,
+                    )
+                    .await?;
+                object.get_id().to_owned()
+            }
+            Err(err) => return Err(err.into()),
+        };
+        existing.insert(uid, object_id);
+        match renamed {
+            true => stats.renamed += 1,
+            false => stats.imported += 1,
+        }
+    }
+    Ok(stats)
+}
+
+pub async fn cmd_import(
+    args: ImportArgs,
+    cal_store: &impl CalendarStore,
+    addr_store: &impl AddressbookStore,
+) -> anyhow::Result<()> {
+    let content = fs::read_to_string(&args.file)?;
+    let collection_id = match args.collection {
+        Some(collection_id) => collection_id,
+        None => args
+            .file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .ok_or_else(|| anyhow::anyhow!("Specify the collection with --collection"))?,
+    };
+
+    let extension = args
+        .file
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let stats = match extension.as_deref() {
+        Some("ics" | "ical" | "ifb") => {
+            import_calendar(
+                &args.principal,
+                &collection_id,
+                &content,
+                args.conflict,
+                cal_store,
+            )
+            .await?
+        }
+        Some("vcf" | "vcard") => {
+            import_addressbook(
+                &args.principal,
+                &collection_id,
+                &content,
+                args.conflict,
+                addr_store,
+            )
+            .await?
+        }
+        _ => anyhow::bail!("Unknown file type, expected an .ics or .vcf file"),
+    };
+
+    println!(
+        "Imported {}, skipped {}, renamed {}, failed {}",
+        stats.imported, stats.skipped, stats.renamed, stats.failed
+    );
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use clap::{Parser, Subcommand};
+use rustical_store::{
+    AddressObject, Addressbook, AddressbookStore, Calendar, CalendarObject, CalendarStore, Error,
+};
+
+#[cfg(feature = "nextcloud")]
+mod nextcloud;
+mod radicale;
+
+#[derive(Debug, Parser)]
+pub struct MigrateArgs {
+    #[command(subcommand)]
+    source: MigrateSource,
+}
+
+#[derive(Debug, Subcommand)]
+enum MigrateSource {
+    #[command(about = "Migrate from Radicale's file system storage")]
+    Radicale(radicale::RadicaleArgs),
+    #[cfg(feature = "nextcloud")]
+    #[command(about = "Migrate from the database of a Nextcloud instance")]
+    Nextcloud(nextcloud::NextcloudArgs),
+}
+
+#[derive(Debug, Parser)]
+struct MigrateOptions {
+    #[arg(long, help = "Only migrate the collections of this user")]
+    user: Option<String>,
+    #[arg(long, help = "Replace objects that already exist in RustiCal")]
+    overwrite: bool,
+}
+
+#[derive(Debug, Default)]
+struct MigrateStats {
+    collections: usize,
+    objects: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+// Resource names are kept so clients don't see the objects as deleted and re-created
+fn object_id_from_href(href: &str) -> String {
+    let lower = href.to_lowercase();
+    match [".ics", ".vcf"].iter().find(|ext| lower.ends_with(*ext)) {
+        Some(ext) => href[..href.len() - ext.len()].to_owned(),
+        None => href.to_owned(),
+    }
+}
+
+async fn migrate_calendar(
+    cal_store: &impl CalendarStore,
+    calendar: Calendar,
+    objects: Vec<(String, String)>,
+    overwrite: bool,
+    stats: &mut MigrateStats,
+) -> anyhow::Result<()> {
+    let (principal, cal_id) = (calendar.principal.to_owned(), calendar.id.to_owned());
+    match cal_store.get_calendar(&principal, &cal_id).await {
+        // Properties changed since an earlier run are left alone
+        Ok(_) => println!("Calendar {principal}/{cal_id} already exists"),
+        Err(Error::NotFound) => {
+            cal_store.insert_calendar(calendar).await?;
+            println!("Created calendar {principal}/{cal_id}");
+            stats.collections += 1;
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    for (href, ics) in objects {
+        let object = match CalendarObject::from_ics(object_id_from_href(&href), ics) {
+            Ok(object) => object,
+            Err(err) => {
+                eprintln!("Skipping invalid object {principal}/{cal_id}/{href}: {err}");
+                stats.failed += 1;
+                continue;
+            }
+        };
+        match cal_store
+            .put_object(principal.to_owned(), cal_id.to_owned(), object, overwrite)
+            .await
+        {
+            Ok(()) => stats.objects += 1,
+            Err(Error::AlreadyExists) => stats.skipped += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+async fn migrate_addressbook(
+    addr_store: &impl AddressbookStore,
+    addressbook: Addressbook,
+    objects: Vec<(String, String)>,
+    overwrite: bool,
+    stats: &mut MigrateStats,
+) -> anyhow::Result<()> {
+    let (principal, addressbook_id) = (addressbook.principal.to_owned(), addressbook.id.to_owned());
+    match addr_store
+        .get_addressbook(&principal, &addressbook_id)
+        .await
+    {
+        Ok(_) => println!("Addressbook {principal}/{addressbook_id} already exists"),
+        Err(Error::NotFound) => {
+            addr_store.insert_addressbook(addressbook).await?;
+            println!("Created addressbook {principal}/{addressbook_id}");
+            stats.collections += 1;
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    for (href, vcf) in objects {
+        let object = match AddressObject::from_vcf(object_id_from_href(&href), vcf) {
+            Ok(object) => object,
+            Err(err) => {
+                eprintln!("Skipping invalid object {principal}/{addressbook_id}/{href}: {err}");
+                stats.failed += 1;
+                continue;
+            }
+        };
+        match addr_store
+            .put_object(
+                principal.to_owned(),
+                addressbook_id.to_owned(),
+                object,
+                overwrite,
+            )
+            .await
+        {
+            Ok(()) => stats.objects += 1,
+            Err(Error::AlreadyExists) => stats.skipped += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+pub async fn cmd_migrate(
+    args: MigrateArgs,
+    cal_store: &impl CalendarStore,
+    addr_store: &impl AddressbookStore,
+) -> anyhow::Result<()> {
+    let stats = match args.source {
+        MigrateSource::Radicale(args) => {
+            radicale::migrate_radicale(args, cal_store, addr_store).await?
+        }
+        #[cfg(feature = "nextcloud")]
+        MigrateSource::Nextcloud(args) => {
+            nextcloud::migrate_nextcloud(args, cal_store, addr_store).await?
+        }
+    };
+    println!(
+        "Created {} collections, migrated {} objects, skipped {} existing, failed {}",
+        stats.collections, stats.objects, stats.skipped, stats.failed
+    );
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use super::{migrate_addressbook, migrate_calendar, MigrateOptions, MigrateStats};
+use clap::Parser;
+use rustical_store::{Addressbook, AddressbookStore, Calendar, CalendarStore};
+use rustical_store_vdir::props::{CollectionProps, TAG_ADDRESSBOOK, TAG_CALENDAR, TAG_SUBSCRIBED};
+use std::{fs, path::Path, path::PathBuf};
+
+#[derive(Debug, Parser)]
+pub struct RadicaleArgs {
+    #[arg(help = "Radicale's storage folder (filesystem_folder) or its collection-root")]
+    path: PathBuf,
+    #[command(flatten)]
+    options: MigrateOptions,
+}
+
+// Radicale keeps its caches and locks in dotfiles
+fn list_entries(path: &Path, dirs: bool) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') && entry.file_type()?.is_dir() == dirs {
+            entries.push((name, entry.path()));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+fn read_objects(path: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    list_entries(path, false)?
+        .into_iter()
+        .map(|(href, path)| Ok((href, fs::read_to_string(path)?)))
+        .collect()
+}
+
+pub(super) async fn migrate_radicale(
+    args: RadicaleArgs,
+    cal_store: &impl CalendarStore,
+    addr_store: &impl AddressbookStore,
+) -> anyhow::Result<MigrateStats> {
+    let collection_root = args.path.join("collection-root");
+    let root = if collection_root.is_dir() {
+        collection_root
+    } else {
+        args.path
+    };
+
+    let mut stats = MigrateStats::default();
+    for (principal, principal_path) in list_entries(&root, true)? {
+        if args
+            .options
+            .user
+            .as_ref()
+            .is_some_and(|user| user != &principal)
+        {
+            continue;
+        }
+        for (id, path) in list_entries(&principal_path, true)? {
+            let props_path = path.join(".Radicale.props");
+            if !props_path.is_file() {
+                continue;
+            }
+            let props: CollectionProps = serde_json::from_slice(&fs::read(&props_path)?)?;
+            match props.tag.as_deref() {
+                Some(TAG_CALENDAR | TAG_SUBSCRIBED) => {
+                    let calendar = Calendar {
+                        principal: principal.to_owned(),
+                        id: id.to_owned(),
+                        components: props.get_components(),
+                        displayname: props.displayname,
+                        order: props
+                            .order
+                            .and_then(|order| order.parse().ok())
+                            .unwrap_or_default(),
+                        description: props.calendar_description,
+                        color: props.color,
+                        timezone: props.timezone,
+                        timezone_id: props.timezone_id,
+                        subscription_url: props.source,
+                        push_topic: uuid::Uuid::new_v4().to_string(),
+                        ..Default::default()
+                    };
+                    // Subscribed calendars are downloaded again by the webcal refresher
+                    let objects = match calendar.subscription_url {
+                        Some(_) => vec![],
+                        None => read_objects(&path)?,
+                    };
+                    migrate_calendar(
+                        cal_store,
+                        calendar,
+                        objects,
+                        args.options.overwrite,
+                        &mut stats,
+                    )
+                    .await?;
+                }
+                Some(TAG_ADDRESSBOOK) => {
+                    let addressbook = Addressbook {
+                        principal: principal.to_owned(),
+                        id: id.to_owned(),
+                        displayname: props.displayname,
+                        description: props.addressbook_description,
+                        deleted_at: None,
+                        synctoken: 0,
+                        push_topic: uuid::Uuid::new_v4().to_string(),
+                    };
+                    migrate_addressbook(
+                        addr_store,
+                        addressbook,
+                        read_objects(&path)?,
+                        args.options.overwrite,
+                        &mut stats,
+                    )
+                    .await?;
+                }
+                // Plain WebDAV collections
+                _ => println!("Skipping {principal}/{id}, it's no calendar or addressbook"),
+            }
+        }
+    }
+    Ok(stats)
+}
@@ -3,8 +3,18 @@ use clap::{Parser, ValueEnum};
 use password_hash::PasswordHasher;
 use pbkdf2::Params;
 use rand::{rngs::OsRng, RngCore};
+use rustical_caldav::scheduling::SchedulingConfig;
+use rustical_caldav::webcal::WebcalConfig;
 use rustical_frontend::FrontendConfig;
-use rustical_store::auth::{static_user_store::UserEntry, StaticUserStoreConfig, User};
+use rustical_store::auth::{
+    static_user_store::UserEntry, RateLimitConfig, StaticUserStoreConfig, User,
+};
+
+pub mod backup;
+pub mod export;
+pub mod import;
+pub mod migrate;
+pub mod users;
 
 use crate::config::{
     AuthConfig, Config, DataStoreConfig, DavPushConfig, HttpConfig, SqliteDataStoreConfig,
@@ -30,16 +40,22 @@ pub fn cmd_gen_config(_args: GenConfigArgs) -> anyhow::Result<()> {
                 user: User {
                     id: "default".to_owned(),
                     displayname: Some("Default user".to_owned()),
+                    principal_type: Default::default(),
                     password: Some(
                         "generate a password hash with rustical pwhash --algorithm argon2"
                             .to_owned(),
                     ),
+                    memberships: vec![],
+                    calendar_proxy_read_for: vec![],
+                    calendar_proxy_write_for: vec![],
                 },
                 app_tokens: vec![
                     "generate an app token hash with rustical pwhash --algorithm pbkdf2".to_owned(),
                 ],
             }],
         }),
+        proxy_auth: None,
+        rate_limit: RateLimitConfig::default(),
         data_store: DataStoreConfig::Sqlite(SqliteDataStoreConfig {
             db_url: "".to_owned(),
         }),
@@ -47,8 +63,12 @@ pub fn cmd_gen_config(_args: GenConfigArgs) -> anyhow::Result<()> {
         frontend: FrontendConfig {
             secret_key: generate_frontend_secret(),
             enabled: true,
+            oidc: None,
         },
         dav_push: DavPushConfig::default(),
+        webcal: WebcalConfig::default(),
+        scheduling: SchedulingConfig::default(),
+        backup: None,
     };
     let generated_config = toml::to_string(&config)?;
     println!("{generated_config}");
@@ -56,7 +76,7 @@ pub fn cmd_gen_config(_args: GenConfigArgs) -> anyhow::Result<()> {
 }
 
 #[derive(Debug, Clone, ValueEnum)]
-enum PwhashAlgorithm {
+pub(crate) enum PwhashAlgorithm {
     #[value(help = "Use this for your password")]
     Argon2,
     #[value(help = "Significantly faster algorithm, use for app tokens")]
@@ -76,11 +96,9 @@ pub struct PwhashArgs {
     rounds: u32,
 }
 
-pub fn cmd_pwhash(args: PwhashArgs) -> anyhow::Result<()> {
-    println!("Enter your password:");
-    let password = rpassword::read_password()?;
+pub(crate) fn hash_password(algorithm: &PwhashAlgorithm, rounds: u32, password: &str) -> String {
     let salt = SaltString::generate(OsRng);
-    let password_hash = match args.algorithm {
+    let password_hash = match algorithm {
         PwhashAlgorithm::Argon2 => argon2::Argon2::default()
             .hash_password(password.as_bytes(), &salt)
             .unwrap(),
@@ -90,13 +108,20 @@ pub fn cmd_pwhash(args: PwhashArgs) -> anyhow::Result<()> {
                 None,
                 None,
                 Params {
-                    rounds: args.rounds,
+                    rounds,
                     ..Default::default()
                 },
                 &salt,
             )
             .unwrap(),
     };
+    password_hash.to_string()
+}
+
+pub fn cmd_pwhash(args: PwhashArgs) -> anyhow::Result<()> {
+    println!("Enter your password:");
+    let password = rpassword::read_password()?;
+    let password_hash = hash_password(&args.algorithm, args.rounds, &password);
     println!("{password_hash}");
     Ok(())
 }
@@ -0,0 +1,228 @@
+use super::{hash_password, PwhashAlgorithm};
+use clap::{Parser, Subcommand};
+use rustical_store::auth::{
+    generate_app_token, AuthenticationProvider, PrincipalType, User, UserStore,
+};
+
+#[derive(Debug, Parser)]
+pub struct UsersArgs {
+    #[command(subcommand)]
+    command: UsersCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum UsersCommand {
+    #[command(about = "List all users")]
+    List,
+    #[command(about = "Create a new user")]
+    Add(AddArgs),
+    #[command(about = "Delete a user and their app tokens")]
+    Remove(UserArgs),
+    #[command(about = "Set the password of a user")]
+    SetPassword(UserArgs),
+    #[command(about = "List the app tokens of a user")]
+    ListTokens(UserArgs),
+    #[command(about = "Create a new app token for a user")]
+    AddToken(AddTokenArgs),
+    #[command(about = "Revoke an app token of a user")]
+    RevokeToken(RevokeTokenArgs),
+    #[command(about = "Add a user to a group")]
+    AddMember(MemberArgs),
+    #[command(about = "Remove a user from a group")]
+    RemoveMember(MemberArgs),
+    #[command(about = "Let a user read or manage the calendars of another principal")]
+    AddProxy(AddProxyArgs),
+    #[command(about = "Revoke the calendar delegation of a user for another principal")]
+    RemoveProxy(ProxyArgs),
+}
+
+#[derive(Debug, Parser)]
+struct UserArgs {
+    id: String,
+}
+
+#[derive(Debug, Parser)]
+struct AddArgs {
+    id: String,
+    #[arg(long)]
+    displayname: Option<String>,
+    #[arg(long, help = "Create a group principal")]
+    group: bool,
+    #[arg(long, help = "Prompt for a password")]
+    password: bool,
+}
+
+#[derive(Debug, Parser)]
+struct AddTokenArgs {
+    id: String,
+    #[arg(help = "Name to recognize the token by, e.g. the device it is used on")]
+    name: String,
+}
+
+#[derive(Debug, Parser)]
+struct RevokeTokenArgs {
+    id: String,
+    token_id: String,
+}
+
+#[derive(Debug, Parser)]
+struct MemberArgs {
+    id: String,
+    group: String,
+}
+
+#[derive(Debug, Parser)]
+struct AddProxyArgs {
+    id: String,
+    #[arg(help = "Principal whose calendars are delegated")]
+    delegator: String,
+    #[arg(
+        long,
+        help = "Allow managing the calendars instead of only reading them"
+    )]
+    write: bool,
+}
+
+#[derive(Debug, Parser)]
+struct ProxyArgs {
+    id: String,
+    delegator: String,
+}
+
+fn prompt_password_hash() -> anyhow::Result<String> {
+    let password = rpassword::prompt_password("Enter the password: ")?;
+    Ok(hash_password(&PwhashAlgorithm::Argon2, 0, &password))
+}
+
+async fn get_user(user_store: &impl UserStore, id: &str) -> anyhow::Result<User> {
+    user_store
+        .get_user(id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("User {id} does not exist"))
+}
+
+async fn get_individual(user_store: &impl UserStore, id: &str) -> anyhow::Result<User> {
+    let user = get_user(user_store, id).await?;
+    if !user.can_log_in() {
+        anyhow::bail!("{id} is a group and cannot log in");
+    }
+    Ok(user)
+}
+
+pub async fn cmd_users(
+    args: UsersArgs,
+    user_store: &(impl UserStore + AuthenticationProvider),
+) -> anyhow::Result<()> {
+    match args.command {
+        UsersCommand::List => {
+            for user in user_store.get_users().await? {
+                println!(
+                    "{}\t{}",
+                    user.id,
+                    user.displayname.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        UsersCommand::Add(args) => {
+            if user_store.get_user(&args.id).await?.is_some() {
+                anyhow::bail!("User {} already exists", args.id);
+            }
+            if args.group && args.password {
+                anyhow::bail!("Groups cannot log in and have no password");
+            }
+            let password = if args.password {
+                Some(prompt_password_hash()?)
+            } else {
+                None
+            };
+            user_store
+                .put_user(User {
+                    id: args.id,
+                    displayname: args.displayname,
+                    principal_type: if args.group {
+                        PrincipalType::Group
+                    } else {
+                        PrincipalType::Individual
+                    },
+                    password,
+                    memberships: vec![],
+                    calendar_proxy_read_for: vec![],
+                    calendar_proxy_write_for: vec![],
+                })
+                .await?;
+        }
+        UsersCommand::Remove(UserArgs { id }) => user_store.delete_user(&id).await?,
+        UsersCommand::SetPassword(UserArgs { id }) => {
+            let mut user = get_individual(user_store, &id).await?;
+            user.password = Some(prompt_password_hash()?);
+            user_store.put_user(user).await?;
+        }
+        UsersCommand::ListTokens(UserArgs { id }) => {
+            get_user(user_store, &id).await?;
+            for app_token in user_store.get_app_tokens(&id).await? {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    app_token.id,
+                    app_token.name,
+                    app_token
+                        .created_at
+                        .map(|time| time.to_string())
+                        .unwrap_or_default(),
+                    app_token
+                        .last_used
+                        .map(|time| time.to_string())
+                        .unwrap_or_default()
+                );
+            }
+        }
+        UsersCommand::AddToken(AddTokenArgs { id, name }) => {
+            get_individual(user_store, &id).await?;
+            let (token, token_hash) = generate_app_token();
+            let token_id = user_store.add_app_token(&id, name, token_hash).await?;
+            println!("Created app token {token_id}, it will only be shown once:");
+            println!("{token}");
+        }
+        UsersCommand::RevokeToken(RevokeTokenArgs { id, token_id }) => {
+            user_store.remove_app_token(&id, &token_id).await?
+        }
+        UsersCommand::AddMember(MemberArgs { id, group }) => {
+            let mut user = get_individual(user_store, &id).await?;
+            if get_user(user_store, &group).await?.principal_type != PrincipalType::Group {
+                anyhow::bail!("{group} is not a group");
+            }
+            if !user.memberships.contains(&group) {
+                user.memberships.push(group);
+                user_store.put_user(user).await?;
+            }
+        }
+        UsersCommand::RemoveMember(MemberArgs { id, group }) => {
+            let mut user = get_user(user_store, &id).await?;
+            user.memberships.retain(|membership| membership != &group);
+            user_store.put_user(user).await?;
+        }
+        UsersCommand::AddProxy(AddProxyArgs {
+            id,
+            delegator,
+            write,
+        }) => {
+            let mut user = get_individual(user_store, &id).await?;
+            get_user(user_store, &delegator).await?;
+            // A principal is either a read or a write proxy
+            user.calendar_proxy_read_for.retain(|p| p != &delegator);
+            user.calendar_proxy_write_for.retain(|p| p != &delegator);
+            if write {
+                user.calendar_proxy_write_for.push(delegator);
+            } else {
+                user.calendar_proxy_read_for.push(delegator);
+            }
+            user_store.put_user(user).await?;
+        }
+        UsersCommand::RemoveProxy(ProxyArgs { id, delegator }) => {
+            let mut user = get_user(user_store, &id).await?;
+            user.calendar_proxy_read_for.retain(|p| p != &delegator);
+            user.calendar_proxy_write_for.retain(|p| p != &delegator);
+            user_store.put_user(user).await?;
+        }
+    }
+    Ok(())
+}
@@ -1,22 +1,51 @@
 use crate::config::Config;
 use actix_web::http::KeepAlive;
+use actix_web::web::Data;
 use actix_web::HttpServer;
+use admin::BackupService;
 use anyhow::Result;
 use app::make_app;
 use clap::{Parser, Subcommand};
-use commands::{cmd_gen_config, cmd_pwhash};
-use config::{DataStoreConfig, SqliteDataStoreConfig};
+use commands::{
+    backup::{cmd_backup, cmd_restore},
+    cmd_gen_config, cmd_pwhash,
+    export::cmd_export,
+    import::cmd_import,
+    migrate::cmd_migrate,
+    users::cmd_users,
+};
+use config::{
+    AuthConfig, DataStoreConfig, PostgresDataStoreConfig, SqliteDataStoreConfig,
+    VdirDataStoreConfig,
+};
+use rustical_caldav::webcal::webcal_refresher;
+use rustical_dav::lock::LockManager;
 use rustical_dav::push::push_notifier;
-use rustical_store::auth::StaticUserStore;
+use rustical_store::auth::{
+    AuthenticationProvider, CollectionProvisioner, LdapUserStore, ProxyAuth, RateLimiter,
+    StaticUserStore,
+};
 use rustical_store::{AddressbookStore, CalendarStore, CollectionOperation, SubscriptionStore};
+use rustical_store_postgres::addressbook_store::PostgresAddressbookStore;
+use rustical_store_postgres::calendar_store::PostgresCalendarStore;
+use rustical_store_postgres::PostgresStore;
 use rustical_store_sqlite::addressbook_store::SqliteAddressbookStore;
 use rustical_store_sqlite::calendar_store::SqliteCalendarStore;
-use rustical_store_sqlite::{create_db_pool, SqliteStore};
+use rustical_store_sqlite::user_store::SqliteUserStore;
+use rustical_store_sqlite::{backup::DbLock, create_db_pool, SqliteStore};
+use rustical_store_vdir::addressbook_store::VdirAddressbookStore;
+use rustical_store_vdir::calendar_store::VdirCalendarStore;
+use rustical_store_vdir::watcher::vdir_watcher;
+use rustical_store_vdir::VdirStore;
 use setup_tracing::setup_tracing;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::Receiver;
+use tracing::warn;
 
+mod admin;
 mod app;
 mod commands;
 mod config;
@@ -38,29 +67,199 @@ struct Args {
 enum Command {
     GenConfig(commands::GenConfigArgs),
     Pwhash(commands::PwhashArgs),
+    Users(commands::users::UsersArgs),
+    #[command(
+        about = "Write a consistent snapshot of the SQLite database, also while the server is running"
+    )]
+    Backup(commands::backup::BackupArgs),
+    #[command(about = "Replace the SQLite database with a backup, the server must be stopped")]
+    Restore(commands::backup::RestoreArgs),
+    #[command(flatten)]
+    Data(DataCommand),
 }
 
-async fn get_data_stores(
-    migrate: bool,
-    config: &DataStoreConfig,
-) -> Result<(
-    Arc<impl AddressbookStore>,
-    Arc<impl CalendarStore>,
-    Arc<impl SubscriptionStore>,
-    Receiver<CollectionOperation>,
-)> {
-    Ok(match &config {
+// Commands working on the collections in the data store
+#[derive(Debug, Subcommand)]
+enum DataCommand {
+    #[command(about = "Export the calendars and addressbooks of a principal")]
+    Export(commands::export::ExportArgs),
+    #[command(about = "Import an .ics or .vcf file into a calendar or addressbook")]
+    Import(commands::import::ImportArgs),
+    #[command(about = "Migrate calendars and addressbooks from Radicale or Nextcloud")]
+    Migrate(commands::migrate::MigrateArgs),
+}
+
+async fn cmd_data(
+    command: DataCommand,
+    cal_store: &impl CalendarStore,
+    addr_store: &impl AddressbookStore,
+) -> Result<()> {
+    match command {
+        DataCommand::Export(args) => cmd_export(args, cal_store, addr_store).await,
+        DataCommand::Import(args) => cmd_import(args, cal_store, addr_store).await,
+        DataCommand::Migrate(args) => cmd_migrate(args, cal_store, addr_store).await,
+    }
+}
+
+async fn cmd_with_data_store(config: Config, migrate: bool, command: DataCommand) -> Result<()> {
+    // Nobody listens for DAV Push notifications here
+    let (send, _) = tokio::sync::mpsc::channel(1);
+    match &config.data_store {
         DataStoreConfig::Sqlite(SqliteDataStoreConfig { db_url }) => {
             let db = create_db_pool(db_url, migrate).await?;
-            // Channel to watch for changes (for DAV Push)
-            let (send, recv) = tokio::sync::mpsc::channel(1000);
+            let cal_store = SqliteCalendarStore::new(db.clone(), send.clone());
+            let addr_store = SqliteAddressbookStore::new(db, send);
+            cmd_data(command, &cal_store, &addr_store).await
+        }
+        DataStoreConfig::Postgres(PostgresDataStoreConfig { db_url }) => {
+            let db = rustical_store_postgres::create_db_pool(db_url, migrate).await?;
+            let cal_store = PostgresCalendarStore::new(db.clone(), send.clone());
+            let addr_store = PostgresAddressbookStore::new(db, send);
+            cmd_data(command, &cal_store, &addr_store).await
+        }
+        DataStoreConfig::Vdir(VdirDataStoreConfig { path, .. }) => {
+            let cal_store = VdirCalendarStore::new(PathBuf::from(path), send.clone());
+            let addr_store = VdirAddressbookStore::new(PathBuf::from(path), send);
+            cmd_data(command, &cal_store, &addr_store).await
+        }
+    }
+}
 
+// The backends have different store types, so everything after this is generic over them
+async fn serve_with_data_store(
+    config: Config,
+    migrate: bool,
+    auth_provider: Arc<impl AuthenticationProvider + Send + Sync>,
+) -> Result<()> {
+    if config.backup.is_some() && !matches!(config.data_store, DataStoreConfig::Sqlite(_)) {
+        warn!("Backups are only supported for the sqlite data store");
+    }
+    // Channel to watch for changes (for DAV Push)
+    let (send, recv) = tokio::sync::mpsc::channel(1000);
+    match &config.data_store {
+        DataStoreConfig::Sqlite(SqliteDataStoreConfig { db_url }) => {
+            // Keeps the database from being restored while we're running
+            let _db_lock = DbLock::shared(Path::new(db_url))?;
+            let db = create_db_pool(db_url, migrate).await?;
             let addressbook_store = Arc::new(SqliteAddressbookStore::new(db.clone(), send.clone()));
             let cal_store = Arc::new(SqliteCalendarStore::new(db.clone(), send));
             let subscription_store = Arc::new(SqliteStore::new(db.clone()));
-            (addressbook_store, cal_store, subscription_store, recv)
+            let backup = config.backup.clone().map(|backup_config| {
+                BackupService::new(db.clone(), PathBuf::from(db_url), backup_config)
+            });
+            serve(
+                config,
+                auth_provider,
+                (addressbook_store, cal_store, subscription_store, recv),
+                backup,
+            )
+            .await
+        }
+        DataStoreConfig::Postgres(PostgresDataStoreConfig { db_url }) => {
+            let db = rustical_store_postgres::create_db_pool(db_url, migrate).await?;
+            let addressbook_store =
+                Arc::new(PostgresAddressbookStore::new(db.clone(), send.clone()));
+            let cal_store = Arc::new(PostgresCalendarStore::new(db.clone(), send));
+            let subscription_store = Arc::new(PostgresStore::new(db.clone()));
+            serve(
+                config,
+                auth_provider,
+                (addressbook_store, cal_store, subscription_store, recv),
+                None,
+            )
+            .await
         }
+        DataStoreConfig::Vdir(VdirDataStoreConfig {
+            path,
+            rescan_interval,
+        }) => {
+            let root = PathBuf::from(path);
+            let addressbook_store = Arc::new(VdirAddressbookStore::new(root.clone(), send.clone()));
+            let cal_store = Arc::new(VdirCalendarStore::new(root.clone(), send));
+            let subscription_store = Arc::new(VdirStore::new(root));
+            tokio::spawn(vdir_watcher(
+                cal_store.clone(),
+                addressbook_store.clone(),
+                Duration::from_secs(*rescan_interval),
+            ));
+            serve(
+                config,
+                auth_provider,
+                (addressbook_store, cal_store, subscription_store, recv),
+                None,
+            )
+            .await
+        }
+    }
+}
+
+fn read_config(config_file: &str) -> Result<Config> {
+    Ok(toml::from_str(
+        &fs::read_to_string(config_file)
+            .unwrap_or_else(|err| panic!("Could not open file at {}: {}", config_file, err)),
+    )?)
+}
+
+async fn serve(
+    config: Config,
+    auth_provider: Arc<impl AuthenticationProvider + Send + Sync>,
+    (addr_store, cal_store, subscription_store, update_recv): (
+        Arc<impl AddressbookStore>,
+        Arc<impl CalendarStore>,
+        Arc<impl SubscriptionStore>,
+        Receiver<CollectionOperation>,
+    ),
+    backup: Option<BackupService>,
+) -> Result<()> {
+    if config.dav_push.enabled {
+        tokio::spawn(push_notifier(
+            config.dav_push.allowed_push_servers,
+            update_recv,
+            subscription_store.clone(),
+        ));
+    }
+
+    if config.webcal.enabled {
+        tokio::spawn(webcal_refresher(config.webcal.clone(), cal_store.clone()));
+    }
+
+    let proxy_auth = match config.proxy_auth {
+        Some(proxy_auth_config) => Some(Data::new(ProxyAuth::new(
+            proxy_auth_config,
+            Arc::new(CollectionProvisioner::new(
+                cal_store.clone(),
+                addr_store.clone(),
+            )),
+        )?)),
+        None => None,
+    };
+
+    let rate_limiter = Data::new(RateLimiter::new(config.rate_limit));
+    let lock_manager = Data::new(LockManager::new());
+    let backup = backup.map(Data::new);
+
+    HttpServer::new(move || {
+        make_app(
+            addr_store.clone(),
+            cal_store.clone(),
+            subscription_store.clone(),
+            auth_provider.clone(),
+            config.frontend.clone(),
+            proxy_auth.clone(),
+            rate_limiter.clone(),
+            lock_manager.clone(),
+            backup.clone(),
+            config.scheduling.clone(),
+        )
     })
+    .bind((config.http.host, config.http.port))?
+    // Workaround for a weird bug where
+    // new requests might timeout since they cannot properly reuse the connection
+    // https://github.com/lennart-k/rustical/issues/10
+    .keep_alive(KeepAlive::Disabled)
+    .run()
+    .await?;
+    Ok(())
 }
 
 #[tokio::main]
@@ -70,46 +269,48 @@ async fn main() -> Result<()> {
     match args.command {
         Some(Command::GenConfig(gen_config_args)) => cmd_gen_config(gen_config_args)?,
         Some(Command::Pwhash(pwhash_args)) => cmd_pwhash(pwhash_args)?,
+        Some(Command::Users(users_args)) => {
+            let config = read_config(&args.config_file)?;
+            let user_store = match config.auth {
+                AuthConfig::Sqlite(SqliteDataStoreConfig { db_url }) => {
+                    SqliteUserStore::new(create_db_pool(&db_url, !args.no_migrations).await?)
+                }
+                _ => anyhow::bail!("Users can only be managed with the sqlite auth backend"),
+            };
+            cmd_users(users_args, &user_store).await?
+        }
+        Some(Command::Backup(backup_args)) => {
+            cmd_backup(backup_args, &read_config(&args.config_file)?).await?
+        }
+        Some(Command::Restore(restore_args)) => {
+            cmd_restore(restore_args, &read_config(&args.config_file)?).await?
+        }
+        Some(Command::Data(command)) => {
+            let config = read_config(&args.config_file)?;
+            cmd_with_data_store(config, !args.no_migrations, command).await?
+        }
         None => {
-            let config: Config = toml::from_str(
-                &fs::read_to_string(&args.config_file).unwrap_or_else(|err| {
-                    panic!("Could not open file at {}: {}", &args.config_file, err)
-                }),
-            )?;
+            let config = read_config(&args.config_file)?;
 
             setup_tracing(&config.tracing);
 
-            let (addr_store, cal_store, subscription_store, update_recv) =
-                get_data_stores(!args.no_migrations, &config.data_store).await?;
-
-            if config.dav_push.enabled {
-                tokio::spawn(push_notifier(
-                    config.dav_push.allowed_push_servers,
-                    update_recv,
-                    subscription_store.clone(),
-                ));
+            match &config.auth {
+                AuthConfig::Static(user_config) => {
+                    let user_store = Arc::new(StaticUserStore::new(user_config.clone()));
+                    serve_with_data_store(config, !args.no_migrations, user_store).await?
+                }
+                AuthConfig::Sqlite(SqliteDataStoreConfig { db_url }) => {
+                    let _db_lock = DbLock::shared(Path::new(db_url))?;
+                    let user_store = Arc::new(SqliteUserStore::new(
+                        create_db_pool(db_url, !args.no_migrations).await?,
+                    ));
+                    serve_with_data_store(config, !args.no_migrations, user_store).await?
+                }
+                AuthConfig::Ldap(ldap_config) => {
+                    let user_store = Arc::new(LdapUserStore::new(ldap_config.clone()));
+                    serve_with_data_store(config, !args.no_migrations, user_store).await?
+                }
             }
-
-            let user_store = Arc::new(match config.auth {
-                config::AuthConfig::Static(config) => StaticUserStore::new(config),
-            });
-
-            HttpServer::new(move || {
-                make_app(
-                    addr_store.clone(),
-                    cal_store.clone(),
-                    subscription_store.clone(),
-                    user_store.clone(),
-                    config.frontend.clone(),
-                )
-            })
-            .bind((config.http.host, config.http.port))?
-            // Workaround for a weird bug where
-            // new requests might timeout since they cannot properly reuse the connection
-            // https://github.com/lennart-k/rustical/issues/10
-            .keep_alive(KeepAlive::Disabled)
-            .run()
-            .await?;
         }
     }
     Ok(())